@@ -85,3 +85,11 @@ pub fn ax_current_dir() -> AxResult<String> {
 pub fn ax_set_current_dir(path: &str) -> AxResult {
     axfs::api::set_current_dir(path)
 }
+
+pub fn ax_mount_disk(dev_id: usize, path: &str) -> AxResult {
+    axfs::api::mount_disk(dev_id, path)
+}
+
+pub fn ax_umount(path: &str) -> AxResult {
+    axfs::api::umount(path)
+}
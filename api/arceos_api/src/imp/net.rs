@@ -1,7 +1,8 @@
 use crate::io::AxPollState;
-use axerrno::AxResult;
-use axnet::{UdpSocket, TcpSocket};
+use axerrno::{AxError, AxResult};
+use axnet::{IcmpSocket, UdpSocket, TcpSocket};
 use core::net::{IpAddr, SocketAddr};
+use core::time::Duration;
 
 /// A handle to a TCP socket.
 pub struct AxTcpSocketHandle(TcpSocket);
@@ -9,6 +10,9 @@ pub struct AxTcpSocketHandle(TcpSocket);
 /// A handle to a UDP socket.
 pub struct AxUdpSocketHandle(UdpSocket);
 
+/// A handle to an ICMP echo socket.
+pub struct AxIcmpSocketHandle(IcmpSocket);
+
 ////////////////////////////////////////////////////////////////////////////////
 // TCP socket
 ////////////////////////////////////////////////////////////////////////////////
@@ -64,6 +68,44 @@ pub fn ax_tcp_shutdown(socket: &AxTcpSocketHandle) -> AxResult {
     socket.0.shutdown()
 }
 
+pub fn ax_tcp_set_nodelay(socket: &AxTcpSocketHandle, nodelay: bool) -> AxResult {
+    socket.0.set_nodelay(nodelay);
+    Ok(())
+}
+
+pub fn ax_tcp_nodelay(socket: &AxTcpSocketHandle) -> AxResult<bool> {
+    Ok(socket.0.nodelay())
+}
+
+pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_recv_timeout(timeout)
+}
+
+pub fn ax_tcp_recv_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.recv_timeout())
+}
+
+pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_send_timeout(timeout)
+}
+
+pub fn ax_tcp_send_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.send_timeout())
+}
+
+pub fn ax_tcp_set_linger(socket: &AxTcpSocketHandle, linger: Option<Duration>) -> AxResult {
+    socket.0.set_linger(linger);
+    Ok(())
+}
+
+pub fn ax_tcp_linger(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.linger())
+}
+
+pub fn ax_tcp_take_error(socket: &AxTcpSocketHandle) -> AxResult<Option<AxError>> {
+    Ok(socket.0.take_error())
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // UDP socket
 ////////////////////////////////////////////////////////////////////////////////
@@ -117,6 +159,75 @@ pub fn ax_udp_poll(socket: &AxUdpSocketHandle) -> AxResult<AxPollState> {
     socket.0.poll()
 }
 
+pub fn ax_udp_set_recv_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_recv_timeout(timeout)
+}
+
+pub fn ax_udp_recv_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.recv_timeout())
+}
+
+pub fn ax_udp_set_send_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult {
+    socket.0.set_send_timeout(timeout)
+}
+
+pub fn ax_udp_send_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>> {
+    Ok(socket.0.send_timeout())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ICMP socket
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn ax_icmp_socket() -> AxIcmpSocketHandle {
+    AxIcmpSocketHandle(IcmpSocket::new())
+}
+
+pub fn ax_icmp_ident(socket: &AxIcmpSocketHandle) -> u16 {
+    socket.0.ident()
+}
+
+pub fn ax_icmp_socket_addr(socket: &AxIcmpSocketHandle) -> AxResult<IpAddr> {
+    socket.0.local_addr()
+}
+
+pub fn ax_icmp_peer_addr(socket: &AxIcmpSocketHandle) -> AxResult<IpAddr> {
+    socket.0.peer_addr()
+}
+
+pub fn ax_icmp_set_nonblocking(socket: &AxIcmpSocketHandle, nonblocking: bool) -> AxResult {
+    socket.0.set_nonblocking(nonblocking);
+    Ok(())
+}
+
+pub fn ax_icmp_bind(socket: &AxIcmpSocketHandle, addr: IpAddr) -> AxResult {
+    socket.0.bind(addr)
+}
+
+pub fn ax_icmp_recv_from(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+    socket.0.recv_from(buf)
+}
+
+pub fn ax_icmp_send_to(socket: &AxIcmpSocketHandle, buf: &[u8], addr: IpAddr) -> AxResult<usize> {
+    socket.0.send_to(buf, addr)
+}
+
+pub fn ax_icmp_connect(socket: &AxIcmpSocketHandle, addr: IpAddr) -> AxResult {
+    socket.0.connect(addr)
+}
+
+pub fn ax_icmp_send(socket: &AxIcmpSocketHandle, buf: &[u8]) -> AxResult<usize> {
+    socket.0.send(buf)
+}
+
+pub fn ax_icmp_recv(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<usize> {
+    socket.0.recv(buf)
+}
+
+pub fn ax_icmp_poll(socket: &AxIcmpSocketHandle) -> AxResult<AxPollState> {
+    socket.0.poll()
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Miscellaneous
 ////////////////////////////////////////////////////////////////////////////////
@@ -129,3 +240,47 @@ pub fn ax_poll_interfaces() -> AxResult {
     axnet::poll_interfaces();
     Ok(())
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// Packet capture
+////////////////////////////////////////////////////////////////////////////////
+
+cfg_pcap! {
+    pub fn ax_pcap_set_enabled(enabled: bool) {
+        axnet::pcap_set_enabled(enabled)
+    }
+
+    pub fn ax_pcap_is_enabled() -> bool {
+        axnet::pcap_is_enabled()
+    }
+
+    pub fn ax_pcap_clear() {
+        axnet::pcap_clear()
+    }
+
+    pub fn ax_pcap_save(path: &str) -> AxResult {
+        #[cfg(feature = "fs")]
+        {
+            axfs::api::write(path, axnet::pcap_dump())
+        }
+        #[cfg(not(feature = "fs"))]
+        {
+            let _ = path;
+            Err(AxError::Unsupported)
+        }
+    }
+
+    pub fn ax_pcap_dump_console() {
+        use core::fmt::Write;
+
+        axlog::ax_println!("-----BEGIN PCAP-----");
+        for chunk in axnet::pcap_dump().chunks(32) {
+            let mut line = alloc::string::String::with_capacity(chunk.len() * 2);
+            for byte in chunk {
+                write!(line, "{:02x}", byte).unwrap();
+            }
+            axlog::ax_println!("{}", line);
+        }
+        axlog::ax_println!("-----END PCAP-----");
+    }
+}
@@ -46,6 +46,8 @@ pub mod time {
     define_api! {
         /// Returns the current clock time.
         pub fn ax_current_time() -> AxTimeValue;
+        /// Returns the current wall time (time since the Unix epoch).
+        pub fn ax_wall_time() -> AxTimeValue;
     }
 }
 
@@ -85,6 +87,7 @@ pub mod task {
         @cfg "multitask";
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
+        pub type AxCpuMask;
     }
 
     define_api! {
@@ -119,6 +122,11 @@ pub mod task {
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Sets the set of CPUs on which the current task is allowed to run.
+        ///
+        /// The current task is migrated immediately if the current CPU is not
+        /// in the set.
+        pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult;
 
         /// Blocks the current task and put it into the wait queue, until the
         /// given condition becomes true, or the the given duration has elapsed
@@ -218,18 +226,25 @@ pub mod fs {
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
         pub fn ax_set_current_dir(path: &str) -> AxResult;
+
+        /// Mounts the filesystem on the block device `dev_id` on `path`.
+        pub fn ax_mount_disk(dev_id: usize, path: &str) -> AxResult;
+        /// Unmounts the filesystem mounted on `path`.
+        pub fn ax_umount(path: &str) -> AxResult;
     }
 }
 
-/// Networking primitives for TCP/UDP communication.
+/// Networking primitives for TCP/UDP/ICMP communication.
 pub mod net {
-    use crate::{io::AxPollState, AxResult};
+    use crate::{io::AxPollState, AxError, AxResult};
     use core::net::{IpAddr, SocketAddr};
+    use core::time::Duration;
 
     define_api_type! {
         @cfg "net";
         pub type AxTcpSocketHandle;
         pub type AxUdpSocketHandle;
+        pub type AxIcmpSocketHandle;
     }
 
     define_api! {
@@ -268,6 +283,29 @@ pub mod net {
         /// Closes the connection on the TCP socket.
         pub fn ax_tcp_shutdown(socket: &AxTcpSocketHandle) -> AxResult;
 
+        /// Disables or enables the Nagle algorithm on the TCP socket
+        /// (`TCP_NODELAY`).
+        pub fn ax_tcp_set_nodelay(socket: &AxTcpSocketHandle, nodelay: bool) -> AxResult;
+        /// Returns whether the Nagle algorithm is disabled on the TCP socket.
+        pub fn ax_tcp_nodelay(socket: &AxTcpSocketHandle) -> AxResult<bool>;
+        /// Sets the timeout of receiving and accepting on the TCP socket
+        /// (`SO_RCVTIMEO`). `None` means blocking indefinitely.
+        pub fn ax_tcp_set_recv_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of receiving and accepting on the TCP socket.
+        pub fn ax_tcp_recv_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the timeout of sending on the TCP socket (`SO_SNDTIMEO`).
+        /// `None` means blocking indefinitely.
+        pub fn ax_tcp_set_send_timeout(socket: &AxTcpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of sending on the TCP socket.
+        pub fn ax_tcp_send_timeout(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets how long closing the TCP socket waits for the data not yet
+        /// sent (`SO_LINGER`).
+        pub fn ax_tcp_set_linger(socket: &AxTcpSocketHandle, linger: Option<Duration>) -> AxResult;
+        /// Returns the `SO_LINGER` option of the TCP socket.
+        pub fn ax_tcp_linger(socket: &AxTcpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Takes the pending error of the TCP socket (`SO_ERROR`).
+        pub fn ax_tcp_take_error(socket: &AxTcpSocketHandle) -> AxResult<Option<AxError>>;
+
         // UDP socket
 
         /// Creates a new UDP socket.
@@ -304,6 +342,51 @@ pub mod net {
         /// Returns whether the UDP socket is readable or writable.
         pub fn ax_udp_poll(socket: &AxUdpSocketHandle) -> AxResult<AxPollState>;
 
+        /// Sets the timeout of receiving on the UDP socket (`SO_RCVTIMEO`).
+        /// `None` means blocking indefinitely.
+        pub fn ax_udp_set_recv_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of receiving on the UDP socket.
+        pub fn ax_udp_recv_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>>;
+        /// Sets the timeout of sending on the UDP socket (`SO_SNDTIMEO`).
+        /// `None` means blocking indefinitely.
+        pub fn ax_udp_set_send_timeout(socket: &AxUdpSocketHandle, timeout: Option<Duration>) -> AxResult;
+        /// Returns the timeout of sending on the UDP socket.
+        pub fn ax_udp_send_timeout(socket: &AxUdpSocketHandle) -> AxResult<Option<Duration>>;
+
+        // ICMP socket
+
+        /// Creates a new ICMP echo socket.
+        pub fn ax_icmp_socket() -> AxIcmpSocketHandle;
+        /// Returns the identifier of the echo requests sent by the ICMP socket.
+        pub fn ax_icmp_ident(socket: &AxIcmpSocketHandle) -> u16;
+        /// Returns the local address of the ICMP socket.
+        pub fn ax_icmp_socket_addr(socket: &AxIcmpSocketHandle) -> AxResult<IpAddr>;
+        /// Returns the remote address of the ICMP socket.
+        pub fn ax_icmp_peer_addr(socket: &AxIcmpSocketHandle) -> AxResult<IpAddr>;
+        /// Moves this ICMP socket into or out of nonblocking mode.
+        pub fn ax_icmp_set_nonblocking(socket: &AxIcmpSocketHandle, nonblocking: bool) -> AxResult;
+
+        /// Binds the ICMP socket to the given local address.
+        pub fn ax_icmp_bind(socket: &AxIcmpSocketHandle, addr: IpAddr) -> AxResult;
+        /// Receives an echo reply on the ICMP socket. On success, returns the
+        /// number of bytes read and the origin.
+        pub fn ax_icmp_recv_from(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr)>;
+        /// Sends an echo request on the ICMP socket to the given address. On
+        /// success, returns the number of bytes written.
+        pub fn ax_icmp_send_to(socket: &AxIcmpSocketHandle, buf: &[u8], addr: IpAddr) -> AxResult<usize>;
+
+        /// Sets the remote address of the ICMP socket, allowing the `send` and
+        /// `recv` to be used, and only receives the replies from it.
+        pub fn ax_icmp_connect(socket: &AxIcmpSocketHandle, addr: IpAddr) -> AxResult;
+        /// Sends an echo request on the ICMP socket to the remote address to
+        /// which it is connected.
+        pub fn ax_icmp_send(socket: &AxIcmpSocketHandle, buf: &[u8]) -> AxResult<usize>;
+        /// Receives an echo reply on the ICMP socket from the remote address to
+        /// which it is connected. On success, returns the number of bytes read.
+        pub fn ax_icmp_recv(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<usize>;
+        /// Returns whether the ICMP socket is readable or writable.
+        pub fn ax_icmp_poll(socket: &AxIcmpSocketHandle) -> AxResult<AxPollState>;
+
         // Miscellaneous
 
         /// Resolves the host name to a list of IP addresses.
@@ -314,6 +397,32 @@ pub mod net {
         /// packets to the NIC.
         pub fn ax_poll_interfaces() -> AxResult;
     }
+
+    define_api! {
+        @cfg "pcap";
+
+        // Packet capture
+
+        /// Starts or stops capturing the frames received and transmitted by
+        /// the NICs. The frames captured so far are kept.
+        pub fn ax_pcap_set_enabled(enabled: bool);
+        /// Returns whether the frames of the NICs are being captured.
+        pub fn ax_pcap_is_enabled() -> bool;
+        /// Discards the captured frames.
+        pub fn ax_pcap_clear();
+        /// Writes the captured frames to the file at `path` in the pcap format.
+        ///
+        /// It returns [`AxError::Unsupported`] if the feature `fs` is not
+        /// enabled.
+        pub fn ax_pcap_save(path: &str) -> AxResult;
+        /// Prints the captured frames in the pcap format to the console, as hex
+        /// digits between the lines `-----BEGIN PCAP-----` and
+        /// `-----END PCAP-----`.
+        ///
+        /// The pcap file can be recovered from the log by
+        /// `sed -n '/BEGIN PCAP/,/END PCAP/{/PCAP/!p}' | xxd -r -p`.
+        pub fn ax_pcap_dump_console();
+    }
 }
 
 /// Graphics manipulation operations.
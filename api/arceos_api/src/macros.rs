@@ -70,6 +70,10 @@ macro_rules! cfg_net {
     ($($item:item)*) => { _cfg_common!{ "net" $($item)* } }
 }
 
+macro_rules! cfg_pcap {
+    ($($item:item)*) => { _cfg_common!{ "pcap" $($item)* } }
+}
+
 macro_rules! cfg_display {
     ($($item:item)*) => { _cfg_common!{ "display" $($item)* } }
 }
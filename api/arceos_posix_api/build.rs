@@ -42,13 +42,25 @@ typedef struct {{
             "off_t",
             "mode_t",
             "sock.*",
+            "linger",
             "fd_set",
             "timeval",
             "pthread_t",
             "pthread_attr_t",
             "pthread_mutex_t",
             "pthread_mutexattr_t",
+            "pthread_cond_t",
+            "pthread_condattr_t",
+            "pthread_rwlock_t",
+            "pthread_rwlockattr_t",
+            "pthread_barrier_t",
+            "pthread_barrierattr_t",
+            "pthread_once_t",
+            "pthread_key_t",
             "epoll_event",
+            "pollfd",
+            "nfds_t",
+            "sigset_t",
             "iovec",
             "clockid_t",
             "rlimit",
@@ -59,14 +71,24 @@ typedef struct {{
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
+            "SOL_.*",
+            "SO_.*",
+            "TCP_.*",
+            "SHUT_.*",
             "FD_.*",
             "F_.*",
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
+            "POLL[A-Z]*",
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "CLOCK_.*",
+            "PROT_.*",
+            "MAP_.*",
+            "MREMAP_.*",
+            "PTHREAD_BARRIER_SERIAL_THREAD",
         ];
 
         #[derive(Debug)]
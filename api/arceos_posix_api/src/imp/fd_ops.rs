@@ -1,8 +1,10 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::ffi::c_int;
+use core::task::Waker;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
+use axsync::spin::SpinNoIrq;
 use flatten_objects::FlattenObjects;
 use spin::RwLock;
 
@@ -18,6 +20,41 @@ pub trait FileLike: Send + Sync {
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
+
+    /// Registers `waker` to be woken once when the result of
+    /// [`poll`](Self::poll) may change, so that `select` and `epoll_wait` can
+    /// sleep instead of polling the file repeatedly.
+    ///
+    /// Returns `false` if the file cannot wake it, which is the default.
+    fn register_waker(&self, _waker: &Waker) -> bool {
+        false
+    }
+}
+
+/// The wakers registered to a file by [`FileLike::register_waker`].
+///
+/// They can be woken in the IRQ context, e.g. on console input.
+pub struct PollWakers(SpinNoIrq<Vec<Waker>>);
+
+impl PollWakers {
+    pub const fn new() -> Self {
+        Self(SpinNoIrq::new(Vec::new()))
+    }
+
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.0.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+
+    /// Wakes and unregisters all the wakers, after the file state changes.
+    pub fn wake_all(&self) {
+        let wakers = core::mem::take(&mut *self.0.lock());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
 }
 
 lazy_static::lazy_static! {
@@ -1,21 +1,119 @@
 //! `epoll` implementation.
 //!
-//! TODO: do not support `EPOLLET` flag
+//! Both level-triggered and edge-triggered (`EPOLLET`) modes are supported, as
+//! well as `EPOLLONESHOT`.
 
 use alloc::collections::btree_map::Entry;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
 use core::{ffi::c_int, time::Duration};
 
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::current_time;
 use axsync::Mutex;
 
+use super::PollWaiter;
 use crate::ctypes;
 use crate::imp::fd_ops::{add_file_like, get_file_like, FileLike};
 
+/// The events that are always reported, even if not requested.
+const EPOLL_ALWAYS: u32 = ctypes::EPOLLERR | ctypes::EPOLLHUP;
+
+/// Records whether the file of an [`EpollEntry`] is woken, and forwards the
+/// wakeup to the waiter of the epoll instance.
+struct EntryNotifier {
+    woken: AtomicBool,
+    waiter: Waker,
+}
+
+impl Wake for EntryNotifier {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.waiter.wake_by_ref();
+    }
+}
+
+struct EpollEntry {
+    event: ctypes::epoll_event,
+    notifier: Arc<EntryNotifier>,
+    /// The events reported last time, for the edge-triggered mode.
+    last_ready: u32,
+    /// Disabled after an event is reported with `EPOLLONESHOT`, until the
+    /// entry is modified by `EPOLL_CTL_MOD`.
+    disabled: bool,
+}
+
+impl EpollEntry {
+    fn new(event: ctypes::epoll_event, waiter: Waker) -> Self {
+        Self {
+            event,
+            // report the current state on the next poll, even in the
+            // edge-triggered mode
+            notifier: Arc::new(EntryNotifier {
+                woken: AtomicBool::new(true),
+                waiter,
+            }),
+            last_ready: 0,
+            disabled: false,
+        }
+    }
+
+    /// Polls the file, and returns the events to report, if any.
+    ///
+    /// It also returns whether the file will wake the epoll instance when its
+    /// state changes.
+    fn poll(&mut self, file: &dyn FileLike) -> (Option<u32>, bool) {
+        if self.disabled {
+            return (None, true);
+        }
+        let woken = self.notifier.woken.swap(false, Ordering::AcqRel);
+        let notifiable = file.register_waker(&Waker::from(self.notifier.clone()));
+
+        let interests = self.event.events;
+        let ready = match file.poll() {
+            Ok(state) => {
+                let mut ready = 0;
+                if state.readable {
+                    ready |= ctypes::EPOLLIN;
+                }
+                if state.writable {
+                    ready |= ctypes::EPOLLOUT;
+                }
+                if state.hangup {
+                    ready |= ctypes::EPOLLHUP;
+                }
+                ready
+            }
+            Err(_) => ctypes::EPOLLERR,
+        } & (interests | EPOLL_ALWAYS);
+
+        let report = if interests & ctypes::EPOLLET != 0 {
+            // only report the new events, or the events after a wakeup
+            ready != 0 && (woken || ready & !self.last_ready != 0)
+        } else {
+            ready != 0
+        };
+        self.last_ready = ready;
+        if !report {
+            return (None, notifiable);
+        }
+        if interests & ctypes::EPOLLONESHOT != 0 {
+            self.disabled = true;
+        }
+        (Some(ready), notifiable)
+    }
+}
+
 pub struct EpollInstance {
-    events: Mutex<BTreeMap<usize, ctypes::epoll_event>>,
+    entries: Mutex<BTreeMap<usize, EpollEntry>>,
+    waiter: PollWaiter,
 }
 
 unsafe impl Send for ctypes::epoll_event {}
@@ -25,7 +123,8 @@ impl EpollInstance {
     // TODO: parse flags
     pub fn new(_flags: usize) -> Self {
         Self {
-            events: Mutex::new(BTreeMap::new()),
+            entries: Mutex::new(BTreeMap::new()),
+            waiter: PollWaiter::new(),
         }
     }
 
@@ -44,23 +143,23 @@ impl EpollInstance {
 
         match op as u32 {
             ctypes::EPOLL_CTL_ADD => {
-                if let Entry::Vacant(e) = self.events.lock().entry(fd) {
-                    e.insert(*event);
+                if let Entry::Vacant(e) = self.entries.lock().entry(fd) {
+                    e.insert(EpollEntry::new(*event, self.waiter.waker()));
                 } else {
                     return Err(LinuxError::EEXIST);
                 }
             }
             ctypes::EPOLL_CTL_MOD => {
-                let mut events = self.events.lock();
-                if let Entry::Occupied(mut ocp) = events.entry(fd) {
-                    ocp.insert(*event);
+                let mut entries = self.entries.lock();
+                if let Entry::Occupied(mut ocp) = entries.entry(fd) {
+                    ocp.insert(EpollEntry::new(*event, self.waiter.waker()));
                 } else {
                     return Err(LinuxError::ENOENT);
                 }
             }
             ctypes::EPOLL_CTL_DEL => {
-                let mut events = self.events.lock();
-                if let Entry::Occupied(ocp) = events.entry(fd) {
+                let mut entries = self.entries.lock();
+                if let Entry::Occupied(ocp) = entries.entry(fd) {
                     ocp.remove_entry();
                 } else {
                     return Err(LinuxError::ENOENT);
@@ -73,35 +172,34 @@ impl EpollInstance {
         Ok(0)
     }
 
-    fn poll_all(&self, events: &mut [ctypes::epoll_event]) -> LinuxResult<usize> {
-        let ready_list = self.events.lock();
+    /// Polls the files of all entries, and fills `events` with the ready
+    /// ones. The entries of the closed files are removed.
+    ///
+    /// Returns the number of the ready files, and whether all the files will
+    /// wake the epoll instance when their states change.
+    fn poll_all(&self, events: &mut [ctypes::epoll_event]) -> (usize, bool) {
+        let mut entries = self.entries.lock();
         let mut events_num = 0;
+        let mut notifiable = true;
 
-        for (infd, ev) in ready_list.iter() {
-            match get_file_like(*infd as c_int)?.poll() {
-                Err(_) => {
-                    if (ev.events & ctypes::EPOLLERR) != 0 {
-                        events[events_num].events = ctypes::EPOLLERR;
-                        events[events_num].data = ev.data;
-                        events_num += 1;
-                    }
-                }
-                Ok(state) => {
-                    if state.readable && (ev.events & ctypes::EPOLLIN != 0) {
-                        events[events_num].events = ctypes::EPOLLIN;
-                        events[events_num].data = ev.data;
-                        events_num += 1;
-                    }
-
-                    if state.writable && (ev.events & ctypes::EPOLLOUT != 0) {
-                        events[events_num].events = ctypes::EPOLLOUT;
-                        events[events_num].data = ev.data;
-                        events_num += 1;
-                    }
-                }
+        entries.retain(|&infd, entry| {
+            if events_num == events.len() {
+                return true;
             }
-        }
-        Ok(events_num)
+            let Ok(file) = get_file_like(infd as c_int) else {
+                debug!("    remove closed fd {}", infd);
+                return false;
+            };
+            let (ready, file_notifiable) = entry.poll(file.as_ref());
+            notifiable &= file_notifiable;
+            if let Some(ready) = ready {
+                events[events_num].events = ready;
+                events[events_num].data = entry.event.data;
+                events_num += 1;
+            }
+            true
+        });
+        (events_num, notifiable)
     }
 }
 
@@ -190,7 +288,8 @@ pub unsafe fn sys_epoll_wait(
         loop {
             #[cfg(feature = "net")]
             axnet::poll_interfaces();
-            let events_num = epoll_instance.poll_all(events)?;
+            let wakeups = epoll_instance.waiter.events();
+            let (events_num, notifiable) = epoll_instance.poll_all(events);
             if events_num > 0 {
                 return Ok(events_num as c_int);
             }
@@ -199,7 +298,7 @@ pub unsafe fn sys_epoll_wait(
                 debug!("    timeout!");
                 return Ok(0);
             }
-            crate::sys_sched_yield();
+            epoll_instance.waiter.wait(wakeups, deadline, notifiable);
         }
     })
 }
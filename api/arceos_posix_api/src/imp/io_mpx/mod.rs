@@ -4,13 +4,108 @@
 //! * [`epoll_create`](epoll::sys_epoll_create)
 //! * [`epoll_ctl`](epoll::sys_epoll_ctl)
 //! * [`epoll_wait`](epoll::sys_epoll_wait)
+//! * [`poll`](poll::sys_poll)
+//! * [`ppoll`](poll::sys_ppoll)
+//!
+//! The waiting tasks sleep until one of the polled files becomes ready, if all
+//! of them support [`FileLike::register_waker`]; otherwise they poll the files
+//! and yield the CPU repeatedly. `PollWaiter` is also used by the blocking
+//! operations of the files that support it.
+//!
+//! [`FileLike::register_waker`]: super::fd_ops::FileLike::register_waker
 
 #[cfg(feature = "epoll")]
 mod epoll;
+#[cfg(feature = "poll")]
+mod poll;
 #[cfg(feature = "select")]
 mod select;
 
 #[cfg(feature = "epoll")]
 pub use self::epoll::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+#[cfg(feature = "poll")]
+pub use self::poll::{sys_poll, sys_ppoll};
 #[cfg(feature = "select")]
 pub use self::select::sys_select;
+
+use alloc::{sync::Arc, task::Wake};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+
+use axhal::time::TimeValue;
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
+
+/// Blocks `select` and `epoll_wait` until one of the polled files wakes it by
+/// the waker registered with [`FileLike::register_waker`].
+///
+/// [`FileLike::register_waker`]: super::fd_ops::FileLike::register_waker
+pub(crate) struct PollWaiter(Arc<WaiterInner>);
+
+struct WaiterInner {
+    /// Incremented each time a polled file is woken.
+    events: AtomicUsize,
+    #[cfg(feature = "multitask")]
+    wq: WaitQueue,
+}
+
+impl Wake for WaiterInner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.events.fetch_add(1, Ordering::Release);
+        #[cfg(feature = "multitask")]
+        self.wq.notify_all(false);
+    }
+}
+
+impl PollWaiter {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(WaiterInner {
+            events: AtomicUsize::new(0),
+            #[cfg(feature = "multitask")]
+            wq: WaitQueue::new(),
+        }))
+    }
+
+    /// Takes a snapshot of the number of wakeups, which must be done before
+    /// polling the files, to not miss the wakeups during polling.
+    pub(crate) fn events(&self) -> usize {
+        self.0.events.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn waker(&self) -> Waker {
+        Waker::from(self.0.clone())
+    }
+
+    /// Blocks the current task until it is woken after the `events` snapshot
+    /// is taken, or the `deadline` is reached.
+    ///
+    /// If not all the polled files are `notifiable`, or the deadline cannot be
+    /// waited for without timer interrupts, it only yields the CPU, then the
+    /// caller polls the files again.
+    pub(crate) fn wait(&self, events: usize, deadline: Option<TimeValue>, notifiable: bool) {
+        #[cfg(feature = "multitask")]
+        if notifiable {
+            let woken = || self.0.events.load(Ordering::Acquire) != events;
+            match deadline {
+                None => return self.0.wq.wait_until(woken),
+                #[cfg(feature = "irq")]
+                Some(deadline) => {
+                    let now = axhal::time::current_time();
+                    if deadline > now {
+                        self.0.wq.wait_timeout_until(deadline - now, woken);
+                    }
+                    return;
+                }
+                #[cfg(not(feature = "irq"))]
+                Some(_) => {}
+            }
+        }
+        #[cfg(not(feature = "multitask"))]
+        let _ = (events, deadline, notifiable);
+        crate::sys_sched_yield();
+    }
+}
@@ -0,0 +1,129 @@
+use core::ffi::c_int;
+use core::task::Waker;
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{current_time, TimeValue};
+
+use super::PollWaiter;
+use crate::{ctypes, imp::fd_ops::get_file_like};
+
+/// The events that are always reported, even if not requested.
+const POLL_ALWAYS: u32 = ctypes::POLLERR | ctypes::POLLHUP | ctypes::POLLNVAL;
+
+/// Polls all the file descriptors in `fds`, registers `waker` to them, and
+/// fills their `revents`.
+///
+/// Returns the number of the file descriptors with nonzero `revents`, and
+/// whether all of them will wake `waker` when their states change.
+fn poll_all(fds: &mut [ctypes::pollfd], waker: &Waker) -> (usize, bool) {
+    let mut res_num = 0;
+    let mut notifiable = true;
+    for pfd in fds.iter_mut() {
+        pfd.revents = 0;
+        if pfd.fd < 0 {
+            continue;
+        }
+        let events = pfd.events as u32 | POLL_ALWAYS;
+        let revents = match get_file_like(pfd.fd) {
+            Ok(file) => {
+                notifiable &= file.register_waker(waker);
+                match file.poll() {
+                    Ok(state) => {
+                        let mut revents = 0;
+                        if state.readable {
+                            revents |= ctypes::POLLIN;
+                        }
+                        if state.writable {
+                            revents |= ctypes::POLLOUT;
+                        }
+                        if state.hangup {
+                            revents |= ctypes::POLLHUP;
+                        }
+                        revents
+                    }
+                    Err(e) => {
+                        debug!("    except: {} {:?}", pfd.fd, e);
+                        ctypes::POLLERR
+                    }
+                }
+            }
+            Err(_) => ctypes::POLLNVAL,
+        } & events;
+        if revents != 0 {
+            pfd.revents = revents as _;
+            res_num += 1;
+        }
+    }
+    (res_num, notifiable)
+}
+
+fn poll_until(fds: &mut [ctypes::pollfd], deadline: Option<TimeValue>) -> LinuxResult<c_int> {
+    let waiter = PollWaiter::new();
+    let waker = waiter.waker();
+    loop {
+        #[cfg(feature = "net")]
+        axnet::poll_interfaces();
+        let wakeups = waiter.events();
+        let (res, notifiable) = poll_all(fds, &waker);
+        if res > 0 {
+            return Ok(res as c_int);
+        }
+
+        if deadline.map_or(false, |ddl| current_time() >= ddl) {
+            debug!("    timeout!");
+            return Ok(0);
+        }
+        waiter.wait(wakeups, deadline, notifiable);
+    }
+}
+
+/// Wait for one of the file descriptors in `fds` to become ready to perform
+/// I/O, or until `timeout` milliseconds elapse.
+///
+/// A negative `timeout` means an infinite timeout.
+pub unsafe fn sys_poll(fds: *mut ctypes::pollfd, nfds: ctypes::nfds_t, timeout: c_int) -> c_int {
+    debug!("sys_poll <= {:#x} {} {}", fds as usize, nfds, timeout);
+    syscall_body!(sys_poll, {
+        if fds.is_null() && nfds > 0 {
+            return Err(LinuxError::EFAULT);
+        }
+        let fds = unsafe { poll_fds(fds, nfds) };
+        let deadline = (!timeout.is_negative())
+            .then(|| current_time() + Duration::from_millis(timeout as u64));
+        poll_until(fds, deadline)
+    })
+}
+
+/// Like [`sys_poll`], but with a `timespec` timeout, and a signal mask to
+/// apply while waiting.
+///
+/// A null `timeout` means an infinite timeout. The signal mask is ignored, as
+/// there are no signals.
+pub unsafe fn sys_ppoll(
+    fds: *mut ctypes::pollfd,
+    nfds: ctypes::nfds_t,
+    timeout: *const ctypes::timespec,
+    _sigmask: *const ctypes::sigset_t,
+) -> c_int {
+    debug!(
+        "sys_ppoll <= {:#x} {} {:#x}",
+        fds as usize, nfds, timeout as usize
+    );
+    syscall_body!(sys_ppoll, {
+        if fds.is_null() && nfds > 0 {
+            return Err(LinuxError::EFAULT);
+        }
+        let fds = unsafe { poll_fds(fds, nfds) };
+        let deadline = unsafe { timeout.as_ref().map(|t| current_time() + (*t).into()) };
+        poll_until(fds, deadline)
+    })
+}
+
+unsafe fn poll_fds<'a>(fds: *mut ctypes::pollfd, nfds: ctypes::nfds_t) -> &'a mut [ctypes::pollfd] {
+    if nfds == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(fds, nfds as usize)
+    }
+}
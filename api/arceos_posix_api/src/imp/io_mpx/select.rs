@@ -1,8 +1,10 @@
 use core::ffi::c_int;
+use core::task::Waker;
 
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::current_time;
 
+use super::PollWaiter;
 use crate::{ctypes, imp::fd_ops::get_file_like};
 
 const FD_SETSIZE: usize = 1024;
@@ -46,17 +48,24 @@ impl FdSets {
         Self { nfds, bits }
     }
 
+    /// Polls all the file descriptors in the sets, and registers `waker` to
+    /// them.
+    ///
+    /// Returns the number of the ready ones, and whether all of them will wake
+    /// `waker` when their states change.
     fn poll_all(
         &self,
         res_read_fds: *mut ctypes::fd_set,
         res_write_fds: *mut ctypes::fd_set,
         res_except_fds: *mut ctypes::fd_set,
-    ) -> LinuxResult<usize> {
+        waker: &Waker,
+    ) -> LinuxResult<(usize, bool)> {
         let mut read_bits_ptr = self.bits.as_ptr();
         let mut write_bits_ptr = unsafe { read_bits_ptr.add(FD_SETSIZE_USIZES) };
         let mut execpt_bits_ptr = unsafe { read_bits_ptr.add(FD_SETSIZE_USIZES * 2) };
         let mut i = 0;
         let mut res_num = 0;
+        let mut notifiable = true;
         while i < self.nfds {
             let read_bits = unsafe { *read_bits_ptr };
             let write_bits = unsafe { *write_bits_ptr };
@@ -80,7 +89,9 @@ impl FdSets {
                     continue;
                 }
                 let fd = i + j;
-                match get_file_like(fd as _)?.poll() {
+                let file = get_file_like(fd as _)?;
+                notifiable &= file.register_waker(waker);
+                match file.poll() {
                     Ok(state) => {
                         if state.readable && read_bits & bit != 0 {
                             unsafe { set_fd_set(res_read_fds, fd) };
@@ -103,7 +114,7 @@ impl FdSets {
             }
             i += BITS_PER_USIZE;
         }
-        Ok(res_num)
+        Ok((res_num, notifiable))
     }
 }
 
@@ -133,10 +144,13 @@ pub unsafe fn sys_select(
             zero_fd_set(exceptfds, nfds);
         }
 
+        let waiter = PollWaiter::new();
+        let waker = waiter.waker();
         loop {
             #[cfg(feature = "net")]
             axnet::poll_interfaces();
-            let res = fd_sets.poll_all(readfds, writefds, exceptfds)?;
+            let wakeups = waiter.events();
+            let (res, notifiable) = fd_sets.poll_all(readfds, writefds, exceptfds, &waker)?;
             if res > 0 {
                 return Ok(res);
             }
@@ -145,7 +159,7 @@ pub unsafe fn sys_select(
                 debug!("    timeout!");
                 return Ok(0);
             }
-            crate::sys_sched_yield();
+            waiter.wait(wakeups, deadline, notifiable);
         }
     })
 }
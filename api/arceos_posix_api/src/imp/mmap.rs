@@ -0,0 +1,318 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axmm::{AddrSpace, Backend, MemoryArea, MmapFile};
+use memory_addr::{align_up_4k, is_aligned_4k};
+use spin::Mutex;
+
+use crate::ctypes;
+
+/// The address ranges created by `sys_mmap` and `sys_mremap`, as a map from
+/// the start to the end of each range.
+///
+/// Only these ranges can be unmapped or changed by the mmap family of
+/// syscalls, the other areas of the kernel address space (the kernel image,
+/// task stacks, etc.) are never touched. Only accessed with the kernel address
+/// space locked.
+static MMAP_RANGES: Mutex<MmapRanges> = Mutex::new(MmapRanges(BTreeMap::new()));
+
+struct MmapRanges(BTreeMap<usize, usize>);
+
+impl MmapRanges {
+    fn insert(&mut self, start: VirtAddr, size: usize) {
+        self.remove(start, size);
+        self.0.insert(start.as_usize(), start.as_usize() + size);
+    }
+
+    fn remove(&mut self, start: VirtAddr, size: usize) {
+        let (start, end) = (start.as_usize(), start.as_usize() + size);
+        let overlapped: Vec<_> = self
+            .0
+            .range(..end)
+            .rev()
+            .take_while(|(_, &e)| e > start)
+            .map(|(&s, &e)| (s, e))
+            .collect();
+        for (s, e) in overlapped {
+            self.0.remove(&s);
+            if s < start {
+                self.0.insert(s, start);
+            }
+            if e > end {
+                self.0.insert(end, e);
+            }
+        }
+    }
+
+    /// Whether `[start, end)` is fully covered by the ranges.
+    fn covers(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        let mut pos = start.as_usize();
+        while pos < end.as_usize() {
+            match self.0.range(..=pos).next_back() {
+                Some((_, &e)) if e > pos => pos = e,
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+fn prot_to_flags(prot: c_int) -> MappingFlags {
+    let prot = prot as u32;
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Checks that `[start, start + size)` is in the address space, and that the
+/// mapped parts of it were all created by `mmap`.
+fn check_range(
+    aspace: &AddrSpace,
+    ranges: &MmapRanges,
+    start: VirtAddr,
+    size: usize,
+) -> LinuxResult {
+    if !start.is_aligned_4k() || size == 0 || !aspace.contains_range(start, size) {
+        return Err(LinuxError::EINVAL);
+    }
+    let end = start + size;
+    let untouchable = aspace.areas().any(|area| {
+        let (s, e) = (area.start().max(start), area.end().min(end));
+        s < e && !ranges.covers(s, e)
+    });
+    if untouchable {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+fn mmap_file(fd: c_int) -> LinuxResult<Arc<dyn MmapFile>> {
+    Ok(super::fs::File::from_fd(fd)?)
+}
+
+#[cfg(not(feature = "fs"))]
+fn mmap_file(_fd: c_int) -> LinuxResult<Arc<dyn MmapFile>> {
+    Err(LinuxError::EBADF)
+}
+
+/// Returns the backend of `area` for the part of it starting from `vaddr`.
+fn backend_from(area: &MemoryArea, vaddr: VirtAddr) -> Backend {
+    match area.backend() {
+        Backend::File { file, offset } => Backend::File {
+            file: file.clone(),
+            offset: offset + (vaddr.as_usize() - area.start().as_usize()) as u64,
+        },
+        backend => backend.clone(),
+    }
+}
+
+/// Maps `[start, start + size)` with `backend`, which is taken from an area
+/// created by `sys_mmap`.
+fn map_backend(
+    aspace: &mut AddrSpace,
+    start: VirtAddr,
+    size: usize,
+    flags: MappingFlags,
+    backend: Backend,
+) -> LinuxResult {
+    match backend {
+        Backend::Alloc { populate } => aspace.map_alloc(start, size, flags, populate)?,
+        Backend::File { file, offset } => aspace.map_file(start, size, flags, file, offset)?,
+        Backend::Linear { .. } => return Err(LinuxError::EFAULT),
+    }
+    Ok(())
+}
+
+/// Creates a new mapping in the virtual address space.
+///
+/// Anonymous mappings are backed by pages allocated on the first access (or
+/// at once if `MAP_POPULATE` is specified). For file mappings, only
+/// `MAP_PRIVATE` is supported, each page is loaded from the file on the first
+/// access (the rest of the last page, as well as the pages past EOF, are left
+/// zero).
+///
+/// Return the start address of the mapping, or a negative error code.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "sys_mmap <= addr: {:p}, len: {:#x}, prot: {:#x}, flags: {:#x}, fd: {}, off: {:#x}",
+        addr, len, prot, flags, fd, off
+    );
+    syscall_body!(sys_mmap, {
+        let map_flags = flags as u32;
+        let anonymous = map_flags & ctypes::MAP_ANONYMOUS != 0;
+        if len == 0 || off < 0 || !is_aligned_4k(off as usize) {
+            return Err(LinuxError::EINVAL);
+        }
+        match map_flags & ctypes::MAP_TYPE {
+            ctypes::MAP_PRIVATE => {}
+            // there is no other process to share the anonymous memory with
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE if anonymous => {}
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => return Err(LinuxError::ENODEV),
+            _ => return Err(LinuxError::EINVAL),
+        }
+
+        let file = if anonymous {
+            None
+        } else {
+            Some(mmap_file(fd)?)
+        };
+
+        let size = align_up_4k(len);
+        let mut aspace = axmm::kernel_aspace().lock();
+        let mut ranges = MMAP_RANGES.lock();
+        let start = if map_flags & ctypes::MAP_FIXED != 0 {
+            let start = VirtAddr::from(addr as usize);
+            check_range(&aspace, &ranges, start, size)?;
+            aspace.unmap(start, size)?;
+            ranges.remove(start, size);
+            start
+        } else {
+            aspace
+                .find_free_area(VirtAddr::from(addr as usize), size)
+                .ok_or(LinuxError::ENOMEM)?
+        };
+
+        let mapping_flags = prot_to_flags(prot);
+        match file {
+            Some(file) => aspace.map_file(start, size, mapping_flags, file, off as u64)?,
+            None => {
+                let populate = map_flags & ctypes::MAP_POPULATE != 0;
+                aspace.map_alloc(start, size, mapping_flags, populate)?;
+            }
+        }
+        ranges.insert(start, size);
+        Ok(start.as_usize())
+    })
+}
+
+/// Removes the mappings in the specified address range.
+///
+/// Return 0 if success.
+pub fn sys_munmap(addr: *mut c_void, len: usize) -> c_int {
+    debug!("sys_munmap <= addr: {:p}, len: {:#x}", addr, len);
+    syscall_body!(sys_munmap, {
+        let start = VirtAddr::from(addr as usize);
+        let size = align_up_4k(len);
+        let mut aspace = axmm::kernel_aspace().lock();
+        let mut ranges = MMAP_RANGES.lock();
+        check_range(&aspace, &ranges, start, size)?;
+        aspace.unmap(start, size)?;
+        ranges.remove(start, size);
+        Ok(0)
+    })
+}
+
+/// Changes the access protections of the mappings in the specified address
+/// range.
+///
+/// Return 0 if success.
+pub fn sys_mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int {
+    debug!(
+        "sys_mprotect <= addr: {:p}, len: {:#x}, prot: {:#x}",
+        addr, len, prot
+    );
+    syscall_body!(sys_mprotect, {
+        let start = VirtAddr::from(addr as usize);
+        let size = align_up_4k(len);
+        let mut aspace = axmm::kernel_aspace().lock();
+        check_range(&aspace, &MMAP_RANGES.lock(), start, size)?;
+        aspace.protect(start, size, prot_to_flags(prot))?;
+        Ok(0)
+    })
+}
+
+/// Expands or shrinks an existing mapping, potentially moving it at the same
+/// time (if `MREMAP_MAYMOVE` is specified).
+///
+/// `MREMAP_FIXED` and `MREMAP_DONTUNMAP` are not supported.
+///
+/// Return the start address of the new mapping, or a negative error code.
+pub fn sys_mremap(
+    old_addr: *mut c_void,
+    old_size: usize,
+    new_size: usize,
+    flags: c_int,
+) -> *mut c_void {
+    debug!(
+        "sys_mremap <= old_addr: {:p}, old_size: {:#x}, new_size: {:#x}, flags: {:#x}",
+        old_addr, old_size, new_size, flags
+    );
+    syscall_body!(sys_mremap, {
+        let flags = flags as u32;
+        if flags & !ctypes::MREMAP_MAYMOVE != 0 || new_size == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let old_start = VirtAddr::from(old_addr as usize);
+        let old_size = align_up_4k(old_size);
+        let new_size = align_up_4k(new_size);
+        let mut aspace = axmm::kernel_aspace().lock();
+        let mut ranges = MMAP_RANGES.lock();
+        check_range(&aspace, &ranges, old_start, old_size)?;
+
+        let old_end = old_start + old_size;
+        let (mapping_flags, backend, backend_at_end) = match aspace.find_area(old_start) {
+            Some(area) if area.end() >= old_end => (
+                area.flags(),
+                backend_from(area, old_start),
+                backend_from(area, old_end),
+            ),
+            _ => return Err(LinuxError::EFAULT),
+        };
+
+        if new_size <= old_size {
+            if new_size < old_size {
+                aspace.unmap(old_start + new_size, old_size - new_size)?;
+                ranges.remove(old_start + new_size, old_size - new_size);
+            }
+            return Ok(old_start.as_usize());
+        }
+
+        // try to expand in place
+        if aspace.find_free_area(old_end, new_size - old_size) == Some(old_end) {
+            let size = new_size - old_size;
+            map_backend(&mut aspace, old_end, size, mapping_flags, backend_at_end)?;
+            ranges.insert(old_end, new_size - old_size);
+            return Ok(old_start.as_usize());
+        }
+        if flags & ctypes::MREMAP_MAYMOVE == 0 {
+            return Err(LinuxError::ENOMEM);
+        }
+
+        // move to a new place, only the populated pages are copied, the
+        // others are still populated by the backend
+        let new_start = aspace
+            .find_free_area(aspace.base(), new_size)
+            .ok_or(LinuxError::ENOMEM)?;
+        map_backend(&mut aspace, new_start, new_size, mapping_flags, backend)?;
+        for off in (0..old_size).step_by(PAGE_SIZE_4K) {
+            if aspace.page_table().query(old_start + off).is_ok() {
+                aspace.copy_page(old_start + off, new_start + off)?;
+            }
+        }
+        aspace.unmap(old_start, old_size)?;
+        ranges.remove(old_start, old_size);
+        ranges.insert(new_start, new_size);
+        Ok(new_start.as_usize())
+    })
+}
@@ -10,11 +10,21 @@ pub mod time;
 pub mod fd_ops;
 #[cfg(feature = "fs")]
 pub mod fs;
-#[cfg(any(feature = "select", feature = "epoll"))]
+#[cfg(any(
+    feature = "select",
+    feature = "epoll",
+    feature = "poll",
+    feature = "net",
+    feature = "pipe"
+))]
 pub mod io_mpx;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 #[cfg(feature = "net")]
 pub mod net;
 #[cfg(feature = "pipe")]
 pub mod pipe;
 #[cfg(feature = "multitask")]
 pub mod pthread;
+#[cfg(feature = "net")]
+pub mod unix;
@@ -1,20 +1,38 @@
 use alloc::{sync::Arc, vec, vec::Vec};
 use core::ffi::{c_char, c_int, c_void};
 use core::mem::size_of;
-use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::task::Waker;
+use core::time::Duration;
 
-use axerrno::{LinuxError, LinuxResult};
+use axerrno::{AxError, LinuxError, LinuxResult};
 use axio::PollState;
-use axnet::{TcpSocket, UdpSocket};
+use axnet::{IcmpSocket, RawSocket, TcpSocket, UdpSocket};
 use axsync::Mutex;
 
 use super::fd_ops::FileLike;
+use super::unix::{UnixAddr, UnixSocket};
 use crate::ctypes;
 use crate::utils::char_ptr_to_str;
 
-pub enum Socket {
+/// The interval of the keep-alive packets when `SO_KEEPALIVE` is set, the same
+/// as the default of `TCP_KEEPINTVL` on Linux.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(75);
+
+/// A socket of `AF_INET` or `AF_INET6`.
+pub struct Socket {
+    /// The address family, only addresses of it are accepted.
+    domain: u32,
+    inner: SocketInner,
+}
+
+enum SocketInner {
     Udp(Mutex<UdpSocket>),
     Tcp(Mutex<TcpSocket>),
+    /// An ICMP echo ("ping") socket, created by `SOCK_DGRAM` with
+    /// `IPPROTO_ICMP` or `IPPROTO_ICMPV6`.
+    Icmp(Mutex<IcmpSocket>),
+    Raw(Mutex<RawSocket>),
 }
 
 impl Socket {
@@ -29,103 +47,177 @@ impl Socket {
             .map_err(|_| LinuxError::EINVAL)
     }
 
+    /// Loads the address from `addr`, which must be of the socket's family.
+    fn load_addr(
+        &self,
+        addr: *const ctypes::sockaddr,
+        addrlen: ctypes::socklen_t,
+    ) -> LinuxResult<SocketAddr> {
+        let ipv6 = self.domain == ctypes::AF_INET6;
+        if ipv6 && (addrlen as usize) < size_of::<ctypes::sockaddr_in6>() {
+            return Err(LinuxError::EINVAL);
+        }
+        match from_sockaddr(addr, addrlen)? {
+            addr @ SocketAddr::V4(_) if !ipv6 => Ok(addr),
+            addr @ SocketAddr::V6(_) if ipv6 => Ok(addr),
+            _ => Err(LinuxError::EAFNOSUPPORT),
+        }
+    }
+
     fn send(&self, buf: &[u8]) -> LinuxResult<usize> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().send(buf)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().send(buf)?),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().send(buf)?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().send(buf)?),
         }
     }
 
     fn recv(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().recv_from(buf).map(|e| e.0)?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().recv_from(buf).map(|e| e.0)?),
         }
     }
 
     pub fn poll(&self) -> LinuxResult<PollState> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().poll()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().poll()?),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().poll()?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().poll()?),
         }
     }
 
     fn local_addr(&self) -> LinuxResult<SocketAddr> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().local_addr()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().local_addr()?),
+            // the identifier takes the place of the port, as on Linux
+            SocketInner::Icmp(icmpsocket) => {
+                let icmpsocket = icmpsocket.lock();
+                Ok(SocketAddr::new(
+                    icmpsocket.local_addr()?,
+                    icmpsocket.ident(),
+                ))
+            }
+            SocketInner::Raw(rawsocket) => Ok(SocketAddr::new(rawsocket.lock().local_addr()?, 0)),
         }
     }
 
     fn peer_addr(&self) -> LinuxResult<SocketAddr> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().peer_addr()?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().peer_addr()?),
+            SocketInner::Icmp(icmpsocket) => Ok(SocketAddr::new(icmpsocket.lock().peer_addr()?, 0)),
+            SocketInner::Raw(rawsocket) => Ok(SocketAddr::new(rawsocket.lock().peer_addr()?, 0)),
         }
     }
 
     fn bind(&self, addr: SocketAddr) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().bind(addr)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().bind(addr)?),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().bind(addr.ip())?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().bind(addr.ip())?),
         }
     }
 
     fn connect(&self, addr: SocketAddr) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().connect(addr)?),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().connect(addr)?),
+            SocketInner::Tcp(tcpsocket) => match tcpsocket.lock().connect(addr) {
+                // the result of a nonblocking connection is got by `SO_ERROR`
+                Err(AxError::WouldBlock) => Err(LinuxError::EINPROGRESS),
+                res => Ok(res?),
+            },
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().connect(addr.ip())?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().connect(addr.ip())?),
         }
     }
 
     fn sendto(&self, buf: &[u8], addr: SocketAddr) -> LinuxResult<usize> {
-        match self {
+        match &self.inner {
             // diff: must bind before sendto
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
-            Socket::Tcp(_) => Err(LinuxError::EISCONN),
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
+            SocketInner::Tcp(_) => Err(LinuxError::EISCONN),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().send_to(buf, addr.ip())?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().send_to(buf, addr.ip())?),
         }
     }
 
     fn recvfrom(&self, buf: &mut [u8]) -> LinuxResult<(usize, Option<SocketAddr>)> {
-        match self {
+        match &self.inner {
             // diff: must bind before recvfrom
-            Socket::Udp(udpsocket) => Ok(udpsocket
+            SocketInner::Udp(udpsocket) => Ok(udpsocket
                 .lock()
                 .recv_from(buf)
                 .map(|res| (res.0, Some(res.1)))?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, Some(SocketAddr::new(res.1, 0))))?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket
+                .lock()
+                .recv_from(buf)
+                .map(|res| (res.0, Some(SocketAddr::new(res.1, 0))))?),
         }
     }
 
     fn listen(&self) -> LinuxResult {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+        match &self.inner {
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
     fn accept(&self) -> LinuxResult<TcpSocket> {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+        match &self.inner {
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+            _ => Err(LinuxError::EOPNOTSUPP),
         }
     }
 
     fn shutdown(&self) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => {
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => {
                 let udpsocket = udpsocket.lock();
                 udpsocket.peer_addr()?;
                 udpsocket.shutdown()?;
                 Ok(())
             }
 
-            Socket::Tcp(tcpsocket) => {
+            SocketInner::Tcp(tcpsocket) => {
                 let tcpsocket = tcpsocket.lock();
                 tcpsocket.peer_addr()?;
                 tcpsocket.shutdown()?;
                 Ok(())
             }
+
+            SocketInner::Icmp(icmpsocket) => Ok(icmpsocket.lock().shutdown()?),
+            SocketInner::Raw(rawsocket) => Ok(rawsocket.lock().shutdown()?),
+        }
+    }
+}
+
+/// A socket of `AF_INET`, `AF_INET6` or `AF_UNIX`.
+enum AnySocket {
+    Inet(Arc<Socket>),
+    Unix(Arc<UnixSocket>),
+}
+
+impl AnySocket {
+    fn from_fd(fd: c_int) -> LinuxResult<Self> {
+        let f = super::fd_ops::get_file_like(fd)?.into_any();
+        match f.downcast::<Socket>() {
+            Ok(socket) => Ok(Self::Inet(socket)),
+            Err(f) => f
+                .downcast::<UnixSocket>()
+                .map(Self::Unix)
+                .map_err(|_| LinuxError::EINVAL),
         }
     }
 }
@@ -162,12 +254,23 @@ impl FileLike for Socket {
     }
 
     fn set_nonblocking(&self, nonblock: bool) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
-            Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
+            SocketInner::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+            SocketInner::Icmp(icmpsocket) => icmpsocket.lock().set_nonblocking(nonblock),
+            SocketInner::Raw(rawsocket) => rawsocket.lock().set_nonblocking(nonblock),
         }
         Ok(())
     }
+
+    fn register_waker(&self, waker: &Waker) -> bool {
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => udpsocket.lock().register_waker(waker),
+            SocketInner::Tcp(tcpsocket) => tcpsocket.lock().register_waker(waker),
+            SocketInner::Icmp(icmpsocket) => icmpsocket.lock().register_waker(waker),
+            SocketInner::Raw(rawsocket) => rawsocket.lock().register_waker(waker),
+        }
+    }
 }
 
 impl From<SocketAddrV4> for ctypes::sockaddr_in {
@@ -194,14 +297,50 @@ impl From<ctypes::sockaddr_in> for SocketAddrV4 {
     }
 }
 
-fn into_sockaddr(addr: SocketAddr) -> (ctypes::sockaddr, ctypes::socklen_t) {
+impl From<SocketAddrV6> for ctypes::sockaddr_in6 {
+    fn from(addr: SocketAddrV6) -> ctypes::sockaddr_in6 {
+        ctypes::sockaddr_in6 {
+            sin6_family: ctypes::AF_INET6 as u16,
+            sin6_port: addr.port().to_be(),
+            sin6_flowinfo: addr.flowinfo().to_be(),
+            sin6_addr: ctypes::in6_addr {
+                __in6_union: ctypes::in6_addr__bindgen_ty_1 {
+                    __s6_addr: addr.ip().octets(),
+                },
+            },
+            sin6_scope_id: addr.scope_id(),
+        }
+    }
+}
+
+impl From<ctypes::sockaddr_in6> for SocketAddrV6 {
+    fn from(addr: ctypes::sockaddr_in6) -> SocketAddrV6 {
+        SocketAddrV6::new(
+            Ipv6Addr::from(unsafe { addr.sin6_addr.__in6_union.__s6_addr }),
+            u16::from_be(addr.sin6_port),
+            u32::from_be(addr.sin6_flowinfo),
+            addr.sin6_scope_id,
+        )
+    }
+}
+
+/// Writes `addr` to the buffer `dst` of `*addrlen` bytes, and sets `*addrlen`
+/// to the size of the address. The address is truncated if the buffer is too
+/// small.
+unsafe fn write_sockaddr(
+    addr: SocketAddr,
+    dst: *mut ctypes::sockaddr,
+    addrlen: *mut ctypes::socklen_t,
+) {
     debug!("    Sockaddr: {}", addr);
+    unsafe fn write<T>(sockaddr: T, dst: *mut ctypes::sockaddr, addrlen: *mut ctypes::socklen_t) {
+        let len = (*addrlen as usize).min(size_of::<T>());
+        core::ptr::copy_nonoverlapping(&sockaddr as *const T as *const u8, dst as *mut u8, len);
+        *addrlen = size_of::<T>() as _;
+    }
     match addr {
-        SocketAddr::V4(addr) => (
-            unsafe { *(&ctypes::sockaddr_in::from(addr) as *const _ as *const ctypes::sockaddr) },
-            size_of::<ctypes::sockaddr>() as _,
-        ),
-        SocketAddr::V6(_) => panic!("IPv6 is not supported"),
+        SocketAddr::V4(addr) => write(ctypes::sockaddr_in::from(addr), dst, addrlen),
+        SocketAddr::V6(addr) => write(ctypes::sockaddr_in6::from(addr), dst, addrlen),
     }
 }
 
@@ -212,38 +351,63 @@ fn from_sockaddr(
     if addr.is_null() {
         return Err(LinuxError::EFAULT);
     }
-    if addrlen != size_of::<ctypes::sockaddr>() as _ {
-        return Err(LinuxError::EINVAL);
-    }
-
-    let mid = unsafe { *(addr as *const ctypes::sockaddr_in) };
-    if mid.sin_family != ctypes::AF_INET as u16 {
+    if (addrlen as usize) < size_of::<ctypes::sockaddr>() {
         return Err(LinuxError::EINVAL);
     }
 
-    let res = SocketAddr::V4(mid.into());
+    let res = match unsafe { (*addr).sa_family } as u32 {
+        ctypes::AF_INET => SocketAddr::V4(unsafe { *(addr as *const ctypes::sockaddr_in) }.into()),
+        ctypes::AF_INET6 if addrlen as usize >= size_of::<ctypes::sockaddr_in6>() => {
+            SocketAddr::V6(unsafe { *(addr as *const ctypes::sockaddr_in6) }.into())
+        }
+        _ => return Err(LinuxError::EINVAL),
+    };
     debug!("    load sockaddr:{:#x} => {:?}", addr as usize, res);
     Ok(res)
 }
 
 /// Create an socket for communication.
 ///
+/// Besides TCP and UDP sockets, it supports ICMP echo ("ping") sockets by
+/// `SOCK_DGRAM` with `IPPROTO_ICMP` (or `IPPROTO_ICMPV6`), raw sockets by
+/// `SOCK_RAW` with an IP protocol number, and Unix domain sockets by `AF_UNIX`.
+///
 /// Return the socket file descriptor.
 pub fn sys_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
     debug!("sys_socket <= {} {} {}", domain, socktype, protocol);
     let (domain, socktype, protocol) = (domain as u32, socktype as u32, protocol as u32);
     syscall_body!(sys_socket, {
-        match (domain, socktype, protocol) {
-            (ctypes::AF_INET, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
-            | (ctypes::AF_INET, ctypes::SOCK_STREAM, 0) => {
-                Socket::Tcp(Mutex::new(TcpSocket::new())).add_to_fd_table()
+        if domain == ctypes::AF_UNIX {
+            let socket = UnixSocket::new(socktype, protocol)?;
+            return super::fd_ops::add_file_like(Arc::new(socket));
+        }
+        if domain != ctypes::AF_INET && domain != ctypes::AF_INET6 {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+        let inner = match (socktype, protocol) {
+            (ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP) | (ctypes::SOCK_STREAM, 0) => {
+                SocketInner::Tcp(Mutex::new(TcpSocket::new()))
             }
-            (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
-            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => {
-                Socket::Udp(Mutex::new(UdpSocket::new())).add_to_fd_table()
+            (ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP) | (ctypes::SOCK_DGRAM, 0) => {
+                SocketInner::Udp(Mutex::new(UdpSocket::new()))
             }
-            _ => Err(LinuxError::EINVAL),
-        }
+            (ctypes::SOCK_DGRAM, ctypes::IPPROTO_ICMP) if domain == ctypes::AF_INET => {
+                SocketInner::Icmp(Mutex::new(IcmpSocket::new()))
+            }
+            (ctypes::SOCK_DGRAM, ctypes::IPPROTO_ICMPV6) if domain == ctypes::AF_INET6 => {
+                SocketInner::Icmp(Mutex::new(IcmpSocket::new()))
+            }
+            (ctypes::SOCK_RAW, 0) | (ctypes::SOCK_RAW, ctypes::IPPROTO_RAW) => {
+                // sending packets with the IP header given is not supported
+                return Err(LinuxError::EPROTONOSUPPORT);
+            }
+            (ctypes::SOCK_RAW, protocol @ 0..256) => {
+                let ipv6 = domain == ctypes::AF_INET6;
+                SocketInner::Raw(Mutex::new(RawSocket::new(ipv6, protocol as u8)))
+            }
+            _ => return Err(LinuxError::EINVAL),
+        };
+        Socket { domain, inner }.add_to_fd_table()
     })
 }
 
@@ -260,8 +424,12 @@ pub fn sys_bind(
         socket_fd, socket_addr as usize, addrlen
     );
     syscall_body!(sys_bind, {
-        let addr = from_sockaddr(socket_addr, addrlen)?;
-        Socket::from_fd(socket_fd)?.bind(addr)?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.bind(socket.load_addr(socket_addr, addrlen)?)?,
+            AnySocket::Unix(socket) => {
+                socket.bind(unsafe { UnixAddr::from_sockaddr(socket_addr, addrlen)? })?
+            }
+        }
         Ok(0)
     })
 }
@@ -279,8 +447,12 @@ pub fn sys_connect(
         socket_fd, socket_addr as usize, addrlen
     );
     syscall_body!(sys_connect, {
-        let addr = from_sockaddr(socket_addr, addrlen)?;
-        Socket::from_fd(socket_fd)?.connect(addr)?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.connect(socket.load_addr(socket_addr, addrlen)?)?,
+            AnySocket::Unix(socket) => {
+                socket.connect(unsafe { UnixAddr::from_sockaddr(socket_addr, addrlen)? })?
+            }
+        }
         Ok(0)
     })
 }
@@ -304,9 +476,14 @@ pub fn sys_sendto(
         if buf_ptr.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let addr = from_sockaddr(socket_addr, addrlen)?;
         let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, len) };
-        Socket::from_fd(socket_fd)?.sendto(buf, addr)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.sendto(buf, socket.load_addr(socket_addr, addrlen)?),
+            AnySocket::Unix(socket) => {
+                let addr = unsafe { UnixAddr::from_sockaddr(socket_addr, addrlen)? };
+                socket.send_to(buf, addr)
+            }
+        }
     })
 }
 
@@ -328,7 +505,10 @@ pub fn sys_send(
             return Err(LinuxError::EFAULT);
         }
         let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, len) };
-        Socket::from_fd(socket_fd)?.send(buf)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.send(buf),
+            AnySocket::Unix(socket) => socket.send(buf),
+        }
     })
 }
 
@@ -351,16 +531,24 @@ pub unsafe fn sys_recvfrom(
         if buf_ptr.is_null() || socket_addr.is_null() || addrlen.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let socket = Socket::from_fd(socket_fd)?;
         let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
 
-        let res = socket.recvfrom(buf)?;
-        if let Some(addr) = res.1 {
-            unsafe {
-                (*socket_addr, *addrlen) = into_sockaddr(addr);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => {
+                let res = socket.recvfrom(buf)?;
+                if let Some(addr) = res.1 {
+                    unsafe { write_sockaddr(addr, socket_addr, addrlen) };
+                }
+                Ok(res.0)
+            }
+            AnySocket::Unix(socket) => {
+                let res = socket.recv_from(buf)?;
+                if let Some(addr) = res.1 {
+                    unsafe { addr.write_to(socket_addr, addrlen) };
+                }
+                Ok(res.0)
             }
         }
-        Ok(res.0)
     })
 }
 
@@ -382,20 +570,25 @@ pub fn sys_recv(
             return Err(LinuxError::EFAULT);
         }
         let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
-        Socket::from_fd(socket_fd)?.recv(buf)
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.recv(buf),
+            AnySocket::Unix(socket) => socket.recv(buf),
+        }
     })
 }
 
 /// Listen for connections on a socket
 ///
+/// The `backlog` is only used by Unix domain sockets.
+///
 /// Return 0 if success.
-pub fn sys_listen(
-    socket_fd: c_int,
-    backlog: c_int, // currently not used
-) -> c_int {
+pub fn sys_listen(socket_fd: c_int, backlog: c_int) -> c_int {
     debug!("sys_listen <= {} {}", socket_fd, backlog);
     syscall_body!(sys_listen, {
-        Socket::from_fd(socket_fd)?.listen()?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.listen()?,
+            AnySocket::Unix(socket) => socket.listen(backlog)?,
+        }
         Ok(0)
     })
 }
@@ -416,41 +609,55 @@ pub unsafe fn sys_accept(
         if socket_addr.is_null() || socket_len.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let socket = Socket::from_fd(socket_fd)?;
-        let new_socket = socket.accept()?;
-        let addr = new_socket.peer_addr()?;
-        let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
-        unsafe {
-            (*socket_addr, *socket_len) = into_sockaddr(addr);
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => {
+                let new_socket = socket.accept()?;
+                let addr = new_socket.peer_addr()?;
+                let new_fd = Socket {
+                    domain: socket.domain,
+                    inner: SocketInner::Tcp(Mutex::new(new_socket)),
+                }
+                .add_to_fd_table()?;
+                unsafe { write_sockaddr(addr, socket_addr, socket_len) };
+                Ok(new_fd)
+            }
+            AnySocket::Unix(socket) => {
+                let (new_socket, addr) = socket.accept()?;
+                let new_fd = super::fd_ops::add_file_like(Arc::new(new_socket))?;
+                unsafe { addr.write_to(socket_addr, socket_len) };
+                Ok(new_fd)
+            }
         }
-        Ok(new_fd)
     })
 }
 
 /// Shut down a full-duplex connection.
 ///
+/// The `flag` is only used by Unix domain sockets, while both directions of
+/// the other sockets are always shut down.
+///
 /// Return 0 if success.
-pub fn sys_shutdown(
-    socket_fd: c_int,
-    flag: c_int, // currently not used
-) -> c_int {
+pub fn sys_shutdown(socket_fd: c_int, flag: c_int) -> c_int {
     debug!("sys_shutdown <= {} {}", socket_fd, flag);
     syscall_body!(sys_shutdown, {
-        Socket::from_fd(socket_fd)?.shutdown()?;
+        match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket.shutdown()?,
+            AnySocket::Unix(socket) => socket.shutdown(flag)?,
+        }
         Ok(0)
     })
 }
 
 /// Query addresses for a domain name.
 ///
-/// Only IPv4. Ports are always 0. Ignore servname and hint.
+/// Only the address family of the hint is used.
 /// Results' ai_flags and ai_canonname are 0 or NULL.
 ///
 /// Return address number if success.
 pub unsafe fn sys_getaddrinfo(
     nodename: *const c_char,
     servname: *const c_char,
-    _hints: *const ctypes::addrinfo,
+    hints: *const ctypes::addrinfo,
     res: *mut *mut ctypes::addrinfo,
 ) -> c_int {
     let name = char_ptr_to_str(nodename);
@@ -465,7 +672,7 @@ pub unsafe fn sys_getaddrinfo(
         }
 
         let port = port.map_or(0, |p| p.parse::<u16>().unwrap_or(0));
-        let ip_addrs = if let Ok(domain) = name {
+        let mut ip_addrs = if let Ok(domain) = name {
             if let Ok(a) = domain.parse::<IpAddr>() {
                 vec![a]
             } else {
@@ -474,6 +681,13 @@ pub unsafe fn sys_getaddrinfo(
         } else {
             vec![Ipv4Addr::LOCALHOST.into()]
         };
+        if !hints.is_null() {
+            match unsafe { (*hints).ai_family } as u32 {
+                ctypes::AF_INET => ip_addrs.retain(IpAddr::is_ipv4),
+                ctypes::AF_INET6 => ip_addrs.retain(IpAddr::is_ipv6),
+                _ => {}
+            }
+        }
 
         let len = ip_addrs.len().min(ctypes::MAXADDRS as usize);
         if len == 0 {
@@ -482,27 +696,38 @@ pub unsafe fn sys_getaddrinfo(
 
         let mut out: Vec<ctypes::aibuf> = Vec::with_capacity(len);
         for (i, &ip) in ip_addrs.iter().enumerate().take(len) {
-            let buf = match ip {
-                IpAddr::V4(ip) => ctypes::aibuf {
-                    ai: ctypes::addrinfo {
-                        ai_family: ctypes::AF_INET as _,
-                        // TODO: This is a hard-code part, only return TCP parameters
-                        ai_socktype: ctypes::SOCK_STREAM as _,
-                        ai_protocol: ctypes::IPPROTO_TCP as _,
-                        ai_addrlen: size_of::<ctypes::sockaddr_in>() as _,
-                        ai_addr: core::ptr::null_mut(),
-                        ai_canonname: core::ptr::null_mut(),
-                        ai_next: core::ptr::null_mut(),
-                        ai_flags: 0,
-                    },
-                    sa: ctypes::aibuf_sa {
+            let (family, addrlen, sa) = match ip {
+                IpAddr::V4(ip) => (
+                    ctypes::AF_INET,
+                    size_of::<ctypes::sockaddr_in>(),
+                    ctypes::aibuf_sa {
                         sin: SocketAddrV4::new(ip, port).into(),
                     },
-                    slot: i as i16,
-                    lock: [0],
-                    ref_: 0,
+                ),
+                IpAddr::V6(ip) => (
+                    ctypes::AF_INET6,
+                    size_of::<ctypes::sockaddr_in6>(),
+                    ctypes::aibuf_sa {
+                        sin6: SocketAddrV6::new(ip, port, 0, 0).into(),
+                    },
+                ),
+            };
+            let buf = ctypes::aibuf {
+                ai: ctypes::addrinfo {
+                    ai_family: family as _,
+                    // TODO: This is a hard-code part, only return TCP parameters
+                    ai_socktype: ctypes::SOCK_STREAM as _,
+                    ai_protocol: ctypes::IPPROTO_TCP as _,
+                    ai_addrlen: addrlen as _,
+                    ai_addr: core::ptr::null_mut(),
+                    ai_canonname: core::ptr::null_mut(),
+                    ai_next: core::ptr::null_mut(),
+                    ai_flags: 0,
                 },
-                _ => panic!("IPv6 is not supported"),
+                sa,
+                slot: i as i16,
+                lock: [0],
+                ref_: 0,
             };
             out.push(buf);
             out[i].ai.ai_addr =
@@ -549,8 +774,11 @@ pub unsafe fn sys_getsockname(
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.local_addr()?);
+        match AnySocket::from_fd(sock_fd)? {
+            AnySocket::Inet(socket) => unsafe {
+                write_sockaddr(socket.local_addr()?, addr, addrlen)
+            },
+            AnySocket::Unix(socket) => unsafe { socket.local_addr().write_to(addr, addrlen) },
         }
         Ok(0)
     })
@@ -573,8 +801,269 @@ pub unsafe fn sys_getpeername(
         if unsafe { *addrlen } < size_of::<ctypes::sockaddr>() as u32 {
             return Err(LinuxError::EINVAL);
         }
-        unsafe {
-            (*addr, *addrlen) = into_sockaddr(Socket::from_fd(sock_fd)?.peer_addr()?);
+        match AnySocket::from_fd(sock_fd)? {
+            AnySocket::Inet(socket) => unsafe {
+                write_sockaddr(socket.peer_addr()?, addr, addrlen)
+            },
+            AnySocket::Unix(socket) => unsafe { socket.peer_addr()?.write_to(addr, addrlen) },
+        }
+        Ok(0)
+    })
+}
+
+/// Reads an option value of type `T` from the buffer `optval` of `optlen`
+/// bytes.
+unsafe fn read_optval<T: Copy>(optval: *const c_void, optlen: ctypes::socklen_t) -> LinuxResult<T> {
+    if optval.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if (optlen as usize) < size_of::<T>() {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok((optval as *const T).read_unaligned())
+}
+
+/// Writes an option value to the buffer `optval` of `*optlen` bytes, and sets
+/// `*optlen` to the number of bytes written. The value is truncated if the
+/// buffer is too small.
+unsafe fn write_optval<T>(
+    val: T,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> LinuxResult {
+    if optval.is_null() || optlen.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let len = (*optlen as usize).min(size_of::<T>());
+    core::ptr::copy_nonoverlapping(&val as *const T as *const u8, optval as *mut u8, len);
+    *optlen = len as _;
+    Ok(())
+}
+
+/// Reads the timeout of `SO_RCVTIMEO` or `SO_SNDTIMEO`, where zero means no
+/// timeout.
+unsafe fn read_timeout(
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> LinuxResult<Option<Duration>> {
+    let tv = read_optval::<ctypes::timeval>(optval, optlen)?;
+    if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+        return Err(LinuxError::EDOM);
+    }
+    let timeout = Duration::from(tv);
+    Ok((!timeout.is_zero()).then_some(timeout))
+}
+
+/// Set options on a socket.
+///
+/// The supported options are `SO_REUSEADDR`, `SO_RCVBUF`, `SO_SNDBUF`,
+/// `SO_RCVTIMEO`, `SO_SNDTIMEO`, `SO_KEEPALIVE` and `SO_LINGER` at the
+/// `SOL_SOCKET` level, and `TCP_NODELAY` at the `IPPROTO_TCP` level. Other
+/// options, and all options of Unix domain sockets, are ignored.
+///
+/// Return 0 if success.
+pub unsafe fn sys_setsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *const c_void,
+    optlen: ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_setsockopt <= {} {} {} {:#x} {}",
+        socket_fd, level, optname, optval as usize, optlen
+    );
+    syscall_body!(sys_setsockopt, {
+        let AnySocket::Inet(socket) = AnySocket::from_fd(socket_fd)? else {
+            warn!(
+                "sys_setsockopt: ignored option {} at level {} of unix socket",
+                optname, level
+            );
+            return Ok(0);
+        };
+        match (level as u32, optname as u32) {
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                let reuse = unsafe { read_optval::<c_int>(optval, optlen)? } != 0;
+                match &socket.inner {
+                    SocketInner::Tcp(tcpsocket) => tcpsocket.lock().set_reuse_address(reuse),
+                    SocketInner::Udp(udpsocket) => udpsocket.lock().set_reuse_address(reuse),
+                    _ => {}
+                }
+            }
+            (ctypes::SOL_SOCKET, optname @ (ctypes::SO_RCVBUF | ctypes::SO_SNDBUF)) => {
+                let size = unsafe { read_optval::<c_int>(optval, optlen)? }.max(0) as usize;
+                match (&socket.inner, optname) {
+                    (SocketInner::Tcp(tcpsocket), ctypes::SO_RCVBUF) => {
+                        tcpsocket.lock().set_recv_buffer_size(size)?
+                    }
+                    (SocketInner::Tcp(tcpsocket), _) => {
+                        tcpsocket.lock().set_send_buffer_size(size)?
+                    }
+                    (SocketInner::Udp(udpsocket), ctypes::SO_RCVBUF) => {
+                        udpsocket.lock().set_recv_buffer_size(size)?
+                    }
+                    (SocketInner::Udp(udpsocket), _) => {
+                        udpsocket.lock().set_send_buffer_size(size)?
+                    }
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, optname @ (ctypes::SO_RCVTIMEO | ctypes::SO_SNDTIMEO)) => {
+                let timeout = unsafe { read_timeout(optval, optlen)? };
+                match (&socket.inner, optname) {
+                    (SocketInner::Tcp(tcpsocket), ctypes::SO_RCVTIMEO) => {
+                        tcpsocket.lock().set_recv_timeout(timeout)?
+                    }
+                    (SocketInner::Tcp(tcpsocket), _) => {
+                        tcpsocket.lock().set_send_timeout(timeout)?
+                    }
+                    (SocketInner::Udp(udpsocket), ctypes::SO_RCVTIMEO) => {
+                        udpsocket.lock().set_recv_timeout(timeout)?
+                    }
+                    (SocketInner::Udp(udpsocket), _) => {
+                        udpsocket.lock().set_send_timeout(timeout)?
+                    }
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_KEEPALIVE) => {
+                let keep_alive = unsafe { read_optval::<c_int>(optval, optlen)? } != 0;
+                if let SocketInner::Tcp(tcpsocket) = &socket.inner {
+                    let interval = keep_alive.then_some(KEEP_ALIVE_INTERVAL);
+                    tcpsocket.lock().set_keep_alive(interval)?;
+                }
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                let linger = unsafe { read_optval::<ctypes::linger>(optval, optlen)? };
+                let linger = (linger.l_onoff != 0)
+                    .then(|| Duration::from_secs(linger.l_linger.max(0) as u64));
+                if let SocketInner::Tcp(tcpsocket) = &socket.inner {
+                    tcpsocket.lock().set_linger(linger);
+                }
+            }
+            (ctypes::IPPROTO_TCP, ctypes::TCP_NODELAY) => {
+                let SocketInner::Tcp(tcpsocket) = &socket.inner else {
+                    return Err(LinuxError::ENOPROTOOPT);
+                };
+                let nodelay = unsafe { read_optval::<c_int>(optval, optlen)? } != 0;
+                tcpsocket.lock().set_nodelay(nodelay);
+            }
+            (level, optname) => {
+                warn!(
+                    "sys_setsockopt: ignored option {} at level {}",
+                    optname, level
+                );
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Get options on a socket.
+///
+/// The supported options are the ones of [`sys_setsockopt`], and `SO_ERROR`.
+/// Unix domain sockets only support `SO_ERROR`, which is always 0.
+///
+/// Return 0 if success.
+pub unsafe fn sys_getsockopt(
+    socket_fd: c_int,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut ctypes::socklen_t,
+) -> c_int {
+    debug!(
+        "sys_getsockopt <= {} {} {} {:#x} {:#x}",
+        socket_fd, level, optname, optval as usize, optlen as usize
+    );
+    syscall_body!(sys_getsockopt, {
+        let socket = match AnySocket::from_fd(socket_fd)? {
+            AnySocket::Inet(socket) => socket,
+            AnySocket::Unix(_) => {
+                if (level as u32, optname as u32) != (ctypes::SOL_SOCKET, ctypes::SO_ERROR) {
+                    return Err(LinuxError::ENOPROTOOPT);
+                }
+                unsafe { write_optval(0 as c_int, optval, optlen)? };
+                return Ok(0);
+            }
+        };
+        let tcpsocket = match &socket.inner {
+            SocketInner::Tcp(tcpsocket) => Some(tcpsocket.lock()),
+            _ => None,
+        };
+        let udpsocket = match &socket.inner {
+            SocketInner::Udp(udpsocket) => Some(udpsocket.lock()),
+            _ => None,
+        };
+        let write_int = |val: c_int| unsafe { write_optval(val, optval, optlen) };
+        let write_timeout = |timeout: Option<Duration>| {
+            let tv = ctypes::timeval::from(timeout.unwrap_or_default());
+            unsafe { write_optval(tv, optval, optlen) }
+        };
+        match (level as u32, optname as u32) {
+            (ctypes::SOL_SOCKET, ctypes::SO_REUSEADDR) => {
+                let reuse = match (&tcpsocket, &udpsocket) {
+                    (Some(tcpsocket), _) => tcpsocket.reuse_address(),
+                    (_, Some(udpsocket)) => udpsocket.reuse_address(),
+                    _ => false,
+                };
+                write_int(reuse as c_int)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVBUF) => {
+                let size = match (&tcpsocket, &udpsocket) {
+                    (Some(tcpsocket), _) => tcpsocket.recv_buffer_size(),
+                    (_, Some(udpsocket)) => udpsocket.recv_buffer_size(),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                };
+                write_int(size as c_int)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_SNDBUF) => {
+                let size = match (&tcpsocket, &udpsocket) {
+                    (Some(tcpsocket), _) => tcpsocket.send_buffer_size(),
+                    (_, Some(udpsocket)) => udpsocket.send_buffer_size(),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                };
+                write_int(size as c_int)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_RCVTIMEO) => {
+                let timeout = match (&tcpsocket, &udpsocket) {
+                    (Some(tcpsocket), _) => tcpsocket.recv_timeout(),
+                    (_, Some(udpsocket)) => udpsocket.recv_timeout(),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                };
+                write_timeout(timeout)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_SNDTIMEO) => {
+                let timeout = match (&tcpsocket, &udpsocket) {
+                    (Some(tcpsocket), _) => tcpsocket.send_timeout(),
+                    (_, Some(udpsocket)) => udpsocket.send_timeout(),
+                    _ => return Err(LinuxError::ENOPROTOOPT),
+                };
+                write_timeout(timeout)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_KEEPALIVE) => {
+                let keep_alive = tcpsocket.is_some_and(|s| s.keep_alive().is_some());
+                write_int(keep_alive as c_int)?;
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_LINGER) => {
+                let linger = tcpsocket.and_then(|s| s.linger());
+                let linger = ctypes::linger {
+                    l_onoff: linger.is_some() as c_int,
+                    l_linger: linger.map_or(0, |d| d.as_secs() as c_int),
+                };
+                unsafe { write_optval(linger, optval, optlen)? };
+            }
+            (ctypes::SOL_SOCKET, ctypes::SO_ERROR) => {
+                let error = tcpsocket.and_then(|s| s.take_error());
+                write_int(error.map_or(0, |e| LinuxError::from(e).code()))?;
+            }
+            (ctypes::IPPROTO_TCP, ctypes::TCP_NODELAY) => {
+                let Some(tcpsocket) = tcpsocket else {
+                    return Err(LinuxError::ENOPROTOOPT);
+                };
+                write_int(tcpsocket.nodelay() as c_int)?;
+            }
+            _ => return Err(LinuxError::ENOPROTOOPT),
         }
         Ok(0)
     })
@@ -1,11 +1,13 @@
 use alloc::sync::Arc;
 use core::ffi::c_int;
+use core::task::Waker;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use axsync::Mutex;
 
-use super::fd_ops::{add_file_like, close_file_like, FileLike};
+use super::fd_ops::{add_file_like, close_file_like, FileLike, PollWakers};
+use super::io_mpx::PollWaiter;
 use crate::ctypes;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -22,6 +24,10 @@ pub struct PipeRingBuffer {
     head: usize,
     tail: usize,
     status: RingBufferStatus,
+    /// One of the ends is closed.
+    closed: bool,
+    /// Woken when data is written or read, or an end is closed.
+    wakers: PollWakers,
 }
 
 impl PipeRingBuffer {
@@ -31,6 +37,8 @@ impl PipeRingBuffer {
             head: 0,
             tail: 0,
             status: RingBufferStatus::Empty,
+            closed: false,
+            wakers: PollWakers::new(),
         }
     }
 
@@ -100,9 +108,13 @@ impl Pipe {
     pub const fn writable(&self) -> bool {
         !self.readable
     }
+}
 
-    pub fn write_end_close(&self) -> bool {
-        Arc::strong_count(&self.buffer) == 1
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.lock();
+        ring_buffer.closed = true;
+        ring_buffer.wakers.wake_all();
     }
 }
 
@@ -111,27 +123,32 @@ impl FileLike for Pipe {
         if !self.readable() {
             return Err(LinuxError::EPERM);
         }
-        let mut read_size = 0usize;
-        let max_len = buf.len();
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let waiter = PollWaiter::new();
+        let waker = waiter.waker();
         loop {
+            let events = waiter.events();
             let mut ring_buffer = self.buffer.lock();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
-                if self.write_end_close() {
-                    return Ok(read_size);
+                if ring_buffer.closed {
+                    return Ok(0);
                 }
-                drop(ring_buffer);
                 // Data not ready, wait for write end
-                crate::sys_sched_yield(); // TODO: use synconize primitive
+                ring_buffer.wakers.register(&waker);
+                drop(ring_buffer);
+                waiter.wait(events, None, true);
                 continue;
             }
-            for _ in 0..loop_read {
-                if read_size == max_len {
-                    return Ok(read_size);
-                }
-                buf[read_size] = ring_buffer.read_byte();
-                read_size += 1;
+            ring_buffer.wakers.wake_all();
+            // return the available data without waiting for more
+            let read_size = loop_read.min(buf.len());
+            for byte in &mut buf[..read_size] {
+                *byte = ring_buffer.read_byte();
             }
+            return Ok(read_size);
         }
     }
 
@@ -141,23 +158,34 @@ impl FileLike for Pipe {
         }
         let mut write_size = 0usize;
         let max_len = buf.len();
-        loop {
+        let waiter = PollWaiter::new();
+        let waker = waiter.waker();
+        while write_size < max_len {
+            let events = waiter.events();
             let mut ring_buffer = self.buffer.lock();
+            if ring_buffer.closed {
+                // the read end is closed
+                return if write_size > 0 {
+                    Ok(write_size)
+                } else {
+                    Err(LinuxError::EPIPE)
+                };
+            }
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
-                drop(ring_buffer);
                 // Buffer is full, wait for read end to consume
-                crate::sys_sched_yield(); // TODO: use synconize primitive
+                ring_buffer.wakers.register(&waker);
+                drop(ring_buffer);
+                waiter.wait(events, None, true);
                 continue;
             }
-            for _ in 0..loop_write {
-                if write_size == max_len {
-                    return Ok(write_size);
-                }
+            ring_buffer.wakers.wake_all();
+            for _ in 0..loop_write.min(max_len - write_size) {
                 ring_buffer.write_byte(buf[write_size]);
                 write_size += 1;
             }
         }
+        Ok(write_size)
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
@@ -180,14 +208,22 @@ impl FileLike for Pipe {
     fn poll(&self) -> LinuxResult<PollState> {
         let buf = self.buffer.lock();
         Ok(PollState {
-            readable: self.readable() && buf.available_read() > 0,
+            // also readable at the end of file
+            readable: self.readable() && (buf.available_read() > 0 || buf.closed),
             writable: self.writable() && buf.available_write() > 0,
+            // the other end is closed
+            hangup: buf.closed,
         })
     }
 
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn register_waker(&self, waker: &Waker) -> bool {
+        self.buffer.lock().wakers.register(waker);
+        true
+    }
 }
 
 /// Create a pipe
@@ -0,0 +1,82 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::LinuxError;
+
+use core::ffi::{c_int, c_uint};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::wait_queue;
+
+static_assertions::const_assert!(
+    size_of::<PthreadBarrier>() <= size_of::<ctypes::pthread_barrier_t>()
+);
+
+#[repr(C)]
+pub struct PthreadBarrier {
+    /// The number of threads to wait for.
+    count: AtomicU32,
+    /// The number of threads arrived in the current round.
+    arrived: AtomicU32,
+    /// Incremented when all threads arrive.
+    generation: AtomicU32,
+}
+
+impl PthreadBarrier {
+    /// Waits for all threads to arrive, returns `true` on one of them.
+    fn wait(&self) -> bool {
+        let generation = self.generation.load(Ordering::Acquire);
+        let arrived = self.arrived.fetch_add(1, Ordering::AcqRel) + 1;
+        if arrived == self.count.load(Ordering::Relaxed) {
+            self.arrived.store(0, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+            wait_queue(self).notify_all(false);
+            true
+        } else {
+            wait_queue(self).wait_until(|| self.generation.load(Ordering::Acquire) != generation);
+            false
+        }
+    }
+}
+
+/// Initialize a barrier that waits for `count` threads.
+pub unsafe fn sys_pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    _attr: *const ctypes::pthread_barrierattr_t,
+    count: c_uint,
+) -> c_int {
+    debug!(
+        "sys_pthread_barrier_init <= {:#x} {}",
+        barrier as usize, count
+    );
+    syscall_body!(sys_pthread_barrier_init, {
+        check_null_mut_ptr(barrier)?;
+        if count == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            barrier.cast::<PthreadBarrier>().write(PthreadBarrier {
+                count: AtomicU32::new(count),
+                arrived: AtomicU32::new(0),
+                generation: AtomicU32::new(0),
+            });
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the barrier until `count` threads arrive.
+///
+/// Returns `PTHREAD_BARRIER_SERIAL_THREAD` on one of the threads, and 0 on the
+/// others.
+pub unsafe fn sys_pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    debug!("sys_pthread_barrier_wait <= {:#x}", barrier as usize);
+    syscall_body!(sys_pthread_barrier_wait, {
+        check_null_mut_ptr(barrier)?;
+        if unsafe { (*barrier.cast::<PthreadBarrier>()).wait() } {
+            Ok(ctypes::PTHREAD_BARRIER_SERIAL_THREAD)
+        } else {
+            Ok(0)
+        }
+    })
+}
@@ -0,0 +1,140 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+
+use core::ffi::c_int;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::mutex::PthreadMutex;
+use super::{wait_queue, wait_until_abstime};
+use crate::imp::time::clock_now;
+
+static_assertions::const_assert!(size_of::<PthreadCond>() <= size_of::<ctypes::pthread_cond_t>());
+
+/// A condition variable, which is valid if all zeros, as initialized by
+/// `PTHREAD_COND_INITIALIZER`.
+#[repr(C)]
+pub struct PthreadCond {
+    /// Incremented on each signal or broadcast.
+    seq: AtomicU32,
+    /// The clock of the timeouts, `CLOCK_REALTIME` by default.
+    clock: ctypes::clockid_t,
+}
+
+impl PthreadCond {
+    /// Unlocks `mutex`, waits for a signal or until `abstime`, and locks
+    /// `mutex` again.
+    fn wait(&self, mutex: &PthreadMutex, abstime: Option<&ctypes::timespec>) -> LinuxResult {
+        // take the sequence before unlocking, so no signal after it is missed
+        let seq = self.seq.load(Ordering::Acquire);
+        mutex.unlock()?;
+        let signaled = wait_until_abstime(wait_queue(self), abstime, self.clock, || {
+            self.seq.load(Ordering::Acquire) != seq
+        });
+        mutex.lock()?;
+        if signaled {
+            Ok(())
+        } else {
+            Err(LinuxError::ETIMEDOUT)
+        }
+    }
+
+    /// Wakes all the waiters, as the wait queue may be shared with other
+    /// objects. It is allowed by POSIX for `pthread_cond_signal` too.
+    fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        wait_queue(self).notify_all(false);
+    }
+}
+
+/// Initialize a condition variable.
+///
+/// The timeouts of `pthread_cond_timedwait` are measured by the clock set by
+/// `pthread_condattr_setclock`, or `CLOCK_REALTIME` if `attr` is null.
+pub unsafe fn sys_pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    debug!("sys_pthread_cond_init <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_init, {
+        check_null_mut_ptr(cond)?;
+        // the lower 31 bits of the attribute are the clock
+        let clock = match unsafe { attr.as_ref() } {
+            Some(attr) => (attr.__attr & 0x7fff_ffff) as ctypes::clockid_t,
+            None => ctypes::CLOCK_REALTIME as ctypes::clockid_t,
+        };
+        clock_now(clock)?;
+        unsafe {
+            cond.write(core::mem::zeroed());
+            (*cond.cast::<PthreadCond>()).clock = clock;
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the condition variable, with the given mutex locked.
+pub unsafe fn sys_pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_wait <= {:#x} {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_wait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>(), None)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the condition variable, with the given mutex locked, until the
+/// absolute time `abstime`.
+///
+/// Returns `ETIMEDOUT` if the time is reached before a signal.
+pub unsafe fn sys_pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_timedwait <= {:#x} {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_timedwait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        let abstime = unsafe { abstime.as_ref() }.ok_or(LinuxError::EINVAL)?;
+        if abstime.tv_nsec < 0 || abstime.tv_nsec >= 1_000_000_000 {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>(), Some(abstime))?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wake up at least one thread waiting on the condition variable.
+pub unsafe fn sys_pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_signal <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_signal, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).notify_all() };
+        Ok(0)
+    })
+}
+
+/// Wake up all threads waiting on the condition variable.
+pub unsafe fn sys_pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_broadcast <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_broadcast, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).notify_all() };
+        Ok(0)
+    })
+}
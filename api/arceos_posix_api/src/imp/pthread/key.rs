@@ -0,0 +1,136 @@
+//! Thread-specific data keys.
+
+use alloc::collections::BTreeMap;
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use spin::RwLock;
+
+use super::{ForceSendSync, Pthread};
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+/// The maximum number of keys, same as `PTHREAD_KEYS_MAX` in musl.
+const PTHREAD_KEYS_MAX: usize = 128;
+
+/// The maximum number of rounds to call the destructors on thread exit, as the
+/// destructors may set the values again.
+const PTHREAD_DESTRUCTOR_ITERATIONS: usize = 4;
+
+type Destructor = Option<unsafe extern "C" fn(*mut c_void)>;
+
+#[derive(Clone, Copy)]
+struct KeySlot {
+    /// Incremented each time the key is created, so the values of the deleted
+    /// key are not visible through the new one.
+    generation: usize,
+    in_use: bool,
+    destructor: Destructor,
+}
+
+static KEYS: RwLock<[KeySlot; PTHREAD_KEYS_MAX]> = RwLock::new(
+    [KeySlot {
+        generation: 0,
+        in_use: false,
+        destructor: None,
+    }; PTHREAD_KEYS_MAX],
+);
+
+/// The values of the keys of a thread, with the generations of the keys when
+/// they are set.
+pub(super) struct SpecificData(BTreeMap<usize, (usize, ForceSendSync<*mut c_void>)>);
+
+impl SpecificData {
+    pub(super) const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+}
+
+fn key_slot(key: ctypes::pthread_key_t) -> LinuxResult<(usize, KeySlot)> {
+    let key = key as usize;
+    match KEYS.read().get(key) {
+        Some(slot) if slot.in_use => Ok((key, *slot)),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Calls the destructors of the non-null values of `thread`, on thread exit.
+pub(super) fn run_destructors(thread: &Pthread) {
+    for _ in 0..PTHREAD_DESTRUCTOR_ITERATIONS {
+        let values = core::mem::replace(&mut *thread.specific.lock(), SpecificData::new());
+        let mut called = false;
+        for (key, (generation, value)) in values.0 {
+            let slot = KEYS.read()[key];
+            if !slot.in_use || slot.generation != generation || value.0.is_null() {
+                continue;
+            }
+            if let Some(destructor) = slot.destructor {
+                unsafe { destructor(value.0) };
+                called = true;
+            }
+        }
+        if !called {
+            break;
+        }
+    }
+}
+
+/// Create a thread-specific data key, with an optional destructor called on
+/// the non-null values on thread exit.
+pub unsafe fn sys_pthread_key_create(
+    key: *mut ctypes::pthread_key_t,
+    destructor: Destructor,
+) -> c_int {
+    debug!("sys_pthread_key_create <= {:#x}", key as usize);
+    syscall_body!(sys_pthread_key_create, {
+        check_null_mut_ptr(key)?;
+        let mut keys = KEYS.write();
+        let (index, slot) = keys
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| !slot.in_use)
+            .ok_or(LinuxError::EAGAIN)?;
+        slot.generation += 1;
+        slot.in_use = true;
+        slot.destructor = destructor;
+        unsafe { key.write(index as _) };
+        Ok(0)
+    })
+}
+
+/// Delete a thread-specific data key, without calling the destructor.
+pub fn sys_pthread_key_delete(key: ctypes::pthread_key_t) -> c_int {
+    debug!("sys_pthread_key_delete <= {}", key);
+    syscall_body!(sys_pthread_key_delete, {
+        let (index, _) = key_slot(key)?;
+        KEYS.write()[index].in_use = false;
+        Ok(0)
+    })
+}
+
+/// Get the value of the key of the current thread, or null if not set.
+pub fn sys_pthread_getspecific(key: ctypes::pthread_key_t) -> *mut c_void {
+    let Ok((index, slot)) = key_slot(key) else {
+        return core::ptr::null_mut();
+    };
+    let Some(thread) = Pthread::current() else {
+        return core::ptr::null_mut();
+    };
+    match thread.specific.lock().0.get(&index) {
+        Some((generation, value)) if *generation == slot.generation => value.0,
+        _ => core::ptr::null_mut(),
+    }
+}
+
+/// Set the value of the key of the current thread.
+pub fn sys_pthread_setspecific(key: ctypes::pthread_key_t, value: *const c_void) -> c_int {
+    debug!("sys_pthread_setspecific <= {} {:#x}", key, value as usize);
+    syscall_body!(sys_pthread_setspecific, {
+        let (index, slot) = key_slot(key)?;
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        thread.specific.lock().0.insert(
+            index,
+            (slot.generation, ForceSendSync(value as *mut c_void)),
+        );
+        Ok(0)
+    })
+}
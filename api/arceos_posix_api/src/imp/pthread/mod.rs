@@ -1,14 +1,101 @@
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use core::cell::UnsafeCell;
 use core::ffi::{c_int, c_void};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
-use axtask::AxTaskRef;
-use spin::RwLock;
+use axtask::{AxTaskRef, WaitQueue};
+use spin::{Mutex, RwLock};
 
+use super::time::clock_now;
 use crate::ctypes;
 
+pub mod barrier;
+pub mod condvar;
+pub mod key;
 pub mod mutex;
+pub mod once;
+pub mod rwlock;
+
+const WAIT_QUEUE_BUCKETS: usize = 64;
+
+/// The wait queues of the condition variables, rwlocks, barriers and once
+/// controls, found by the addresses of these objects. So the objects need no
+/// initialization other than zeroing, as the static initializers in C do.
+///
+/// Objects may share a wait queue, so the waiters must recheck their
+/// conditions after being woken up, and the wakers must wake all of them.
+static WAIT_QUEUES: [WaitQueue; WAIT_QUEUE_BUCKETS] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const WQ: WaitQueue = WaitQueue::new();
+    [WQ; WAIT_QUEUE_BUCKETS]
+};
+
+fn wait_queue<T>(obj: *const T) -> &'static WaitQueue {
+    &WAIT_QUEUES[(obj as usize / core::mem::size_of::<usize>()) % WAIT_QUEUE_BUCKETS]
+}
+
+/// Blocks the current thread on `wq` until `condition` becomes true, or the
+/// absolute time `abstime` of the clock `clock` is reached.
+///
+/// Returns `false` on timeout.
+fn wait_until_abstime<F>(
+    wq: &WaitQueue,
+    abstime: Option<&ctypes::timespec>,
+    clock: ctypes::clockid_t,
+    condition: F,
+) -> bool
+where
+    F: Fn() -> bool,
+{
+    match abstime {
+        Some(abstime) => wait_until_deadline(wq, (*abstime).into(), clock, condition),
+        None => {
+            wq.wait_until(condition);
+            true
+        }
+    }
+}
+
+#[cfg(feature = "irq")]
+fn wait_until_deadline<F>(
+    wq: &WaitQueue,
+    deadline: Duration,
+    clock: ctypes::clockid_t,
+    condition: F,
+) -> bool
+where
+    F: Fn() -> bool,
+{
+    // the clock is checked when the condition variable is initialized
+    let now = clock_now(clock).unwrap();
+    if deadline <= now {
+        return condition();
+    }
+    !wq.wait_timeout_until(deadline - now, condition)
+}
+
+#[cfg(not(feature = "irq"))]
+fn wait_until_deadline<F>(
+    _wq: &WaitQueue,
+    deadline: Duration,
+    clock: ctypes::clockid_t,
+    condition: F,
+) -> bool
+where
+    F: Fn() -> bool,
+{
+    // no timer interrupts to wake us up, poll the condition instead
+    loop {
+        if condition() {
+            return true;
+        }
+        if clock_now(clock).unwrap() >= deadline {
+            return false;
+        }
+        axtask::yield_now();
+    }
+}
 
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
@@ -20,6 +107,7 @@ lazy_static::lazy_static! {
             retval: Arc::new(Packet {
                 result: UnsafeCell::new(core::ptr::null_mut()),
             }),
+            specific: Mutex::new(key::SpecificData::new()),
         };
         let ptr = Box::into_raw(Box::new(main_thread)) as *mut c_void;
         map.insert(main_tid, ForceSendSync(ptr));
@@ -37,6 +125,8 @@ unsafe impl<T> Sync for Packet<T> {}
 pub struct Pthread {
     inner: AxTaskRef,
     retval: Arc<Packet<*mut c_void>>,
+    /// The thread-specific data, see [`key`].
+    specific: Mutex<key::SpecificData>,
 }
 
 impl Pthread {
@@ -57,6 +147,9 @@ impl Pthread {
             let ret = start_routine(arg.0);
             unsafe { *their_packet.result.get() = ret };
             drop(their_packet);
+            if let Some(thread) = Self::current() {
+                key::run_destructors(thread);
+            }
         };
 
         let task_inner = axtask::spawn(main);
@@ -64,6 +157,7 @@ impl Pthread {
         let thread = Pthread {
             inner: task_inner,
             retval: my_packet,
+            specific: Mutex::new(key::SpecificData::new()),
         };
         let ptr = Box::into_raw(Box::new(thread)) as *mut c_void;
         TID_TO_PTHREAD.write().insert(tid, ForceSendSync(ptr));
@@ -85,6 +179,7 @@ impl Pthread {
     fn exit_current(retval: *mut c_void) -> ! {
         let thread = Self::current().expect("fail to get current thread");
         unsafe { *thread.retval.result.get() = retval };
+        key::run_destructors(thread);
         axtask::exit(0);
     }
 
@@ -0,0 +1,39 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use core::ffi::c_int;
+use core::sync::atomic::{AtomicI32, Ordering};
+
+use super::wait_queue;
+
+/// The states of `pthread_once_t`, which is initialized to 0 by
+/// `PTHREAD_ONCE_INIT`.
+const INCOMPLETE: i32 = 0;
+const RUNNING: i32 = 1;
+const COMPLETE: i32 = 2;
+
+/// Call `init_routine` only once, even if called from multiple threads with the
+/// same `once_control`.
+///
+/// The other threads wait until `init_routine` returns.
+pub unsafe fn sys_pthread_once(
+    once_control: *mut ctypes::pthread_once_t,
+    init_routine: extern "C" fn(),
+) -> c_int {
+    debug!("sys_pthread_once <= {:#x}", once_control as usize);
+    syscall_body!(sys_pthread_once, {
+        check_null_mut_ptr(once_control)?;
+        let state = unsafe { AtomicI32::from_ptr(once_control.cast()) };
+        match state.compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire) {
+            Ok(_) => {
+                init_routine();
+                state.store(COMPLETE, Ordering::Release);
+                wait_queue(state).notify_all(false);
+            }
+            Err(COMPLETE) => {}
+            Err(_) => {
+                wait_queue(state).wait_until(|| state.load(Ordering::Acquire) == COMPLETE);
+            }
+        }
+        Ok(0)
+    })
+}
@@ -0,0 +1,153 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+
+use core::ffi::c_int;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::wait_queue;
+
+static_assertions::const_assert!(
+    size_of::<PthreadRwLock>() <= size_of::<ctypes::pthread_rwlock_t>()
+);
+
+/// The state of a write-locked [`PthreadRwLock`].
+const WRITE_LOCKED: u32 = u32::MAX;
+
+/// A readers-writer lock, which is valid if all zeros, as initialized by
+/// `PTHREAD_RWLOCK_INITIALIZER`.
+#[repr(C)]
+pub struct PthreadRwLock {
+    /// The number of readers, or [`WRITE_LOCKED`].
+    state: AtomicU32,
+}
+
+impl PthreadRwLock {
+    fn try_read(&self) -> LinuxResult {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state == WRITE_LOCKED {
+                return Err(LinuxError::EBUSY);
+            }
+            if state == WRITE_LOCKED - 1 {
+                return Err(LinuxError::EAGAIN); // too many readers
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    fn read(&self) -> LinuxResult {
+        loop {
+            match self.try_read() {
+                Err(LinuxError::EBUSY) => wait_queue(self)
+                    .wait_until(|| self.state.load(Ordering::Relaxed) != WRITE_LOCKED),
+                res => return res,
+            }
+        }
+    }
+
+    fn try_write(&self) -> LinuxResult {
+        self.state
+            .compare_exchange(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| LinuxError::EBUSY)
+    }
+
+    fn write(&self) -> LinuxResult {
+        while self.try_write().is_err() {
+            wait_queue(self).wait_until(|| self.state.load(Ordering::Relaxed) == 0);
+        }
+        Ok(())
+    }
+
+    fn unlock(&self) -> LinuxResult {
+        let state = self.state.load(Ordering::Relaxed);
+        if state == 0 {
+            return Err(LinuxError::EPERM);
+        }
+        let unlocked = if state == WRITE_LOCKED {
+            self.state.store(0, Ordering::Release);
+            true
+        } else {
+            self.state.fetch_sub(1, Ordering::Release) == 1
+        };
+        if unlocked {
+            wait_queue(self).notify_all(false);
+        }
+        Ok(())
+    }
+}
+
+/// Initialize a readers-writer lock.
+pub unsafe fn sys_pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    _attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    debug!("sys_pthread_rwlock_init <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_init, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { rwlock.write(core::mem::zeroed()) };
+        Ok(0)
+    })
+}
+
+/// Lock the readers-writer lock for reading.
+pub unsafe fn sys_pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_rdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_rdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).read()? };
+        Ok(0)
+    })
+}
+
+/// Try to lock the readers-writer lock for reading, fails with `EBUSY` if a
+/// writer holds it.
+pub unsafe fn sys_pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_tryrdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_tryrdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).try_read()? };
+        Ok(0)
+    })
+}
+
+/// Lock the readers-writer lock for writing.
+pub unsafe fn sys_pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_wrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_wrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).write()? };
+        Ok(0)
+    })
+}
+
+/// Try to lock the readers-writer lock for writing, fails with `EBUSY` if it
+/// is already locked.
+pub unsafe fn sys_pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_trywrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_trywrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).try_write()? };
+        Ok(0)
+    })
+}
+
+/// Unlock the readers-writer lock.
+pub unsafe fn sys_pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_unlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_unlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).unlock()? };
+        Ok(0)
+    })
+}
@@ -2,6 +2,8 @@ use axerrno::AxResult;
 use axio::{prelude::*, BufReader};
 use axsync::Mutex;
 
+#[cfg(feature = "fd")]
+use core::task::Waker;
 #[cfg(feature = "fd")]
 use {alloc::sync::Arc, axerrno::LinuxError, axerrno::LinuxResult, axio::PollState};
 
@@ -123,14 +125,35 @@ impl super::fd_ops::FileLike for Stdin {
 
     fn poll(&self) -> LinuxResult<PollState> {
         Ok(PollState {
-            readable: true,
+            readable: !self.inner.lock().fill_buf()?.is_empty(),
             writable: true,
+            hangup: false,
         })
     }
 
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn register_waker(&self, waker: &Waker) -> bool {
+        #[cfg(feature = "irq")]
+        {
+            // woken from the console IRQ handler
+            static STDIN_WAKERS: super::fd_ops::PollWakers = super::fd_ops::PollWakers::new();
+            static INPUT_IRQ: spin::Once<bool> = spin::Once::new();
+            let notifiable = *INPUT_IRQ
+                .call_once(|| axhal::console::set_input_handler(|| STDIN_WAKERS.wake_all()));
+            if notifiable {
+                STDIN_WAKERS.register(waker);
+            }
+            notifiable
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            let _ = waker;
+            false
+        }
+    }
 }
 
 #[cfg(feature = "fd")]
@@ -161,6 +184,7 @@ impl super::fd_ops::FileLike for Stdout {
         Ok(PollState {
             readable: true,
             writable: true,
+            hangup: false,
         })
     }
 
@@ -1,4 +1,4 @@
-use axerrno::LinuxError;
+use axerrno::{LinuxError, LinuxResult};
 use core::ffi::{c_int, c_long};
 use core::time::Duration;
 
@@ -34,13 +34,40 @@ impl From<Duration> for ctypes::timeval {
     }
 }
 
-/// Get clock time since booting
-pub unsafe fn sys_clock_gettime(_clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
+/// Returns the current time of the clock `clk`, or `EINVAL` if the clock is
+/// unknown.
+///
+/// The realtime clocks count the wall time since the Unix epoch, and the
+/// others count the time since booting: there is no suspension or clock
+/// adjustment to tell the monotonic clocks apart, and the CPU-time clocks of
+/// the only process are approximated by the time since booting.
+pub(crate) fn clock_now(clk: ctypes::clockid_t) -> LinuxResult<Duration> {
+    match clk as u32 {
+        ctypes::CLOCK_REALTIME | ctypes::CLOCK_REALTIME_COARSE => Ok(axhal::time::wall_time()),
+        ctypes::CLOCK_MONOTONIC
+        | ctypes::CLOCK_MONOTONIC_RAW
+        | ctypes::CLOCK_MONOTONIC_COARSE
+        | ctypes::CLOCK_BOOTTIME
+        | ctypes::CLOCK_PROCESS_CPUTIME_ID
+        | ctypes::CLOCK_THREAD_CPUTIME_ID => Ok(axhal::time::current_time()),
+        _ => {
+            warn!("unsupported clock {}", clk);
+            Err(LinuxError::EINVAL)
+        }
+    }
+}
+
+/// Get the time of the specified clock
+///
+/// `CLOCK_REALTIME` is the wall time since the Unix epoch, and
+/// `CLOCK_MONOTONIC` is the time since booting. The other known clocks count
+/// the same time as one of them.
+pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
     syscall_body!(sys_clock_gettime, {
         if ts.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let now = axhal::time::current_time().into();
+        let now: ctypes::timespec = clock_now(clk)?.into();
         unsafe { *ts = now };
         debug!("sys_clock_gettime: {}.{:09}s", now.tv_sec, now.tv_nsec);
         Ok(0)
@@ -0,0 +1,824 @@
+//! Unix domain sockets (`AF_UNIX`), for the communication between the tasks of
+//! the same application.
+//!
+//! Both stream and datagram sockets are supported. A socket can be bound to a
+//! path, where an empty file is created as its placeholder in the file system
+//! if the `fs` feature is enabled, or to a name in the abstract namespace.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::{string::String, vec::Vec};
+use core::ffi::{c_char, c_int};
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::Waker;
+
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsync::Mutex;
+
+use super::fd_ops::{add_file_like, close_file_like, FileLike, PollWakers};
+use super::io_mpx::PollWaiter;
+use crate::ctypes;
+
+/// The capacity of each direction of a stream connection, and of the receive
+/// queue of a datagram socket.
+const UNIX_BUF_LEN: usize = 64 * 1024;
+
+/// The maximum length of the pending connections of a listening socket.
+const MAX_BACKLOG: usize = 4096;
+
+const SUN_PATH_OFFSET: usize = offset_of!(ctypes::sockaddr_un, sun_path);
+
+/// The address of a Unix domain socket.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnixAddr {
+    /// The socket is not bound.
+    Unnamed,
+    /// A canonicalized path in the file system.
+    Path(String),
+    /// A name in the abstract namespace, whose `sun_path` starts with a null
+    /// byte.
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// Loads the address from the buffer `addr` of `addrlen` bytes.
+    pub unsafe fn from_sockaddr(
+        addr: *const ctypes::sockaddr,
+        addrlen: ctypes::socklen_t,
+    ) -> LinuxResult<Self> {
+        if addr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let addrlen = (addrlen as usize).min(size_of::<ctypes::sockaddr_un>());
+        if addrlen < SUN_PATH_OFFSET || (*addr).sa_family as u32 != ctypes::AF_UNIX {
+            return Err(LinuxError::EINVAL);
+        }
+
+        let path = core::slice::from_raw_parts(
+            (addr as *const u8).add(SUN_PATH_OFFSET),
+            addrlen - SUN_PATH_OFFSET,
+        );
+        let res = match path.first() {
+            None => Self::Unnamed,
+            Some(0) => Self::Abstract(path[1..].to_vec()),
+            Some(_) => {
+                let len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+                let path = core::str::from_utf8(&path[..len]).map_err(|_| LinuxError::EINVAL)?;
+                Self::Path(canonicalize(path)?)
+            }
+        };
+        debug!("    load sockaddr_un:{:#x} => {:?}", addr as usize, res);
+        Ok(res)
+    }
+
+    /// Writes the address to the buffer `dst` of `*addrlen` bytes, and sets
+    /// `*addrlen` to the size of the address. The address is truncated if the
+    /// buffer is too small.
+    pub unsafe fn write_to(&self, dst: *mut ctypes::sockaddr, addrlen: *mut ctypes::socklen_t) {
+        debug!("    Sockaddr: {:?}", self);
+        let mut sockaddr: ctypes::sockaddr_un = core::mem::zeroed();
+        sockaddr.sun_family = ctypes::AF_UNIX as _;
+        // the path is null-terminated, and the abstract name starts with a null byte
+        let (name, offset, len) = match self {
+            Self::Unnamed => (&[][..], 0, 0),
+            Self::Path(path) => (path.as_bytes(), 0, path.len() + 1),
+            Self::Abstract(name) => (&name[..], 1, name.len() + 1),
+        };
+        for (dst, &src) in sockaddr.sun_path[offset..].iter_mut().zip(name) {
+            *dst = src as c_char;
+        }
+
+        let size = (SUN_PATH_OFFSET + len).min(size_of::<ctypes::sockaddr_un>());
+        let len = (*addrlen as usize).min(size);
+        core::ptr::copy_nonoverlapping(
+            &sockaddr as *const ctypes::sockaddr_un as *const u8,
+            dst as *mut u8,
+            len,
+        );
+        *addrlen = size as _;
+    }
+}
+
+#[cfg(feature = "fs")]
+fn canonicalize(path: &str) -> LinuxResult<String> {
+    Ok(axfs::api::canonicalize(path)?)
+}
+
+#[cfg(not(feature = "fs"))]
+fn canonicalize(path: &str) -> LinuxResult<String> {
+    Ok(path.into())
+}
+
+/// What a bound address refers to.
+#[derive(Clone)]
+enum Binding {
+    Stream(Weak<Backlog>),
+    Dgram(Weak<DgramQueue>),
+}
+
+impl Binding {
+    fn is_alive(&self) -> bool {
+        match self {
+            Binding::Stream(backlog) => backlog.strong_count() > 0,
+            Binding::Dgram(queue) => queue.strong_count() > 0,
+        }
+    }
+}
+
+/// The bound addresses. Entries of the closed sockets are removed lazily.
+static BINDINGS: Mutex<BTreeMap<UnixAddr, Binding>> = Mutex::new(BTreeMap::new());
+
+/// Whether the socket file of `addr` still exists, as it may be removed by
+/// `unlink()` while the socket is open.
+fn socket_file_exists(addr: &UnixAddr) -> bool {
+    match addr {
+        #[cfg(feature = "fs")]
+        UnixAddr::Path(path) => axfs::api::metadata(path).is_ok(),
+        _ => true,
+    }
+}
+
+fn bind_addr(addr: &UnixAddr, binding: Binding) -> LinuxResult {
+    if *addr == UnixAddr::Unnamed {
+        // autobind is not supported
+        return Err(LinuxError::EINVAL);
+    }
+    let mut bindings = BINDINGS.lock();
+    bindings.retain(|_, binding| binding.is_alive());
+    if bindings.contains_key(addr) && socket_file_exists(addr) {
+        return Err(LinuxError::EADDRINUSE);
+    }
+    #[cfg(feature = "fs")]
+    if let UnixAddr::Path(path) = addr {
+        // like on Linux, the socket file must not exist yet
+        axfs::api::File::create_new(path).map_err(|e| match e {
+            axerrno::AxError::AlreadyExists => LinuxError::EADDRINUSE,
+            e => e.into(),
+        })?;
+    }
+    bindings.insert(addr.clone(), binding);
+    Ok(())
+}
+
+fn lookup_addr(addr: &UnixAddr) -> LinuxResult<Binding> {
+    if *addr == UnixAddr::Unnamed {
+        return Err(LinuxError::EINVAL);
+    }
+    if !socket_file_exists(addr) {
+        return Err(LinuxError::ENOENT);
+    }
+    let bindings = BINDINGS.lock();
+    match bindings.get(addr) {
+        Some(binding) if binding.is_alive() => Ok(binding.clone()),
+        _ => Err(LinuxError::ECONNREFUSED),
+    }
+}
+
+/// One direction of a stream connection.
+struct StreamBuf {
+    data: Mutex<VecDeque<u8>>,
+    /// The writing end is shut down or closed.
+    write_closed: AtomicBool,
+    /// The reading end is shut down or closed.
+    read_closed: AtomicBool,
+    /// Woken when data is written or read, or an end is closed.
+    wakers: PollWakers,
+}
+
+impl StreamBuf {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            data: Mutex::new(VecDeque::new()),
+            write_closed: AtomicBool::new(false),
+            read_closed: AtomicBool::new(false),
+            wakers: PollWakers::new(),
+        })
+    }
+
+    fn close_read(&self) {
+        self.read_closed.store(true, Ordering::Release);
+        self.wakers.wake_all();
+    }
+
+    fn close_write(&self) {
+        self.write_closed.store(true, Ordering::Release);
+        self.wakers.wake_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.write_closed.load(Ordering::Acquire) || self.read_closed.load(Ordering::Acquire)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut data = self.data.lock();
+        if data.is_empty() {
+            // end of file if no more data will come
+            return if self.is_closed() {
+                Ok(0)
+            } else {
+                Err(LinuxError::EAGAIN)
+            };
+        }
+        let len = buf.len().min(data.len());
+        for (dst, src) in buf.iter_mut().zip(data.drain(..len)) {
+            *dst = src;
+        }
+        self.wakers.wake_all();
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        if self.is_closed() {
+            return Err(LinuxError::EPIPE);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut data = self.data.lock();
+        let len = buf.len().min(UNIX_BUF_LEN - data.len());
+        if len == 0 {
+            return Err(LinuxError::EAGAIN);
+        }
+        data.extend(&buf[..len]);
+        self.wakers.wake_all();
+        Ok(len)
+    }
+
+    fn readable(&self) -> bool {
+        !self.data.lock().is_empty() || self.is_closed()
+    }
+
+    fn writable(&self) -> bool {
+        self.data.lock().len() < UNIX_BUF_LEN || self.is_closed()
+    }
+}
+
+/// An end of a stream connection.
+struct StreamConn {
+    rx: Arc<StreamBuf>,
+    tx: Arc<StreamBuf>,
+    peer_addr: UnixAddr,
+}
+
+impl StreamConn {
+    /// Creates both ends of a connection between the sockets of `addr1` and
+    /// `addr2`.
+    fn pair(addr1: UnixAddr, addr2: UnixAddr) -> (Self, Self) {
+        let (buf1, buf2) = (StreamBuf::new(), StreamBuf::new());
+        let end1 = Self {
+            rx: buf1.clone(),
+            tx: buf2.clone(),
+            peer_addr: addr2,
+        };
+        let end2 = Self {
+            rx: buf2,
+            tx: buf1,
+            peer_addr: addr1,
+        };
+        (end1, end2)
+    }
+}
+
+impl Drop for StreamConn {
+    fn drop(&mut self) {
+        self.rx.close_read();
+        self.tx.close_write();
+    }
+}
+
+/// The pending connections of a bound stream socket.
+struct Backlog {
+    queue: Mutex<VecDeque<StreamConn>>,
+    /// The maximum number of pending connections, or 0 if not listening.
+    capacity: AtomicUsize,
+    /// Woken when a connection comes in.
+    wakers: PollWakers,
+}
+
+struct StreamSocket {
+    /// Created on `bind()`, and accepts connections after `listen()`.
+    backlog: Mutex<Option<Arc<Backlog>>>,
+    conn: Mutex<Option<StreamConn>>,
+    /// Woken when the socket is bound or connected, before which there is
+    /// neither a backlog nor a connection to wait on.
+    wakers: PollWakers,
+}
+
+impl StreamSocket {
+    /// Returns the receive and send buffers of the connection.
+    fn conn_bufs(&self) -> LinuxResult<(Arc<StreamBuf>, Arc<StreamBuf>)> {
+        let conn = self.conn.lock();
+        let conn = conn.as_ref().ok_or(LinuxError::ENOTCONN)?;
+        Ok((conn.rx.clone(), conn.tx.clone()))
+    }
+
+    /// Returns the backlog if the socket is listening.
+    fn listening_backlog(&self) -> Option<Arc<Backlog>> {
+        self.backlog
+            .lock()
+            .clone()
+            .filter(|backlog| backlog.capacity.load(Ordering::Acquire) > 0)
+    }
+}
+
+/// The receive queue of a datagram socket.
+struct DgramQueue {
+    /// The datagrams with their source addresses.
+    datagrams: Mutex<VecDeque<(Vec<u8>, UnixAddr)>>,
+    /// The total length of the queued datagrams.
+    len: AtomicUsize,
+    /// Woken when a datagram is queued or received.
+    wakers: PollWakers,
+}
+
+impl DgramQueue {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            datagrams: Mutex::new(VecDeque::new()),
+            len: AtomicUsize::new(0),
+            wakers: PollWakers::new(),
+        })
+    }
+
+    fn push(&self, buf: &[u8], src: &UnixAddr) -> LinuxResult<usize> {
+        if buf.len() > UNIX_BUF_LEN {
+            return Err(LinuxError::EMSGSIZE);
+        }
+        let mut datagrams = self.datagrams.lock();
+        if self.len.load(Ordering::Acquire) + buf.len() > UNIX_BUF_LEN {
+            return Err(LinuxError::EAGAIN);
+        }
+        datagrams.push_back((buf.to_vec(), src.clone()));
+        self.len.fetch_add(buf.len(), Ordering::Release);
+        self.wakers.wake_all();
+        Ok(buf.len())
+    }
+
+    /// Receives a datagram, whose excess bytes are discarded if `buf` is too
+    /// small.
+    fn pop(&self, buf: &mut [u8]) -> LinuxResult<(usize, UnixAddr)> {
+        let mut datagrams = self.datagrams.lock();
+        let (data, src) = datagrams.pop_front().ok_or(LinuxError::EAGAIN)?;
+        self.len.fetch_sub(data.len(), Ordering::Release);
+        self.wakers.wake_all();
+        let len = buf.len().min(data.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok((len, src))
+    }
+
+    fn readable(&self) -> bool {
+        !self.datagrams.lock().is_empty()
+    }
+
+    fn writable(&self) -> bool {
+        self.len.load(Ordering::Acquire) < UNIX_BUF_LEN
+    }
+}
+
+struct DgramSocket {
+    queue: Arc<DgramQueue>,
+    /// The address and the receive queue of the peer set by `connect()`.
+    peer: Mutex<Option<(UnixAddr, Weak<DgramQueue>)>>,
+}
+
+impl DgramSocket {
+    fn peer_queue(&self) -> LinuxResult<Arc<DgramQueue>> {
+        let peer = self.peer.lock();
+        let (_, queue) = peer.as_ref().ok_or(LinuxError::ENOTCONN)?;
+        queue.upgrade().ok_or(LinuxError::ECONNREFUSED)
+    }
+}
+
+enum UnixSocketInner {
+    Stream(StreamSocket),
+    Dgram(DgramSocket),
+}
+
+/// A Unix domain socket.
+pub struct UnixSocket {
+    local_addr: Mutex<UnixAddr>,
+    nonblock: AtomicBool,
+    inner: UnixSocketInner,
+}
+
+impl UnixSocket {
+    /// Creates a socket of type `SOCK_STREAM` or `SOCK_DGRAM`.
+    pub fn new(socktype: u32, protocol: u32) -> LinuxResult<Self> {
+        if protocol != 0 {
+            return Err(LinuxError::EPROTONOSUPPORT);
+        }
+        let inner = match socktype {
+            ctypes::SOCK_STREAM => UnixSocketInner::Stream(StreamSocket {
+                backlog: Mutex::new(None),
+                conn: Mutex::new(None),
+                wakers: PollWakers::new(),
+            }),
+            ctypes::SOCK_DGRAM => UnixSocketInner::Dgram(DgramSocket {
+                queue: DgramQueue::new(),
+                peer: Mutex::new(None),
+            }),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Creates a pair of unnamed sockets connected to each other.
+    pub fn pair(socktype: u32, protocol: u32) -> LinuxResult<(Self, Self)> {
+        let socket1 = Self::new(socktype, protocol)?;
+        let socket2 = Self::new(socktype, protocol)?;
+        match (&socket1.inner, &socket2.inner) {
+            (UnixSocketInner::Stream(stream1), UnixSocketInner::Stream(stream2)) => {
+                let (end1, end2) = StreamConn::pair(UnixAddr::Unnamed, UnixAddr::Unnamed);
+                *stream1.conn.lock() = Some(end1);
+                *stream2.conn.lock() = Some(end2);
+            }
+            (UnixSocketInner::Dgram(dgram1), UnixSocketInner::Dgram(dgram2)) => {
+                let queue1 = Arc::downgrade(&dgram1.queue);
+                *dgram1.peer.lock() = Some((UnixAddr::Unnamed, Arc::downgrade(&dgram2.queue)));
+                *dgram2.peer.lock() = Some((UnixAddr::Unnamed, queue1));
+            }
+            _ => unreachable!(),
+        }
+        Ok((socket1, socket2))
+    }
+
+    fn from_inner(inner: UnixSocketInner) -> Self {
+        Self {
+            local_addr: Mutex::new(UnixAddr::Unnamed),
+            nonblock: AtomicBool::new(false),
+            inner,
+        }
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Retries `f` until it does not return `EAGAIN`, unless the socket is
+    /// non-blocking. The current task sleeps between the retries, until it is
+    /// woken by `wakers`.
+    fn block_on<T>(
+        &self,
+        wakers: &PollWakers,
+        mut f: impl FnMut() -> LinuxResult<T>,
+    ) -> LinuxResult<T> {
+        if self.is_nonblocking() {
+            return f();
+        }
+        let waiter = PollWaiter::new();
+        let waker = waiter.waker();
+        loop {
+            // register before trying, to not miss the wakeups in between
+            wakers.register(&waker);
+            let events = waiter.events();
+            match f() {
+                Err(LinuxError::EAGAIN) => waiter.wait(events, None, true),
+                res => return res,
+            }
+        }
+    }
+
+    pub fn local_addr(&self) -> UnixAddr {
+        self.local_addr.lock().clone()
+    }
+
+    pub fn peer_addr(&self) -> LinuxResult<UnixAddr> {
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                let conn = stream.conn.lock();
+                Ok(conn.as_ref().ok_or(LinuxError::ENOTCONN)?.peer_addr.clone())
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                let peer = dgram.peer.lock();
+                Ok(peer.as_ref().ok_or(LinuxError::ENOTCONN)?.0.clone())
+            }
+        }
+    }
+
+    pub fn bind(&self, addr: UnixAddr) -> LinuxResult {
+        let mut local_addr = self.local_addr.lock();
+        if *local_addr != UnixAddr::Unnamed {
+            return Err(LinuxError::EINVAL);
+        }
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                let backlog = Arc::new(Backlog {
+                    queue: Mutex::new(VecDeque::new()),
+                    capacity: AtomicUsize::new(0),
+                    wakers: PollWakers::new(),
+                });
+                bind_addr(&addr, Binding::Stream(Arc::downgrade(&backlog)))?;
+                *stream.backlog.lock() = Some(backlog);
+                stream.wakers.wake_all();
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                bind_addr(&addr, Binding::Dgram(Arc::downgrade(&dgram.queue)))?;
+            }
+        }
+        *local_addr = addr;
+        Ok(())
+    }
+
+    pub fn connect(&self, addr: UnixAddr) -> LinuxResult {
+        match (&self.inner, lookup_addr(&addr)?) {
+            (UnixSocketInner::Stream(stream), Binding::Stream(backlog)) => {
+                if stream.conn.lock().is_some() {
+                    return Err(LinuxError::EISCONN);
+                }
+                if stream.listening_backlog().is_some() {
+                    return Err(LinuxError::EINVAL);
+                }
+                let backlog = backlog.upgrade().ok_or(LinuxError::ECONNREFUSED)?;
+                let (client, server) = StreamConn::pair(self.local_addr(), addr);
+                let mut server = Some(server);
+                self.block_on(&backlog.wakers, || {
+                    let capacity = backlog.capacity.load(Ordering::Acquire);
+                    if capacity == 0 {
+                        return Err(LinuxError::ECONNREFUSED);
+                    }
+                    let mut queue = backlog.queue.lock();
+                    if queue.len() >= capacity {
+                        return Err(LinuxError::EAGAIN);
+                    }
+                    queue.extend(server.take());
+                    backlog.wakers.wake_all();
+                    Ok(())
+                })?;
+                *stream.conn.lock() = Some(client);
+                stream.wakers.wake_all();
+            }
+            (UnixSocketInner::Dgram(dgram), Binding::Dgram(queue)) => {
+                *dgram.peer.lock() = Some((addr, queue));
+            }
+            _ => return Err(LinuxError::EPROTOTYPE),
+        }
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: c_int) -> LinuxResult {
+        let UnixSocketInner::Stream(stream) = &self.inner else {
+            return Err(LinuxError::EOPNOTSUPP);
+        };
+        if stream.conn.lock().is_some() {
+            return Err(LinuxError::EINVAL);
+        }
+        let capacity = (backlog.max(1) as usize).min(MAX_BACKLOG);
+        match &*stream.backlog.lock() {
+            Some(backlog) => backlog.capacity.store(capacity, Ordering::Release),
+            // autobind is not supported
+            None => return Err(LinuxError::EINVAL),
+        }
+        Ok(())
+    }
+
+    /// Accepts a connection, and returns the new socket with the address of
+    /// its peer.
+    pub fn accept(&self) -> LinuxResult<(UnixSocket, UnixAddr)> {
+        let UnixSocketInner::Stream(stream) = &self.inner else {
+            return Err(LinuxError::EOPNOTSUPP);
+        };
+        let backlog = stream.listening_backlog().ok_or(LinuxError::EINVAL)?;
+        let conn = self.block_on(&backlog.wakers, || {
+            let conn = backlog.queue.lock().pop_front().ok_or(LinuxError::EAGAIN)?;
+            // wake the connecting tasks waiting for the room in the queue
+            backlog.wakers.wake_all();
+            Ok(conn)
+        })?;
+        let peer_addr = conn.peer_addr.clone();
+        let socket = Self::from_inner(UnixSocketInner::Stream(StreamSocket {
+            backlog: Mutex::new(None),
+            conn: Mutex::new(Some(conn)),
+            wakers: PollWakers::new(),
+        }));
+        *socket.local_addr.lock() = self.local_addr();
+        Ok((socket, peer_addr))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> LinuxResult<usize> {
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                let (_, tx) = stream.conn_bufs()?;
+                // a blocking write returns after all the data is written
+                let mut sent = 0;
+                self.block_on(&tx.wakers, || loop {
+                    match tx.write(&buf[sent..]) {
+                        Ok(len) => sent += len,
+                        // a short count if non-blocking, or the peer is closed
+                        Err(LinuxError::EAGAIN) if sent > 0 && self.is_nonblocking() => {
+                            return Ok(sent)
+                        }
+                        Err(LinuxError::EPIPE) if sent > 0 => return Ok(sent),
+                        Err(e) => return Err(e),
+                    }
+                    if sent == buf.len() {
+                        return Ok(sent);
+                    }
+                })
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                let queue = dgram.peer_queue()?;
+                let local_addr = self.local_addr();
+                self.block_on(&queue.wakers, || queue.push(buf, &local_addr))
+            }
+        }
+    }
+
+    pub fn send_to(&self, buf: &[u8], addr: UnixAddr) -> LinuxResult<usize> {
+        let UnixSocketInner::Dgram(_) = &self.inner else {
+            return Err(LinuxError::EISCONN);
+        };
+        let Binding::Dgram(queue) = lookup_addr(&addr)? else {
+            return Err(LinuxError::EPROTOTYPE);
+        };
+        let queue = queue.upgrade().ok_or(LinuxError::ECONNREFUSED)?;
+        let local_addr = self.local_addr();
+        self.block_on(&queue.wakers, || queue.push(buf, &local_addr))
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.recv_from(buf).map(|res| res.0)
+    }
+
+    /// Receives data, and returns the source address if it is a datagram
+    /// socket.
+    pub fn recv_from(&self, buf: &mut [u8]) -> LinuxResult<(usize, Option<UnixAddr>)> {
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                let (rx, _) = stream.conn_bufs()?;
+                Ok((self.block_on(&rx.wakers, || rx.read(buf))?, None))
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                let (len, src) = self.block_on(&dgram.queue.wakers, || dgram.queue.pop(buf))?;
+                Ok((len, Some(src)))
+            }
+        }
+    }
+
+    /// Shuts down the reading (`SHUT_RD`), the writing (`SHUT_WR`) or both
+    /// (`SHUT_RDWR`) directions of the connection.
+    pub fn shutdown(&self, how: c_int) -> LinuxResult {
+        let (read, write) = match how as u32 {
+            ctypes::SHUT_RD => (true, false),
+            ctypes::SHUT_WR => (false, true),
+            ctypes::SHUT_RDWR => (true, true),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                let (rx, tx) = stream.conn_bufs()?;
+                if read {
+                    rx.close_read();
+                }
+                if write {
+                    tx.close_write();
+                }
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                dgram.peer.lock().as_ref().ok_or(LinuxError::ENOTCONN)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn poll(&self) -> PollState {
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                if let Ok((rx, tx)) = stream.conn_bufs() {
+                    PollState {
+                        readable: rx.readable(),
+                        writable: tx.writable(),
+                        hangup: rx.is_closed() && tx.is_closed(),
+                    }
+                } else if let Some(backlog) = stream.listening_backlog() {
+                    PollState {
+                        readable: !backlog.queue.lock().is_empty(),
+                        writable: false,
+                        hangup: false,
+                    }
+                } else {
+                    PollState {
+                        readable: false,
+                        writable: false,
+                        hangup: false,
+                    }
+                }
+            }
+            UnixSocketInner::Dgram(dgram) => PollState {
+                readable: dgram.queue.readable(),
+                writable: dgram.peer_queue().map_or(true, |queue| queue.writable()),
+                hangup: false,
+            },
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if let UnixSocketInner::Stream(stream) = &self.inner {
+            if let Some(backlog) = stream.backlog.lock().take() {
+                // refuse the pending and further connections
+                backlog.capacity.store(0, Ordering::Release);
+                backlog.queue.lock().clear();
+                backlog.wakers.wake_all();
+            }
+        }
+    }
+}
+
+impl FileLike for UnixSocket {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.recv(buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.send(buf)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o140000 | 0o777u32; // S_IFSOCK | rwxrwxrwx
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            st_uid: 1000,
+            st_gid: 1000,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(self.poll())
+    }
+
+    fn set_nonblocking(&self, nonblock: bool) -> LinuxResult {
+        self.nonblock.store(nonblock, Ordering::Release);
+        Ok(())
+    }
+
+    fn register_waker(&self, waker: &Waker) -> bool {
+        match &self.inner {
+            UnixSocketInner::Stream(stream) => {
+                // registered under the locks, to not miss `bind()` and
+                // `connect()` in between
+                let conn = stream.conn.lock();
+                let backlog = stream.backlog.lock();
+                if let Some(conn) = conn.as_ref() {
+                    conn.rx.wakers.register(waker);
+                    conn.tx.wakers.register(waker);
+                } else if let Some(backlog) = backlog.as_ref() {
+                    backlog.wakers.register(waker);
+                } else {
+                    stream.wakers.register(waker);
+                }
+            }
+            UnixSocketInner::Dgram(dgram) => {
+                dgram.queue.wakers.register(waker);
+                if let Ok(queue) = dgram.peer_queue() {
+                    queue.wakers.register(waker);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Create a pair of connected Unix domain sockets.
+///
+/// Return 0 if succeed
+pub fn sys_socketpair(domain: c_int, socktype: c_int, protocol: c_int, fds: &mut [c_int]) -> c_int {
+    debug!(
+        "sys_socketpair <= {} {} {} {:#x}",
+        domain,
+        socktype,
+        protocol,
+        fds.as_ptr() as usize
+    );
+    syscall_body!(sys_socketpair, {
+        if fds.len() != 2 {
+            return Err(LinuxError::EFAULT);
+        }
+        if domain as u32 != ctypes::AF_UNIX {
+            return Err(LinuxError::EAFNOSUPPORT);
+        }
+
+        let (socket1, socket2) = UnixSocket::pair(socktype as u32, protocol as u32)?;
+        let fd1 = add_file_like(Arc::new(socket1))?;
+        let fd2 = add_file_like(Arc::new(socket2)).inspect_err(|_| {
+            close_file_like(fd1).ok();
+        })?;
+
+        fds[0] = fd1;
+        fds[1] = fd2;
+        Ok(0)
+    })
+}
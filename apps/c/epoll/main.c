@@ -0,0 +1,155 @@
+#include <assert.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/epoll.h>
+#include <unistd.h>
+
+static int epoll_add(int fd, unsigned int events)
+{
+    int epfd = epoll_create(1);
+    assert(epfd >= 0);
+    struct epoll_event ev = {.events = events, .data.fd = fd};
+    assert(epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &ev) == 0);
+    return epfd;
+}
+
+// returns the number of the ready files without blocking
+static int epoll_ready(int epfd, int fd)
+{
+    struct epoll_event ev;
+    int n = epoll_wait(epfd, &ev, 1, 0);
+    assert(n >= 0);
+    if (n > 0) assert(ev.data.fd == fd && (ev.events & EPOLLIN));
+    return n;
+}
+
+void test_level_triggered()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    int epfd = epoll_add(fds[0], EPOLLIN);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    // reported until the data is read
+    assert(write(fds[1], "a", 1) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 1);
+    char buf[16];
+    assert(read(fds[0], buf, sizeof(buf)) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    close(epfd);
+    close(fds[0]);
+    close(fds[1]);
+    puts("level-triggered OK");
+}
+
+void test_edge_triggered()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    int epfd = epoll_add(fds[0], EPOLLIN | EPOLLET);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    // reported once per write, even if the data is not read
+    for (int i = 0; i < 3; i++) {
+        assert(write(fds[1], "a", 1) == 1);
+        assert(epoll_ready(epfd, fds[0]) == 1);
+        assert(epoll_ready(epfd, fds[0]) == 0);
+    }
+    char buf[16];
+    assert(read(fds[0], buf, sizeof(buf)) == 3);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    close(epfd);
+    close(fds[0]);
+    close(fds[1]);
+    puts("edge-triggered OK");
+}
+
+void test_oneshot()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    int epfd = epoll_add(fds[0], EPOLLIN | EPOLLONESHOT);
+
+    assert(write(fds[1], "a", 1) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 1);
+    // disabled after reported once
+    assert(epoll_ready(epfd, fds[0]) == 0);
+    assert(write(fds[1], "b", 1) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    // enabled again by EPOLL_CTL_MOD
+    struct epoll_event ev = {.events = EPOLLIN | EPOLLONESHOT, .data.fd = fds[0]};
+    assert(epoll_ctl(epfd, EPOLL_CTL_MOD, fds[0], &ev) == 0);
+    assert(epoll_ready(epfd, fds[0]) == 1);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    close(epfd);
+    close(fds[0]);
+    close(fds[1]);
+    puts("oneshot OK");
+}
+
+void test_hangup_and_close()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    int epfd = epoll_add(fds[0], EPOLLOUT);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    // reported even if not requested
+    close(fds[1]);
+    struct epoll_event ev;
+    assert(epoll_wait(epfd, &ev, 1, 0) == 1);
+    assert(ev.data.fd == fds[0] && ev.events == EPOLLHUP);
+
+    // closed files are removed
+    close(fds[0]);
+    assert(epoll_wait(epfd, &ev, 1, 0) == 0);
+
+    close(epfd);
+    puts("hang-up and closed files OK");
+}
+
+static void *writer(void *arg)
+{
+    int fd = *(int *)arg;
+    assert(write(fd, "x", 1) == 1);
+    return NULL;
+}
+
+void test_blocking()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    int epfd = epoll_add(fds[0], EPOLLIN | EPOLLET);
+    assert(epoll_ready(epfd, fds[0]) == 0);
+
+    // sleeps until the other thread writes
+    pthread_t t;
+    assert(pthread_create(&t, NULL, writer, &fds[1]) == 0);
+    struct epoll_event ev;
+    assert(epoll_wait(epfd, &ev, 1, -1) == 1);
+    assert(ev.data.fd == fds[0] && (ev.events & EPOLLIN));
+    pthread_join(t, NULL);
+
+    close(epfd);
+    close(fds[0]);
+    close(fds[1]);
+    puts("blocking wait OK");
+}
+
+int main()
+{
+    puts("Running epoll tests...");
+    test_level_triggered();
+    test_edge_triggered();
+    test_oneshot();
+    test_hangup_and_close();
+    test_blocking();
+    puts("epoll tests run OK!");
+    return 0;
+}
@@ -0,0 +1,146 @@
+#include <arpa/inet.h>
+#include <assert.h>
+#include <errno.h>
+#include <netdb.h>
+#include <netinet/in.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#define TCP_PORT 5555
+#define UDP_PORT 5556
+
+static struct sockaddr_in6 loopback6(uint16_t port)
+{
+    struct sockaddr_in6 addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin6_family = AF_INET6;
+    addr.sin6_port = htons(port);
+    assert(inet_pton(AF_INET6, "::1", &addr.sin6_addr) == 1);
+    return addr;
+}
+
+static void *tcp_server(void *arg)
+{
+    int listener = *(int *)arg;
+    struct sockaddr_in6 peer;
+    socklen_t len = sizeof(peer);
+    int conn = accept(listener, (struct sockaddr *)&peer, &len);
+    assert(conn >= 0);
+    assert(peer.sin6_family == AF_INET6);
+
+    char buf[64];
+    ssize_t n;
+    while ((n = recv(conn, buf, sizeof(buf), 0)) > 0) assert(send(conn, buf, n, 0) == n);
+    close(conn);
+    return NULL;
+}
+
+void test_tcp()
+{
+    struct sockaddr_in6 addr = loopback6(TCP_PORT);
+    int listener = socket(AF_INET6, SOCK_STREAM, 0);
+    assert(listener >= 0);
+    assert(bind(listener, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+    assert(listen(listener, 1) == 0);
+    pthread_t t;
+    assert(pthread_create(&t, NULL, tcp_server, &listener) == 0);
+
+    int s = socket(AF_INET6, SOCK_STREAM, 0);
+    assert(s >= 0);
+    assert(connect(s, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+    struct sockaddr_in6 local;
+    socklen_t len = sizeof(local);
+    char str[INET6_ADDRSTRLEN];
+    assert(getsockname(s, (struct sockaddr *)&local, &len) == 0);
+    assert(local.sin6_family == AF_INET6);
+    printf("TCP connected from %s\n", inet_ntop(AF_INET6, &local.sin6_addr, str, sizeof(str)));
+
+    const char *msg = "hello over TCP";
+    char buf[64] = {0};
+    assert(send(s, msg, strlen(msg), 0) == strlen(msg));
+    assert(recv(s, buf, sizeof(buf), 0) == strlen(msg));
+    assert(strcmp(buf, msg) == 0);
+    close(s);
+    pthread_join(t, NULL);
+    close(listener);
+    puts("TCP over ::1 OK");
+}
+
+void test_udp()
+{
+    struct sockaddr_in6 addr = loopback6(UDP_PORT);
+    int server = socket(AF_INET6, SOCK_DGRAM, 0);
+    assert(server >= 0);
+    assert(bind(server, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+
+    int client = socket(AF_INET6, SOCK_DGRAM, 0);
+    struct sockaddr_in6 client_addr = loopback6(0);
+    assert(client >= 0);
+    assert(bind(client, (struct sockaddr *)&client_addr, sizeof(client_addr)) == 0);
+
+    const char *msg = "hello over UDP";
+    char buf[64] = {0};
+    struct sockaddr_in6 peer;
+    socklen_t len = sizeof(peer);
+    assert(sendto(client, msg, strlen(msg), 0, (struct sockaddr *)&addr, sizeof(addr)) ==
+           strlen(msg));
+    ssize_t n = recvfrom(server, buf, sizeof(buf), 0, (struct sockaddr *)&peer, &len);
+    assert(n == strlen(msg));
+    assert(peer.sin6_family == AF_INET6);
+    assert(sendto(server, buf, n, 0, (struct sockaddr *)&peer, len) == n);
+    memset(buf, 0, sizeof(buf));
+    assert(recv(client, buf, sizeof(buf), 0) == strlen(msg));
+    assert(strcmp(buf, msg) == 0);
+    close(client);
+    close(server);
+    puts("UDP over ::1 OK");
+}
+
+void test_address_family()
+{
+    // addresses of the other family are rejected, as on Linux
+    struct sockaddr_in addr4;
+    memset(&addr4, 0, sizeof(addr4));
+    addr4.sin_family = AF_INET;
+    addr4.sin_port = htons(UDP_PORT);
+    assert(inet_pton(AF_INET, "127.0.0.1", &addr4.sin_addr) == 1);
+    int s6 = socket(AF_INET6, SOCK_DGRAM, 0);
+    errno = 0;
+    assert(bind(s6, (struct sockaddr *)&addr4, sizeof(addr4)) == -1 && errno == EINVAL);
+    close(s6);
+
+    struct sockaddr_in6 addr6 = loopback6(TCP_PORT);
+    int s4 = socket(AF_INET, SOCK_STREAM, 0);
+    errno = 0;
+    assert(connect(s4, (struct sockaddr *)&addr6, sizeof(addr6)) == -1 && errno == EAFNOSUPPORT);
+    close(s4);
+    puts("address family checks OK");
+}
+
+void test_getaddrinfo()
+{
+    struct addrinfo hints, *res;
+    char str[INET6_ADDRSTRLEN];
+    memset(&hints, 0, sizeof(hints));
+    hints.ai_family = AF_INET6;
+    assert(getaddrinfo("localhost", "80", &hints, &res) == 0);
+    assert(res->ai_family == AF_INET6);
+    struct sockaddr_in6 *addr = (struct sockaddr_in6 *)res->ai_addr;
+    assert(ntohs(addr->sin6_port) == 80);
+    printf("localhost: %s\n", inet_ntop(AF_INET6, &addr->sin6_addr, str, sizeof(str)));
+    freeaddrinfo(res);
+}
+
+int main()
+{
+    puts("Running IPv6 loopback tests...");
+    test_tcp();
+    test_udp();
+    test_address_family();
+    test_getaddrinfo();
+    puts("IPv6 loopback tests run OK!");
+    return 0;
+}
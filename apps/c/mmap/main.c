@@ -0,0 +1,128 @@
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#define PAGE_SIZE 4096
+
+static char kernel_data[PAGE_SIZE * 2];
+
+static void fill(char *p, size_t len, char c)
+{
+    for (size_t i = 0; i < len; i++) p[i] = c + i % 16;
+}
+
+static int check(const char *p, size_t len, char c)
+{
+    for (size_t i = 0; i < len; i++)
+        if (p[i] != (char)(c + i % 16)) return 0;
+    return 1;
+}
+
+void test_anonymous()
+{
+    char *p = mmap(NULL, PAGE_SIZE * 4, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    assert(p != MAP_FAILED);
+    assert(((uintptr_t)p & (PAGE_SIZE - 1)) == 0);
+    assert(p[0] == 0 && p[PAGE_SIZE * 4 - 1] == 0);
+    fill(p, PAGE_SIZE * 4, 'a');
+    assert(check(p, PAGE_SIZE * 4, 'a'));
+    puts("anonymous mmap OK");
+
+    // unmap the second page, then map it again at the same address
+    assert(munmap(p + PAGE_SIZE, PAGE_SIZE) == 0);
+    char *q = mmap(p + PAGE_SIZE, PAGE_SIZE, PROT_READ | PROT_WRITE,
+                   MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+    assert(q == p + PAGE_SIZE);
+    assert(q[0] == 0);
+    assert(check(p, PAGE_SIZE, 'a'));
+    assert(check(p + PAGE_SIZE * 2, PAGE_SIZE * 2, 'a'));
+    puts("munmap and MAP_FIXED OK");
+
+    assert(mprotect(p, PAGE_SIZE * 4, PROT_READ) == 0);
+    assert(check(p, PAGE_SIZE, 'a'));
+    assert(mprotect(p, PAGE_SIZE * 4, PROT_READ | PROT_WRITE) == 0);
+    p[0] = 'z';
+    assert(p[0] == 'z');
+    puts("mprotect OK");
+
+    char *r = mremap(p, PAGE_SIZE * 4, PAGE_SIZE * 16, MREMAP_MAYMOVE);
+    assert(r != MAP_FAILED);
+    assert(r[0] == 'z');
+    assert(check(r + PAGE_SIZE * 2, PAGE_SIZE * 2, 'a'));
+    assert(r[PAGE_SIZE * 16 - 1] == 0);
+    assert(munmap(r, PAGE_SIZE * 16) == 0);
+    puts("mremap OK");
+}
+
+void test_untouchable()
+{
+    // the kernel image and other areas not created by mmap cannot be touched
+    void *p = (void *)(((uintptr_t)kernel_data + PAGE_SIZE - 1) & ~(uintptr_t)(PAGE_SIZE - 1));
+    errno = 0;
+    assert(munmap(p, PAGE_SIZE) == -1 && errno == EINVAL);
+    errno = 0;
+    assert(mprotect(p, PAGE_SIZE, PROT_READ) == -1 && errno == EINVAL);
+    errno = 0;
+    assert(mmap(p, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1,
+                0) == MAP_FAILED &&
+           errno == EINVAL);
+    errno = 0;
+    assert(mremap(p, PAGE_SIZE, PAGE_SIZE * 2, MREMAP_MAYMOVE) == MAP_FAILED && errno == EINVAL);
+
+    // still mapped and writable
+    memset(kernel_data, 0x5a, sizeof(kernel_data));
+    assert(kernel_data[PAGE_SIZE] == 0x5a);
+    puts("untouchable areas OK");
+}
+
+void test_file()
+{
+    // one and a half pages
+    static char buf[PAGE_SIZE * 3 / 2];
+    int fd = open("/mmap.txt", O_RDWR | O_CREAT | O_TRUNC, 0644);
+    assert(fd >= 0);
+    fill(buf, sizeof(buf), 'f');
+    assert(write(fd, buf, sizeof(buf)) == sizeof(buf));
+
+    char *p = mmap(NULL, PAGE_SIZE * 3, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    assert(p != MAP_FAILED);
+    assert(check(p, sizeof(buf), 'f'));
+    // the rest of the last page and the pages past EOF are zero
+    assert(p[sizeof(buf)] == 0 && p[PAGE_SIZE * 2] == 0);
+
+    // modifications are not written back to the file
+    p[0] = 'z';
+    char c = 0;
+    assert(lseek(fd, 0, SEEK_SET) == 0);
+    assert(read(fd, &c, 1) == 1 && c == 'f');
+
+    char *q = mmap(NULL, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, PAGE_SIZE);
+    assert(q != MAP_FAILED);
+    assert(check(q, PAGE_SIZE / 2, 'f') && q[PAGE_SIZE / 2] == 0);
+    assert(munmap(q, PAGE_SIZE) == 0);
+    puts("file mmap OK");
+
+    // the modified page is kept, the others are still loaded from the file
+    char *r = mremap(p, PAGE_SIZE * 3, PAGE_SIZE * 16, MREMAP_MAYMOVE);
+    assert(r != MAP_FAILED);
+    assert(r[0] == 'z' && r[1] == 'f' + 1 && check(r + 16, sizeof(buf) - 16, 'f'));
+    assert(r[PAGE_SIZE * 16 - 1] == 0);
+    assert(munmap(r, PAGE_SIZE * 16) == 0);
+    close(fd);
+    puts("file mremap OK");
+}
+
+int main()
+{
+    puts("Running mmap tests...");
+    test_anonymous();
+    test_untouchable();
+    test_file();
+    puts("mmap tests run OK!");
+    return 0;
+}
@@ -0,0 +1,133 @@
+#include <assert.h>
+#include <poll.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <time.h>
+#include <unistd.h>
+
+static long elapsed_ms(const struct timespec *start)
+{
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    return (now.tv_sec - start->tv_sec) * 1000 + (now.tv_nsec - start->tv_nsec) / 1000000;
+}
+
+void test_in_out()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    struct pollfd pfds[2] = {
+        {.fd = fds[0], .events = POLLIN},
+        {.fd = fds[1], .events = POLLOUT},
+    };
+
+    // only the write end is ready
+    assert(poll(pfds, 2, 0) == 1);
+    assert(pfds[0].revents == 0);
+    assert(pfds[1].revents == POLLOUT);
+
+    // the read end is ready after a write
+    assert(write(fds[1], "a", 1) == 1);
+    assert(poll(pfds, 2, 0) == 2);
+    assert(pfds[0].revents == POLLIN);
+    assert(pfds[1].revents == POLLOUT);
+
+    // negative fds are ignored
+    char buf[16];
+    assert(read(fds[0], buf, sizeof(buf)) == 1);
+    pfds[1].fd = -1;
+    assert(poll(pfds, 2, 0) == 0);
+    assert(pfds[0].revents == 0 && pfds[1].revents == 0);
+
+    close(fds[0]);
+    close(fds[1]);
+    puts("POLLIN and POLLOUT OK");
+}
+
+void test_hangup()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    assert(write(fds[1], "a", 1) == 1);
+    close(fds[1]);
+
+    // reported even if not requested
+    struct pollfd pfd = {.fd = fds[0], .events = 0};
+    assert(poll(&pfd, 1, 0) == 1);
+    assert(pfd.revents == POLLHUP);
+    pfd.events = POLLIN;
+    assert(poll(&pfd, 1, 0) == 1);
+    assert(pfd.revents == (POLLIN | POLLHUP));
+
+    // still reported after all data is read
+    char buf[16];
+    assert(read(fds[0], buf, sizeof(buf)) == 1);
+    assert(read(fds[0], buf, sizeof(buf)) == 0);
+    assert(poll(&pfd, 1, 0) == 1);
+    assert(pfd.revents & POLLHUP);
+
+    close(fds[0]);
+
+    // closed fds are reported with POLLNVAL
+    assert(poll(&pfd, 1, 0) == 1);
+    assert(pfd.revents == POLLNVAL);
+    puts("POLLHUP and POLLNVAL OK");
+}
+
+void test_timeout()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+    struct pollfd pfd = {.fd = fds[0], .events = POLLIN};
+
+    struct timespec start;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    assert(poll(&pfd, 1, 100) == 0);
+    assert(elapsed_ms(&start) >= 100);
+
+    struct timespec timeout = {.tv_sec = 0, .tv_nsec = 100000000};
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    assert(ppoll(&pfd, 1, &timeout, NULL) == 0);
+    assert(elapsed_ms(&start) >= 100);
+
+    close(fds[0]);
+    close(fds[1]);
+    puts("timeout OK");
+}
+
+static void *writer(void *arg)
+{
+    int fd = *(int *)arg;
+    usleep(10000);
+    assert(write(fd, "x", 1) == 1);
+    return NULL;
+}
+
+void test_blocking()
+{
+    int fds[2];
+    assert(pipe(fds) == 0);
+
+    // sleeps until the other thread writes
+    pthread_t t;
+    assert(pthread_create(&t, NULL, writer, &fds[1]) == 0);
+    struct pollfd pfd = {.fd = fds[0], .events = POLLIN};
+    assert(ppoll(&pfd, 1, NULL, NULL) == 1);
+    assert(pfd.revents == POLLIN);
+    pthread_join(t, NULL);
+
+    close(fds[0]);
+    close(fds[1]);
+    puts("blocking wait OK");
+}
+
+int main()
+{
+    puts("Running poll tests...");
+    test_in_out();
+    test_hangup();
+    test_timeout();
+    test_blocking();
+    puts("poll tests run OK!");
+    return 0;
+}
@@ -0,0 +1,274 @@
+#include <assert.h>
+#include <errno.h>
+#include <pthread.h>
+#include <sched.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <time.h>
+
+#define NUM_THREADS 8
+#define NUM_ITEMS   100
+
+static pthread_mutex_t lock = PTHREAD_MUTEX_INITIALIZER;
+static pthread_cond_t not_empty = PTHREAD_COND_INITIALIZER;
+static pthread_cond_t not_full = PTHREAD_COND_INITIALIZER;
+static int queue_len = 0;
+static int consumed = 0;
+
+void *producer(void *arg)
+{
+    for (int i = 0; i < NUM_ITEMS; i++) {
+        pthread_mutex_lock(&lock);
+        while (queue_len == 4) pthread_cond_wait(&not_full, &lock);
+        queue_len++;
+        pthread_cond_signal(&not_empty);
+        pthread_mutex_unlock(&lock);
+    }
+    return NULL;
+}
+
+void *consumer(void *arg)
+{
+    for (int i = 0; i < NUM_ITEMS; i++) {
+        pthread_mutex_lock(&lock);
+        while (queue_len == 0) pthread_cond_wait(&not_empty, &lock);
+        queue_len--;
+        consumed++;
+        pthread_cond_signal(&not_full);
+        pthread_mutex_unlock(&lock);
+    }
+    return NULL;
+}
+
+void test_condvar()
+{
+    pthread_t p[NUM_THREADS / 2], c[NUM_THREADS / 2];
+    for (int i = 0; i < NUM_THREADS / 2; i++) {
+        pthread_create(&p[i], NULL, producer, NULL);
+        pthread_create(&c[i], NULL, consumer, NULL);
+    }
+    for (int i = 0; i < NUM_THREADS / 2; i++) {
+        pthread_join(p[i], NULL);
+        pthread_join(c[i], NULL);
+    }
+    printf("test_condvar: consumed = %d\n", consumed);
+    assert(consumed == NUM_ITEMS * NUM_THREADS / 2);
+    assert(queue_len == 0);
+}
+
+void test_cond_timedwait()
+{
+    struct timespec ts;
+    clock_gettime(CLOCK_REALTIME, &ts);
+    ts.tv_nsec += 100 * 1000 * 1000;
+    if (ts.tv_nsec >= 1000 * 1000 * 1000) {
+        ts.tv_sec += 1;
+        ts.tv_nsec -= 1000 * 1000 * 1000;
+    }
+
+    pthread_mutex_lock(&lock);
+    int res = pthread_cond_timedwait(&not_empty, &lock, &ts);
+    pthread_mutex_unlock(&lock);
+    assert(res == ETIMEDOUT);
+    puts("test_cond_timedwait: timed out");
+}
+
+void test_cond_timedwait_monotonic()
+{
+    pthread_condattr_t attr;
+    pthread_cond_t cond;
+    pthread_condattr_init(&attr);
+    assert(pthread_condattr_setclock(&attr, CLOCK_MONOTONIC) == 0);
+    assert(pthread_cond_init(&cond, &attr) == 0);
+    pthread_condattr_destroy(&attr);
+
+    struct timespec start, ts;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    ts = start;
+    ts.tv_nsec += 100 * 1000 * 1000;
+    if (ts.tv_nsec >= 1000 * 1000 * 1000) {
+        ts.tv_sec += 1;
+        ts.tv_nsec -= 1000 * 1000 * 1000;
+    }
+
+    pthread_mutex_lock(&lock);
+    int res = pthread_cond_timedwait(&cond, &lock, &ts);
+    pthread_mutex_unlock(&lock);
+    assert(res == ETIMEDOUT);
+
+    // the deadline is of the monotonic clock, so it is not already passed
+    struct timespec end;
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    long elapsed_ms = (end.tv_sec - start.tv_sec) * 1000 + (end.tv_nsec - start.tv_nsec) / 1000000;
+    assert(elapsed_ms >= 100);
+    pthread_cond_destroy(&cond);
+    puts("test_cond_timedwait_monotonic: timed out");
+}
+
+void test_mutex_trylock()
+{
+    pthread_mutex_lock(&lock);
+    assert(pthread_mutex_trylock(&lock) == EBUSY);
+    pthread_mutex_unlock(&lock);
+    assert(pthread_mutex_trylock(&lock) == 0);
+    pthread_mutex_unlock(&lock);
+    puts("test_mutex_trylock: OK");
+}
+
+static pthread_rwlock_t rwlock = PTHREAD_RWLOCK_INITIALIZER;
+static int shared_data = 0;
+
+void *rwlock_writer(void *arg)
+{
+    for (int i = 0; i < NUM_ITEMS; i++) {
+        pthread_rwlock_wrlock(&rwlock);
+        int value = shared_data;
+        sched_yield();
+        shared_data = value + 1;
+        pthread_rwlock_unlock(&rwlock);
+    }
+    return NULL;
+}
+
+void *rwlock_reader(void *arg)
+{
+    for (int i = 0; i < NUM_ITEMS; i++) {
+        pthread_rwlock_rdlock(&rwlock);
+        int value = shared_data;
+        sched_yield();
+        assert(value == shared_data);
+        pthread_rwlock_unlock(&rwlock);
+    }
+    return NULL;
+}
+
+void test_rwlock()
+{
+    pthread_t w[NUM_THREADS / 2], r[NUM_THREADS / 2];
+    for (int i = 0; i < NUM_THREADS / 2; i++) {
+        pthread_create(&w[i], NULL, rwlock_writer, NULL);
+        pthread_create(&r[i], NULL, rwlock_reader, NULL);
+    }
+    for (int i = 0; i < NUM_THREADS / 2; i++) {
+        pthread_join(w[i], NULL);
+        pthread_join(r[i], NULL);
+    }
+
+    pthread_rwlock_rdlock(&rwlock);
+    assert(pthread_rwlock_tryrdlock(&rwlock) == 0);
+    assert(pthread_rwlock_trywrlock(&rwlock) == EBUSY);
+    pthread_rwlock_unlock(&rwlock);
+    pthread_rwlock_unlock(&rwlock);
+
+    printf("test_rwlock: data = %d\n", shared_data);
+    assert(shared_data == NUM_ITEMS * NUM_THREADS / 2);
+}
+
+static pthread_barrier_t barrier;
+static int arrived = 0;
+static int serial_count = 0;
+
+void *barrier_thread(void *arg)
+{
+    __atomic_fetch_add(&arrived, 1, __ATOMIC_SEQ_CST);
+    int res = pthread_barrier_wait(&barrier);
+    assert(__atomic_load_n(&arrived, __ATOMIC_SEQ_CST) == NUM_THREADS);
+    if (res == PTHREAD_BARRIER_SERIAL_THREAD) {
+        __atomic_fetch_add(&serial_count, 1, __ATOMIC_SEQ_CST);
+    } else {
+        assert(res == 0);
+    }
+    return NULL;
+}
+
+void test_barrier()
+{
+    pthread_t t[NUM_THREADS];
+    pthread_barrier_init(&barrier, NULL, NUM_THREADS);
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_create(&t[i], NULL, barrier_thread, NULL);
+    }
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_join(t[i], NULL);
+    }
+    pthread_barrier_destroy(&barrier);
+    printf("test_barrier: serial threads = %d\n", serial_count);
+    assert(serial_count == 1);
+}
+
+static pthread_once_t once = PTHREAD_ONCE_INIT;
+static int init_count = 0;
+
+void init_routine()
+{
+    init_count++;
+}
+
+void *once_thread(void *arg)
+{
+    pthread_once(&once, init_routine);
+    assert(init_count == 1);
+    return NULL;
+}
+
+void test_once()
+{
+    pthread_t t[NUM_THREADS];
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_create(&t[i], NULL, once_thread, NULL);
+    }
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_join(t[i], NULL);
+    }
+    printf("test_once: init count = %d\n", init_count);
+    assert(init_count == 1);
+}
+
+static pthread_key_t key;
+static int destructed = 0;
+
+void key_destructor(void *value)
+{
+    __atomic_fetch_add(&destructed, 1, __ATOMIC_SEQ_CST);
+    free(value);
+}
+
+void *key_thread(void *arg)
+{
+    assert(pthread_getspecific(key) == NULL);
+    int *value = malloc(sizeof(int));
+    *value = (int)(long)arg;
+    pthread_setspecific(key, value);
+    sched_yield();
+    assert(*(int *)pthread_getspecific(key) == (int)(long)arg);
+    return NULL;
+}
+
+void test_key()
+{
+    pthread_t t[NUM_THREADS];
+    assert(pthread_key_create(&key, key_destructor) == 0);
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_create(&t[i], NULL, key_thread, (void *)(long)i);
+    }
+    for (int i = 0; i < NUM_THREADS; i++) {
+        pthread_join(t[i], NULL);
+    }
+    assert(pthread_key_delete(key) == 0);
+    printf("test_key: destructed = %d\n", destructed);
+    assert(destructed == NUM_THREADS);
+}
+
+int main()
+{
+    test_condvar();
+    test_cond_timedwait();
+    test_cond_timedwait_monotonic();
+    test_mutex_trylock();
+    test_rwlock();
+    test_barrier();
+    test_once();
+    test_key();
+    puts("(C)Pthread sync tests run OK!");
+    return 0;
+}
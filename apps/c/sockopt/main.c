@@ -0,0 +1,140 @@
+#include <arpa/inet.h>
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/select.h>
+#include <sys/socket.h>
+#include <sys/time.h>
+#include <unistd.h>
+
+#define CLOSED_PORT 5555
+
+static int get_int(int fd, int level, int optname)
+{
+    int val = -1;
+    socklen_t len = sizeof(val);
+    assert(getsockopt(fd, level, optname, &val, &len) == 0);
+    assert(len == sizeof(val));
+    return val;
+}
+
+static void set_int(int fd, int level, int optname, int val)
+{
+    assert(setsockopt(fd, level, optname, &val, sizeof(val)) == 0);
+}
+
+static struct sockaddr_in loopback(uint16_t port)
+{
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(port);
+    assert(inet_pton(AF_INET, "127.0.0.1", &addr.sin_addr) == 1);
+    return addr;
+}
+
+void test_reuseaddr()
+{
+    int tcp = socket(AF_INET, SOCK_STREAM, 0);
+    int udp = socket(AF_INET, SOCK_DGRAM, 0);
+    assert(tcp >= 0 && udp >= 0);
+    assert(get_int(tcp, SOL_SOCKET, SO_REUSEADDR) == 0);
+    set_int(tcp, SOL_SOCKET, SO_REUSEADDR, 1);
+    assert(get_int(tcp, SOL_SOCKET, SO_REUSEADDR) == 1);
+    set_int(tcp, SOL_SOCKET, SO_REUSEADDR, 0);
+    assert(get_int(tcp, SOL_SOCKET, SO_REUSEADDR) == 0);
+    set_int(udp, SOL_SOCKET, SO_REUSEADDR, 1);
+    assert(get_int(udp, SOL_SOCKET, SO_REUSEADDR) == 1);
+    close(tcp);
+    close(udp);
+    puts("SO_REUSEADDR OK");
+}
+
+void test_rcvtimeo()
+{
+    int udp = socket(AF_INET, SOCK_DGRAM, 0);
+    assert(udp >= 0);
+    struct timeval tv = {.tv_sec = 1, .tv_usec = 1};
+    socklen_t len = sizeof(tv);
+    assert(getsockopt(udp, SOL_SOCKET, SO_RCVTIMEO, &tv, &len) == 0);
+    assert(len == sizeof(tv) && tv.tv_sec == 0 && tv.tv_usec == 0);
+
+    tv.tv_sec = 0;
+    tv.tv_usec = 200000;
+    assert(setsockopt(udp, SOL_SOCKET, SO_RCVTIMEO, &tv, sizeof(tv)) == 0);
+    memset(&tv, 0, sizeof(tv));
+    assert(getsockopt(udp, SOL_SOCKET, SO_RCVTIMEO, &tv, &len) == 0);
+    assert(tv.tv_sec == 0 && tv.tv_usec == 200000);
+
+    // times out as nothing is sent to it
+    struct sockaddr_in addr = loopback(0);
+    char buf[16];
+    assert(bind(udp, (struct sockaddr *)&addr, sizeof(addr)) == 0);
+    errno = 0;
+    assert(recv(udp, buf, sizeof(buf), 0) == -1 && errno == EAGAIN);
+
+    tv.tv_usec = 1000000;
+    errno = 0;
+    assert(setsockopt(udp, SOL_SOCKET, SO_RCVTIMEO, &tv, sizeof(tv)) == -1 && errno == EDOM);
+    close(udp);
+    puts("SO_RCVTIMEO OK");
+}
+
+void test_nodelay()
+{
+    int tcp = socket(AF_INET, SOCK_STREAM, 0);
+    int udp = socket(AF_INET, SOCK_DGRAM, 0);
+    assert(tcp >= 0 && udp >= 0);
+    assert(get_int(tcp, IPPROTO_TCP, TCP_NODELAY) == 0);
+    set_int(tcp, IPPROTO_TCP, TCP_NODELAY, 1);
+    assert(get_int(tcp, IPPROTO_TCP, TCP_NODELAY) == 1);
+    set_int(tcp, IPPROTO_TCP, TCP_NODELAY, 0);
+    assert(get_int(tcp, IPPROTO_TCP, TCP_NODELAY) == 0);
+
+    int val = 1;
+    errno = 0;
+    assert(setsockopt(udp, IPPROTO_TCP, TCP_NODELAY, &val, sizeof(val)) == -1 &&
+           errno == ENOPROTOOPT);
+    close(tcp);
+    close(udp);
+    puts("TCP_NODELAY OK");
+}
+
+void test_error()
+{
+    int tcp = socket(AF_INET, SOCK_STREAM, 0);
+    assert(tcp >= 0);
+    assert(get_int(tcp, SOL_SOCKET, SO_ERROR) == 0);
+
+    // nothing listens on the port, so the connection is refused
+    struct sockaddr_in addr = loopback(CLOSED_PORT);
+    assert(fcntl(tcp, F_SETFL, fcntl(tcp, F_GETFL) | O_NONBLOCK) == 0);
+    errno = 0;
+    assert(connect(tcp, (struct sockaddr *)&addr, sizeof(addr)) == -1 && errno == EINPROGRESS);
+
+    fd_set wfds;
+    FD_ZERO(&wfds);
+    FD_SET(tcp, &wfds);
+    struct timeval timeout = {.tv_sec = 5, .tv_usec = 0};
+    assert(select(tcp + 1, NULL, &wfds, NULL, &timeout) == 1 && FD_ISSET(tcp, &wfds));
+    assert(get_int(tcp, SOL_SOCKET, SO_ERROR) == ECONNREFUSED);
+    // the error is cleared once read
+    assert(get_int(tcp, SOL_SOCKET, SO_ERROR) == 0);
+    close(tcp);
+    puts("SO_ERROR OK");
+}
+
+int main()
+{
+    puts("Running socket option tests...");
+    test_reuseaddr();
+    test_rcvtimeo();
+    test_nodelay();
+    test_error();
+    puts("Socket option tests run OK!");
+    return 0;
+}
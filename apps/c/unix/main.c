@@ -0,0 +1,201 @@
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <stddef.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <unistd.h>
+
+// larger than the socket buffer, so the writers have to block
+#define BIG_LEN (256 * 1024)
+
+static char big_buf[BIG_LEN];
+
+// names in the abstract namespace start with a null byte
+static socklen_t abstract_addr(struct sockaddr_un *addr, const char *name)
+{
+    memset(addr, 0, sizeof(*addr));
+    addr->sun_family = AF_UNIX;
+    strcpy(addr->sun_path + 1, name);
+    return offsetof(struct sockaddr_un, sun_path) + 1 + strlen(name);
+}
+
+static void fill(char *buf, size_t len)
+{
+    for (size_t i = 0; i < len; i++) buf[i] = i % 251;
+}
+
+// reads until EOF, and checks the data written by `fill`
+static size_t read_all(int fd)
+{
+    static char buf[4096];
+    size_t total = 0;
+    ssize_t n;
+    while ((n = read(fd, buf, sizeof(buf))) > 0) {
+        for (ssize_t i = 0; i < n; i++) assert(buf[i] == (char)((total + i) % 251));
+        total += n;
+    }
+    assert(n == 0);
+    return total;
+}
+
+static void *stream_server(void *arg)
+{
+    int listener = *(int *)arg;
+    struct sockaddr_un peer;
+    socklen_t len = sizeof(peer);
+    int conn = accept(listener, (struct sockaddr *)&peer, &len);
+    assert(conn >= 0);
+    // the client is not bound
+    assert(len == sizeof(sa_family_t) && peer.sun_family == AF_UNIX);
+
+    size_t total = read_all(conn);
+    assert(write(conn, &total, sizeof(total)) == sizeof(total));
+    close(conn);
+    return NULL;
+}
+
+void test_stream()
+{
+    struct sockaddr_un addr;
+    socklen_t addrlen = abstract_addr(&addr, "arceos-unix-stream");
+    int listener = socket(AF_UNIX, SOCK_STREAM, 0);
+    assert(listener >= 0);
+    assert(bind(listener, (struct sockaddr *)&addr, addrlen) == 0);
+    assert(listen(listener, 1) == 0);
+
+    int other = socket(AF_UNIX, SOCK_STREAM, 0);
+    errno = 0;
+    assert(bind(other, (struct sockaddr *)&addr, addrlen) == -1 && errno == EADDRINUSE);
+    close(other);
+
+    pthread_t server;
+    assert(pthread_create(&server, NULL, stream_server, &listener) == 0);
+
+    int client = socket(AF_UNIX, SOCK_STREAM, 0);
+    assert(client >= 0);
+    assert(connect(client, (struct sockaddr *)&addr, addrlen) == 0);
+    struct sockaddr_un peer;
+    socklen_t len = sizeof(peer);
+    assert(getpeername(client, (struct sockaddr *)&peer, &len) == 0);
+    assert(len == addrlen && memcmp(&peer, &addr, addrlen) == 0);
+
+    // a blocking write returns after all the data is written
+    fill(big_buf, BIG_LEN);
+    assert(write(client, big_buf, BIG_LEN) == BIG_LEN);
+    assert(shutdown(client, SHUT_WR) == 0);
+    size_t total = 0;
+    assert(read(client, &total, sizeof(total)) == sizeof(total));
+    assert(total == BIG_LEN);
+    assert(read(client, &total, sizeof(total)) == 0);
+    pthread_join(server, NULL);
+    close(client);
+    close(listener);
+
+    // no one is listening after it is closed
+    client = socket(AF_UNIX, SOCK_STREAM, 0);
+    errno = 0;
+    assert(connect(client, (struct sockaddr *)&addr, addrlen) == -1 && errno == ECONNREFUSED);
+    close(client);
+    puts("stream OK");
+}
+
+#define DGRAM_LEN (16 * 1024)
+#define DGRAM_NUM 64
+
+static void *dgram_receiver(void *arg)
+{
+    int fd = *(int *)arg;
+    static char buf[DGRAM_LEN];
+    for (int i = 0; i < DGRAM_NUM; i++) {
+        assert(recv(fd, buf, sizeof(buf), 0) == DGRAM_LEN);
+        assert(buf[0] == (char)i && buf[DGRAM_LEN - 1] == (char)i);
+    }
+    return NULL;
+}
+
+void test_dgram()
+{
+    struct sockaddr_un addr1, addr2;
+    socklen_t addrlen1 = abstract_addr(&addr1, "arceos-unix-dgram1");
+    socklen_t addrlen2 = abstract_addr(&addr2, "arceos-unix-dgram2");
+    int fd1 = socket(AF_UNIX, SOCK_DGRAM, 0);
+    int fd2 = socket(AF_UNIX, SOCK_DGRAM, 0);
+    assert(fd1 >= 0 && fd2 >= 0);
+    assert(bind(fd1, (struct sockaddr *)&addr1, addrlen1) == 0);
+    assert(bind(fd2, (struct sockaddr *)&addr2, addrlen2) == 0);
+
+    assert(sendto(fd1, "hello", 5, 0, (struct sockaddr *)&addr2, addrlen2) == 5);
+    assert(sendto(fd1, "world!", 6, 0, (struct sockaddr *)&addr2, addrlen2) == 6);
+    char buf[16];
+    struct sockaddr_un src;
+    socklen_t len = sizeof(src);
+    assert(recvfrom(fd2, buf, sizeof(buf), 0, (struct sockaddr *)&src, &len) == 5);
+    assert(memcmp(buf, "hello", 5) == 0);
+    assert(len == addrlen1 && memcmp(&src, &addr1, addrlen1) == 0);
+    // the excess bytes of a datagram are discarded
+    assert(recv(fd2, buf, 3, 0) == 3 && memcmp(buf, "wor", 3) == 0);
+
+    // the sender blocks until the receiver makes room for the datagrams
+    pthread_t receiver;
+    assert(pthread_create(&receiver, NULL, dgram_receiver, &fd2) == 0);
+    assert(connect(fd1, (struct sockaddr *)&addr2, addrlen2) == 0);
+    for (int i = 0; i < DGRAM_NUM; i++) {
+        memset(big_buf, i, DGRAM_LEN);
+        assert(send(fd1, big_buf, DGRAM_LEN, 0) == DGRAM_LEN);
+    }
+    pthread_join(receiver, NULL);
+    close(fd1);
+    close(fd2);
+    puts("dgram OK");
+}
+
+void test_socketpair()
+{
+    int fds[2];
+    assert(socketpair(AF_UNIX, SOCK_STREAM, 0, fds) == 0);
+    assert(write(fds[0], "ping", 4) == 4);
+    char buf[16];
+    assert(read(fds[1], buf, sizeof(buf)) == 4 && memcmp(buf, "ping", 4) == 0);
+
+    // a non-blocking write may be short, then fails with EAGAIN
+    assert(fcntl(fds[0], F_SETFL, O_NONBLOCK) == 0);
+    fill(big_buf, BIG_LEN);
+    ssize_t n = write(fds[0], big_buf, BIG_LEN);
+    assert(n > 0 && n < BIG_LEN);
+    errno = 0;
+    assert(write(fds[0], big_buf, BIG_LEN) == -1 && errno == EAGAIN);
+    assert(fcntl(fds[1], F_SETFL, O_NONBLOCK) == 0);
+    size_t total = 0;
+    ssize_t m;
+    while ((m = read(fds[1], big_buf, BIG_LEN)) > 0) total += m;
+    assert(m == -1 && errno == EAGAIN);
+    assert(total == (size_t)n);
+
+    // EOF after the peer is closed
+    close(fds[0]);
+    assert(read(fds[1], buf, sizeof(buf)) == 0);
+    close(fds[1]);
+
+    assert(socketpair(AF_UNIX, SOCK_DGRAM, 0, fds) == 0);
+    assert(send(fds[1], "abc", 3, 0) == 3);
+    assert(send(fds[1], "de", 2, 0) == 2);
+    assert(recv(fds[0], buf, sizeof(buf), 0) == 3 && memcmp(buf, "abc", 3) == 0);
+    assert(recv(fds[0], buf, sizeof(buf), 0) == 2 && memcmp(buf, "de", 2) == 0);
+    close(fds[0]);
+    close(fds[1]);
+    puts("socketpair OK");
+}
+
+int main()
+{
+    puts("Running AF_UNIX socket tests...");
+    test_stream();
+    test_dgram();
+    test_socketpair();
+    puts("AF_UNIX socket tests run OK!");
+    return 0;
+}
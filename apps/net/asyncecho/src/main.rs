@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate axstd as std;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use axasync::net::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use axasync::time::{sleep, timeout, Elapsed};
+
+const NUM_CLIENTS: usize = 8;
+const TCP_PORT: u16 = 5555;
+const UDP_PORT: u16 = 5556;
+
+fn local_addr(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+async fn tcp_echo(stream: AsyncTcpStream) {
+    let mut buf = [0; 1024];
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if stream.write_all(&buf[..n]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn tcp_client(id: usize) -> usize {
+    let stream = AsyncTcpStream::connect(local_addr(TCP_PORT)).await.unwrap();
+    let request = format!("Hello from client {}!", id);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut reply = [0; 64];
+    let mut len = 0;
+    while len < request.len() {
+        let n = stream.read(&mut reply[len..]).await.unwrap();
+        assert_ne!(n, 0, "connection closed early");
+        len += n;
+    }
+    assert_eq!(&reply[..len], request.as_bytes());
+    stream.shutdown().unwrap();
+    len
+}
+
+async fn test_tcp() {
+    let listener = AsyncTcpListener::bind(local_addr(TCP_PORT)).unwrap();
+    axasync::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            axasync::spawn(tcp_echo(stream));
+        }
+    });
+
+    let clients: Vec<_> = (0..NUM_CLIENTS)
+        .map(|id| axasync::spawn(tcp_client(id)))
+        .collect();
+    let mut total = 0;
+    for client in clients {
+        total += client.await;
+    }
+    println!("TCP echo: {} clients, {} bytes", NUM_CLIENTS, total);
+}
+
+async fn test_udp() {
+    let server = AsyncUdpSocket::bind(local_addr(UDP_PORT)).unwrap();
+    axasync::spawn(async move {
+        let mut buf = [0; 1024];
+        loop {
+            let (n, addr) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&buf[..n], addr).await.unwrap();
+        }
+    });
+
+    let client = AsyncUdpSocket::bind(local_addr(0)).unwrap();
+    client.connect(local_addr(UDP_PORT)).unwrap();
+    let mut buf = [0; 1024];
+    for i in 0..NUM_CLIENTS {
+        let request = format!("datagram {}", i);
+        client.send(request.as_bytes()).await.unwrap();
+        let n = client.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], request.as_bytes());
+    }
+    println!("UDP echo: {} datagrams", NUM_CLIENTS);
+}
+
+async fn test_timers() {
+    let start = Instant::now();
+    let sleepers: Vec<_> = (1..=4)
+        .map(|i| {
+            axasync::spawn(async move {
+                sleep(Duration::from_millis(100 * i)).await;
+                i
+            })
+        })
+        .collect();
+    for (i, sleeper) in sleepers.into_iter().enumerate() {
+        assert_eq!(sleeper.await, i as u64 + 1);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(400));
+
+    let socket = AsyncUdpSocket::bind(local_addr(0)).unwrap();
+    let mut buf = [0; 16];
+    let res = timeout(Duration::from_millis(200), socket.recv_from(&mut buf)).await;
+    assert_eq!(res.err(), Some(Elapsed));
+    println!("timers: ok");
+}
+
+#[no_mangle]
+fn main() {
+    println!("Hello, async echo test!");
+    axasync::block_on(async {
+        test_tcp().await;
+        test_udp().await;
+        test_timers().await;
+    });
+    println!("Async echo test OK!");
+}
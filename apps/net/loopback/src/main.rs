@@ -0,0 +1,102 @@
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::io::{self, prelude::*};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::thread;
+use std::time::Duration;
+use std::vec;
+use std::vec::Vec;
+
+const TCP_ADDR: &str = "127.0.0.1:5555";
+const UDP_ADDR: &str = "127.0.0.1:5556";
+
+/// The sizes of the messages to echo, a UDP datagram is sent for each.
+const MESSAGE_SIZES: [usize; 5] = [1, 100, 1500, 8192, 60000];
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn message(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8).collect()
+}
+
+fn tcp_echo_server(listener: TcpListener) -> io::Result<()> {
+    let (mut stream, _) = listener.accept()?;
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        stream.write_all(&buf[..n])?;
+    }
+}
+
+fn test_tcp() -> io::Result<()> {
+    let listener = TcpListener::bind(TCP_ADDR)?;
+    let server = thread::spawn(move || tcp_echo_server(listener));
+
+    let mut stream = TcpStream::connect(TCP_ADDR)?;
+    println!(
+        "TCP: {} -> {}",
+        stream.local_addr()?.ip(),
+        stream.peer_addr()?
+    );
+    for size in MESSAGE_SIZES {
+        let request = message(size);
+        let mut reply = vec![0; size];
+        // the server echoes while it is receiving, so write and read in turns
+        for (req, rep) in request.chunks(1024).zip(reply.chunks_mut(1024)) {
+            stream.write_all(req)?;
+            stream.read_exact(rep)?;
+        }
+        assert_eq!(reply, request);
+        println!("TCP echo {} bytes: ok", size);
+    }
+    drop(stream);
+    server.join().unwrap()
+}
+
+fn udp_echo_server(socket: UdpSocket) -> io::Result<()> {
+    let mut buf = vec![0; 65536];
+    for _ in MESSAGE_SIZES {
+        let (n, addr) = socket.recv_from(&mut buf)?;
+        socket.send_to(&buf[..n], addr)?;
+    }
+    Ok(())
+}
+
+fn test_udp() -> io::Result<()> {
+    let socket = UdpSocket::bind(UDP_ADDR)?;
+    let server = thread::spawn(move || udp_echo_server(socket));
+
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    socket.connect(UDP_ADDR)?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+    println!(
+        "UDP: {} -> {}",
+        socket.local_addr()?.ip(),
+        socket.peer_addr()?
+    );
+    for size in MESSAGE_SIZES {
+        let request = message(size);
+        let mut reply = vec![0; size + 1];
+        socket.send(&request)?;
+        let n = socket.recv(&mut reply)?;
+        assert_eq!(reply[..n], request[..]);
+        println!("UDP echo {} bytes: ok", size);
+    }
+    server.join().unwrap()
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, loopback test!");
+    test_tcp().expect("test TCP echo failed");
+    test_udp().expect("test UDP echo failed");
+    println!("Loopback test OK!");
+}
@@ -0,0 +1,113 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate axstd as std;
+
+use std::io;
+use std::net::{IcmpSocket, IpAddr, ToSocketAddrs};
+use std::os::arceos::api;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The hosts to ping, separated by commas. Set `PING_HOST` at build time to
+/// override.
+const DEFAULT_HOSTS: &str = "127.0.0.1,10.0.2.2";
+
+const COUNT: u16 = 4;
+const PAYLOAD_LEN: usize = 56;
+const ECHO_HEADER_LEN: usize = 8;
+const INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Builds an echo request with the sequence number `seq`. The identifier and
+/// the checksum are filled in by the network stack.
+fn echo_request(addr: IpAddr, seq: u16) -> [u8; ECHO_HEADER_LEN + PAYLOAD_LEN] {
+    let mut packet = [0; ECHO_HEADER_LEN + PAYLOAD_LEN];
+    packet[0] = match addr {
+        IpAddr::V4(_) => 8,   // ICMP Echo Request
+        IpAddr::V6(_) => 128, // ICMPv6 Echo Request
+    };
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    for (i, b) in packet[ECHO_HEADER_LEN..].iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    packet
+}
+
+/// Waits for the echo reply with the sequence number `seq`, and returns its
+/// length, or `None` on timeout.
+fn wait_reply(socket: &IcmpSocket, seq: u16) -> io::Result<Option<usize>> {
+    let mut buf = [0; 1024];
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        match socket.recv(&mut buf) {
+            Ok(len) if len >= ECHO_HEADER_LEN && buf[6..8] == seq.to_be_bytes() => {
+                return Ok(Some(len));
+            }
+            Ok(_) => {} // a late reply to a previous request
+            Err(io::Error::WouldBlock) => {
+                // nonblocking sockets do not drive the network stack
+                api::net::ax_poll_interfaces()?;
+                thread::yield_now();
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+fn ping(host: &str) -> io::Result<()> {
+    let Some(addr) = (host, 0).to_socket_addrs()?.next() else {
+        return Err(io::Error::InvalidInput);
+    };
+    let addr = addr.ip();
+    println!("PING {} ({}): {} data bytes", host, addr, PAYLOAD_LEN);
+
+    let socket = IcmpSocket::new()?;
+    socket.connect(addr)?;
+    socket.set_nonblocking(true)?;
+
+    let mut received = 0;
+    for seq in 1..=COUNT {
+        let start = Instant::now();
+        socket.send(&echo_request(addr, seq))?;
+        match wait_reply(&socket, seq)? {
+            Some(len) => {
+                let rtt = start.elapsed();
+                println!(
+                    "{} bytes from {}: icmp_seq={} time={}.{:03} ms",
+                    len,
+                    addr,
+                    seq,
+                    rtt.as_millis(),
+                    rtt.as_micros() % 1000
+                );
+                received += 1;
+            }
+            None => println!("Request timeout for icmp_seq {}", seq),
+        }
+        if seq < COUNT {
+            thread::sleep(INTERVAL);
+        }
+    }
+
+    println!("--- {} ping statistics ---", host);
+    println!(
+        "{} packets transmitted, {} packets received, {}% packet loss",
+        COUNT,
+        received,
+        (COUNT - received) * 100 / COUNT
+    );
+    Ok(())
+}
+
+#[no_mangle]
+fn main() {
+    let hosts = option_env!("PING_HOST").unwrap_or(DEFAULT_HOSTS);
+    for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+        if let Err(e) = ping(host) {
+            println!("ping {} failed: {:?}", host, e);
+        }
+    }
+}
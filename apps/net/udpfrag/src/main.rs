@@ -0,0 +1,52 @@
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+use std::vec;
+use std::vec::Vec;
+
+/// The UDP echo server, which is the host of the QEMU user netdev.
+const ECHO_SERVER: &str = "10.0.2.2:5555";
+
+/// The sizes of the datagrams to send, most of them exceed the MTU.
+const DATAGRAM_SIZES: [usize; 6] = [1000, 1473, 3000, 8192, 32768, 65000];
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn echo(socket: &UdpSocket, size: usize) -> io::Result<()> {
+    let request: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    let mut reply = vec![0; size + 1];
+    socket.send(&request)?;
+    let len = socket.recv(&mut reply)?;
+    if reply[..len] == request[..] {
+        println!("echo {} bytes: ok", size);
+    } else {
+        println!("echo {} bytes: mismatch, received {} bytes", size, len);
+    }
+    Ok(())
+}
+
+fn client() -> io::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let server: SocketAddr = ECHO_SERVER.parse().unwrap();
+    socket.connect(server)?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+    println!("echo server: {}", socket.peer_addr()?);
+    for size in DATAGRAM_SIZES {
+        echo(&socket, size)?;
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Hello, UDP fragmentation test!");
+    client().expect("test UDP fragmentation failed");
+    println!("UDP fragmentation test OK!");
+}
@@ -7,7 +7,7 @@ extern crate axstd as std;
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const NUM_TASKS: usize = 5;
 
@@ -16,6 +16,8 @@ static FINISHED_TASKS: AtomicUsize = AtomicUsize::new(0);
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
     println!("Hello, main task!");
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    println!("wall time since the Unix epoch: {}s", since_epoch.as_secs());
     let now = Instant::now();
     thread::sleep(Duration::from_secs(1));
     let elapsed = now.elapsed();
@@ -0,0 +1,202 @@
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, SendError, TrySendError};
+use std::sync::{Arc, Barrier, Condvar, Mutex, Once, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+const NUM_TASKS: usize = 8;
+const NUM_ITERS: usize = 100;
+
+/// `axstd` does not support lock poisoning, so the results are not wrapped.
+#[cfg(feature = "axstd")]
+macro_rules! unpoison {
+    ($e:expr) => {
+        $e
+    };
+}
+
+#[cfg(not(feature = "axstd"))]
+macro_rules! unpoison {
+    ($e:expr) => {
+        $e.unwrap()
+    };
+}
+
+fn test_condvar() {
+    let pair = Arc::new((Mutex::new(0), Condvar::new()));
+    let tasks: Vec<_> = (0..NUM_TASKS)
+        .map(|_| {
+            let pair = pair.clone();
+            thread::spawn(move || {
+                let (count, cvar) = &*pair;
+                *unpoison!(count.lock()) += 1;
+                cvar.notify_all();
+            })
+        })
+        .collect();
+
+    let (count, cvar) = &*pair;
+    let guard = unpoison!(count.lock());
+    let guard = unpoison!(cvar.wait_while(guard, |n| *n < NUM_TASKS));
+    assert_eq!(*guard, NUM_TASKS);
+    drop(guard);
+    tasks.into_iter().for_each(|t| t.join().unwrap());
+
+    let start = Instant::now();
+    let guard = unpoison!(count.lock());
+    let (_guard, res) = unpoison!(cvar.wait_timeout(guard, Duration::from_millis(100)));
+    assert!(res.timed_out());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    println!("Condvar test OK");
+}
+
+fn test_rwlock() {
+    let lock = Arc::new(RwLock::new(0));
+    let tasks: Vec<_> = (0..NUM_TASKS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                for _ in 0..NUM_ITERS {
+                    if i % 2 == 0 {
+                        *unpoison!(lock.write()) += 1;
+                    } else {
+                        let value = *unpoison!(lock.read());
+                        assert!(value <= NUM_TASKS / 2 * NUM_ITERS);
+                    }
+                    thread::yield_now();
+                }
+            })
+        })
+        .collect();
+    tasks.into_iter().for_each(|t| t.join().unwrap());
+    assert_eq!(*unpoison!(lock.read()), NUM_TASKS / 2 * NUM_ITERS);
+    println!("RwLock test OK");
+}
+
+fn test_once() {
+    static ONCE: Once = Once::new();
+    static CELL: OnceLock<usize> = OnceLock::new();
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    let tasks: Vec<_> = (0..NUM_TASKS)
+        .map(|i| {
+            thread::spawn(move || {
+                ONCE.call_once(|| {
+                    CALLS.fetch_add(1, Ordering::Relaxed);
+                });
+                assert!(ONCE.is_completed());
+                *CELL.get_or_init(|| i)
+            })
+        })
+        .collect();
+    let values: Vec<_> = tasks.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    assert!(values.iter().all(|v| Some(v) == CELL.get()));
+    assert!(CELL.set(NUM_TASKS).is_err());
+    println!("Once test OK");
+}
+
+fn test_barrier() {
+    let barrier = Arc::new(Barrier::new(NUM_TASKS));
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..NUM_TASKS)
+        .map(|_| {
+            let barrier = barrier.clone();
+            let arrived = arrived.clone();
+            thread::spawn(move || {
+                let mut leaders = 0;
+                for round in 1..=3 {
+                    arrived.fetch_add(1, Ordering::Relaxed);
+                    if barrier.wait().is_leader() {
+                        leaders += 1;
+                    }
+                    assert!(arrived.load(Ordering::Relaxed) >= round * NUM_TASKS);
+                    barrier.wait();
+                }
+                leaders
+            })
+        })
+        .collect();
+    let leaders: usize = tasks.into_iter().map(|t| t.join().unwrap()).sum();
+    assert_eq!(leaders, 3);
+    println!("Barrier test OK");
+}
+
+fn test_mpsc() {
+    let (tx, rx) = mpsc::channel();
+    for i in 0..NUM_TASKS {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for j in 0..NUM_ITERS {
+                tx.send(i * NUM_ITERS + j).unwrap();
+            }
+        });
+    }
+    drop(tx);
+    let sum: usize = rx.iter().sum();
+    let n = NUM_TASKS * NUM_ITERS;
+    assert_eq!(sum, n * (n - 1) / 2);
+
+    let (tx, rx) = mpsc::sync_channel(2);
+    tx.try_send(1).unwrap();
+    tx.try_send(2).unwrap();
+    assert!(matches!(tx.try_send(3), Err(TrySendError::Full(3))));
+    let task = thread::spawn(move || {
+        for i in 3..=NUM_ITERS {
+            tx.send(i).unwrap();
+        }
+    });
+    assert_eq!(rx.iter().sum::<usize>(), NUM_ITERS * (NUM_ITERS + 1) / 2);
+    task.join().unwrap();
+
+    let (tx, rx) = mpsc::sync_channel(0);
+    let task = thread::spawn(move || tx.send(42).unwrap());
+    assert_eq!(rx.recv(), Ok(42));
+    task.join().unwrap();
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+
+    // the sender of a rendezvous channel gets the message back if the
+    // receiver is dropped before receiving it
+    let (tx, rx) = mpsc::sync_channel(0);
+    let task = thread::spawn(move || tx.send(42));
+    thread::sleep(Duration::from_millis(10));
+    drop(rx);
+    assert_eq!(task.join().unwrap(), Err(SendError(42)));
+
+    // the pending messages are dropped with the receiver
+    let (tx, rx) = mpsc::channel();
+    let msg = Arc::new(());
+    tx.send(msg.clone()).unwrap();
+    drop(rx);
+    assert_eq!(Arc::strong_count(&msg), 1);
+    assert!(tx.send(msg).is_err());
+
+    let (_tx, rx) = mpsc::channel::<()>();
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    );
+    println!("mpsc test OK");
+}
+
+#[cfg_attr(feature = "axstd", no_mangle)]
+fn main() {
+    println!("Running synchronization primitive tests...");
+    test_condvar();
+    test_rwlock();
+    test_once();
+    test_barrier();
+    test_mpsc();
+    println!("Synchronization tests run OK!");
+}
@@ -0,0 +1,69 @@
+//! Types and definitions for the ARM PL031 real time clock (RTC).
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ddi0224/latest>
+
+#![no_std]
+#![feature(const_ptr_as_ref)]
+#![feature(const_option)]
+#![feature(const_nonnull_new)]
+
+use core::ptr::NonNull;
+
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+register_structs! {
+    /// PL031 registers.
+    Pl031RtcRegs {
+        /// Data Register.
+        (0x00 => dr: ReadOnly<u32>),
+        /// Match Register.
+        (0x04 => mr: ReadWrite<u32>),
+        /// Load Register.
+        (0x08 => lr: ReadWrite<u32>),
+        /// Control Register.
+        (0x0c => cr: ReadWrite<u32>),
+        (0x10 => @END),
+    }
+}
+
+/// The PL031 RTC, which counts the seconds since the Unix epoch.
+pub struct Pl031Rtc {
+    base: NonNull<Pl031RtcRegs>,
+}
+
+unsafe impl Send for Pl031Rtc {}
+unsafe impl Sync for Pl031Rtc {}
+
+impl Pl031Rtc {
+    /// Constructs a new PL031 RTC instance from the base address.
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+        }
+    }
+
+    const fn regs(&self) -> &Pl031RtcRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Starts the counter if it is not running.
+    pub fn init(&mut self) {
+        if self.regs().cr.get() & 1 == 0 {
+            self.regs().cr.set(1);
+        }
+    }
+
+    /// Returns the current time in seconds since the Unix epoch.
+    pub fn get_unix_timestamp(&self) -> u32 {
+        self.regs().dr.get()
+    }
+
+    /// Sets the current time in seconds since the Unix epoch.
+    pub fn set_unix_timestamp(&mut self, secs: u32) {
+        self.regs().lr.set(secs);
+    }
+}
@@ -85,6 +85,10 @@ pub enum AxError {
     /// An error returned when an operation could not be completed because a
     /// call to `write()` returned [`Ok(0)`](Ok).
     WriteZero,
+    /// Too many symbolic links were encountered while resolving a path.
+    FilesystemLoop,
+    /// The network operation failed because there is no route to the destination.
+    NetworkUnreachable,
 }
 
 /// A specialized [`Result`] type with [`AxError`] as the error type.
@@ -195,10 +199,12 @@ impl AxError {
             ConnectionRefused => "Connection refused",
             ConnectionReset => "Connection reset",
             DirectoryNotEmpty => "Directory not empty",
+            FilesystemLoop => "Too many levels of symbolic links",
             InvalidData => "Invalid data",
             InvalidInput => "Invalid input parameter",
             Io => "I/O error",
             IsADirectory => "Is a directory",
+            NetworkUnreachable => "Network is unreachable",
             NoMemory => "Out of memory",
             NotADirectory => "Not a directory",
             NotConnected => "Not connected",
@@ -248,9 +254,11 @@ impl From<AxError> for LinuxError {
             ConnectionRefused => LinuxError::ECONNREFUSED,
             ConnectionReset => LinuxError::ECONNRESET,
             DirectoryNotEmpty => LinuxError::ENOTEMPTY,
+            FilesystemLoop => LinuxError::ELOOP,
             InvalidInput | InvalidData => LinuxError::EINVAL,
             Io => LinuxError::EIO,
             IsADirectory => LinuxError::EISDIR,
+            NetworkUnreachable => LinuxError::ENETUNREACH,
             NoMemory => LinuxError::ENOMEM,
             NotADirectory => LinuxError::ENOTDIR,
             NotConnected => LinuxError::ENOTCONN,
@@ -283,13 +291,14 @@ mod tests {
     #[test]
     fn test_try_from() {
         let max_code = core::mem::variant_count::<AxError>() as i32;
-        assert_eq!(max_code, 22);
-        assert_eq!(max_code, AxError::WriteZero.code());
+        assert_eq!(max_code, 24);
+        assert_eq!(max_code, AxError::NetworkUnreachable.code());
+        assert_eq!(AxError::WriteZero.code(), 22);
 
         assert_eq!(AxError::AddrInUse.code(), 1);
         assert_eq!(Ok(AxError::AddrInUse), AxError::try_from(1));
         assert_eq!(Ok(AxError::AlreadyExists), AxError::try_from(2));
-        assert_eq!(Ok(AxError::WriteZero), AxError::try_from(max_code));
+        assert_eq!(Ok(AxError::NetworkUnreachable), AxError::try_from(max_code));
         assert_eq!(Err(max_code + 1), AxError::try_from(max_code + 1));
         assert_eq!(Err(0), AxError::try_from(0));
         assert_eq!(Err(-1), AxError::try_from(-1));
@@ -0,0 +1,37 @@
+#!/bin/bash
+
+# Creates the test images with e2fsprogs, no root privileges are required.
+
+CUR_DIR=`dirname $0`
+
+create_test_img() {
+	local name=$1
+	local fstype=$2
+	local features=$3
+	local root=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$root/long.txt"
+	done
+	echo "Rust is cool!" >>"$root/short.txt"
+	mkdir -p "$root/very/long/path"
+	echo "Rust is cool!" >>"$root/very/long/path/test.txt"
+	mkdir -p "$root/very-long-dir-name"
+	echo "Rust is cool!" >>"$root/very-long-dir-name/very-long-file-name.txt"
+	ln -s short.txt "$root/link"
+	mkdir -p "$root/many"
+	for i in $(seq 1 200); do
+	  echo "$i" >"$root/many/file-with-a-long-name-$i"
+	done
+	seq 1 100000 >"$root/big.txt"
+
+	rm -f "$name"
+	E2FSPROGS_FAKE_TIME=1700000000 mke2fs -q -t $fstype -O "$features" -b 1024 \
+	  -U 12345678-1234-1234-1234-123456789abc -E hash_seed=12345678-1234-1234-1234-123456789abc \
+	  -d "$root" "$name" 8M
+	# build hash tree indexes for large directories
+	E2FSPROGS_FAKE_TIME=1700000000 e2fsck -fyD "$name" >/dev/null
+	rm -rf "$root"
+}
+
+create_test_img "$CUR_DIR/ext4.img" ext4 "metadata_csum,64bit"
+create_test_img "$CUR_DIR/ext2.img" ext2 "^dir_index"
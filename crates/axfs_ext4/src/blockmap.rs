@@ -0,0 +1,172 @@
+//! Indirect block maps of ext2/3, used by inodes without the extents flag.
+//!
+//! The first 12 entries of `i_block` point to data blocks directly, the
+//! following 3 entries point to single, double and triple indirect blocks.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use crate::fs::Ext4Fs;
+use crate::layout::*;
+use crate::BlockDevice;
+
+const DIRECT_BLOCKS: u64 = 12;
+
+impl<D: BlockDevice> Ext4Fs<D> {
+    fn ptrs_per_block(&self) -> u64 {
+        (self.block_size / 4) as u64
+    }
+
+    /// Returns the indexes in `i_block` and each level of indirect blocks to
+    /// reach the logical block `lblk`.
+    fn blockmap_path(&self, lblk: u64) -> VfsResult<Vec<usize>> {
+        let per = self.ptrs_per_block();
+        if lblk < DIRECT_BLOCKS {
+            return Ok(vec![lblk as usize]);
+        }
+        let lblk = lblk - DIRECT_BLOCKS;
+        if lblk < per {
+            return Ok(vec![12, lblk as usize]);
+        }
+        let lblk = lblk - per;
+        if lblk < per * per {
+            return Ok(vec![13, (lblk / per) as usize, (lblk % per) as usize]);
+        }
+        let lblk = lblk - per * per;
+        if lblk < per * per * per {
+            return Ok(vec![
+                14,
+                (lblk / per / per) as usize,
+                (lblk / per % per) as usize,
+                (lblk % per) as usize,
+            ]);
+        }
+        Err(VfsError::InvalidInput)
+    }
+
+    pub fn blockmap_map(&mut self, inode: &Inode, lblk: u64) -> VfsResult<Option<u64>> {
+        let path = self.blockmap_path(lblk)?;
+        let mut block = read_u32(inode.block(), path[0] * 4) as u64;
+        let mut buf = vec![0; self.block_size];
+        for &idx in &path[1..] {
+            if block == 0 {
+                return Ok(None);
+            }
+            self.read_block(block, &mut buf)?;
+            block = read_u32(&buf, idx * 4) as u64;
+        }
+        Ok((block != 0).then_some(block))
+    }
+
+    /// Maps the unmapped logical block `lblk` to `pblk`, the missing indirect
+    /// blocks are allocated.
+    pub fn blockmap_insert(&mut self, inode: &mut Inode, lblk: u64, pblk: u64) -> VfsResult {
+        if pblk >= 1 << 32 {
+            return Err(VfsError::InvalidData);
+        }
+        let path = self.blockmap_path(lblk)?;
+        if path.len() == 1 {
+            write_u32(inode.block_mut(), path[0] * 4, pblk as u32);
+            return self.write_inode(inode);
+        }
+
+        let mut block = read_u32(inode.block(), path[0] * 4) as u64;
+        if block == 0 {
+            block = self.alloc_zeroed_block(pblk)?;
+            self.add_inode_blocks(inode, 1);
+            write_u32(inode.block_mut(), path[0] * 4, block as u32);
+            self.write_inode(inode)?;
+        }
+        let mut buf = vec![0; self.block_size];
+        for (level, &idx) in path[1..].iter().enumerate() {
+            self.read_block(block, &mut buf)?;
+            if level == path.len() - 2 {
+                write_u32(&mut buf, idx * 4, pblk as u32);
+                return self.write_block(block, &buf);
+            }
+            let mut next = read_u32(&buf, idx * 4) as u64;
+            if next == 0 {
+                next = self.alloc_zeroed_block(pblk)?;
+                self.add_inode_blocks(inode, 1);
+                write_u32(&mut buf, idx * 4, next as u32);
+                self.write_block(block, &buf)?;
+                self.write_inode(inode)?;
+            }
+            block = next;
+        }
+        unreachable!()
+    }
+
+    /// Frees all the blocks starting from the logical block `from`.
+    pub fn blockmap_truncate(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        for i in from.min(DIRECT_BLOCKS)..DIRECT_BLOCKS {
+            let block = read_u32(inode.block(), i as usize * 4) as u64;
+            if block != 0 {
+                self.free_block(block)?;
+                self.add_inode_blocks(inode, -1);
+                write_u32(inode.block_mut(), i as usize * 4, 0);
+            }
+        }
+
+        let per = self.ptrs_per_block();
+        let mut base = DIRECT_BLOCKS;
+        for (slot, level) in [(12, 1), (13, 2), (14, 3)] {
+            let span = per.pow(level);
+            let block = read_u32(inode.block(), slot * 4) as u64;
+            if block != 0
+                && base + span > from
+                && self.truncate_indirect(inode, block, level, base, from)?
+            {
+                self.free_block(block)?;
+                self.add_inode_blocks(inode, -1);
+                write_u32(inode.block_mut(), slot * 4, 0);
+            }
+            base += span;
+        }
+        self.write_inode(inode)
+    }
+
+    /// Frees the blocks starting from `from` referenced by the indirect block
+    /// `block` of `level`, which maps the logical blocks starting from `base`.
+    ///
+    /// Returns `true` if the indirect block becomes empty, it is not freed
+    /// and should be freed by the caller.
+    fn truncate_indirect(
+        &mut self,
+        inode: &mut Inode,
+        block: u64,
+        level: u32,
+        base: u64,
+        from: u64,
+    ) -> VfsResult<bool> {
+        let per = self.ptrs_per_block();
+        let span = per.pow(level - 1);
+        let mut buf = vec![0; self.block_size];
+        self.read_block(block, &mut buf)?;
+        let mut modified = false;
+        for i in 0..per {
+            let entry = read_u32(&buf, i as usize * 4) as u64;
+            let entry_base = base + i * span;
+            if entry == 0 || entry_base + span <= from {
+                continue;
+            }
+            let empty =
+                level == 1 || self.truncate_indirect(inode, entry, level - 1, entry_base, from)?;
+            if empty {
+                self.free_block(entry)?;
+                self.add_inode_blocks(inode, -1);
+                write_u32(&mut buf, i as usize * 4, 0);
+                modified = true;
+            }
+        }
+        if buf.iter().all(|&b| b == 0) {
+            return Ok(true);
+        }
+        if modified {
+            self.write_block(block, &buf)?;
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,54 @@
+//! Checksum algorithms used by ext4 metadata.
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = (crc >> 1) ^ (0x82f6_3b78 & (crc & 1).wrapping_neg());
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut j = 0;
+        while j < 8 {
+            crc = (crc >> 1) ^ (0xa001 & (crc & 1).wrapping_neg());
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32C_TABLE: [u32; 256] = crc32c_table();
+static CRC16_TABLE: [u16; 256] = crc16_table();
+
+/// CRC32C (Castagnoli) without the final inversion, as `ext4_chksum()` in
+/// Linux.
+pub fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC16 (ANSI, reflected), used by the `uninit_bg` group descriptor
+/// checksums.
+pub fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &b in data {
+        crc = CRC16_TABLE[((crc ^ b as u16) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
@@ -0,0 +1,548 @@
+//! Linear directories.
+//!
+//! Hash tree (`dir_index`) directories are read as linear directories, their
+//! index blocks look like empty entries. The index is dropped when such a
+//! directory is modified.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use crate::crc::crc32c;
+use crate::fs::Ext4Fs;
+use crate::layout::*;
+use crate::BlockDevice;
+
+const DIRENT_HEADER_SIZE: usize = 8;
+const DIRENT_TAIL_SIZE: usize = 12;
+const DIRENT_TAIL_FT: u8 = 0xde;
+const MAX_NAME_LEN: usize = 255;
+
+/// A directory entry in a directory block.
+struct RawDirEntry {
+    /// The offset in the block.
+    off: usize,
+    ino: u32,
+    rec_len: usize,
+    name_len: usize,
+    file_type: u8,
+}
+
+impl RawDirEntry {
+    fn name<'a>(&self, buf: &'a [u8]) -> &'a [u8] {
+        let start = self.off + DIRENT_HEADER_SIZE;
+        &buf[start..start + self.name_len]
+    }
+
+    /// The size actually used by the entry.
+    fn used_len(&self) -> usize {
+        if self.ino == 0 {
+            0
+        } else {
+            rec_len(self.name_len)
+        }
+    }
+}
+
+/// The minimal record length of an entry with the name of `name_len` bytes.
+const fn rec_len(name_len: usize) -> usize {
+    (DIRENT_HEADER_SIZE + name_len + 3) & !3
+}
+
+impl<D: BlockDevice> Ext4Fs<D> {
+    fn has_filetype(&self) -> bool {
+        self.sb.has_incompat(INCOMPAT_FILETYPE)
+    }
+
+    fn write_dirent(
+        &self,
+        buf: &mut [u8],
+        off: usize,
+        ino: u32,
+        rec_len: usize,
+        name: &[u8],
+        ty: VfsNodeType,
+    ) {
+        write_u32(buf, off, ino);
+        write_u16(buf, off + 4, rec_len as u16);
+        if self.has_filetype() {
+            buf[off + 6] = name.len() as u8;
+            buf[off + 7] = type_to_dirent_type(ty);
+        } else {
+            write_u16(buf, off + 6, name.len() as u16);
+        }
+        buf[off + DIRENT_HEADER_SIZE..off + DIRENT_HEADER_SIZE + name.len()].copy_from_slice(name);
+    }
+
+    fn has_dirent_tail(&self, buf: &[u8]) -> bool {
+        let off = self.block_size - DIRENT_TAIL_SIZE;
+        read_u32(buf, off) == 0
+            && read_u16(buf, off + 4) as usize == DIRENT_TAIL_SIZE
+            && buf[off + 6] == 0
+            && buf[off + 7] == DIRENT_TAIL_FT
+    }
+
+    fn init_dirent_tail(&self, buf: &mut [u8]) {
+        let off = self.block_size - DIRENT_TAIL_SIZE;
+        buf[off..].fill(0);
+        write_u16(buf, off + 4, DIRENT_TAIL_SIZE as u16);
+        buf[off + 7] = DIRENT_TAIL_FT;
+    }
+
+    /// Returns the size of a directory block that can be used by entries.
+    fn dir_block_capacity(&self) -> usize {
+        if self.has_metadata_csum() {
+            self.block_size - DIRENT_TAIL_SIZE
+        } else {
+            self.block_size
+        }
+    }
+
+    /// Parses all entries (including the unused ones) in a directory block.
+    fn parse_dir_block(&self, buf: &[u8]) -> VfsResult<Vec<RawDirEntry>> {
+        let end = if self.has_dirent_tail(buf) {
+            self.block_size - DIRENT_TAIL_SIZE
+        } else {
+            self.block_size
+        };
+        let mut entries = Vec::new();
+        let mut off = 0;
+        while off < end {
+            if off + DIRENT_HEADER_SIZE > end {
+                return Err(VfsError::InvalidData);
+            }
+            let rec_len = read_u16(buf, off + 4) as usize;
+            let (name_len, file_type) = if self.has_filetype() {
+                (buf[off + 6] as usize, buf[off + 7])
+            } else {
+                (read_u16(buf, off + 6) as usize, 0)
+            };
+            if rec_len < DIRENT_HEADER_SIZE
+                || rec_len % 4 != 0
+                || off + rec_len > end
+                || DIRENT_HEADER_SIZE + name_len > rec_len
+            {
+                warn!("ext4: corrupted directory entry at offset {}", off);
+                return Err(VfsError::InvalidData);
+            }
+            entries.push(RawDirEntry {
+                off,
+                ino: read_u32(buf, off),
+                rec_len,
+                name_len,
+                file_type,
+            });
+            off += rec_len;
+        }
+        Ok(entries)
+    }
+
+    /// Reads the `lblk`-th block of the directory, returns its physical block.
+    fn read_dir_block(&mut self, dir: &Inode, lblk: u64, buf: &mut [u8]) -> VfsResult<u64> {
+        match self.map_block(dir, lblk)? {
+            Some(m) if !m.unwritten => {
+                self.read_block(m.pblk, buf)?;
+                Ok(m.pblk)
+            }
+            _ => Err(VfsError::InvalidData), // directories have no holes
+        }
+    }
+
+    fn write_dir_block(&mut self, dir: &Inode, pblk: u64, buf: &mut [u8]) -> VfsResult {
+        if self.has_metadata_csum() && self.has_dirent_tail(buf) {
+            let off = self.block_size - DIRENT_TAIL_SIZE;
+            let csum = crc32c(dir.checksum_seed(&self.sb), &buf[..off]);
+            write_u32(buf, self.block_size - 4, csum);
+        }
+        self.write_block(pblk, buf)
+    }
+
+    fn dir_block_count(&self, dir: &Inode) -> u64 {
+        dir.size() / self.block_size as u64
+    }
+
+    /// Returns the type of the node pointed by a directory entry.
+    pub fn dirent_node_type(&mut self, ino: u32, file_type: u8) -> VfsResult<VfsNodeType> {
+        Ok(match file_type {
+            1 => VfsNodeType::File,
+            2 => VfsNodeType::Dir,
+            3 => VfsNodeType::CharDevice,
+            4 => VfsNodeType::BlockDevice,
+            5 => VfsNodeType::Fifo,
+            6 => VfsNodeType::Socket,
+            7 => VfsNodeType::SymLink,
+            _ => self.read_inode(ino)?.node_type()?,
+        })
+    }
+
+    /// Calls `f` with the inode number, name and file type of each entry in
+    /// the directory, until `f` returns `false`.
+    pub fn dir_iterate(
+        &mut self,
+        dir: &Inode,
+        mut f: impl FnMut(u32, &[u8], u8) -> bool,
+    ) -> VfsResult {
+        let mut buf = vec![0; self.block_size];
+        for lblk in 0..self.dir_block_count(dir) {
+            self.read_dir_block(dir, lblk, &mut buf)?;
+            for ent in self.parse_dir_block(&buf)? {
+                if ent.ino != 0 && !f(ent.ino, ent.name(&buf), ent.file_type) {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up `name` in the directory, returns the inode number and the
+    /// file type in the entry.
+    pub fn dir_lookup(&mut self, dir: &Inode, name: &str) -> VfsResult<Option<(u32, u8)>> {
+        let mut found = None;
+        self.dir_iterate(dir, |ino, ent_name, file_type| {
+            if ent_name == name.as_bytes() {
+                found = Some((ino, file_type));
+            }
+            found.is_none()
+        })?;
+        Ok(found)
+    }
+
+    /// Whether the directory contains only `.` and `..`.
+    pub fn dir_is_empty(&mut self, dir: &Inode) -> VfsResult<bool> {
+        let mut empty = true;
+        self.dir_iterate(dir, |_, name, _| {
+            empty = name == b"." || name == b"..";
+            empty
+        })?;
+        Ok(empty)
+    }
+
+    /// Converts a hash tree directory to a linear one before modifying it.
+    fn drop_dir_index(&mut self, dir: &mut Inode) -> VfsResult {
+        if dir.flags() & INODE_FLAG_INDEX == 0 {
+            return Ok(());
+        }
+        if self.has_metadata_csum() {
+            // the index blocks have no directory entry tails
+            let mut buf = vec![0; self.block_size];
+            for lblk in 0..self.dir_block_count(dir) {
+                let pblk = self.read_dir_block(dir, lblk, &mut buf)?;
+                if self.has_dirent_tail(&buf) {
+                    continue;
+                }
+                let entries = self.parse_dir_block(&buf)?;
+                let last = entries.last().ok_or(VfsError::InvalidData)?;
+                if last.rec_len - last.used_len() < DIRENT_TAIL_SIZE {
+                    warn!("ext4: no space for the directory entry tail");
+                    return Err(VfsError::Unsupported);
+                }
+                write_u16(
+                    &mut buf,
+                    last.off + 4,
+                    (last.rec_len - DIRENT_TAIL_SIZE) as u16,
+                );
+                self.init_dirent_tail(&mut buf);
+                self.write_dir_block(dir, pblk, &mut buf)?;
+            }
+        }
+        dir.set_flags(dir.flags() & !INODE_FLAG_INDEX);
+        self.write_inode(dir)
+    }
+
+    /// Adds an entry to the directory, the name must not exist.
+    pub fn dir_add_entry(
+        &mut self,
+        dir: &mut Inode,
+        name: &str,
+        ino: u32,
+        ty: VfsNodeType,
+    ) -> VfsResult {
+        let name = name.as_bytes();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(VfsError::InvalidInput);
+        }
+        self.drop_dir_index(dir)?;
+        let need = rec_len(name.len());
+
+        let mut buf = vec![0; self.block_size];
+        let nblocks = self.dir_block_count(dir);
+        for lblk in 0..nblocks {
+            let pblk = self.read_dir_block(dir, lblk, &mut buf)?;
+            for ent in self.parse_dir_block(&buf)? {
+                let used = ent.used_len();
+                if ent.rec_len - used < need {
+                    continue;
+                }
+                if used > 0 {
+                    write_u16(&mut buf, ent.off + 4, used as u16);
+                }
+                self.write_dirent(&mut buf, ent.off + used, ino, ent.rec_len - used, name, ty);
+                self.write_dir_block(dir, pblk, &mut buf)?;
+                self.touch_modified(dir);
+                return self.write_inode(dir);
+            }
+        }
+
+        // no space in existing blocks, append a new one
+        let (pblk, _) = self.get_or_alloc_block(dir, nblocks)?;
+        buf.fill(0);
+        self.write_dirent(&mut buf, 0, ino, self.dir_block_capacity(), name, ty);
+        if self.has_metadata_csum() {
+            self.init_dirent_tail(&mut buf);
+        }
+        self.write_dir_block(dir, pblk, &mut buf)?;
+        dir.set_size((nblocks + 1) * self.block_size as u64);
+        self.touch_modified(dir);
+        self.write_inode(dir)
+    }
+
+    /// Removes the entry `name` from the directory, returns its inode number.
+    pub fn dir_remove_entry(&mut self, dir: &mut Inode, name: &str) -> VfsResult<u32> {
+        self.drop_dir_index(dir)?;
+        let mut buf = vec![0; self.block_size];
+        for lblk in 0..self.dir_block_count(dir) {
+            let pblk = self.read_dir_block(dir, lblk, &mut buf)?;
+            let entries = self.parse_dir_block(&buf)?;
+            for (i, ent) in entries.iter().enumerate() {
+                if ent.ino == 0 || ent.name(&buf) != name.as_bytes() {
+                    continue;
+                }
+                if i > 0 {
+                    // merge into the previous entry
+                    let prev = &entries[i - 1];
+                    write_u16(&mut buf, prev.off + 4, (prev.rec_len + ent.rec_len) as u16);
+                } else {
+                    write_u32(&mut buf, ent.off, 0);
+                }
+                let ino = ent.ino;
+                self.write_dir_block(dir, pblk, &mut buf)?;
+                self.touch_modified(dir);
+                self.write_inode(dir)?;
+                return Ok(ino);
+            }
+        }
+        Err(VfsError::NotFound)
+    }
+
+    /// Initializes a new directory with the `.` and `..` entries.
+    pub fn dir_init(&mut self, dir: &mut Inode, parent: u32) -> VfsResult {
+        let (pblk, _) = self.get_or_alloc_block(dir, 0)?;
+        let mut buf = vec![0; self.block_size];
+        let dot_len = rec_len(1);
+        self.write_dirent(&mut buf, 0, dir.ino, dot_len, b".", VfsNodeType::Dir);
+        let dotdot_len = self.dir_block_capacity() - dot_len;
+        self.write_dirent(
+            &mut buf,
+            dot_len,
+            parent,
+            dotdot_len,
+            b"..",
+            VfsNodeType::Dir,
+        );
+        if self.has_metadata_csum() {
+            self.init_dirent_tail(&mut buf);
+        }
+        self.write_dir_block(dir, pblk, &mut buf)?;
+        dir.set_size(self.block_size as u64);
+        self.write_inode(dir)
+    }
+
+    /// Changes the `..` entry of the directory to `parent`.
+    pub fn dir_set_parent(&mut self, dir: &mut Inode, parent: u32) -> VfsResult {
+        self.drop_dir_index(dir)?;
+        let mut buf = vec![0; self.block_size];
+        let pblk = self.read_dir_block(dir, 0, &mut buf)?;
+        let entries = self.parse_dir_block(&buf)?;
+        let dotdot = entries
+            .iter()
+            .find(|ent| ent.ino != 0 && ent.name(&buf) == b"..")
+            .ok_or(VfsError::InvalidData)?;
+        write_u32(&mut buf, dotdot.off, parent);
+        self.write_dir_block(dir, pblk, &mut buf)
+    }
+}
+
+// Namespace operations.
+impl<D: BlockDevice> Ext4Fs<D> {
+    /// Creates a node `name` of type `ty` in the directory `parent`, returns
+    /// its inode number.
+    pub fn create_node(
+        &mut self,
+        parent: u32,
+        name: &str,
+        ty: VfsNodeType,
+        perm: u16,
+    ) -> VfsResult<u32> {
+        self.check_writable()?;
+        let mut dir = self.read_inode(parent)?;
+        if self.dir_lookup(&dir, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut inode = self.new_inode(parent, ty, perm)?;
+        let res = if ty == VfsNodeType::Dir {
+            inode.set_links_count(2);
+            self.dir_init(&mut inode, parent)
+        } else {
+            inode.set_links_count(1);
+            self.write_inode(&mut inode)
+        }
+        .and_then(|_| self.dir_add_entry(&mut dir, name, inode.ino, ty));
+        if let Err(e) = res {
+            self.release_inode(&mut inode)?;
+            return Err(e);
+        }
+        if ty == VfsNodeType::Dir {
+            dir.set_links_count(dir.links_count() + 1);
+            self.write_inode(&mut dir)?;
+        }
+        Ok(inode.ino)
+    }
+
+    /// Creates a symbolic link `name` to `target` in the directory `parent`.
+    ///
+    /// Short targets are stored in `i_block` as fast symbolic links.
+    pub fn create_symlink(&mut self, parent: u32, name: &str, target: &str) -> VfsResult {
+        if target.is_empty() || target.len() >= self.block_size {
+            return Err(VfsError::InvalidInput);
+        }
+        let ino = self.create_node(parent, name, VfsNodeType::SymLink, 0o777)?;
+        let mut inode = self.read_inode(ino)?;
+        let res = if target.len() < INODE_BLOCK_SIZE {
+            inode.block_mut()[..target.len()].copy_from_slice(target.as_bytes());
+            inode.set_size(target.len() as u64);
+            self.write_inode(&mut inode)
+        } else {
+            if self.sb.has_incompat(INCOMPAT_EXTENTS) {
+                self.init_extent_root(&mut inode);
+            }
+            self.write_file(&mut inode, 0, target.as_bytes())
+                .and_then(|n| match n {
+                    n if n == target.len() => Ok(()),
+                    _ => Err(VfsError::StorageFull),
+                })
+        };
+        if res.is_err() {
+            self.unlink(parent, name)?;
+        }
+        res
+    }
+
+    /// Adds an entry `name` in the directory `parent` which refers to the
+    /// existing non-directory inode `ino`.
+    pub fn link(&mut self, parent: u32, name: &str, ino: u32) -> VfsResult {
+        self.check_writable()?;
+        let mut dir = self.read_inode(parent)?;
+        if self.dir_lookup(&dir, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut inode = self.read_inode(ino)?;
+        let ty = inode.node_type()?;
+        if ty == VfsNodeType::Dir {
+            return Err(VfsError::PermissionDenied);
+        } else if inode.links_count() == 0 {
+            return Err(VfsError::NotFound);
+        } else if inode.links_count() >= MAX_LINKS {
+            return Err(VfsError::StorageFull);
+        }
+        self.dir_add_entry(&mut dir, name, ino, ty)?;
+        inode.set_links_count(inode.links_count() + 1);
+        self.touch_changed(&mut inode);
+        self.write_inode(&mut inode)
+    }
+
+    /// Removes the entry `name` in the directory `parent`, the inode is
+    /// released if it has no more links.
+    pub fn unlink(&mut self, parent: u32, name: &str) -> VfsResult {
+        self.check_writable()?;
+        let mut dir = self.read_inode(parent)?;
+        let (ino, _) = self.dir_lookup(&dir, name)?.ok_or(VfsError::NotFound)?;
+        let mut inode = self.read_inode(ino)?;
+        let is_dir = inode.node_type()? == VfsNodeType::Dir;
+        if is_dir && !self.dir_is_empty(&inode)? {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        self.dir_remove_entry(&mut dir, name)?;
+        if is_dir {
+            dir.set_links_count(dir.links_count().saturating_sub(1));
+            self.write_inode(&mut dir)?;
+            inode.set_links_count(0);
+        } else {
+            inode.set_links_count(inode.links_count().saturating_sub(1));
+        }
+        if inode.links_count() == 0 {
+            self.release_inode(&mut inode)
+        } else {
+            self.touch_changed(&mut inode);
+            self.write_inode(&mut inode)
+        }
+    }
+
+    /// Whether the directory `ancestor` is `ino` or one of its ancestors.
+    fn is_ancestor(&mut self, ancestor: u32, mut ino: u32) -> VfsResult<bool> {
+        loop {
+            if ino == ancestor {
+                return Ok(true);
+            } else if ino == ROOT_INO {
+                return Ok(false);
+            }
+            let dir = self.read_inode(ino)?;
+            ino = self.dir_lookup(&dir, "..")?.ok_or(VfsError::InvalidData)?.0;
+        }
+    }
+
+    /// Moves the entry `src_name` in the directory `src_parent` to
+    /// `dst_name` in the directory `dst_parent`, replacing the existing one.
+    pub fn rename(
+        &mut self,
+        src_parent: u32,
+        src_name: &str,
+        dst_parent: u32,
+        dst_name: &str,
+    ) -> VfsResult {
+        self.check_writable()?;
+        if src_parent == dst_parent && src_name == dst_name {
+            return Ok(());
+        }
+        let src_dir = self.read_inode(src_parent)?;
+        let (ino, _) = self
+            .dir_lookup(&src_dir, src_name)?
+            .ok_or(VfsError::NotFound)?;
+        let mut inode = self.read_inode(ino)?;
+        let ty = inode.node_type()?;
+        let is_dir = ty == VfsNodeType::Dir;
+        if is_dir && self.is_ancestor(ino, dst_parent)? {
+            return Err(VfsError::InvalidInput); // move a directory into itself
+        }
+
+        let dst_dir = self.read_inode(dst_parent)?;
+        if let Some((dst_ino, file_type)) = self.dir_lookup(&dst_dir, dst_name)? {
+            if dst_ino == ino {
+                return Ok(());
+            }
+            match (
+                is_dir,
+                self.dirent_node_type(dst_ino, file_type)? == VfsNodeType::Dir,
+            ) {
+                (false, true) => return Err(VfsError::IsADirectory),
+                (true, false) => return Err(VfsError::NotADirectory),
+                _ => self.unlink(dst_parent, dst_name)?,
+            }
+        }
+
+        let mut dst_dir = self.read_inode(dst_parent)?;
+        self.dir_add_entry(&mut dst_dir, dst_name, ino, ty)?;
+        let mut src_dir = self.read_inode(src_parent)?;
+        self.dir_remove_entry(&mut src_dir, src_name)?;
+        if is_dir && src_parent != dst_parent {
+            self.dir_set_parent(&mut inode, dst_parent)?;
+            src_dir.set_links_count(src_dir.links_count().saturating_sub(1));
+            self.write_inode(&mut src_dir)?;
+            let mut dst_dir = self.read_inode(dst_parent)?;
+            dst_dir.set_links_count(dst_dir.links_count() + 1);
+            self.write_inode(&mut dst_dir)?;
+        }
+        self.touch_changed(&mut inode);
+        self.write_inode(&mut inode)
+    }
+}
@@ -0,0 +1,457 @@
+//! Extent trees, which map logical blocks of an inode to physical blocks.
+//!
+//! The root node is stored in `i_block` of the inode, and the other nodes are
+//! stored in separate blocks. Each node has a 12-byte header followed by
+//! 12-byte entries, which are extents in leaf nodes, or indexes in internal
+//! nodes. The first field of both kinds of entries is the first logical block
+//! they cover.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+use crate::crc::crc32c;
+use crate::fs::{Ext4Fs, Mapping};
+use crate::layout::*;
+use crate::BlockDevice;
+
+const EXTENT_MAGIC: u16 = 0xf30a;
+const HEADER_SIZE: usize = 12;
+const ENTRY_SIZE: usize = 12;
+/// The maximum length of an initialized extent.
+const MAX_INIT_LEN: u16 = 32768;
+/// The maximum depth of an extent tree, as in Linux.
+const MAX_DEPTH: u16 = 5;
+
+/// Where an extent tree node is stored.
+#[derive(Clone, Copy)]
+enum NodeLoc {
+    Root,
+    Block(u64),
+}
+
+/// An extent tree node, with its content copied out.
+struct Node {
+    loc: NodeLoc,
+    buf: Vec<u8>,
+}
+
+impl Node {
+    fn entries(&self) -> usize {
+        read_u16(&self.buf, 2) as usize
+    }
+
+    fn set_entries(&mut self, n: usize) {
+        write_u16(&mut self.buf, 2, n as u16)
+    }
+
+    fn max(&self) -> usize {
+        read_u16(&self.buf, 4) as usize
+    }
+
+    fn depth(&self) -> u16 {
+        read_u16(&self.buf, 6)
+    }
+
+    fn entry(&self, i: usize) -> &[u8] {
+        let off = HEADER_SIZE + i * ENTRY_SIZE;
+        &self.buf[off..off + ENTRY_SIZE]
+    }
+
+    fn entry_mut(&mut self, i: usize) -> &mut [u8] {
+        let off = HEADER_SIZE + i * ENTRY_SIZE;
+        &mut self.buf[off..off + ENTRY_SIZE]
+    }
+
+    /// The first logical block covered by the `i`-th entry.
+    fn key(&self, i: usize) -> u64 {
+        read_u32(self.entry(i), 0) as u64
+    }
+
+    /// Returns the index of the last entry whose key is not greater than
+    /// `lblk`, or `None` if there is no such entry.
+    fn search(&self, lblk: u64) -> Option<usize> {
+        (0..self.entries()).rev().find(|&i| self.key(i) <= lblk)
+    }
+
+    fn insert_entry(&mut self, pos: usize, entry: &[u8]) {
+        let n = self.entries();
+        debug_assert!(n < self.max());
+        let start = HEADER_SIZE + pos * ENTRY_SIZE;
+        let end = HEADER_SIZE + n * ENTRY_SIZE;
+        self.buf.copy_within(start..end, start + ENTRY_SIZE);
+        self.buf[start..start + ENTRY_SIZE].copy_from_slice(entry);
+        self.set_entries(n + 1);
+    }
+
+    fn remove_entry(&mut self, pos: usize) {
+        let n = self.entries();
+        let start = HEADER_SIZE + pos * ENTRY_SIZE;
+        let end = HEADER_SIZE + n * ENTRY_SIZE;
+        self.buf.copy_within(start + ENTRY_SIZE..end, start);
+        self.set_entries(n - 1);
+    }
+}
+
+/// A leaf entry.
+struct Extent {
+    block: u64,
+    len: u16,
+    start: u64,
+    unwritten: bool,
+}
+
+impl Extent {
+    fn parse(raw: &[u8]) -> Self {
+        let len = read_u16(raw, 4);
+        let (len, unwritten) = if len > MAX_INIT_LEN {
+            (len - MAX_INIT_LEN, true)
+        } else {
+            (len, false)
+        };
+        Self {
+            block: read_u32(raw, 0) as u64,
+            len,
+            start: read_u32(raw, 8) as u64 | (read_u16(raw, 6) as u64) << 32,
+            unwritten,
+        }
+    }
+
+    fn write(&self, raw: &mut [u8]) {
+        let len = if self.unwritten {
+            self.len + MAX_INIT_LEN
+        } else {
+            self.len
+        };
+        write_u32(raw, 0, self.block as u32);
+        write_u16(raw, 4, len);
+        write_u16(raw, 6, (self.start >> 32) as u16);
+        write_u32(raw, 8, self.start as u32);
+    }
+
+    fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut raw = [0; ENTRY_SIZE];
+        self.write(&mut raw);
+        raw
+    }
+}
+
+fn index_leaf(raw: &[u8]) -> u64 {
+    read_u32(raw, 4) as u64 | (read_u16(raw, 8) as u64) << 32
+}
+
+fn index_bytes(block: u64, leaf: u64) -> [u8; ENTRY_SIZE] {
+    let mut raw = [0; ENTRY_SIZE];
+    write_u32(&mut raw, 0, block as u32);
+    write_u32(&mut raw, 4, leaf as u32);
+    write_u16(&mut raw, 8, (leaf >> 32) as u16);
+    raw
+}
+
+fn init_header(buf: &mut [u8], max: usize, depth: u16) {
+    write_u16(buf, 0, EXTENT_MAGIC);
+    write_u16(buf, 2, 0);
+    write_u16(buf, 4, max as u16);
+    write_u16(buf, 6, depth);
+    write_u32(buf, 8, 0);
+}
+
+impl<D: BlockDevice> Ext4Fs<D> {
+    /// Initializes an empty extent tree in the inode.
+    pub fn init_extent_root(&self, inode: &mut Inode) {
+        init_header(
+            inode.block_mut(),
+            (INODE_BLOCK_SIZE - HEADER_SIZE) / ENTRY_SIZE,
+            0,
+        );
+        inode.set_flags(inode.flags() | INODE_FLAG_EXTENTS);
+    }
+
+    fn read_node(&mut self, inode: &Inode, loc: NodeLoc) -> VfsResult<Node> {
+        let buf = match loc {
+            NodeLoc::Root => inode.block().to_vec(),
+            NodeLoc::Block(block) => {
+                let mut buf = vec![0; self.block_size];
+                self.read_block(block, &mut buf)?;
+                buf
+            }
+        };
+        let node = Node { loc, buf };
+        let max_entries = (node.buf.len() - HEADER_SIZE) / ENTRY_SIZE;
+        if read_u16(&node.buf, 0) != EXTENT_MAGIC
+            || node.max() > max_entries
+            || node.entries() > node.max()
+            || node.depth() > MAX_DEPTH
+        {
+            return Err(VfsError::InvalidData);
+        }
+        Ok(node)
+    }
+
+    fn write_node(&mut self, inode: &mut Inode, node: &mut Node) -> VfsResult {
+        match node.loc {
+            NodeLoc::Root => {
+                inode.block_mut().copy_from_slice(&node.buf);
+                self.write_inode(inode)
+            }
+            NodeLoc::Block(block) => {
+                if self.has_metadata_csum() {
+                    let tail = HEADER_SIZE + node.max() * ENTRY_SIZE;
+                    let csum = crc32c(inode.checksum_seed(&self.sb), &node.buf[..tail]);
+                    write_u32(&mut node.buf, tail, csum);
+                }
+                self.write_block(block, &node.buf)
+            }
+        }
+    }
+
+    /// Returns the nodes from the root to the leaf that may contain `lblk`,
+    /// with the index of the chosen entry in each node.
+    fn find_path(&mut self, inode: &Inode, lblk: u64) -> VfsResult<Vec<(Node, Option<usize>)>> {
+        let mut path = Vec::new();
+        let mut node = self.read_node(inode, NodeLoc::Root)?;
+        loop {
+            let idx = node.search(lblk);
+            if node.depth() == 0 {
+                path.push((node, idx));
+                return Ok(path);
+            }
+            // the first index also covers the blocks before its key
+            let i = idx.unwrap_or(0);
+            if node.entries() == 0 {
+                return Err(VfsError::InvalidData);
+            }
+            let child = index_leaf(node.entry(i));
+            let depth = node.depth();
+            path.push((node, Some(i)));
+            node = self.read_node(inode, NodeLoc::Block(child))?;
+            if node.depth() != depth - 1 {
+                return Err(VfsError::InvalidData);
+            }
+        }
+    }
+
+    pub fn extent_map(&mut self, inode: &Inode, lblk: u64) -> VfsResult<Option<Mapping>> {
+        let path = self.find_path(inode, lblk)?;
+        let (leaf, idx) = path.last().unwrap();
+        Ok(idx.and_then(|i| {
+            let ext = Extent::parse(leaf.entry(i));
+            (lblk < ext.block + ext.len as u64)
+                .then(|| Mapping::new(ext.start + lblk - ext.block, ext.unwritten))
+        }))
+    }
+
+    /// Maps the unmapped logical block `lblk` to `pblk`.
+    pub fn extent_insert(&mut self, inode: &mut Inode, lblk: u64, pblk: u64) -> VfsResult {
+        if lblk >= 1 << 32 {
+            return Err(VfsError::InvalidInput);
+        }
+        loop {
+            let mut path = self.find_path(inode, lblk)?;
+            let level = path.len() - 1;
+            let (leaf, idx) = path.last_mut().unwrap();
+
+            // try to append to the previous extent
+            if let Some(i) = *idx {
+                let mut ext = Extent::parse(leaf.entry(i));
+                if !ext.unwritten
+                    && ext.block + ext.len as u64 == lblk
+                    && ext.start + ext.len as u64 == pblk
+                    && ext.len < MAX_INIT_LEN
+                {
+                    ext.len += 1;
+                    ext.write(leaf.entry_mut(i));
+                    return self.write_node(inode, leaf);
+                }
+            }
+
+            if leaf.entries() < leaf.max() {
+                let pos = idx.map_or(0, |i| i + 1);
+                let ext = Extent {
+                    block: lblk,
+                    len: 1,
+                    start: pblk,
+                    unwritten: false,
+                };
+                leaf.insert_entry(pos, &ext.to_bytes());
+                self.write_node(inode, leaf)?;
+                if pos == 0 {
+                    self.update_keys(inode, &mut path, lblk)?;
+                }
+                return Ok(());
+            }
+
+            // the leaf is full, split it and retry
+            self.split_node(inode, &mut path, level)?;
+        }
+    }
+
+    /// Updates the keys of the ancestors after the first key of the leaf in
+    /// `path` is changed to `key`.
+    fn update_keys(
+        &mut self,
+        inode: &mut Inode,
+        path: &mut [(Node, Option<usize>)],
+        key: u64,
+    ) -> VfsResult {
+        for level in (0..path.len() - 1).rev() {
+            let (node, idx) = &mut path[level];
+            let i = idx.unwrap();
+            if node.key(i) <= key {
+                break;
+            }
+            write_u32(node.entry_mut(i), 0, key as u32);
+            self.write_node(inode, node)?;
+            if i != 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits the full node at `level` of `path`.
+    ///
+    /// The root is split by moving all its entries to a new child, which
+    /// increases the depth of the tree. Other nodes are split into halves, or
+    /// only the last entry is moved if inserting at the end. If the parent is
+    /// also full, the parent is split instead, and the caller should retry.
+    fn split_node(
+        &mut self,
+        inode: &mut Inode,
+        path: &mut [(Node, Option<usize>)],
+        level: usize,
+    ) -> VfsResult {
+        let goal = self.inode_goal(inode);
+        if level == 0 {
+            let root = &mut path[0].0;
+            if root.depth() >= MAX_DEPTH {
+                return Err(VfsError::StorageFull);
+            }
+            let block = self.alloc_block(goal)?;
+            self.add_inode_blocks(inode, 1);
+            let mut child = Node {
+                loc: NodeLoc::Block(block),
+                buf: vec![0; self.block_size],
+            };
+            let n = root.entries();
+            init_header(
+                &mut child.buf,
+                (self.block_size - HEADER_SIZE) / ENTRY_SIZE,
+                root.depth(),
+            );
+            child.buf[HEADER_SIZE..HEADER_SIZE + n * ENTRY_SIZE]
+                .copy_from_slice(&root.buf[HEADER_SIZE..HEADER_SIZE + n * ENTRY_SIZE]);
+            child.set_entries(n);
+            self.write_node(inode, &mut child)?;
+
+            let key = if n > 0 { root.key(0) } else { 0 };
+            let depth = root.depth() + 1;
+            let max = root.max();
+            init_header(&mut root.buf, max, depth);
+            root.insert_entry(0, &index_bytes(key, block));
+            return self.write_node(inode, root);
+        }
+
+        let (parent, _) = &path[level - 1];
+        if parent.entries() == parent.max() {
+            return self.split_node(inode, path, level - 1);
+        }
+
+        let block = self.alloc_block(goal)?;
+        self.add_inode_blocks(inode, 1);
+        let (node, idx) = &mut path[level];
+        let n = node.entries();
+        // when appending, keep the node full as the new entries will go to
+        // the right
+        let half = if *idx == Some(n - 1) { n - 1 } else { n / 2 };
+        let mut right = Node {
+            loc: NodeLoc::Block(block),
+            buf: vec![0; self.block_size],
+        };
+        init_header(&mut right.buf, node.max(), node.depth());
+        right.buf[HEADER_SIZE..HEADER_SIZE + (n - half) * ENTRY_SIZE].copy_from_slice(
+            &node.buf[HEADER_SIZE + half * ENTRY_SIZE..HEADER_SIZE + n * ENTRY_SIZE],
+        );
+        right.set_entries(n - half);
+        node.set_entries(half);
+        let key = right.key(0);
+        self.write_node(inode, &mut right)?;
+        self.write_node(inode, node)?;
+
+        let (parent, idx) = &mut path[level - 1];
+        parent.insert_entry(idx.unwrap() + 1, &index_bytes(key, block));
+        self.write_node(inode, parent)
+    }
+
+    /// Converts the unwritten extent containing `lblk` to an initialized one,
+    /// its blocks are filled with zeros.
+    pub fn init_unwritten(&mut self, inode: &mut Inode, lblk: u64) -> VfsResult {
+        let mut path = self.find_path(inode, lblk)?;
+        let (leaf, idx) = path.last_mut().unwrap();
+        let i = idx.ok_or(VfsError::InvalidData)?;
+        let mut ext = Extent::parse(leaf.entry(i));
+        for block in ext.start..ext.start + ext.len as u64 {
+            self.zero_block(block)?;
+        }
+        ext.unwritten = false;
+        ext.write(leaf.entry_mut(i));
+        self.write_node(inode, leaf)
+    }
+
+    /// Frees all the blocks starting from the logical block `from`.
+    pub fn extent_truncate(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        let mut root = self.read_node(inode, NodeLoc::Root)?;
+        self.truncate_node(inode, &mut root, from)?;
+        if root.entries() == 0 && root.depth() != 0 {
+            let max = root.max();
+            init_header(&mut root.buf, max, 0);
+        }
+        self.write_node(inode, &mut root)
+    }
+
+    /// Frees the blocks starting from `from` in the subtree of `node`, and
+    /// removes the emptied entries. The node itself is not written.
+    fn truncate_node(&mut self, inode: &mut Inode, node: &mut Node, from: u64) -> VfsResult {
+        let mut i = node.entries();
+        while i > 0 {
+            i -= 1;
+            if node.depth() == 0 {
+                let mut ext = Extent::parse(node.entry(i));
+                let end = ext.block + ext.len as u64;
+                if end <= from {
+                    break;
+                }
+                let keep = from.saturating_sub(ext.block);
+                for block in ext.start + keep..ext.start + ext.len as u64 {
+                    self.free_block(block)?;
+                }
+                self.add_inode_blocks(inode, keep as i64 - ext.len as i64);
+                if keep == 0 {
+                    node.remove_entry(i);
+                } else {
+                    ext.len = keep as u16;
+                    ext.write(node.entry_mut(i));
+                    break;
+                }
+            } else {
+                let key = node.key(i);
+                let child_block = index_leaf(node.entry(i));
+                let mut child = self.read_node(inode, NodeLoc::Block(child_block))?;
+                self.truncate_node(inode, &mut child, from)?;
+                if child.entries() == 0 {
+                    self.free_block(child_block)?;
+                    self.add_inode_blocks(inode, -1);
+                    node.remove_entry(i);
+                } else {
+                    self.write_node(inode, &mut child)?;
+                }
+                if key < from {
+                    // the previous children only contain blocks before `from`
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,707 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use crate::crc::crc32c;
+use crate::layout::*;
+use crate::BlockDevice;
+
+/// The mounted filesystem, all operations are performed with it locked.
+pub struct Ext4Fs<D> {
+    dev: D,
+    pub(crate) sb: SuperBlock,
+    groups: Vec<GroupDesc>,
+    pub(crate) block_size: usize,
+    read_only: bool,
+    time_source: Option<fn() -> Duration>,
+}
+
+impl<D: BlockDevice> Ext4Fs<D> {
+    pub fn open(mut dev: D) -> VfsResult<Self> {
+        let mut raw = [0; SUPERBLOCK_SIZE];
+        dev.read_at(SUPERBLOCK_OFFSET, &mut raw)?;
+        let sb = SuperBlock::from_bytes(raw)?;
+
+        let unsupported = sb.feature_incompat() & !INCOMPAT_SUPPORTED;
+        if unsupported != 0 {
+            warn!("ext4: unsupported incompatible features {:#x}", unsupported);
+            return Err(VfsError::Unsupported);
+        }
+        let mut read_only = false;
+        let ro_unsupported = sb.feature_ro_compat() & !RO_COMPAT_WRITE_SUPPORTED;
+        if ro_unsupported != 0 {
+            warn!(
+                "ext4: unsupported read-only compatible features {:#x}, mount read-only",
+                ro_unsupported
+            );
+            read_only = true;
+        }
+        if sb.has_incompat(INCOMPAT_RECOVER | INCOMPAT_MMP) {
+            warn!("ext4: the journal needs recovery or MMP is enabled, mount read-only");
+            read_only = true;
+        }
+
+        let block_size = sb.block_size();
+        let desc_size = sb.desc_size();
+        let group_count = sb.group_count();
+        let mut fs = Self {
+            dev,
+            sb,
+            groups: Vec::with_capacity(group_count as usize),
+            block_size,
+            read_only,
+            time_source: None,
+        };
+
+        let descs_per_block = (block_size / desc_size) as u64;
+        let mut buf = vec![0; block_size];
+        for i in 0..group_count.div_ceil(descs_per_block) {
+            fs.read_block(fs.desc_block_location(i), &mut buf)?;
+            for desc in buf.chunks_exact(desc_size) {
+                if fs.groups.len() as u64 == group_count {
+                    break;
+                }
+                fs.groups.push(GroupDesc::from_bytes(desc));
+            }
+        }
+        debug!(
+            "ext4: block size {}, {} blocks, {} groups, features {:#x}/{:#x}",
+            block_size,
+            fs.sb.blocks_count(),
+            group_count,
+            fs.sb.feature_incompat(),
+            fs.sb.feature_ro_compat()
+        );
+        Ok(fs)
+    }
+
+    pub fn set_time_source(&mut self, time_source: fn() -> Duration) {
+        self.time_source = Some(time_source);
+    }
+
+    pub fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn has_metadata_csum(&self) -> bool {
+        self.sb.has_ro_compat(RO_COMPAT_METADATA_CSUM)
+    }
+
+    pub fn read_block(&mut self, block: u64, buf: &mut [u8]) -> VfsResult {
+        debug_assert_eq!(buf.len(), self.block_size);
+        self.dev.read_at(block * self.block_size as u64, buf)
+    }
+
+    pub fn write_block(&mut self, block: u64, buf: &[u8]) -> VfsResult {
+        debug_assert_eq!(buf.len(), self.block_size);
+        self.dev.write_at(block * self.block_size as u64, buf)
+    }
+
+    pub fn zero_block(&mut self, block: u64) -> VfsResult {
+        let zeros = vec![0; self.block_size];
+        self.write_block(block, &zeros)
+    }
+
+    /// Returns the current time, or `None` if the time source is not set, in
+    /// which case the timestamps are not updated.
+    pub fn now(&self) -> Option<Duration> {
+        self.time_source.map(|f| f())
+    }
+
+    /// Updates `mtime` and `ctime` of the inode to the current time.
+    pub fn touch_modified(&self, inode: &mut Inode) {
+        if let Some(now) = self.now() {
+            inode.set_mtime(now.as_secs(), now.subsec_nanos());
+            inode.set_ctime(now.as_secs(), now.subsec_nanos());
+        }
+    }
+
+    /// Updates `ctime` of the inode to the current time.
+    pub fn touch_changed(&self, inode: &mut Inode) {
+        if let Some(now) = self.now() {
+            inode.set_ctime(now.as_secs(), now.subsec_nanos());
+        }
+    }
+}
+
+// Superblock and group descriptors.
+impl<D: BlockDevice> Ext4Fs<D> {
+    fn group_first_block(&self, group: u64) -> u64 {
+        self.sb.first_data_block() + group * self.sb.blocks_per_group()
+    }
+
+    fn blocks_in_group(&self, group: u64) -> u64 {
+        let remaining = self.sb.blocks_count() - self.group_first_block(group);
+        remaining.min(self.sb.blocks_per_group())
+    }
+
+    /// Returns the location of the `idx`-th block of the group descriptor
+    /// table.
+    fn desc_block_location(&self, idx: u64) -> u64 {
+        let descs_per_block = (self.block_size / self.sb.desc_size()) as u64;
+        if !self.sb.has_incompat(INCOMPAT_META_BG) || idx < self.sb.first_meta_bg() {
+            self.sb.first_data_block() + 1 + idx
+        } else {
+            // with `meta_bg`, the descriptor block is in the first group of
+            // its meta group
+            let group = idx * descs_per_block;
+            let has_super = self.sb.group_has_super(group) as u64;
+            self.group_first_block(group) + has_super
+        }
+    }
+
+    /// Returns the number of blocks used by the superblock and group
+    /// descriptor table (and its reserved blocks) in the group.
+    fn group_base_meta_blocks(&self, group: u64) -> u64 {
+        let has_super = self.sb.group_has_super(group) as u64;
+        let descs_per_block = (self.block_size / self.sb.desc_size()) as u64;
+        let desc_blocks = self.sb.group_count().div_ceil(descs_per_block);
+        let meta_group = group / descs_per_block;
+        if !self.sb.has_incompat(INCOMPAT_META_BG) || meta_group < self.sb.first_meta_bg() {
+            if has_super == 0 {
+                0
+            } else {
+                let gdt_blocks = if self.sb.has_incompat(INCOMPAT_META_BG) {
+                    self.sb.first_meta_bg()
+                } else {
+                    desc_blocks
+                };
+                has_super + gdt_blocks + self.sb.reserved_gdt_blocks()
+            }
+        } else {
+            let idx = group % descs_per_block;
+            let has_desc = idx == 0 || idx == 1 || idx == descs_per_block - 1;
+            has_super + has_desc as u64
+        }
+    }
+
+    pub fn write_super(&mut self) -> VfsResult {
+        self.sb.update_checksum();
+        self.dev.write_at(SUPERBLOCK_OFFSET, self.sb.as_bytes())
+    }
+
+    fn write_group_desc(&mut self, group: u64) -> VfsResult {
+        let desc_size = self.sb.desc_size();
+        let descs_per_block = (self.block_size / desc_size) as u64;
+        let block = self.desc_block_location(group / descs_per_block);
+        let offset = block * self.block_size as u64 + (group % descs_per_block) * desc_size as u64;
+        let desc = &mut self.groups[group as usize];
+        desc.update_checksum(&self.sb, group);
+        self.dev.write_at(offset, desc.as_bytes())
+    }
+
+    fn update_group_desc(&mut self, group: u64, f: impl FnOnce(&mut GroupDesc)) -> VfsResult {
+        f(&mut self.groups[group as usize]);
+        self.write_group_desc(group)
+    }
+}
+
+// Bitmaps and allocation.
+impl<D: BlockDevice> Ext4Fs<D> {
+    fn read_block_bitmap(&mut self, group: u64) -> VfsResult<Vec<u8>> {
+        let mut bitmap = vec![0; self.block_size];
+        let desc = self.groups[group as usize].clone();
+        if desc.flags() & BG_BLOCK_UNINIT == 0 {
+            self.read_block(desc.block_bitmap(), &mut bitmap)?;
+            return Ok(bitmap);
+        }
+
+        // The bitmap is not initialized, the used blocks are the metadata
+        // blocks in this group.
+        let start = self.group_first_block(group);
+        let end = start + self.sb.blocks_per_group();
+        for i in 0..self.group_base_meta_blocks(group) {
+            set_bit(&mut bitmap, i as usize);
+        }
+        for desc in self.groups.iter() {
+            let table_blocks = (self.sb.inodes_per_group() as usize * self.sb.inode_size())
+                .div_ceil(self.block_size) as u64;
+            let meta = [
+                (desc.block_bitmap(), 1),
+                (desc.inode_bitmap(), 1),
+                (desc.inode_table(), table_blocks),
+            ];
+            for (block, count) in meta {
+                for b in block..block + count {
+                    if (start..end).contains(&b) {
+                        set_bit(&mut bitmap, (b - start) as usize);
+                    }
+                }
+            }
+        }
+        for i in self.blocks_in_group(group) as usize..self.block_size * 8 {
+            set_bit(&mut bitmap, i);
+        }
+        self.groups[group as usize].set_flags(desc.flags() & !BG_BLOCK_UNINIT);
+        Ok(bitmap)
+    }
+
+    fn write_block_bitmap(&mut self, group: u64, bitmap: &[u8]) -> VfsResult {
+        let desc = &mut self.groups[group as usize];
+        if self.sb.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            let len = self.sb.blocks_per_group() as usize / 8;
+            desc.set_block_bitmap_csum(crc32c(self.sb.checksum_seed(), &bitmap[..len]));
+        }
+        let block = desc.block_bitmap();
+        self.write_block(block, bitmap)
+    }
+
+    fn read_inode_bitmap(&mut self, group: u64) -> VfsResult<Vec<u8>> {
+        let mut bitmap = vec![0; self.block_size];
+        let desc = &mut self.groups[group as usize];
+        if desc.flags() & BG_INODE_UNINIT == 0 {
+            let block = desc.inode_bitmap();
+            self.read_block(block, &mut bitmap)?;
+        } else {
+            desc.set_flags(desc.flags() & !BG_INODE_UNINIT);
+            for i in self.sb.inodes_per_group() as usize..self.block_size * 8 {
+                set_bit(&mut bitmap, i);
+            }
+        }
+        Ok(bitmap)
+    }
+
+    fn write_inode_bitmap(&mut self, group: u64, bitmap: &[u8]) -> VfsResult {
+        let desc = &mut self.groups[group as usize];
+        if self.sb.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            let len = self.sb.inodes_per_group() as usize / 8;
+            desc.set_inode_bitmap_csum(crc32c(self.sb.checksum_seed(), &bitmap[..len]));
+        }
+        let block = desc.inode_bitmap();
+        self.write_block(block, bitmap)
+    }
+
+    /// Allocates a block, preferably at `goal` or after it.
+    ///
+    /// The content of the block is not initialized.
+    pub fn alloc_block(&mut self, goal: u64) -> VfsResult<u64> {
+        self.check_writable()?;
+        let group_count = self.sb.group_count();
+        let goal = goal.clamp(self.sb.first_data_block(), self.sb.blocks_count() - 1);
+        let goal_group = (goal - self.sb.first_data_block()) / self.sb.blocks_per_group();
+        for i in 0..group_count {
+            let group = (goal_group + i) % group_count;
+            if self.groups[group as usize].free_blocks_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_block_bitmap(group)?;
+            let start_bit = if i == 0 {
+                (goal - self.group_first_block(group)) as usize
+            } else {
+                0
+            };
+            let nbits = self.blocks_in_group(group) as usize;
+            let Some(bit) = find_zero_bit(&bitmap, start_bit, nbits)
+                .or_else(|| find_zero_bit(&bitmap, 0, start_bit))
+            else {
+                continue;
+            };
+            set_bit(&mut bitmap, bit);
+            self.write_block_bitmap(group, &bitmap)?;
+            self.update_group_desc(group, |desc| {
+                desc.set_free_blocks_count(desc.free_blocks_count() - 1)
+            })?;
+            self.sb
+                .set_free_blocks_count(self.sb.free_blocks_count().saturating_sub(1));
+            self.write_super()?;
+            return Ok(self.group_first_block(group) + bit as u64);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    /// Allocates a block and fills it with zeros.
+    pub fn alloc_zeroed_block(&mut self, goal: u64) -> VfsResult<u64> {
+        let block = self.alloc_block(goal)?;
+        self.zero_block(block)?;
+        Ok(block)
+    }
+
+    pub fn free_block(&mut self, block: u64) -> VfsResult {
+        if block < self.sb.first_data_block() || block >= self.sb.blocks_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (block - self.sb.first_data_block()) / self.sb.blocks_per_group();
+        let bit = (block - self.group_first_block(group)) as usize;
+        let mut bitmap = self.read_block_bitmap(group)?;
+        if !test_bit(&bitmap, bit) {
+            warn!("ext4: freeing free block {}", block);
+            return Ok(());
+        }
+        clear_bit(&mut bitmap, bit);
+        self.write_block_bitmap(group, &bitmap)?;
+        self.update_group_desc(group, |desc| {
+            desc.set_free_blocks_count(desc.free_blocks_count() + 1)
+        })?;
+        self.sb
+            .set_free_blocks_count(self.sb.free_blocks_count() + 1);
+        self.write_super()
+    }
+
+    /// Allocates an inode, preferably in the same group as `parent`.
+    pub fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> VfsResult<u32> {
+        self.check_writable()?;
+        let group_count = self.sb.group_count();
+        let ipg = self.sb.inodes_per_group();
+        let parent_group = ((parent - 1) / ipg) as u64;
+        for i in 0..group_count {
+            let group = (parent_group + i) % group_count;
+            if self.groups[group as usize].free_inodes_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_inode_bitmap(group)?;
+            let Some(bit) = find_zero_bit(&bitmap, 0, ipg as usize) else {
+                continue;
+            };
+            let ino = group as u32 * ipg + bit as u32 + 1;
+            if ino < self.sb.first_ino() {
+                // reserved inodes must have been marked as used
+                return Err(VfsError::InvalidData);
+            }
+            set_bit(&mut bitmap, bit);
+            self.write_inode_bitmap(group, &bitmap)?;
+            self.update_group_desc(group, |desc| {
+                desc.set_free_inodes_count(desc.free_inodes_count() - 1);
+                if is_dir {
+                    desc.set_used_dirs_count(desc.used_dirs_count() + 1);
+                }
+                let unused_start = ipg - desc.itable_unused();
+                if bit as u32 >= unused_start {
+                    desc.set_itable_unused(ipg - bit as u32 - 1);
+                }
+            })?;
+            self.sb
+                .set_free_inodes_count(self.sb.free_inodes_count() - 1);
+            self.write_super()?;
+            return Ok(ino);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let ipg = self.sb.inodes_per_group();
+        let group = ((ino - 1) / ipg) as u64;
+        let bit = ((ino - 1) % ipg) as usize;
+        let mut bitmap = self.read_inode_bitmap(group)?;
+        clear_bit(&mut bitmap, bit);
+        self.write_inode_bitmap(group, &bitmap)?;
+        self.update_group_desc(group, |desc| {
+            desc.set_free_inodes_count(desc.free_inodes_count() + 1);
+            if is_dir {
+                desc.set_used_dirs_count(desc.used_dirs_count().saturating_sub(1));
+            }
+        })?;
+        self.sb
+            .set_free_inodes_count(self.sb.free_inodes_count() + 1);
+        self.write_super()
+    }
+}
+
+// Inodes.
+impl<D: BlockDevice> Ext4Fs<D> {
+    fn inode_location(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.sb.inodes_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let ipg = self.sb.inodes_per_group();
+        let desc = &self.groups[((ino - 1) / ipg) as usize];
+        let index = ((ino - 1) % ipg) as u64;
+        Ok(desc.inode_table() * self.block_size as u64 + index * self.sb.inode_size() as u64)
+    }
+
+    pub fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let mut raw = vec![0; self.sb.inode_size()];
+        self.dev.read_at(self.inode_location(ino)?, &mut raw)?;
+        Ok(Inode::from_bytes(ino, &raw))
+    }
+
+    pub fn write_inode(&mut self, inode: &mut Inode) -> VfsResult {
+        self.check_writable()?;
+        inode.update_checksum(&self.sb);
+        let offset = self.inode_location(inode.ino)?;
+        self.dev.write_at(offset, inode.as_bytes())
+    }
+
+    /// Allocates and initializes a new inode of type `ty`, it is not linked
+    /// to any directory yet.
+    pub fn new_inode(&mut self, parent: u32, ty: VfsNodeType, perm: u16) -> VfsResult<Inode> {
+        let ino = self.alloc_inode(parent, ty == VfsNodeType::Dir)?;
+        let mut inode = Inode::new_zeroed(ino, self.sb.inode_size());
+        inode.set_mode(type_to_mode(ty) | (perm & 0o7777));
+        if let Some(now) = self.now() {
+            let (secs, nsecs) = (now.as_secs(), now.subsec_nanos());
+            inode.set_atime(secs, nsecs);
+            inode.set_ctime(secs, nsecs);
+            inode.set_mtime(secs, nsecs);
+            inode.set_crtime(secs, nsecs);
+        }
+        if matches!(ty, VfsNodeType::File | VfsNodeType::Dir)
+            && self.sb.has_incompat(INCOMPAT_EXTENTS)
+        {
+            self.init_extent_root(&mut inode);
+        }
+        Ok(inode)
+    }
+
+    /// Adds `delta` blocks to the `i_blocks` of the inode.
+    pub fn add_inode_blocks(&self, inode: &mut Inode, delta: i64) {
+        let sectors = (self.block_size / 512) as i64 * delta;
+        let blocks = inode.blocks(&self.sb) as i64 + sectors;
+        inode.set_blocks(&self.sb, blocks.max(0) as u64);
+    }
+
+    /// Releases all the blocks of an unlinked inode and frees it.
+    pub fn release_inode(&mut self, inode: &mut Inode) -> VfsResult {
+        let is_dir = inode.node_type()? == VfsNodeType::Dir;
+        if !self.is_fast_symlink(inode) {
+            self.truncate_blocks(inode, 0)?;
+        }
+        if inode.file_acl() != 0 {
+            self.free_block(inode.file_acl())?;
+        }
+        inode.set_links_count(0);
+        inode.set_size(0);
+        // a small `dtime` is taken as the next inode of the orphan list
+        let dtime = self
+            .now()
+            .map_or(self.sb.write_time(), |now| now.as_secs() as u32);
+        inode.set_dtime(dtime.max(self.sb.inodes_count()));
+        self.write_inode(inode)?;
+        self.free_inode(inode.ino, is_dir)
+    }
+
+    /// Whether the inode is a symlink with the target stored in `i_block`.
+    pub fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        if inode.mode() & S_IFMT != S_IFLNK {
+            return false;
+        }
+        let xattr_blocks = if inode.file_acl() != 0 {
+            (self.block_size / 512) as u64
+        } else {
+            0
+        };
+        inode.blocks(&self.sb) <= xattr_blocks
+    }
+}
+
+// File content.
+impl<D: BlockDevice> Ext4Fs<D> {
+    pub fn read_file(&mut self, inode: &Inode, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if inode.flags() & INODE_FLAG_INLINE_DATA != 0 {
+            return Err(VfsError::Unsupported);
+        }
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        let bs = self.block_size as u64;
+        let mut block_buf = vec![0; self.block_size];
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let lblk = pos / bs;
+            let off = (pos % bs) as usize;
+            let n = (self.block_size - off).min(len - done);
+            match self.map_block(inode, lblk)? {
+                Some(m) if !m.unwritten => {
+                    self.read_block(m.pblk, &mut block_buf)?;
+                    buf[done..done + n].copy_from_slice(&block_buf[off..off + n]);
+                }
+                _ => buf[done..done + n].fill(0), // hole
+            }
+            done += n;
+        }
+        Ok(len)
+    }
+
+    pub fn write_file(&mut self, inode: &mut Inode, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.check_writable()?;
+        if inode.flags() & INODE_FLAG_INLINE_DATA != 0 {
+            return Err(VfsError::Unsupported);
+        }
+        let bs = self.block_size as u64;
+        let mut block_buf = vec![0; self.block_size];
+        let mut done = 0;
+        let mut res = Ok(());
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let lblk = pos / bs;
+            let off = (pos % bs) as usize;
+            let n = (self.block_size - off).min(buf.len() - done);
+            let pblk = match self.get_or_alloc_block(inode, lblk) {
+                Ok((pblk, fresh)) => {
+                    if n < self.block_size {
+                        if fresh {
+                            block_buf.fill(0);
+                        } else if let Err(e) = self.read_block(pblk, &mut block_buf) {
+                            res = Err(e);
+                            break;
+                        }
+                    }
+                    pblk
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            };
+            block_buf[off..off + n].copy_from_slice(&buf[done..done + n]);
+            if let Err(e) = self.write_block(pblk, &block_buf) {
+                res = Err(e);
+                break;
+            }
+            done += n;
+        }
+        if offset + done as u64 > inode.size() {
+            inode.set_size(offset + done as u64);
+        }
+        if done > 0 {
+            self.touch_modified(inode);
+        }
+        self.write_inode(inode)?;
+        match res {
+            Err(e) if done == 0 => Err(e),
+            _ => Ok(done),
+        }
+    }
+
+    pub fn truncate_file(&mut self, inode: &mut Inode, size: u64) -> VfsResult {
+        self.check_writable()?;
+        if inode.flags() & INODE_FLAG_INLINE_DATA != 0 {
+            return Err(VfsError::Unsupported);
+        }
+        let bs = self.block_size as u64;
+        let old_size = inode.size();
+        if size < old_size {
+            self.truncate_blocks(inode, size.div_ceil(bs))?;
+            // zero the tail of the last block, which may be exposed by a
+            // later extension
+            if !size.is_multiple_of(bs) {
+                if let Some(m) = self.map_block(inode, size / bs)? {
+                    if !m.unwritten {
+                        let mut block_buf = vec![0; self.block_size];
+                        self.read_block(m.pblk, &mut block_buf)?;
+                        block_buf[(size % bs) as usize..].fill(0);
+                        self.write_block(m.pblk, &block_buf)?;
+                    }
+                }
+            }
+        }
+        inode.set_size(size);
+        self.touch_modified(inode);
+        self.write_inode(inode)
+    }
+
+    /// Returns the physical block of the logical block `lblk`, allocates it if
+    /// it is not mapped.
+    ///
+    /// The second return value is `true` if the block is newly allocated and
+    /// its content is not initialized.
+    pub fn get_or_alloc_block(&mut self, inode: &mut Inode, lblk: u64) -> VfsResult<(u64, bool)> {
+        match self.map_block(inode, lblk)? {
+            Some(m) if m.unwritten => {
+                self.init_unwritten(inode, lblk)?;
+                Ok((m.pblk, false))
+            }
+            Some(m) => Ok((m.pblk, false)),
+            None => {
+                let goal = match lblk.checked_sub(1) {
+                    Some(prev) => self.map_block(inode, prev)?.map(|m| m.pblk + 1),
+                    None => None,
+                };
+                let goal = goal.unwrap_or_else(|| self.inode_goal(inode));
+                let pblk = self.alloc_block(goal)?;
+                if let Err(e) = self.set_block_mapping(inode, lblk, pblk) {
+                    self.free_block(pblk)?;
+                    return Err(e);
+                }
+                self.add_inode_blocks(inode, 1);
+                Ok((pblk, true))
+            }
+        }
+    }
+
+    /// The preferred location of the blocks of the inode, i.e., the start of
+    /// its block group.
+    pub fn inode_goal(&self, inode: &Inode) -> u64 {
+        let group = ((inode.ino - 1) / self.sb.inodes_per_group()) as u64;
+        self.group_first_block(group)
+    }
+
+    /// Maps the logical block `lblk` of the inode to the physical block `pblk`.
+    fn set_block_mapping(&mut self, inode: &mut Inode, lblk: u64, pblk: u64) -> VfsResult {
+        if inode.flags() & INODE_FLAG_EXTENTS != 0 {
+            self.extent_insert(inode, lblk, pblk)
+        } else {
+            self.blockmap_insert(inode, lblk, pblk)
+        }
+    }
+
+    /// Looks up the physical block of the logical block `lblk` of the inode.
+    pub fn map_block(&mut self, inode: &Inode, lblk: u64) -> VfsResult<Option<Mapping>> {
+        if inode.flags() & INODE_FLAG_EXTENTS != 0 {
+            self.extent_map(inode, lblk)
+        } else {
+            self.blockmap_map(inode, lblk)
+                .map(|pblk| pblk.map(|pblk| Mapping::new(pblk, false)))
+        }
+    }
+
+    /// Frees all the blocks of the inode starting from the logical block
+    /// `from`.
+    pub fn truncate_blocks(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        if inode.flags() & INODE_FLAG_EXTENTS != 0 {
+            self.extent_truncate(inode, from)
+        } else {
+            self.blockmap_truncate(inode, from)
+        }
+    }
+}
+
+/// The result of a block lookup.
+#[derive(Debug, Clone, Copy)]
+pub struct Mapping {
+    pub pblk: u64,
+    /// Whether the block is in an unwritten (preallocated) extent, which
+    /// should be read as zeros.
+    pub unwritten: bool,
+}
+
+impl Mapping {
+    pub const fn new(pblk: u64, unwritten: bool) -> Self {
+        Self { pblk, unwritten }
+    }
+}
+
+fn test_bit(bitmap: &[u8], bit: usize) -> bool {
+    bitmap[bit / 8] & (1 << (bit % 8)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], bit: usize) {
+    bitmap[bit / 8] |= 1 << (bit % 8);
+}
+
+fn clear_bit(bitmap: &mut [u8], bit: usize) {
+    bitmap[bit / 8] &= !(1 << (bit % 8));
+}
+
+fn find_zero_bit(bitmap: &[u8], start: usize, end: usize) -> Option<usize> {
+    let mut bit = start;
+    while bit < end {
+        if bit % 8 == 0 && bit + 8 <= end && bitmap[bit / 8] == 0xff {
+            bit += 8;
+            continue;
+        }
+        if !test_bit(bitmap, bit) {
+            return Some(bit);
+        }
+        bit += 1;
+    }
+    None
+}
@@ -0,0 +1,624 @@
+//! On-disk structures of ext2/3/4.
+//!
+//! The structures are kept as raw little-endian bytes, and only the fields
+//! used by this crate have accessors.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use crate::crc::{crc16, crc32c};
+
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+pub const EXT4_MAGIC: u16 = 0xef53;
+pub const ROOT_INO: u32 = 2;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_META_BG: u32 = 0x10;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_MMP: u32 = 0x100;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_EA_INODE: u32 = 0x400;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+pub const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_META_BG
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_MMP
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_EA_INODE
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_BTREE_DIR: u32 = 0x4;
+pub const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+pub const RO_COMPAT_WRITE_SUPPORTED: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_BTREE_DIR
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM;
+
+pub const BG_INODE_UNINIT: u16 = 0x1;
+pub const BG_BLOCK_UNINIT: u16 = 0x2;
+
+pub const INODE_FLAG_INDEX: u32 = 0x1000;
+pub const INODE_FLAG_HUGE_FILE: u32 = 0x4_0000;
+pub const INODE_FLAG_EXTENTS: u32 = 0x8_0000;
+pub const INODE_FLAG_INLINE_DATA: u32 = 0x1000_0000;
+
+/// Maximum number of hard links to an inode.
+pub const MAX_LINKS: u16 = 65000;
+
+pub const S_IFMT: u16 = 0o170000;
+pub const S_IFIFO: u16 = 0o010000;
+pub const S_IFCHR: u16 = 0o020000;
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFBLK: u16 = 0o060000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFLNK: u16 = 0o120000;
+pub const S_IFSOCK: u16 = 0o140000;
+
+/// Size of the `i_block` field, also the maximum length of a fast symlink
+/// (exclusive).
+pub const INODE_BLOCK_SIZE: usize = 60;
+const INODE_BLOCK_OFFSET: usize = 0x28;
+const GOOD_OLD_INODE_SIZE: usize = 128;
+
+pub fn read_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+pub fn read_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+pub fn write_u16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+pub fn write_u32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+/// Converts the file type in `i_mode` to [`VfsNodeType`].
+pub fn mode_to_type(mode: u16) -> VfsResult<VfsNodeType> {
+    Ok(match mode & S_IFMT {
+        S_IFIFO => VfsNodeType::Fifo,
+        S_IFCHR => VfsNodeType::CharDevice,
+        S_IFDIR => VfsNodeType::Dir,
+        S_IFBLK => VfsNodeType::BlockDevice,
+        S_IFREG => VfsNodeType::File,
+        S_IFLNK => VfsNodeType::SymLink,
+        S_IFSOCK => VfsNodeType::Socket,
+        _ => return Err(VfsError::InvalidData),
+    })
+}
+
+/// Converts [`VfsNodeType`] to the file type bits in `i_mode`.
+pub const fn type_to_mode(ty: VfsNodeType) -> u16 {
+    match ty {
+        VfsNodeType::Fifo => S_IFIFO,
+        VfsNodeType::CharDevice => S_IFCHR,
+        VfsNodeType::Dir => S_IFDIR,
+        VfsNodeType::BlockDevice => S_IFBLK,
+        VfsNodeType::File => S_IFREG,
+        VfsNodeType::SymLink => S_IFLNK,
+        VfsNodeType::Socket => S_IFSOCK,
+    }
+}
+
+/// Converts [`VfsNodeType`] to the `file_type` field of directory entries.
+pub const fn type_to_dirent_type(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+/// The superblock, located at byte offset 1024 of the device.
+pub struct SuperBlock {
+    raw: [u8; SUPERBLOCK_SIZE],
+}
+
+impl SuperBlock {
+    pub fn from_bytes(raw: [u8; SUPERBLOCK_SIZE]) -> VfsResult<Self> {
+        let sb = Self { raw };
+        if read_u16(&sb.raw, 0x38) != EXT4_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let log_block_size = read_u32(&sb.raw, 0x18);
+        if log_block_size > 6
+            || sb.blocks_per_group() == 0
+            || sb.inodes_per_group() == 0
+            || sb.inode_size() < GOOD_OLD_INODE_SIZE
+            || !sb.inode_size().is_power_of_two()
+            || sb.inode_size() > sb.block_size()
+        {
+            return Err(VfsError::InvalidData);
+        }
+        Ok(sb)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x00)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        let lo = read_u32(&self.raw, 0x04) as u64;
+        if self.has_incompat(INCOMPAT_64BIT) {
+            lo | (read_u32(&self.raw, 0x150) as u64) << 32
+        } else {
+            lo
+        }
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        let lo = read_u32(&self.raw, 0x0c) as u64;
+        if self.has_incompat(INCOMPAT_64BIT) {
+            lo | (read_u32(&self.raw, 0x158) as u64) << 32
+        } else {
+            lo
+        }
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        write_u32(&mut self.raw, 0x0c, count as u32);
+        if self.has_incompat(INCOMPAT_64BIT) {
+            write_u32(&mut self.raw, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        write_u32(&mut self.raw, 0x10, count);
+    }
+
+    /// The last write time, in seconds since the Unix epoch.
+    pub fn write_time(&self) -> u32 {
+        read_u32(&self.raw, 0x30)
+    }
+
+    pub fn first_data_block(&self) -> u64 {
+        read_u32(&self.raw, 0x14) as u64
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << read_u32(&self.raw, 0x18)
+    }
+
+    pub fn blocks_per_group(&self) -> u64 {
+        read_u32(&self.raw, 0x20) as u64
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        read_u32(&self.raw, 0x28)
+    }
+
+    fn rev_level(&self) -> u32 {
+        read_u32(&self.raw, 0x4c)
+    }
+
+    pub fn first_ino(&self) -> u32 {
+        if self.rev_level() == 0 {
+            11
+        } else {
+            read_u32(&self.raw, 0x54)
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        if self.rev_level() == 0 {
+            GOOD_OLD_INODE_SIZE
+        } else {
+            read_u16(&self.raw, 0x58) as usize
+        }
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        read_u32(&self.raw, 0x60)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    pub fn has_incompat(&self, feature: u32) -> bool {
+        self.feature_incompat() & feature != 0
+    }
+
+    pub fn has_ro_compat(&self, feature: u32) -> bool {
+        self.feature_ro_compat() & feature != 0
+    }
+
+    pub fn reserved_gdt_blocks(&self) -> u64 {
+        read_u16(&self.raw, 0xce) as u64
+    }
+
+    pub fn desc_size(&self) -> usize {
+        if self.has_incompat(INCOMPAT_64BIT) {
+            (read_u16(&self.raw, 0xfe) as usize).max(32)
+        } else {
+            32
+        }
+    }
+
+    pub fn first_meta_bg(&self) -> u64 {
+        read_u32(&self.raw, 0x104) as u64
+    }
+
+    pub fn group_count(&self) -> u64 {
+        (self.blocks_count() - self.first_data_block()).div_ceil(self.blocks_per_group())
+    }
+
+    /// The seed of all metadata checksums.
+    pub fn checksum_seed(&self) -> u32 {
+        if self.has_incompat(INCOMPAT_CSUM_SEED) {
+            read_u32(&self.raw, 0x270)
+        } else {
+            crc32c(!0, &self.raw[0x68..0x78]) // s_uuid
+        }
+    }
+
+    /// Whether the group contains a backup of the superblock.
+    pub fn group_has_super(&self, group: u64) -> bool {
+        fn is_power_of(mut n: u64, base: u64) -> bool {
+            while n > 1 && n.is_multiple_of(base) {
+                n /= base;
+            }
+            n == 1
+        }
+        !self.has_ro_compat(RO_COMPAT_SPARSE_SUPER)
+            || group <= 1
+            || is_power_of(group, 3)
+            || is_power_of(group, 5)
+            || is_power_of(group, 7)
+    }
+
+    pub fn update_checksum(&mut self) {
+        if self.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            let csum = crc32c(!0, &self.raw[..0x3fc]);
+            write_u32(&mut self.raw, 0x3fc, csum);
+        }
+    }
+}
+
+/// A block group descriptor.
+#[derive(Clone)]
+pub struct GroupDesc {
+    raw: [u8; 64],
+    size: usize,
+}
+
+impl GroupDesc {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut raw = [0; 64];
+        raw[..bytes.len()].copy_from_slice(bytes);
+        Self {
+            raw,
+            size: bytes.len(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw[..self.size]
+    }
+
+    fn is_64bit(&self) -> bool {
+        self.size >= 64
+    }
+
+    fn read_lo_hi32(&self, lo: usize, hi: usize) -> u64 {
+        let val = read_u32(&self.raw, lo) as u64;
+        if self.is_64bit() {
+            val | (read_u32(&self.raw, hi) as u64) << 32
+        } else {
+            val
+        }
+    }
+
+    fn read_lo_hi16(&self, lo: usize, hi: usize) -> u32 {
+        let val = read_u16(&self.raw, lo) as u32;
+        if self.is_64bit() {
+            val | (read_u16(&self.raw, hi) as u32) << 16
+        } else {
+            val
+        }
+    }
+
+    fn write_lo_hi16(&mut self, lo: usize, hi: usize, val: u32) {
+        write_u16(&mut self.raw, lo, val as u16);
+        if self.is_64bit() {
+            write_u16(&mut self.raw, hi, (val >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.read_lo_hi32(0x00, 0x20)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.read_lo_hi32(0x04, 0x24)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.read_lo_hi32(0x08, 0x28)
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.read_lo_hi16(0x0c, 0x2c)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u32) {
+        self.write_lo_hi16(0x0c, 0x2c, count)
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.read_lo_hi16(0x0e, 0x2e)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        self.write_lo_hi16(0x0e, 0x2e, count)
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.read_lo_hi16(0x10, 0x30)
+    }
+
+    pub fn set_used_dirs_count(&mut self, count: u32) {
+        self.write_lo_hi16(0x10, 0x30, count)
+    }
+
+    pub fn flags(&self) -> u16 {
+        read_u16(&self.raw, 0x12)
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        write_u16(&mut self.raw, 0x12, flags)
+    }
+
+    pub fn itable_unused(&self) -> u32 {
+        self.read_lo_hi16(0x1c, 0x32)
+    }
+
+    pub fn set_itable_unused(&mut self, count: u32) {
+        self.write_lo_hi16(0x1c, 0x32, count)
+    }
+
+    pub fn set_block_bitmap_csum(&mut self, csum: u32) {
+        write_u16(&mut self.raw, 0x18, csum as u16);
+        if self.is_64bit() {
+            write_u16(&mut self.raw, 0x38, (csum >> 16) as u16);
+        }
+    }
+
+    pub fn set_inode_bitmap_csum(&mut self, csum: u32) {
+        write_u16(&mut self.raw, 0x1a, csum as u16);
+        if self.is_64bit() {
+            write_u16(&mut self.raw, 0x3a, (csum >> 16) as u16);
+        }
+    }
+
+    /// Updates `bg_checksum` with either the `metadata_csum` or the
+    /// `gdt_csum` algorithm, if enabled.
+    pub fn update_checksum(&mut self, sb: &SuperBlock, group: u64) {
+        let group = (group as u32).to_le_bytes();
+        let csum = if sb.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            write_u16(&mut self.raw, 0x1e, 0);
+            let crc = crc32c(sb.checksum_seed(), &group);
+            crc32c(crc, self.as_bytes()) as u16
+        } else if sb.has_ro_compat(RO_COMPAT_GDT_CSUM) {
+            let mut crc = crc16(!0, &sb.as_bytes()[0x68..0x78]);
+            crc = crc16(crc, &group);
+            crc = crc16(crc, &self.raw[..0x1e]);
+            if self.is_64bit() {
+                crc = crc16(crc, &self.raw[0x20..self.size]);
+            }
+            crc
+        } else {
+            return;
+        };
+        write_u16(&mut self.raw, 0x1e, csum);
+    }
+}
+
+/// An inode, with its number.
+pub struct Inode {
+    pub ino: u32,
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    pub fn from_bytes(ino: u32, bytes: &[u8]) -> Self {
+        Self {
+            ino,
+            raw: bytes.to_vec(),
+        }
+    }
+
+    /// Creates a zeroed inode of `size` bytes.
+    pub fn new_zeroed(ino: u32, size: usize) -> Self {
+        let mut inode = Self {
+            ino,
+            raw: vec![0; size],
+        };
+        if size > GOOD_OLD_INODE_SIZE {
+            // up to `i_projid`, the size of `struct ext4_inode` in Linux
+            let extra_isize = (size - GOOD_OLD_INODE_SIZE).min(32);
+            write_u16(&mut inode.raw, 0x80, extra_isize as u16);
+        }
+        inode
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.raw, 0x00)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        write_u16(&mut self.raw, 0x00, mode)
+    }
+
+    pub fn node_type(&self) -> VfsResult<VfsNodeType> {
+        mode_to_type(self.mode())
+    }
+
+    pub fn size(&self) -> u64 {
+        read_u32(&self.raw, 0x04) as u64 | (read_u32(&self.raw, 0x6c) as u64) << 32
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        write_u32(&mut self.raw, 0x04, size as u32);
+        write_u32(&mut self.raw, 0x6c, (size >> 32) as u32);
+    }
+
+    pub fn links_count(&self) -> u16 {
+        read_u16(&self.raw, 0x1a)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        write_u16(&mut self.raw, 0x1a, count)
+    }
+
+    /// Returns the number of 512-byte sectors used by the inode.
+    pub fn blocks(&self, sb: &SuperBlock) -> u64 {
+        let mut blocks = read_u32(&self.raw, 0x1c) as u64;
+        if sb.has_ro_compat(RO_COMPAT_HUGE_FILE) {
+            blocks |= (read_u16(&self.raw, 0x74) as u64) << 32;
+            if self.flags() & INODE_FLAG_HUGE_FILE != 0 {
+                blocks *= (sb.block_size() / 512) as u64;
+            }
+        }
+        blocks
+    }
+
+    /// Sets the number of 512-byte sectors used by the inode.
+    pub fn set_blocks(&mut self, sb: &SuperBlock, blocks: u64) {
+        self.set_flags(self.flags() & !INODE_FLAG_HUGE_FILE);
+        write_u32(&mut self.raw, 0x1c, blocks as u32);
+        if sb.has_ro_compat(RO_COMPAT_HUGE_FILE) {
+            write_u16(&mut self.raw, 0x74, (blocks >> 32) as u16);
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        write_u32(&mut self.raw, 0x20, flags)
+    }
+
+    pub fn generation(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    pub fn file_acl(&self) -> u64 {
+        read_u32(&self.raw, 0x68) as u64 | (read_u16(&self.raw, 0x76) as u64) << 32
+    }
+
+    pub fn set_dtime(&mut self, time: u32) {
+        write_u32(&mut self.raw, 0x14, time)
+    }
+
+    /// The `i_block` field, which contains the block map, the extent tree
+    /// root, or the target of a fast symlink.
+    pub fn block(&self) -> &[u8] {
+        &self.raw[INODE_BLOCK_OFFSET..INODE_BLOCK_OFFSET + INODE_BLOCK_SIZE]
+    }
+
+    pub fn block_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[INODE_BLOCK_OFFSET..INODE_BLOCK_OFFSET + INODE_BLOCK_SIZE]
+    }
+
+    fn extra_isize(&self) -> usize {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            read_u16(&self.raw, 0x80) as usize
+        } else {
+            0
+        }
+    }
+
+    /// Whether the field at `off` (of `len` bytes) in the extra space exists.
+    fn has_extra_field(&self, off: usize, len: usize) -> bool {
+        off + len <= GOOD_OLD_INODE_SIZE + self.extra_isize() && off + len <= self.raw.len()
+    }
+
+    fn set_time(&mut self, off: usize, extra_off: usize, secs: u64, nsecs: u32) {
+        write_u32(&mut self.raw, off, secs as u32);
+        if self.has_extra_field(extra_off, 4) {
+            let epoch = ((secs >> 32) & 3) as u32;
+            write_u32(&mut self.raw, extra_off, nsecs << 2 | epoch);
+        }
+    }
+
+    pub fn set_atime(&mut self, secs: u64, nsecs: u32) {
+        self.set_time(0x08, 0x8c, secs, nsecs)
+    }
+
+    pub fn set_ctime(&mut self, secs: u64, nsecs: u32) {
+        self.set_time(0x0c, 0x84, secs, nsecs)
+    }
+
+    pub fn set_mtime(&mut self, secs: u64, nsecs: u32) {
+        self.set_time(0x10, 0x88, secs, nsecs)
+    }
+
+    pub fn set_crtime(&mut self, secs: u64, nsecs: u32) {
+        if self.has_extra_field(0x90, 4) {
+            self.set_time(0x90, 0x94, secs, nsecs)
+        }
+    }
+
+    /// The seed of the checksums of the metadata blocks owned by the inode
+    /// (extent tree blocks and directory blocks).
+    pub fn checksum_seed(&self, sb: &SuperBlock) -> u32 {
+        let crc = crc32c(sb.checksum_seed(), &self.ino.to_le_bytes());
+        crc32c(crc, &self.generation().to_le_bytes())
+    }
+
+    pub fn update_checksum(&mut self, sb: &SuperBlock) {
+        if !sb.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            return;
+        }
+        let has_hi = self.has_extra_field(0x82, 2);
+        write_u16(&mut self.raw, 0x7c, 0);
+        if has_hi {
+            write_u16(&mut self.raw, 0x82, 0);
+        }
+        let csum = crc32c(self.checksum_seed(sb), &self.raw);
+        write_u16(&mut self.raw, 0x7c, csum as u16);
+        if has_hi {
+            write_u16(&mut self.raw, 0x82, (csum >> 16) as u16);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Ext4 filesystem used by [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! The implementation is based on [`axfs_vfs`]. It reads and writes ext4
+//! filesystems, as well as ext2/3 filesystems without the `extents` feature.
+//!
+//! Limitations:
+//!
+//! - The journal is not replayed nor written. A filesystem that needs
+//!   recovery is mounted read-only, the same for unknown read-only compatible
+//!   features.
+//! - Hash tree directories are converted to linear directories when modified.
+//! - Inline data and encryption are not supported.
+//! - Only the primary superblock and group descriptors are updated.
+//! - Timestamps are only updated if a time source is set by
+//!   [`Ext4FileSystem::set_time_source`], and the access time is never
+//!   updated.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+#[macro_use]
+extern crate log;
+
+mod blockmap;
+mod crc;
+mod dir;
+mod extent;
+mod fs;
+mod layout;
+mod node;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::node::{DirNode, FileNode};
+
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use spin::Mutex;
+
+use self::node::Shared;
+
+/// The block device that the filesystem is stored on.
+pub trait BlockDevice: Send + 'static {
+    /// Reads `buf.len()` bytes at the byte offset `offset`.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> VfsResult;
+    /// Writes `buf` at the byte offset `offset`.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> VfsResult;
+}
+
+/// An ext4 filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct Ext4FileSystem<D> {
+    shared: Arc<Shared<D>>,
+}
+
+impl<D: BlockDevice> Ext4FileSystem<D> {
+    /// Opens the filesystem on the block device.
+    pub fn new(dev: D) -> VfsResult<Self> {
+        Ok(Self {
+            shared: Arc::new(Shared {
+                fs: Mutex::new(fs::Ext4Fs::open(dev)?),
+                mount_parent: Mutex::new(None),
+            }),
+        })
+    }
+
+    /// Sets the function that returns the current time since the Unix epoch,
+    /// which is used to update timestamps.
+    pub fn set_time_source(&self, time_source: fn() -> Duration) {
+        self.shared.fs.lock().set_time_source(time_source);
+    }
+
+    /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
+    pub fn root_dir_node(&self) -> Arc<DirNode<D>> {
+        Arc::new(DirNode::new(self.shared.clone(), layout::ROOT_INO))
+    }
+}
+
+impl<D: BlockDevice> VfsOps for Ext4FileSystem<D> {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        // may be mounted again at another place after unmounted
+        *self.shared.mount_parent.lock() = mount_point.parent();
+        Ok(())
+    }
+
+    fn umount(&self) -> VfsResult {
+        *self.shared.mount_parent.lock() = None;
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root_dir_node()
+    }
+}
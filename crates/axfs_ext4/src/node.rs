@@ -0,0 +1,307 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsResult};
+use spin::Mutex;
+
+use crate::fs::Ext4Fs;
+use crate::layout::{Inode, ROOT_INO};
+use crate::BlockDevice;
+
+/// The state shared by all nodes of a filesystem.
+pub(crate) struct Shared<D> {
+    pub(crate) fs: Mutex<Ext4Fs<D>>,
+    pub(crate) mount_parent: Mutex<Option<VfsNodeRef>>,
+}
+
+/// The directory node in the ext4 filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct DirNode<D> {
+    shared: Arc<Shared<D>>,
+    ino: u32,
+}
+
+/// The non-directory node in the ext4 filesystem, i.e., regular files,
+/// symbolic links and special files.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct FileNode<D> {
+    shared: Arc<Shared<D>>,
+    ino: u32,
+    ty: VfsNodeType,
+}
+
+pub(crate) fn new_node<D: BlockDevice>(
+    shared: &Arc<Shared<D>>,
+    ino: u32,
+    ty: VfsNodeType,
+) -> VfsNodeRef {
+    if ty == VfsNodeType::Dir {
+        Arc::new(DirNode {
+            shared: shared.clone(),
+            ino,
+        })
+    } else {
+        Arc::new(FileNode {
+            shared: shared.clone(),
+            ino,
+            ty,
+        })
+    }
+}
+
+/// Reads an inode that is still linked, the node may refer to a removed
+/// inode.
+fn read_linked_inode<D: BlockDevice>(fs: &mut Ext4Fs<D>, ino: u32) -> VfsResult<Inode> {
+    let inode = fs.read_inode(ino)?;
+    if inode.links_count() == 0 {
+        return Err(VfsError::NotFound);
+    }
+    Ok(inode)
+}
+
+fn node_attr<D: BlockDevice>(fs: &Ext4Fs<D>, inode: &Inode) -> VfsResult<VfsNodeAttr> {
+    Ok(VfsNodeAttr::new(
+        VfsNodePerm::from_bits_truncate(inode.mode() & 0o777),
+        inode.node_type()?,
+        inode.size(),
+        inode.blocks(&fs.sb),
+    ))
+}
+
+impl<D: BlockDevice> DirNode<D> {
+    pub(crate) fn new(shared: Arc<Shared<D>>, ino: u32) -> Self {
+        Self { shared, ino }
+    }
+
+    fn child(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        let mut fs = self.shared.fs.lock();
+        let dir = read_linked_inode(&mut fs, self.ino)?;
+        let (ino, file_type) = fs.dir_lookup(&dir, name)?.ok_or(VfsError::NotFound)?;
+        let ty = fs.dirent_node_type(ino, file_type)?;
+        Ok(new_node(&self.shared, ino, ty))
+    }
+
+    /// Resolves the parent directory of the last component of `path`.
+    fn parent_of(self: Arc<Self>, path: &str) -> VfsResult<(Arc<Self>, &str)> {
+        let path = path.trim_end_matches('/');
+        let (dir, name) = match path.rfind('/') {
+            Some(n) => {
+                let dir = self.lookup(&path[..n])?;
+                let dir = dir
+                    .as_any()
+                    .downcast_ref::<Self>()
+                    .ok_or(VfsError::Unsupported)? // not in this filesystem
+                    .clone_ref();
+                (Arc::new(dir), &path[n + 1..])
+            }
+            None => (self, path),
+        };
+        match name {
+            "" | "." | ".." => Err(VfsError::InvalidInput),
+            _ => Ok((dir, name)),
+        }
+    }
+
+    fn clone_ref(&self) -> Self {
+        Self::new(self.shared.clone(), self.ino)
+    }
+
+    fn root(&self) -> Arc<Self> {
+        Arc::new(Self::new(self.shared.clone(), ROOT_INO))
+    }
+}
+
+impl<D: BlockDevice> VfsNodeOps for DirNode<D> {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut fs = self.shared.fs.lock();
+        let inode = read_linked_inode(&mut fs, self.ino)?;
+        node_attr(&fs, &inode)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.ino == ROOT_INO {
+            self.shared.mount_parent.lock().clone()
+        } else {
+            self.child("..").ok()
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext4: {}", path);
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.child(name),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut fs = self.shared.fs.lock();
+        let dir = read_linked_inode(&mut fs, self.ino)?;
+        let mut entries = Vec::with_capacity(dirents.len());
+        let mut idx = 0;
+        fs.dir_iterate(&dir, |ino, name, file_type| {
+            if idx >= start_idx {
+                entries.push((ino, String::from_utf8_lossy(name).into_owned(), file_type));
+            }
+            idx += 1;
+            entries.len() < dirents.len()
+        })?;
+        for (ent, (ino, name, file_type)) in dirents.iter_mut().zip(entries.iter()) {
+            *ent = VfsDirEntry::new(name, fs.dirent_node_type(*ino, *file_type)?);
+        }
+        Ok(entries.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext4: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => self.child(name)?.create(rest, ty),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            let perm = match ty {
+                VfsNodeType::File => VfsNodePerm::default_file(),
+                VfsNodeType::Dir => VfsNodePerm::default_dir(),
+                _ => return Err(VfsError::Unsupported),
+            };
+            let mut fs = self.shared.fs.lock();
+            let dir = read_linked_inode(&mut fs, self.ino)?;
+            if let Some((ino, file_type)) = fs.dir_lookup(&dir, name)? {
+                return if fs.dirent_node_type(ino, file_type)? == ty {
+                    Ok(()) // already exists
+                } else {
+                    Err(VfsError::AlreadyExists)
+                };
+            }
+            fs.create_node(self.ino, name, ty, perm.bits())?;
+            Ok(())
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext4: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => self.child(name)?.remove(rest),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::InvalidInput) // remove '.' or '..
+        } else {
+            self.shared.fs.lock().unlink(self.ino, name)
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        // `src_path` is relative to this directory, `dst_path` is relative to
+        // the root of this filesystem
+        debug!(
+            "rename at ext4, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        let (src_dir, src_name) = Arc::new(self.clone_ref()).parent_of(src_path)?;
+        let (dst_dir, dst_name) = self.root().parent_of(dst_path.trim_start_matches('/'))?;
+        self.shared
+            .fs
+            .lock()
+            .rename(src_dir.ino, src_name, dst_dir.ino, dst_name)
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        debug!("symlink at ext4: {} -> {}", path, target);
+        let (dir, name) = Arc::new(self.clone_ref()).parent_of(path)?;
+        self.shared.fs.lock().create_symlink(dir.ino, name, target)
+    }
+
+    fn link(&self, path: &str, node: &VfsNodeRef) -> VfsResult {
+        debug!("link at ext4: {}", path);
+        let ino = match node.as_any().downcast_ref::<FileNode<D>>() {
+            Some(file) if Arc::ptr_eq(&file.shared, &self.shared) => file.ino,
+            _ if node.as_any().is::<Self>() => return Err(VfsError::PermissionDenied),
+            _ => return Err(VfsError::Unsupported), // not in this filesystem
+        };
+        let (dir, name) = Arc::new(self.clone_ref()).parent_of(path)?;
+        self.shared.fs.lock().link(dir.ino, name, ino)
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+impl<D: BlockDevice> VfsNodeOps for FileNode<D> {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut fs = self.shared.fs.lock();
+        let inode = read_linked_inode(&mut fs, self.ino)?;
+        node_attr(&fs, &inode)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut fs = self.shared.fs.lock();
+        let inode = read_linked_inode(&mut fs, self.ino)?;
+        if fs.is_fast_symlink(&inode) {
+            // the target is stored in `i_block`
+            let target = &inode.block()[..(inode.size() as usize).min(inode.block().len())];
+            let start = target.len().min(offset as usize);
+            let end = target.len().min(start + buf.len());
+            buf[..end - start].copy_from_slice(&target[start..end]);
+            return Ok(end - start);
+        }
+        fs.read_file(&inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if self.ty != VfsNodeType::File {
+            return Err(VfsError::Unsupported);
+        }
+        let mut fs = self.shared.fs.lock();
+        let mut inode = read_linked_inode(&mut fs, self.ino)?;
+        fs.write_file(&mut inode, offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // all writes go to the device directly
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        if self.ty != VfsNodeType::File {
+            return Err(VfsError::Unsupported);
+        }
+        let mut fs = self.shared.fs.lock();
+        let mut inode = read_linked_inode(&mut fs, self.ino)?;
+        fs.truncate_file(&mut inode, size)
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.ty != VfsNodeType::SymLink {
+            return Err(VfsError::InvalidInput);
+        }
+        self.read_at(0, buf)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
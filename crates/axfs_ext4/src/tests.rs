@@ -0,0 +1,313 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use crate::*;
+
+/// Loads a test image created by `resources/create_test_img.sh`.
+fn load_image(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join(name);
+    std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "failed to load {:?}: {}, run `resources/create_test_img.sh` first",
+            path, e
+        )
+    })
+}
+
+#[derive(Clone)]
+struct RamDisk(Arc<Mutex<Vec<u8>>>);
+
+impl RamDisk {
+    fn new(img: &[u8]) -> Self {
+        Self(Arc::new(Mutex::new(img.to_vec())))
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> VfsResult {
+        let data = self.0.lock().unwrap();
+        let start = offset as usize;
+        let src = data.get(start..start + buf.len()).ok_or(VfsError::Io)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> VfsResult {
+        let mut data = self.0.lock().unwrap();
+        let start = offset as usize;
+        let dst = data.get_mut(start..start + buf.len()).ok_or(VfsError::Io)?;
+        dst.copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+fn read_all(node: &VfsNodeRef) -> VfsResult<Vec<u8>> {
+    let mut buf = vec![0; node.get_attr()?.size() as usize];
+    assert_eq!(node.read_at(0, &mut buf)?, buf.len());
+    Ok(buf)
+}
+
+fn list_dir(dir: &VfsNodeRef) -> VfsResult<Vec<String>> {
+    let mut names = Vec::new();
+    let mut dirents = [
+        VfsDirEntry::default(),
+        VfsDirEntry::default(),
+        VfsDirEntry::default(),
+    ];
+    loop {
+        let n = dir.read_dir(names.len(), &mut dirents)?;
+        if n == 0 {
+            return Ok(names);
+        }
+        for ent in &dirents[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+    }
+}
+
+fn read_link(node: &VfsNodeRef) -> VfsResult<String> {
+    let mut buf = [0; 256];
+    let len = node.readlink(&mut buf)?;
+    Ok(String::from_utf8(buf[..len].to_vec()).unwrap())
+}
+
+fn big_txt() -> String {
+    (1..=100000).map(|i| format!("{}\n", i)).collect()
+}
+
+fn test_read(fs: &Ext4FileSystem<RamDisk>) -> VfsResult {
+    let root = fs.root_dir();
+    assert!(root.get_attr()?.is_dir());
+
+    let long = root.clone().lookup("long.txt")?;
+    assert_eq!(long.get_attr()?.file_type(), VfsNodeType::File);
+    assert_eq!(read_all(&long)?, "Rust is cool!\n".repeat(1000).as_bytes());
+
+    let node = root
+        .clone()
+        .lookup("///very/long//.././long//./path/./test.txt")?;
+    assert_eq!(read_all(&node)?, b"Rust is cool!\n");
+    let node = root
+        .clone()
+        .lookup("very-long-dir-name/very-long-file-name.txt")?;
+    assert_eq!(read_all(&node)?, b"Rust is cool!\n");
+    assert_eq!(
+        read_all(&root.clone().lookup("big.txt")?)?,
+        big_txt().as_bytes()
+    );
+
+    let link = root.clone().lookup("link")?;
+    assert_eq!(link.get_attr()?.file_type(), VfsNodeType::SymLink);
+    assert_eq!(read_all(&link)?, b"short.txt");
+
+    let many = root.clone().lookup("many")?;
+    assert_eq!(
+        read_all(&many.clone().lookup("file-with-a-long-name-150")?)?,
+        b"150\n"
+    );
+    assert_eq!(list_dir(&many)?.len(), 202);
+
+    let names = list_dir(&root)?;
+    for name in [
+        ".",
+        "..",
+        "long.txt",
+        "short.txt",
+        "very",
+        "very-long-dir-name",
+        "link",
+    ] {
+        assert!(names.contains(&name.into()), "{} not found", name);
+    }
+
+    assert_eq!(
+        root.clone().lookup("not-exist").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(
+        root.clone().lookup("short.txt/").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.clone().lookup("very/long/path/test.txt/..").err(),
+        Some(VfsError::NotADirectory)
+    );
+    Ok(())
+}
+
+fn test_write(fs: &Ext4FileSystem<RamDisk>) -> VfsResult {
+    let root = fs.root_dir();
+
+    // overwrite and append
+    let node = root.clone().lookup("short.txt")?;
+    assert_eq!(node.write_at(0, b"Ext4")?, 4);
+    assert_eq!(node.write_at(14, b"Hello, world!\n")?, 14);
+    assert_eq!(read_all(&node)?, b"Ext4 is cool!\nHello, world!\n");
+
+    // sparse file
+    root.create("sparse", VfsNodeType::File)?;
+    let node = root.clone().lookup("sparse")?;
+    assert_eq!(node.write_at(5 << 20, b"end")?, 3);
+    let content = read_all(&node)?;
+    assert_eq!(content.len(), (5 << 20) + 3);
+    assert!(content[..5 << 20].iter().all(|&b| b == 0));
+    assert_eq!(&content[5 << 20..], b"end");
+    assert!(node.get_attr()?.blocks() <= 16);
+
+    // a large file with many blocks
+    let data = (0..1_500_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    root.create("large", VfsNodeType::File)?;
+    let node = root.clone().lookup("large")?;
+    for (i, chunk) in data.chunks(4000).enumerate() {
+        assert_eq!(node.write_at(i as u64 * 4000, chunk)?, chunk.len());
+    }
+    assert_eq!(read_all(&node)?, data);
+    node.truncate(100_000)?;
+    assert_eq!(read_all(&node)?, &data[..100_000]);
+    node.truncate(200_000)?;
+    let content = read_all(&node)?;
+    assert_eq!(&content[..100_000], &data[..100_000]);
+    assert!(content[100_000..].iter().all(|&b| b == 0));
+
+    // create, remove and rename
+    root.create("/very/long/new-dir", VfsNodeType::Dir)?;
+    root.create("/very/long/new-dir/../new-dir/new-file", VfsNodeType::File)?;
+    root.create("very/long/new-dir", VfsNodeType::Dir)?; // already exists
+    assert_eq!(
+        root.create("very/long/new-dir/new-file", VfsNodeType::Dir)
+            .err(),
+        Some(VfsError::AlreadyExists)
+    );
+    let dir = root.clone().lookup("very/long/new-dir")?;
+    assert_eq!(list_dir(&dir)?, [".", "..", "new-file"]);
+    let node = dir.parent().unwrap().lookup("../../short.txt")?;
+    assert_eq!(node.get_attr()?.size(), 28);
+    assert_eq!(
+        root.remove("very/long/new-dir").err(),
+        Some(VfsError::DirectoryNotEmpty)
+    );
+    root.rename("very/long/new-dir/new-file", "/very/renamed-file")?;
+    root.rename("very/long/new-dir", "very-long-dir-name/renamed-dir")?;
+    assert_eq!(
+        root.rename("very-long-dir-name", "very-long-dir-name/renamed-dir/sub")
+            .err(),
+        Some(VfsError::InvalidInput)
+    );
+    root.rename(
+        "very/renamed-file",
+        "very-long-dir-name/very-long-file-name.txt",
+    )?;
+    assert_eq!(
+        root.clone().lookup("very/renamed-file").err(),
+        Some(VfsError::NotFound)
+    );
+    let node = root
+        .clone()
+        .lookup("very-long-dir-name/very-long-file-name.txt")?;
+    assert_eq!(node.get_attr()?.size(), 0);
+    root.remove("very-long-dir-name/renamed-dir")?;
+    assert_eq!(
+        root.clone().lookup("very/long/new-dir").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // symbolic links and hard links
+    let slow_target = "very-long-dir-name/".repeat(5);
+    root.symlink("very/fast-link", "../short.txt")?;
+    root.symlink("slow-link", &slow_target)?;
+    assert_eq!(
+        read_link(&root.clone().lookup("very/fast-link")?)?,
+        "../short.txt"
+    );
+    assert_eq!(read_link(&root.clone().lookup("slow-link")?)?, slow_target);
+    assert_eq!(
+        root.symlink("link", "short.txt").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    let node = root.clone().lookup("long.txt")?;
+    assert_eq!(
+        node.readlink(&mut [0; 16]).err(),
+        Some(VfsError::InvalidInput)
+    );
+    root.link("very/hard-link", &node)?;
+    assert_eq!(
+        root.link("dir-link", &root.clone().lookup("very")?).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    root.remove("long.txt")?;
+    assert_eq!(
+        read_all(&root.clone().lookup("very/hard-link")?)?,
+        "Rust is cool!\n".repeat(1000).as_bytes()
+    );
+
+    // modify a hash tree directory
+    let many = root.clone().lookup("many")?;
+    for i in 1..=100 {
+        many.remove(&format!("file-with-a-long-name-{}", i))?;
+    }
+    for i in 0..300 {
+        many.create(&format!("new-{}", i), VfsNodeType::File)?;
+    }
+    assert_eq!(list_dir(&many)?.len(), 402);
+    assert_eq!(
+        read_all(&many.lookup("file-with-a-long-name-150")?)?,
+        b"150\n"
+    );
+    Ok(())
+}
+
+fn test_persist(fs: &Ext4FileSystem<RamDisk>) -> VfsResult {
+    let root = fs.root_dir();
+    let node = root.clone().lookup("short.txt")?;
+    assert_eq!(read_all(&node)?, b"Ext4 is cool!\nHello, world!\n");
+    assert_eq!(
+        root.clone().lookup("sparse")?.get_attr()?.size(),
+        (5 << 20) + 3
+    );
+    assert_eq!(root.clone().lookup("large")?.get_attr()?.size(), 200_000);
+    assert_eq!(
+        list_dir(&root.clone().lookup("very-long-dir-name")?)?.len(),
+        3
+    );
+    assert_eq!(
+        read_link(&root.clone().lookup("slow-link")?)?,
+        "very-long-dir-name/".repeat(5)
+    );
+    assert_eq!(
+        root.clone().lookup("very/hard-link")?.get_attr()?.size(),
+        14000
+    );
+    assert_eq!(
+        root.clone().lookup("long.txt").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(list_dir(&root.lookup("many")?)?.len(), 402);
+    Ok(())
+}
+
+fn test_image(img: &[u8]) {
+    let disk = RamDisk::new(img);
+    let fs = Ext4FileSystem::new(disk.clone()).expect("failed to open the filesystem");
+    test_read(&fs).expect("test_read() failed");
+    test_write(&fs).expect("test_write() failed");
+    drop(fs);
+
+    let fs = Ext4FileSystem::new(disk).expect("failed to reopen the filesystem");
+    test_persist(&fs).expect("test_persist() failed");
+}
+
+#[test]
+fn test_ext4() {
+    test_image(&load_image("ext4.img"));
+}
+
+#[test]
+fn test_ext2() {
+    test_image(&load_image("ext2.img"));
+}
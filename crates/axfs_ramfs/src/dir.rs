@@ -7,6 +7,7 @@ use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::symlink::SymlinkNode;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -42,19 +43,54 @@ impl DirNode {
 
     /// Creates a new node with the given name and type in this directory.
     pub fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
-        if self.exist(name) {
-            log::error!("AlreadyExists {}", name);
-            return Err(VfsError::AlreadyExists);
-        }
         let node: VfsNodeRef = match ty {
             VfsNodeType::File => Arc::new(FileNode::new()),
             VfsNodeType::Dir => Self::new(Some(self.this.clone())),
             _ => return Err(VfsError::Unsupported),
         };
-        self.children.write().insert(name.into(), node);
+        self.insert_node(name, node)
+    }
+
+    /// Creates a symbolic link with the given name in this directory, which
+    /// points to `target`.
+    pub fn create_symlink(&self, name: &str, target: &str) -> VfsResult {
+        self.insert_node(name, Arc::new(SymlinkNode::new(target)))
+    }
+
+    /// Adds a hard link with the given name in this directory, which refers
+    /// to the existing file or symbolic link `node`.
+    pub fn link_node(&self, name: &str, node: &VfsNodeRef) -> VfsResult {
+        let any = node.as_any();
+        if any.is::<DirNode>() {
+            return Err(VfsError::PermissionDenied); // no hard links to directories
+        } else if !any.is::<FileNode>() && !any.is::<SymlinkNode>() {
+            return Err(VfsError::Unsupported); // not in a RAM filesystem
+        }
+        self.insert_node(name, node.clone())
+    }
+
+    fn insert_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            log::error!("AlreadyExists {}", name);
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
         Ok(())
     }
 
+    fn child(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        match name {
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .read()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }
+    }
+
     /// Removes a node by the given name in this directory.
     pub fn remove_node(&self, name: &str) -> VfsResult {
         let mut children = self.children.write();
@@ -165,6 +201,36 @@ impl VfsNodeOps for DirNode {
         }
     }
 
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink at ramfs: {} -> {}", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.symlink(rest, target),
+                _ => self.child(name)?.symlink(rest, target),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.create_symlink(name, target)
+        }
+    }
+
+    fn link(&self, path: &str, node: &VfsNodeRef) -> VfsResult {
+        log::debug!("link at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.link(rest, node),
+                _ => self.child(name)?.link(rest, node),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.link_node(name, node)
+        }
+    }
+
     axfs_vfs::impl_vfs_dir_default! {}
 }
 
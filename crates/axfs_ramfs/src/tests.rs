@@ -88,6 +88,55 @@ fn test_get_parent(devfs: &RamFileSystem) -> VfsResult {
     Ok(())
 }
 
+fn test_links(devfs: &RamFileSystem) -> VfsResult {
+    let mut buf = [0; 32];
+    let root = devfs.root_dir();
+
+    // symbolic links are not followed by the filesystem itself
+    root.symlink("foo/link", "../f2")?;
+    root.symlink("//foo/bar/link", "/foo")?;
+    let link = root.clone().lookup("foo/link")?;
+    assert_eq!(link.get_attr()?.file_type(), VfsNodeType::SymLink);
+    assert_eq!(link.get_attr()?.size(), 5);
+    assert_eq!(link.readlink(&mut buf)?, 5);
+    assert_eq!(&buf[..5], b"../f2");
+    assert_eq!(link.readlink(&mut buf[..2])?, 2);
+    assert_eq!(
+        root.clone().lookup("foo/bar/link/f3").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.symlink("foo/link", "f1").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.symlink("f1/link", "f1").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.clone().lookup("foo")?.readlink(&mut buf).err(),
+        Some(VfsError::InvalidInput)
+    );
+
+    // hard links share the same node
+    let f1 = root.clone().lookup("f1")?;
+    root.link("foo/bar/f1-link", &f1)?;
+    root.link("foo/link2", &link)?;
+    assert!(Arc::ptr_eq(&f1, &root.clone().lookup("foo/bar/f1-link")?));
+    assert_eq!(
+        root.link("f1-dir", &root.clone().lookup("foo")?).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(root.link("f1", &f1).err(), Some(VfsError::AlreadyExists));
+
+    root.remove("foo/bar/f1-link")?;
+    root.remove("foo/bar/link")?;
+    root.remove("foo/link2")?;
+    root.remove("foo/link")?;
+    assert_eq!(f1.get_attr()?.file_type(), VfsNodeType::File);
+    Ok(())
+}
+
 #[test]
 fn test_ramfs() {
     // .
@@ -117,6 +166,7 @@ fn test_ramfs() {
 
     test_ramfs_ops(&ramfs).unwrap();
     test_get_parent(&ramfs).unwrap();
+    test_links(&ramfs).unwrap();
 
     let root = ramfs.root_dir();
     assert_eq!(root.remove("f1"), Ok(()));
@@ -257,4 +257,6 @@ pub struct PollState {
     pub readable: bool,
     /// Object can be writen now.
     pub writable: bool,
+    /// The peer or the other end has closed the connection.
+    pub hangup: bool,
 }
@@ -64,6 +64,15 @@ pub trait NetDriverOps: BaseDriverOps {
     /// Allocate a memory buffer of a specified size for network transmission,
     /// returns [`DevResult`]
     fn alloc_tx_buffer(&mut self, size: usize) -> DevResult<NetBufPtr>;
+
+    /// Acknowledges the interrupt raised by the NIC, returns whether there
+    /// was one.
+    ///
+    /// It should be called before processing the received packets, otherwise
+    /// the interrupt may be raised again immediately.
+    fn ack_interrupt(&mut self) -> bool {
+        false
+    }
 }
 
 /// A raw buffer struct for network device.
@@ -99,4 +99,20 @@ fn test_percpu() {
         assert_eq!(s.foo, 0x2333);
         assert_eq!(s.bar, 100);
     });
+
+    #[cfg(not(feature = "sp-naive"))]
+    unsafe {
+        assert_eq!(USIZE.remote_ptr(0), USIZE.current_ptr());
+        assert_eq!(
+            percpu_area_base(1) + USIZE.offset(),
+            USIZE.remote_ptr(1) as usize
+        );
+        *(USIZE.remote_ptr(1) as *mut usize) = 0xeeee;
+        assert_eq!(*USIZE.remote_ref_raw(1), 0xeeee);
+        assert_eq!(USIZE.read_current(), 0xffff_0000);
+    }
+    #[cfg(feature = "sp-naive")]
+    unsafe {
+        assert_eq!(USIZE.remote_ptr(1), USIZE.current_ptr());
+    }
 }
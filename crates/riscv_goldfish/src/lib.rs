@@ -0,0 +1,65 @@
+//! Types and definitions for the Goldfish real time clock (RTC), used by the
+//! RISC-V QEMU virt machine.
+//!
+//! The documentation: <https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT>
+
+#![no_std]
+#![feature(const_ptr_as_ref)]
+#![feature(const_option)]
+#![feature(const_nonnull_new)]
+
+use core::ptr::NonNull;
+
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::ReadWrite,
+};
+
+register_structs! {
+    /// Goldfish RTC registers.
+    GoldfishRtcRegs {
+        /// Low 32 bits of the time in nanoseconds, reading it latches the
+        /// high 32 bits.
+        (0x00 => time_low: ReadWrite<u32>),
+        /// High 32 bits of the time in nanoseconds.
+        (0x04 => time_high: ReadWrite<u32>),
+        (0x08 => @END),
+    }
+}
+
+/// The Goldfish RTC, which counts the nanoseconds since the Unix epoch.
+pub struct GoldfishRtc {
+    base: NonNull<GoldfishRtcRegs>,
+}
+
+unsafe impl Send for GoldfishRtc {}
+unsafe impl Sync for GoldfishRtc {}
+
+impl GoldfishRtc {
+    /// Constructs a new Goldfish RTC instance from the base address.
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+        }
+    }
+
+    const fn regs(&self) -> &GoldfishRtcRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Returns the current time in nanoseconds since the Unix epoch.
+    pub fn get_unix_timestamp_nanos(&self) -> u64 {
+        // the low half must be read first
+        let low = self.regs().time_low.get() as u64;
+        let high = self.regs().time_high.get() as u64;
+        (high << 32) | low
+    }
+
+    /// Sets the current time in nanoseconds since the Unix epoch.
+    pub fn set_unix_timestamp_nanos(&mut self, nanos: u64) {
+        // the high half is latched until the low half is written
+        self.regs().time_high.set((nanos >> 32) as u32);
+        self.regs().time_low.set(nanos as u32);
+    }
+}
@@ -0,0 +1,173 @@
+//! RISC-V Platform-Level Interrupt Controller (PLIC) register definitions and
+//! basic operations.
+//!
+//! The PLIC multiplexes external interrupt sources to the interrupt targets
+//! (called *contexts*, usually a privilege mode on a hart). Each source has a
+//! priority, and each context has an enable bit for every source and a priority
+//! threshold. Interrupts are acquired by *claiming* them, and the context must
+//! signal the *completion* after handling.
+//!
+//! The official specification: <https://github.com/riscv/riscv-plic-spec>
+
+#![no_std]
+#![feature(const_option)]
+#![feature(const_nonnull_new)]
+
+use core::num::NonZeroU32;
+use core::ptr::NonNull;
+
+/// Maximum number of interrupt sources supported by the PLIC, including the
+/// reserved source 0.
+pub const PLIC_MAX_IRQ: usize = 1024;
+
+/// Maximum number of contexts supported by the PLIC.
+pub const PLIC_MAX_CONTEXT: usize = 15872;
+
+const PRIORITY_BASE: usize = 0x0;
+const PENDING_BASE: usize = 0x1000;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0x0;
+const CONTEXT_CLAIM: usize = 0x4;
+
+/// The RISC-V Platform-Level Interrupt Controller.
+pub struct Plic {
+    base: NonNull<u8>,
+}
+
+unsafe impl Send for Plic {}
+unsafe impl Sync for Plic {}
+
+impl Plic {
+    /// Construct a new PLIC instance from the base address.
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap(),
+        }
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { self.base.as_ptr().add(offset).cast::<u32>().read_volatile() }
+    }
+
+    fn write(&self, offset: usize, val: u32) {
+        unsafe {
+            self.base
+                .as_ptr()
+                .add(offset)
+                .cast::<u32>()
+                .write_volatile(val)
+        }
+    }
+
+    /// Sets the priority of the given interrupt source.
+    ///
+    /// Priority 0 means "never interrupt", larger values mean higher priorities.
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        assert!(irq > 0 && irq < PLIC_MAX_IRQ);
+        self.write(PRIORITY_BASE + irq * 4, priority);
+    }
+
+    /// Returns the priority of the given interrupt source.
+    pub fn priority(&self, irq: usize) -> u32 {
+        assert!(irq > 0 && irq < PLIC_MAX_IRQ);
+        self.read(PRIORITY_BASE + irq * 4)
+    }
+
+    /// Whether the given interrupt source is pending.
+    pub fn is_pending(&self, irq: usize) -> bool {
+        assert!(irq < PLIC_MAX_IRQ);
+        self.read(PENDING_BASE + irq / 32 * 4) & (1 << (irq % 32)) != 0
+    }
+
+    /// Enables or disables the given interrupt source for the given context.
+    ///
+    /// The read-modify-write of the enable bits is not atomic, the caller must
+    /// serialize concurrent updates to the same context.
+    pub fn set_enable(&self, context: usize, irq: usize, enable: bool) {
+        assert!(context < PLIC_MAX_CONTEXT);
+        assert!(irq > 0 && irq < PLIC_MAX_IRQ);
+        let offset = ENABLE_BASE + context * ENABLE_STRIDE + irq / 32 * 4;
+        let mask = 1 << (irq % 32);
+        let val = self.read(offset);
+        if enable {
+            self.write(offset, val | mask);
+        } else {
+            self.write(offset, val & !mask);
+        }
+    }
+
+    /// Whether the given interrupt source is enabled for the given context.
+    pub fn is_enabled(&self, context: usize, irq: usize) -> bool {
+        assert!(context < PLIC_MAX_CONTEXT);
+        assert!(irq < PLIC_MAX_IRQ);
+        let offset = ENABLE_BASE + context * ENABLE_STRIDE + irq / 32 * 4;
+        self.read(offset) & (1 << (irq % 32)) != 0
+    }
+
+    /// Sets the priority threshold of the given context.
+    ///
+    /// Interrupts with priorities less than or equal to the threshold are
+    /// masked for this context.
+    pub fn set_threshold(&self, context: usize, threshold: u32) {
+        assert!(context < PLIC_MAX_CONTEXT);
+        self.write(
+            CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_THRESHOLD,
+            threshold,
+        );
+    }
+
+    /// Returns the priority threshold of the given context.
+    pub fn threshold(&self, context: usize) -> u32 {
+        assert!(context < PLIC_MAX_CONTEXT);
+        self.read(CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_THRESHOLD)
+    }
+
+    /// Claims the highest priority pending interrupt for the given context.
+    ///
+    /// Returns [`None`] if there is no pending interrupt.
+    pub fn claim(&self, context: usize) -> Option<NonZeroU32> {
+        assert!(context < PLIC_MAX_CONTEXT);
+        NonZeroU32::new(self.read(CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_CLAIM))
+    }
+
+    /// Signals the completion of handling the given interrupt, which was
+    /// previously obtained by [`claim`](Self::claim) on the same context.
+    pub fn complete(&self, context: usize, irq: NonZeroU32) {
+        assert!(context < PLIC_MAX_CONTEXT);
+        self.write(
+            CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_CLAIM,
+            irq.get(),
+        );
+    }
+
+    /// Claims a pending interrupt for the given context, calls the handler
+    /// with its number, then signals the completion.
+    ///
+    /// Returns `false` if there is no pending interrupt.
+    pub fn handle_irq<F>(&self, context: usize, handler: F) -> bool
+    where
+        F: FnOnce(usize),
+    {
+        if let Some(irq) = self.claim(context) {
+            handler(irq.get() as usize);
+            self.complete(context, irq);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Initializes the given context: disables all interrupt sources and sets
+    /// the priority threshold to 0, so that any enabled interrupt with a
+    /// non-zero priority can be taken.
+    pub fn init_context(&self, context: usize) {
+        assert!(context < PLIC_MAX_CONTEXT);
+        for i in 0..PLIC_MAX_IRQ / 32 {
+            self.write(ENABLE_BASE + context * ENABLE_STRIDE + i * 4, 0);
+        }
+        self.set_threshold(context, 0);
+    }
+}
@@ -49,6 +49,14 @@ pub trait BaseScheduler {
     /// Returns [`None`] if there is not runnable task.
     fn pick_next_task(&mut self) -> Option<Self::SchedItem>;
 
+    /// Picks the first task that satisfies `filter`, in the order that
+    /// [`pick_next_task`](Self::pick_next_task) picks tasks. It will be
+    /// removed from the scheduler, and the other tasks are kept in place.
+    /// Returns [`None`] if there is no such task.
+    fn pick_next_task_filter<F>(&mut self, filter: F) -> Option<Self::SchedItem>
+    where
+        F: FnMut(&Self::SchedItem) -> bool;
+
     /// Puts the previous task back to the scheduler. The previous task is
     /// usually placed at the end of the ready queue, making it less likely
     /// to be re-scheduled.
@@ -28,6 +28,28 @@ macro_rules! def_test_sched {
                 assert_eq!(n, NUM_TASKS);
             }
 
+            #[test]
+            fn test_pick_filter() {
+                const NUM_TASKS: usize = 11;
+
+                let mut scheduler = <$scheduler>::new();
+                for i in 0..NUM_TASKS {
+                    scheduler.add_task(Arc::new(<$task>::new(i)));
+                }
+
+                let picked = scheduler.pick_next_task_filter(|t| *t.inner() % 4 == 3);
+                assert_eq!(*picked.unwrap().inner(), 3);
+                assert!(scheduler
+                    .pick_next_task_filter(|t| *t.inner() >= NUM_TASKS)
+                    .is_none());
+
+                // the order of the other tasks is kept
+                for i in (0..NUM_TASKS).filter(|&i| i != 3) {
+                    assert_eq!(*scheduler.pick_next_task().unwrap().inner(), i);
+                }
+                assert!(scheduler.pick_next_task().is_none());
+            }
+
             #[test]
             fn bench_yield() {
                 const NUM_TASKS: usize = 1_000_000;
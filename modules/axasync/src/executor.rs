@@ -0,0 +1,222 @@
+//! The executor of async tasks.
+
+use alloc::{boxed::Box, collections::VecDeque, format, sync::Arc, task::Wake};
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use axsync::Mutex;
+use axtask::WaitQueue;
+use spinlock::SpinNoIrq;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// The spawned tasks that are woken and wait to be polled.
+static READY_QUEUE: SpinNoIrq<VecDeque<Arc<AsyncTask>>> = SpinNoIrq::new(VecDeque::new());
+/// The place where the idle workers wait for ready tasks.
+static WORKER_WQ: WaitQueue = WaitQueue::new();
+static WORKERS_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A spawned future, which is also the waker of itself.
+struct AsyncTask {
+    /// `None` once it has completed.
+    future: Mutex<Option<BoxFuture>>,
+    /// Whether it is in the [`READY_QUEUE`], so it will not be queued twice.
+    queued: AtomicBool,
+}
+
+impl AsyncTask {
+    fn schedule(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            READY_QUEUE.lock().push_back(self.clone());
+            WORKER_WQ.notify_one(false);
+        }
+    }
+
+    fn run(self: Arc<Self>) {
+        // clear it before polling, so a wakeup during the poll queues it again
+        self.queued.store(false, Ordering::Release);
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = self.future.lock();
+        if let Some(fut) = future.as_mut() {
+            if fut.as_mut().poll(&mut cx).is_ready() {
+                *future = None;
+            }
+        }
+    }
+}
+
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.schedule();
+    }
+}
+
+fn worker() {
+    loop {
+        WORKER_WQ.wait_until(|| !READY_QUEUE.lock().is_empty());
+        let task = READY_QUEUE.lock().pop_front();
+        if let Some(task) = task {
+            task.run();
+        }
+    }
+}
+
+fn start_workers() {
+    if WORKERS_STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    for i in 0..axconfig::SMP {
+        axtask::spawn_raw(
+            worker,
+            format!("async-worker-{}", i),
+            axconfig::TASK_STACK_SIZE,
+        );
+    }
+}
+
+/// Spawns a new async task, which runs in the background on the worker tasks.
+///
+/// The returned [`JoinHandle`] can be awaited for the output of the future.
+/// Dropping it detaches the task, which keeps running.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    start_workers();
+    let state = Arc::new(SpinNoIrq::new(JoinState {
+        output: None,
+        finished: false,
+        waker: None,
+    }));
+    let join_state = state.clone();
+    let task = Arc::new(AsyncTask {
+        future: Mutex::new(Some(Box::pin(async move {
+            let output = future.await;
+            let waker = {
+                let mut state = join_state.lock();
+                state.output = Some(output);
+                state.finished = true;
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }))),
+        queued: AtomicBool::new(false),
+    });
+    task.schedule();
+    JoinHandle { state }
+}
+
+/// Wakes the task blocked in [`block_on`].
+struct BlockOnWaker {
+    notified: AtomicBool,
+    wq: WaitQueue,
+}
+
+impl Wake for BlockOnWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        self.wq.notify_one(false);
+    }
+}
+
+/// Runs a future to completion on the current task, and returns its output.
+///
+/// The current task sleeps while the future is pending, until it is woken.
+/// It must not be called in a spawned async task, which would block a worker
+/// of the executor.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let notifier = Arc::new(BlockOnWaker {
+        notified: AtomicBool::new(false),
+        wq: WaitQueue::new(),
+    });
+    let waker = Waker::from(notifier.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        notifier
+            .wq
+            .wait_until(|| notifier.notified.swap(false, Ordering::AcqRel));
+    }
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    finished: bool,
+    /// The task awaiting the [`JoinHandle`].
+    waker: Option<Waker>,
+}
+
+/// An owned permission to await the output of a spawned task, returned by
+/// [`spawn`].
+pub struct JoinHandle<T> {
+    state: Arc<SpinNoIrq<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns `true` if the task has completed.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    /// # Panics
+    ///
+    /// Panics if it is polled again after returning the output.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock();
+        if let Some(output) = state.output.take() {
+            Poll::Ready(output)
+        } else if state.finished {
+            panic!("`JoinHandle` polled after completion");
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Yields the execution back to the executor, so that other async tasks can
+/// run.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by [`yield_now`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
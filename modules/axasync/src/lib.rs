@@ -0,0 +1,40 @@
+//! [ArceOS](https://github.com/rcore-os/arceos) async runtime.
+//!
+//! It runs futures on top of the ArceOS tasks:
+//!
+//! - [`block_on`]: Runs a future to completion on the current task, which
+//!   sleeps while the future is pending.
+//! - [`spawn`]: Runs a future in the background on the worker tasks of the
+//!   executor, one per CPU, which are spawned on the first call.
+//! - mod [`time`]: Timers backed by the timer list of [`axtask`].
+//! - mod [`net`]: TCP and UDP sockets woken by the readiness events of the
+//!   sockets in [`axnet`].
+//!
+//! # Cargo Features
+//!
+//! - `irq`: Interrupts are enabled. The timers require this feature to wake
+//!   the tasks when they expire, and so does the network stack to deliver the
+//!   socket events. Otherwise, the pending timers and sockets are polled again
+//!   after yielding the CPU.
+//! - `net`: Enable the async sockets.
+
+#![no_std]
+#![feature(doc_auto_cfg)]
+
+extern crate alloc;
+
+mod executor;
+pub mod time;
+
+#[cfg(feature = "net")]
+pub mod net;
+
+pub use self::executor::{block_on, spawn, yield_now, JoinHandle, YieldNow};
+
+/// Wakes `waker` after yielding the CPU, for the futures that have no event
+/// to wait for but have to be polled again.
+#[cfg(any(not(feature = "irq"), feature = "net"))]
+fn wake_after_yield(waker: &core::task::Waker) {
+    axtask::yield_now();
+    waker.wake_by_ref();
+}
@@ -0,0 +1,278 @@
+//! Async TCP and UDP sockets.
+//!
+//! They wrap the nonblocking sockets of [`axnet`]. When an operation would
+//! block, the waker of the task is registered to the socket, and woken by the
+//! next readiness event of it. If the network stack is not interrupt-driven,
+//! there are no such events, so the interfaces are polled and the task is
+//! polled again after yielding the CPU instead.
+
+use core::future::poll_fn;
+use core::net::SocketAddr;
+use core::task::{Context, Poll, Waker};
+
+use axerrno::{AxError, AxResult};
+use axnet::{TcpSocket, UdpSocket};
+
+/// Calls `f` on a nonblocking socket, and arranges for the task to be woken
+/// when it may be ready if `f` would block.
+///
+/// `register` registers the given waker to the socket, and returns `false` if
+/// the network stack is not interrupt-driven.
+fn poll_io<T, R, F>(cx: &mut Context<'_>, register: R, mut f: F) -> Poll<AxResult<T>>
+where
+    R: FnOnce(&Waker) -> bool,
+    F: FnMut() -> AxResult<T>,
+{
+    match f() {
+        Err(AxError::WouldBlock) => {}
+        res => return Poll::Ready(res),
+    }
+    if register(cx.waker()) {
+        // try again, in case the socket became ready before the registration
+        match f() {
+            Err(AxError::WouldBlock) => Poll::Pending,
+            res => Poll::Ready(res),
+        }
+    } else {
+        axnet::poll_interfaces();
+        crate::wake_after_yield(cx.waker());
+        Poll::Pending
+    }
+}
+
+/// A TCP stream between a local and a remote socket.
+pub struct AsyncTcpStream(TcpSocket);
+
+impl AsyncTcpStream {
+    fn new(socket: TcpSocket) -> Self {
+        socket.set_nonblocking(true);
+        Self(socket)
+    }
+
+    /// Opens a TCP connection to a remote host.
+    pub async fn connect(addr: SocketAddr) -> AxResult<Self> {
+        let stream = Self::new(TcpSocket::new());
+        let socket = &stream.0;
+        match socket.connect(addr) {
+            Ok(()) => return Ok(stream),
+            Err(AxError::WouldBlock) => {}
+            Err(e) => return Err(e),
+        }
+        poll_fn(|cx| {
+            poll_io(
+                cx,
+                |waker| socket.register_waker(waker),
+                || {
+                    if !socket.poll()?.writable {
+                        return Err(AxError::WouldBlock);
+                    }
+                    match socket.take_error() {
+                        Some(e) => Err(e),
+                        None => Ok(()),
+                    }
+                },
+            )
+        })
+        .await?;
+        Ok(stream)
+    }
+
+    /// Returns the socket address of the local half of this TCP connection.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Returns the socket address of the remote peer of this TCP connection.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Sets the value of the `TCP_NODELAY` option on this socket.
+    pub fn set_nodelay(&self, nodelay: bool) {
+        self.0.set_nodelay(nodelay);
+    }
+
+    /// Attempts to receive data from the socket, registering the waker of
+    /// `cx` if no data is available yet.
+    pub fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        poll_io(
+            cx,
+            |waker| self.0.register_waker(waker),
+            || self.0.recv(buf),
+        )
+    }
+
+    /// Attempts to send data to the socket, registering the waker of `cx` if
+    /// the send buffer is full.
+    pub fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        poll_io(
+            cx,
+            |waker| self.0.register_waker(waker),
+            || self.0.send(buf),
+        )
+    }
+
+    /// Receives data from the socket, returning the number of bytes read,
+    /// which is 0 if the peer has closed the connection.
+    pub async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Sends data to the socket, returning the number of bytes written.
+    pub async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    /// Sends the entire buffer to the socket.
+    pub async fn write_all(&self, mut buf: &[u8]) -> AxResult {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(AxError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Shuts down the connection.
+    pub fn shutdown(&self) -> AxResult {
+        self.0.shutdown()
+    }
+}
+
+/// A TCP socket server, listening for connections.
+pub struct AsyncTcpListener(TcpSocket);
+
+impl AsyncTcpListener {
+    /// Creates a new `AsyncTcpListener` which will be bound to the specified
+    /// address.
+    pub fn bind(addr: SocketAddr) -> AxResult<Self> {
+        let socket = TcpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(addr)?;
+        socket.listen()?;
+        Ok(Self(socket))
+    }
+
+    /// Returns the local socket address of this listener.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Attempts to accept a new incoming connection, registering the waker of
+    /// `cx` if there is none yet.
+    pub fn poll_accept(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<AxResult<(AsyncTcpStream, SocketAddr)>> {
+        poll_io(
+            cx,
+            |waker| self.0.register_waker(waker),
+            || {
+                let socket = self.0.accept()?;
+                let addr = socket.peer_addr()?;
+                Ok((AsyncTcpStream::new(socket), addr))
+            },
+        )
+    }
+
+    /// Accepts a new incoming connection, returning the stream and the remote
+    /// address.
+    pub async fn accept(&self) -> AxResult<(AsyncTcpStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+}
+
+/// A UDP socket.
+pub struct AsyncUdpSocket(UdpSocket);
+
+impl AsyncUdpSocket {
+    /// Creates a UDP socket bound to the given address.
+    pub fn bind(addr: SocketAddr) -> AxResult<Self> {
+        let socket = UdpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(addr)?;
+        Ok(Self(socket))
+    }
+
+    /// Returns the socket address that this socket was bound to.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Returns the socket address of the remote peer this socket was connected
+    /// to.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Connects this socket to a remote address, so that [`send`](Self::send)
+    /// and [`recv`](Self::recv) can be used.
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        self.0.connect(addr)
+    }
+
+    /// Attempts to send a datagram to the given address, registering the
+    /// waker of `cx` if the send buffer is full.
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<AxResult<usize>> {
+        poll_io(
+            cx,
+            |waker| self.0.register_waker(waker),
+            || self.0.send_to(buf, addr),
+        )
+    }
+
+    /// Attempts to receive a datagram, registering the waker of `cx` if there
+    /// is none yet.
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<(usize, SocketAddr)>> {
+        poll_io(
+            cx,
+            |waker| self.0.register_waker(waker),
+            || self.0.recv_from(buf),
+        )
+    }
+
+    /// Sends a datagram to the given address, returning the number of bytes
+    /// written.
+    pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_send_to(cx, buf, addr)).await
+    }
+
+    /// Receives a datagram, returning the number of bytes read and the origin.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    /// Sends a datagram to the connected remote address.
+    pub async fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        poll_fn(|cx| {
+            poll_io(
+                cx,
+                |waker| self.0.register_waker(waker),
+                || self.0.send(buf),
+            )
+        })
+        .await
+    }
+
+    /// Receives a datagram from the connected remote address.
+    pub async fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        poll_fn(|cx| {
+            poll_io(
+                cx,
+                |waker| self.0.register_waker(waker),
+                || self.0.recv(buf),
+            )
+        })
+        .await
+    }
+}
@@ -0,0 +1,134 @@
+//! Async timers.
+//!
+//! With the `irq` feature, the pending timers register the wakers of their
+//! tasks to the timer list of [`axtask`], which wakes them in the timer
+//! interrupt handler when they expire, and the alarms are canceled when the
+//! timers are dropped. Otherwise, they are polled again after yielding the CPU
+//! until they expire.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+#[cfg(feature = "irq")]
+use core::task::Waker;
+use core::task::{Context, Poll};
+use core::time::Duration;
+
+use axhal::time::{current_time, TimeValue};
+#[cfg(feature = "irq")]
+use axtask::AlarmToken;
+
+/// Waits until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(current_time() + duration)
+}
+
+/// Waits until `deadline` is reached.
+pub fn sleep_until(deadline: TimeValue) -> Sleep {
+    Sleep {
+        deadline,
+        #[cfg(feature = "irq")]
+        alarm: None,
+    }
+}
+
+/// Requires the `future` to complete before `duration` has elapsed.
+///
+/// The future is dropped and [`Err(Elapsed)`](Elapsed) is returned if it does
+/// not complete in time.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// The future returned by [`sleep`] and [`sleep_until`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    deadline: TimeValue,
+    /// The waker registered to the timer list, with the token to cancel it.
+    #[cfg(feature = "irq")]
+    alarm: Option<(Waker, AlarmToken)>,
+}
+
+impl Sleep {
+    /// Returns the instant at which the future will complete.
+    pub fn deadline(&self) -> TimeValue {
+        self.deadline
+    }
+
+    /// Returns `true` if the deadline has been reached.
+    pub fn is_elapsed(&self) -> bool {
+        current_time() >= self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.is_elapsed() {
+            return Poll::Ready(());
+        }
+        #[cfg(feature = "irq")]
+        {
+            let registered = matches!(&this.alarm, Some((w, _)) if w.will_wake(cx.waker()));
+            if !registered {
+                // replace the alarm if polled by another task
+                if let Some((_, token)) = this.alarm.take() {
+                    token.cancel();
+                }
+                let token = axtask::set_alarm_waker(this.deadline, cx.waker().clone());
+                this.alarm = Some((cx.waker().clone(), token));
+            }
+        }
+        #[cfg(not(feature = "irq"))]
+        crate::wake_after_yield(cx.waker());
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "irq")]
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((_, token)) = self.alarm.take() {
+            token.cancel();
+        }
+    }
+}
+
+/// The future returned by [`timeout`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of the pinned `Timeout`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The error returned by [`timeout`] when the deadline has elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
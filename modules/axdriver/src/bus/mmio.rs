@@ -5,13 +5,19 @@ impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
         // TODO: parse device tree
         #[cfg(feature = "virtio")]
-        for reg in axconfig::VIRTIO_MMIO_REGIONS {
+        for (i, reg) in axconfig::VIRTIO_MMIO_REGIONS.iter().enumerate() {
+            // VirtIO MMIO devices use consecutive IRQ numbers
+            let irq_num = match axconfig::VIRTIO_MMIO_IRQ_BASE {
+                0 => None,
+                base => Some(base + i),
+            };
             for_each_drivers!(type Driver, {
-                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
+                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1, irq_num) {
                     info!(
-                        "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
+                        "registered a new {:?} device at [PA:{:#x}, PA:{:#x}), IRQ {:?}: {:?}",
                         dev.device_type(),
                         reg.0, reg.0 + reg.1,
+                        dev.irq_num(),
                         dev.device_name(),
                     );
                     self.add_device(dev);
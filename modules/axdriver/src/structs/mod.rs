@@ -48,4 +48,18 @@ impl BaseDriverOps for AxDeviceEnum {
             _ => unreachable!(),
         }
     }
+
+    #[inline]
+    #[allow(unreachable_patterns)]
+    fn irq_num(&self) -> Option<usize> {
+        match self {
+            #[cfg(feature = "net")]
+            Self::Net(dev) => dev.irq_num(),
+            #[cfg(feature = "block")]
+            Self::Block(dev) => dev.irq_num(),
+            #[cfg(feature = "display")]
+            Self::Display(dev) => dev.irq_num(),
+            _ => unreachable!(),
+        }
+    }
 }
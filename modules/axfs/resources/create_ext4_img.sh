@@ -0,0 +1,26 @@
+#!/bin/bash
+
+# Creates the ext4 test image with e2fsprogs, no root privileges are required.
+
+CUR_DIR=`dirname $0`
+
+create_ext4_img() {
+	local name=$1
+	local blkcount=$2
+	local root=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$root/long.txt"
+	done
+	echo "Rust is cool!" >>"$root/short.txt"
+	mkdir -p "$root/very/long/path"
+	echo "Rust is cool!" >>"$root/very/long/path/test.txt"
+	mkdir -p "$root/very-long-dir-name"
+	echo "Rust is cool!" >>"$root/very-long-dir-name/very-long-file-name.txt"
+
+	# populate the image without mounting it
+	rm -f "$name"
+	mke2fs -q -t ext4 -b 1024 -L "Test!" -d "$root" "$name" $blkcount
+	rm -rf "$root"
+}
+
+create_ext4_img "$CUR_DIR/ext4.img" 4096
@@ -6,7 +6,8 @@ mod file;
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_vfs::VfsOps;
 use axio::{self as io, prelude::*};
 
 /// Returns an iterator over the entries within a directory.
@@ -59,6 +60,33 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Query the metadata about a file without following symlinks.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::root::lookup_nofollow(None, path)?
+        .get_attr()
+        .map(Metadata)
+}
+
+/// Reads a symbolic link, returning the file that the link points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(None, path)
+}
+
+/// Creates a new symbolic link on the filesystem.
+///
+/// The `link` path will be a symbolic link pointing to the `original` path.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::create_symlink(None, original, link)
+}
+
+/// Creates a new hard link on the filesystem.
+///
+/// The `link` path will be a link pointing to the `original` path. Note that
+/// directories and nodes in different filesystems can not be linked.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::create_link(None, original, link)
+}
+
 /// Creates a new, empty directory at the provided path.
 pub fn create_dir(path: &str) -> io::Result<()> {
     DirBuilder::new().create(path)
@@ -87,3 +115,31 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
+
+/// Mounts the filesystem `fs` on the directory `path`.
+///
+/// The directory is created if it does not exist. Mount points can be nested
+/// in other mounted filesystems.
+pub fn mount(path: &str, fs: Arc<dyn VfsOps>) -> io::Result<()> {
+    crate::root::mount(path, fs)
+}
+
+/// Mounts the filesystem on the block device `dev_id` on the directory `path`.
+///
+/// The device `0` is used by the root filesystem, others can be mounted at
+/// one place at a time, and can be mounted again after unmounted. The type of
+/// the filesystem is the same as the root filesystem.
+pub fn mount_disk(dev_id: usize, path: &str) -> io::Result<()> {
+    let fs = crate::dev::mount_spare_disk(dev_id, crate::fs::new_disk_fs)?;
+    crate::root::mount(path, fs.clone()).inspect_err(|_| crate::dev::umount_spare_disk(&fs))
+}
+
+/// Unmounts the filesystem mounted on `path`.
+///
+/// It fails with [`ResourceBusy`](io::Error::ResourceBusy) if other
+/// filesystems are mounted under it, or the current directory is in it.
+pub fn umount(path: &str) -> io::Result<()> {
+    let fs = crate::root::umount(path)?;
+    crate::dev::umount_spare_disk(&fs);
+    Ok(())
+}
@@ -1,12 +1,93 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::ManuallyDrop;
+
 use axdriver::prelude::*;
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsOps;
+use axsync::Mutex;
 
 const BLOCK_SIZE: usize = 512;
 
+/// Block devices not used by the root filesystem, indexed by the device ID
+/// minus one.
+static SPARE_DISKS: Mutex<Vec<SpareDisk>> = Mutex::new(Vec::new());
+
+enum SpareDisk {
+    /// The filesystem on it has not been opened.
+    Disk(Disk),
+    /// The filesystem on it is being opened. The disk is put back by
+    /// [`Disk::drop`] if it fails.
+    Opening,
+    /// The filesystem on it is opened, it is kept after unmounted (some
+    /// filesystems cannot be closed), and is reused when mounted again.
+    Opened { fs: Arc<dyn VfsOps>, mounted: bool },
+}
+
+/// Registers a block device that can be mounted later, returns its device ID.
+pub(crate) fn add_spare_disk(mut disk: Disk) -> usize {
+    let mut disks = SPARE_DISKS.lock();
+    disk.spare_id = Some(disks.len() + 1);
+    disks.push(SpareDisk::Disk(disk));
+    disks.len()
+}
+
+/// Gets the filesystem on the spare block device with the given ID to mount
+/// it, and marks it as mounted.
+///
+/// The filesystem is created by `new_fs` the first time. The disk is given
+/// back if it fails, so that it can be tried again.
+pub(crate) fn mount_spare_disk(
+    dev_id: usize,
+    new_fs: impl FnOnce(Disk) -> AxResult<Arc<dyn VfsOps>>,
+) -> AxResult<Arc<dyn VfsOps>> {
+    let mut disks = SPARE_DISKS.lock();
+    let Some(slot) = dev_id.checked_sub(1).and_then(|i| disks.get_mut(i)) else {
+        return ax_err!(NotFound, "no such block device");
+    };
+    let disk = match core::mem::replace(slot, SpareDisk::Opening) {
+        SpareDisk::Disk(disk) => disk,
+        SpareDisk::Opened { fs, mounted: false } => {
+            *slot = SpareDisk::Opened {
+                fs: fs.clone(),
+                mounted: true,
+            };
+            return Ok(fs);
+        }
+        busy => {
+            *slot = busy;
+            return ax_err!(ResourceBusy);
+        }
+    };
+    drop(disks); // `Disk::drop` locks it
+
+    let fs = new_fs(disk)?;
+    SPARE_DISKS.lock()[dev_id - 1] = SpareDisk::Opened {
+        fs: fs.clone(),
+        mounted: true,
+    };
+    Ok(fs)
+}
+
+/// Marks `fs` as unmounted if it is on a spare block device, so that the
+/// device can be mounted again.
+pub(crate) fn umount_spare_disk(fs: &Arc<dyn VfsOps>) {
+    for disk in SPARE_DISKS.lock().iter_mut() {
+        if let SpareDisk::Opened { fs: f, mounted } = disk {
+            if Arc::ptr_eq(f, fs) {
+                *mounted = false;
+            }
+        }
+    }
+}
+
 /// A disk device with a cursor.
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    dev: ManuallyDrop<AxBlockDevice>,
+    /// The device ID if it is a spare disk.
+    spare_id: Option<usize>,
 }
 
 impl Disk {
@@ -16,7 +97,8 @@ impl Disk {
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            dev: ManuallyDrop::new(dev),
+            spare_id: None,
         }
     }
 
@@ -90,3 +172,20 @@ impl Disk {
         Ok(write_size)
     }
 }
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        // SAFETY: `self.dev` is not used after this.
+        let dev = unsafe { ManuallyDrop::take(&mut self.dev) };
+        // give the spare disk back if the filesystem failed to be opened
+        if let Some(id) = self.spare_id {
+            let mut disks = SPARE_DISKS.lock();
+            let slot = &mut disks[id - 1];
+            if matches!(slot, SpareDisk::Opening) {
+                let mut disk = Disk::new(dev);
+                disk.spare_id = Some(id);
+                *slot = SpareDisk::Disk(disk);
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Low-level filesystem operations.
 
+use alloc::string::String;
 use axerrno::{ax_err, ax_err_type, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
@@ -31,6 +32,9 @@ pub struct File {
 /// [`read_dir`](Directory::read_dir).
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
+    /// The absolute path of the directory, which relative paths are resolved
+    /// from.
+    path: String,
     entry_idx: usize,
 }
 
@@ -112,7 +116,7 @@ impl OpenOptions {
 }
 
 impl File {
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
@@ -243,7 +247,7 @@ impl File {
 }
 
 impl Directory {
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -252,28 +256,32 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
+        let (node, abs_path) = crate::root::lookup_with_path(dir, path)?;
         let attr = node.get_attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        let perm_cap = perm_to_cap(attr.perm());
+        if !perm_cap.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
         node.open()?;
         Ok(Self {
-            node: WithCap::new(node, access_cap),
+            // searchable if the permission allows, for the `*_at` operations
+            node: WithCap::new(node, access_cap | (perm_cap & Cap::EXECUTE)),
+            path: abs_path,
             entry_idx: 0,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    fn access_at(&self, path: &str) -> AxResult<Option<&str>> {
         if path.starts_with('/') {
             Ok(None)
         } else {
-            Ok(Some(self.node.access(Cap::EXECUTE)?))
+            self.node.access(Cap::EXECUTE)?;
+            Ok(Some(&self.path))
         }
     }
 
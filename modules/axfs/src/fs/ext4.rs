@@ -0,0 +1,38 @@
+use alloc::sync::Arc;
+
+use axfs_ext4::{BlockDevice, Ext4FileSystem};
+use axfs_vfs::{VfsError, VfsResult};
+
+use crate::dev::Disk;
+
+pub fn new_ext4fs(disk: Disk) -> VfsResult<Arc<Ext4FileSystem<Disk>>> {
+    let fs = Ext4FileSystem::new(disk)?;
+    fs.set_time_source(axhal::time::wall_time);
+    Ok(Arc::new(fs))
+}
+
+impl BlockDevice for Disk {
+    fn read_at(&mut self, offset: u64, mut buf: &mut [u8]) -> VfsResult {
+        self.set_position(offset);
+        while !buf.is_empty() {
+            match self.read_one(buf) {
+                Ok(0) => return Err(VfsError::UnexpectedEof),
+                Ok(n) => buf = &mut buf[n..],
+                Err(_) => return Err(VfsError::Io),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, mut buf: &[u8]) -> VfsResult {
+        self.set_position(offset);
+        while !buf.is_empty() {
+            match self.write_one(buf) {
+                Ok(0) => return Err(VfsError::WriteZero),
+                Ok(n) => buf = &buf[n..],
+                Err(_) => return Err(VfsError::Io),
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,19 +4,25 @@ use core::cell::UnsafeCell;
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, File, LossyOemCpConverter, Read, Seek, SeekFrom, Time, Write};
 
 use crate::dev::Disk;
 
 const BLOCK_SIZE: usize = 512;
 
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<Disk, AxTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+/// Provides the timestamps of files from the wall clock, in UTC.
+///
+/// The time is clamped to the range that FAT can represent (1980 to 2107).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxTimeProvider;
+
+pub struct FileWrapper<'a>(Mutex<File<'a, Disk, AxTimeProvider, LossyOemCpConverter>>);
+pub struct DirWrapper<'a>(Dir<'a, Disk, AxTimeProvider, LossyOemCpConverter>);
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -27,25 +33,27 @@ unsafe impl<'a> Sync for DirWrapper<'a> {}
 
 impl FatFileSystem {
     #[cfg(feature = "use-ramdisk")]
-    pub fn new(mut disk: Disk) -> Self {
+    pub fn new(mut disk: Disk) -> VfsResult<Self> {
         let opts = fatfs::FormatVolumeOptions::new();
-        fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
+        fatfs::format_volume(&mut disk, opts).map_err(as_vfs_err)?;
+        let inner = fatfs::FileSystem::new(disk, Self::fs_options()).map_err(as_vfs_err)?;
+        Ok(Self {
             inner,
             root_dir: UnsafeCell::new(None),
-        }
+        })
     }
 
     #[cfg(not(feature = "use-ramdisk"))]
-    pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
+    pub fn new(disk: Disk) -> VfsResult<Self> {
+        let inner = fatfs::FileSystem::new(disk, Self::fs_options()).map_err(as_vfs_err)?;
+        Ok(Self {
             inner,
             root_dir: UnsafeCell::new(None),
-        }
+        })
+    }
+
+    fn fs_options() -> fatfs::FsOptions<AxTimeProvider, LossyOemCpConverter> {
+        fatfs::FsOptions::new().time_provider(AxTimeProvider)
     }
 
     pub fn init(&'static self) {
@@ -53,11 +61,11 @@ impl FatFileSystem {
         unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
+    fn new_file(file: File<'_, Disk, AxTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
         Arc::new(FileWrapper(Mutex::new(file)))
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
+    fn new_dir(dir: Dir<'_, Disk, AxTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
         Arc::new(DirWrapper(dir))
     }
 }
@@ -198,6 +206,18 @@ impl VfsNodeOps for DirWrapper<'static> {
     }
 }
 
+/// Creates a FAT filesystem on `disk` and initializes it.
+///
+/// The directory handles borrow the filesystem for `'static`, so one
+/// reference is leaked and the filesystem is never freed, even after it is
+/// unmounted.
+pub fn new_fatfs(disk: Disk) -> VfsResult<Arc<FatFileSystem>> {
+    let fs = Arc::new(FatFileSystem::new(disk)?);
+    let leaked: &'static FatFileSystem = unsafe { &*Arc::into_raw(fs.clone()) };
+    leaked.init();
+    Ok(fs)
+}
+
 impl VfsOps for FatFileSystem {
     fn root_dir(&self) -> VfsNodeRef {
         let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
@@ -281,3 +301,51 @@ const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
         _ => VfsError::Io,
     }
 }
+
+impl AxTimeProvider {
+    fn now() -> DateTime {
+        let nanos = axhal::time::wall_time_nanos();
+        let secs = nanos / axhal::time::NANOS_PER_SEC;
+        let millis = nanos % axhal::time::NANOS_PER_SEC / axhal::time::NANOS_PER_MILLIS;
+        let (year, month, day) = civil_from_days(secs / 86400);
+        let secs_of_day = secs % 86400;
+        match year {
+            ..=1979 => DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0)),
+            2108.. => DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999)),
+            _ => DateTime::new(
+                Date::new(year as u16, month as u16, day as u16),
+                Time::new(
+                    (secs_of_day / 3600) as u16,
+                    (secs_of_day % 3600 / 60) as u16,
+                    (secs_of_day % 60) as u16,
+                    millis as u16,
+                ),
+            ),
+        }
+    }
+}
+
+impl fatfs::TimeProvider for AxTimeProvider {
+    fn get_current_date(&self) -> Date {
+        Self::now().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        Self::now()
+    }
+}
+
+/// Converts the number of days since 1970-01-01 to the `(year, month, day)`
+/// in the proleptic Gregorian calendar.
+const fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097; // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // [0, 399]
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // March is 0
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
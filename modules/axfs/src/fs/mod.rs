@@ -1,6 +1,8 @@
 cfg_if::cfg_if! {
     if #[cfg(feature = "myfs")] {
         pub mod myfs;
+    } else if #[cfg(feature = "ext4")] {
+        pub mod ext4;
     } else if #[cfg(feature = "fatfs")] {
         pub mod fatfs;
     }
@@ -11,3 +13,21 @@ pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+use alloc::sync::Arc;
+use axfs_vfs::{VfsOps, VfsResult};
+
+use crate::dev::Disk;
+
+/// Creates the disk filesystem selected by the cargo features on `disk`.
+pub(crate) fn new_disk_fs(disk: Disk) -> VfsResult<Arc<dyn VfsOps>> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "myfs")] { // override the default filesystem
+            Ok(myfs::new_myfs(disk))
+        } else if #[cfg(feature = "ext4")] {
+            Ok(ext4::new_ext4fs(disk)?)
+        } else if #[cfg(feature = "fatfs")] {
+            Ok(fatfs::new_fatfs(disk)?)
+        }
+    }
+}
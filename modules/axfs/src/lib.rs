@@ -6,6 +6,9 @@
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
+//! - `ext4`: Use [`axfs_ext4::Ext4FileSystem`] as the main filesystem and mount
+//!    it on `/`. This feature is **disabled** by default, but it will override
+//!    `fatfs` if both are enabled.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
@@ -31,6 +34,9 @@ mod fs;
 mod mounts;
 mod root;
 
+#[cfg(all(test, not(feature = "myfs")))]
+mod tests;
+
 pub mod api;
 pub mod fops;
 
@@ -43,4 +49,10 @@ pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     let dev = blk_devs.take_one().expect("No block device found!");
     info!("  use block device 0: {:?}", dev.device_name());
     self::root::init_rootfs(self::dev::Disk::new(dev));
+
+    while let Some(dev) = blk_devs.take_one() {
+        let name = alloc::string::String::from(dev.device_name());
+        let id = self::dev::add_spare_disk(self::dev::Disk::new(dev));
+        info!("  found block device {}: {:?}", id, name);
+    }
 }
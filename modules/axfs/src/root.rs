@@ -1,35 +1,42 @@
 //! Root directory of the filesystem
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! The root directory is the root of the main filesystem. Other filesystems
+//! are mounted on directories of the main filesystem or of other mounted
+//! filesystems, and are found by the absolute path of the mount point while
+//! resolving paths.
+//!
+//! Relative paths are resolved from the absolute path of the base directory
+//! (the current directory, or an opened [`Directory`](crate::fops::Directory)),
+//! so the mount points under the base directory are also found.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
 use lazy_init::LazyInit;
 
-use crate::{api::FileType, fs, mounts};
+use crate::{fs, mounts};
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
-static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
 
 struct MountPoint {
-    path: &'static str,
     fs: Arc<dyn VfsOps>,
 }
 
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    /// Mounted filesystems, keyed by the canonical absolute path of the
+    /// mount point.
+    mounts: Mutex<BTreeMap<String, MountPoint>>,
 }
 
-static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
+static ROOT_DIR: LazyInit<RootDirectory> = LazyInit::new();
 
-impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
-    }
-}
+/// The maximum number of symbolic links followed in one path resolution.
+const MAX_SYMLINKS: usize = 40;
+
+/// The maximum length of the target of a symbolic link.
+const MAX_SYMLINK_LEN: usize = 4096;
 
 impl Drop for MountPoint {
     fn drop(&mut self) {
@@ -41,210 +48,338 @@ impl RootDirectory {
     pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
         Self {
             main_fs,
-            mounts: Vec::new(),
+            mounts: Mutex::new(BTreeMap::new()),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    pub fn mount(&self, path: &str, fs: Arc<dyn VfsOps>) -> AxResult {
+        if !path.starts_with('/') {
+            return ax_err!(InvalidInput, "mount path must start with '/'");
+        }
+        let path = axfs_vfs::path::canonicalize(path);
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
-        if !path.starts_with('/') {
-            return ax_err!(InvalidInput, "mount path must start with '/'");
+        // create the mount point if it does not exist
+        match create_dir(None, &path) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
+        // mount on the real directory if there are symbolic links in `path`
+        let (node, path) = resolve_with_path(None, &path, true)?;
+        if !node.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+
+        let mut mounts = self.mounts.lock();
+        if mounts.contains_key(&path) {
             return ax_err!(InvalidInput, "mount point already exists");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        fs.mount(&path, node)?;
+        mounts.insert(path, MountPoint { fs });
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
-    }
-
-    pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
-    }
-
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
+    /// Unmounts the filesystem mounted on `path`, returns the filesystem.
+    pub fn umount(&self, path: &str) -> AxResult<Arc<dyn VfsOps>> {
+        let path = absolute_path(path)?;
+        let mut mounts = self.mounts.lock();
+        if !mounts.contains_key(&path) {
+            return ax_err!(InvalidInput, "not a mount point");
         }
-
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
-        }
-
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
+        let prefix = path.clone() + "/";
+        if mounts.keys().any(|p| p.starts_with(&prefix))
+            || CURRENT_DIR_PATH.lock().starts_with(&prefix)
+        {
+            return ax_err!(ResourceBusy);
         }
-    }
-}
-
-impl VfsNodeOps for RootDirectory {
-    axfs_vfs::impl_vfs_dir_default! {}
-
-    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+        // `MountPoint::drop` unmounts the filesystem
+        let mp = mounts.remove(&path).unwrap();
+        Ok(mp.fs.clone())
     }
 
-    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
+    /// Returns the filesystem mounted exactly at `abs_path`.
+    fn mounted_fs(&self, abs_path: &str) -> Option<Arc<dyn VfsOps>> {
+        self.mounts.lock().get(abs_path).map(|mp| mp.fs.clone())
     }
 
-    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                Ok(()) // already exists
-            } else {
-                fs.root_dir().create(rest_path, ty)
-            }
-        })
+    pub fn contains(&self, abs_path: &str) -> bool {
+        self.mounts.lock().contains_key(abs_path)
     }
 
-    fn remove(&self, path: &str) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot remove mount points
-            } else {
-                fs.root_dir().remove(rest_path)
+    /// Returns the filesystem that `abs_path` is in, and the rest of the path
+    /// relative to the root of that filesystem.
+    fn containing_fs<'a>(&self, abs_path: &'a str) -> (Arc<dyn VfsOps>, &'a str) {
+        let mounts = self.mounts.lock();
+        let mut prefix = abs_path;
+        loop {
+            if let Some(mp) = mounts.get(prefix) {
+                return (mp.fs.clone(), &abs_path[prefix.len()..]);
             }
-        })
-    }
-
-    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(rest_path, dst_path)
+            match prefix.rfind('/') {
+                Some(n) if n > 0 => prefix = &prefix[..n],
+                _ => return (self.main_fs.clone(), abs_path),
             }
-        })
+        }
     }
 }
 
 pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_by(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
-        }
-    }
-
-    let mut root_dir = RootDirectory::new(main_fs);
+    let main_fs = fs::new_disk_fs(disk).expect("failed to initialize the root filesystem");
+    ROOT_DIR.init_by(RootDirectory::new(main_fs));
+    *CURRENT_DIR_PATH.lock() = "/".into();
 
     #[cfg(feature = "devfs")]
-    root_dir
+    ROOT_DIR
         .mount("/dev", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
-    root_dir
+    ROOT_DIR
         .mount("/tmp", mounts::ramfs())
         .expect("failed to mount ramfs at /tmp");
 
     // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
-    root_dir // should not fail
+    ROOT_DIR // should not fail
         .mount("/proc", mounts::procfs().unwrap())
         .expect("fail to mount procfs at /proc");
 
     // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
-    root_dir // should not fail
+    ROOT_DIR // should not fail
         .mount("/sys", mounts::sysfs().unwrap())
         .expect("fail to mount sysfs at /sys");
+}
 
-    ROOT_DIR.init_by(Arc::new(root_dir));
-    CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
+pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
+    absolute_path_at(None, path)
 }
 
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
+/// Returns the absolute path of `path` relative to the directory at the
+/// absolute path `dir`, or the current directory if `dir` is `None`.
+fn absolute_path_at(dir: Option<&str>, path: &str) -> AxResult<String> {
     if path.starts_with('/') {
-        ROOT_DIR.clone()
+        Ok(axfs_vfs::path::canonicalize(path))
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        let base = match dir {
+            Some(dir) => String::from(dir) + "/",
+            None => CURRENT_DIR_PATH.lock().clone(),
+        };
+        Ok(axfs_vfs::path::canonicalize(&(base + path)))
     }
 }
 
-pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
-    if path.starts_with('/') {
-        Ok(axfs_vfs::path::canonicalize(path))
-    } else {
-        let path = CURRENT_DIR_PATH.lock().clone() + path;
-        Ok(axfs_vfs::path::canonicalize(&path))
+/// Pushes the components of `path` to `pending` in reverse order, so that
+/// they can be popped in order.
+fn push_components(pending: &mut Vec<String>, path: &str) {
+    pending.extend(path.rsplit('/').filter(|c| !c.is_empty()).map(String::from));
+}
+
+fn read_link_target(node: &VfsNodeRef) -> AxResult<String> {
+    let mut buf = vec![0; MAX_SYMLINK_LEN];
+    let len = node.readlink(&mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Resolves `path` component by component, starting from the directory at
+/// the absolute path `dir`, or the current directory if `dir` is `None`.
+///
+/// Symbolic links are followed in all the components except the last one,
+/// which is followed only if `follow` is `true` or `path` ends with '/'.
+fn resolve(dir: Option<&str>, path: &str, follow: bool) -> AxResult<VfsNodeRef> {
+    resolve_with_path(dir, path, follow).map(|(node, _)| node)
+}
+
+/// Same as [`resolve`], but also returns the absolute path of the resolved
+/// node without symbolic links.
+fn resolve_with_path(
+    dir: Option<&str>,
+    path: &str,
+    follow: bool,
+) -> AxResult<(VfsNodeRef, String)> {
+    // Paths are always resolved from the root directory, then the mount points
+    // can be found by the absolute path of the resolved part.
+    let mut pending = Vec::new();
+    push_components(&mut pending, path);
+    if !path.starts_with('/') {
+        match dir {
+            Some(dir) => push_components(&mut pending, dir),
+            None => push_components(&mut pending, &CURRENT_DIR_PATH.lock()),
+        }
     }
+
+    let follow_last = follow || path.ends_with('/');
+    let mut resolved: Vec<(String, VfsNodeRef)> = Vec::new();
+    let mut links = 0;
+    while let Some(name) = pending.pop() {
+        let cur = match resolved.last() {
+            Some((_, node)) => node.clone(),
+            None => ROOT_DIR.main_fs.root_dir(),
+        };
+        if name == "." || name == ".." {
+            if !cur.get_attr()?.is_dir() {
+                return ax_err!(NotADirectory);
+            }
+            if name == ".." {
+                // the parent of the root is the root itself
+                resolved.pop();
+            }
+            continue;
+        }
+
+        let mut abs_path = String::new();
+        for (name, _) in &resolved {
+            abs_path += "/";
+            abs_path += name;
+        }
+        abs_path += "/";
+        abs_path += &name;
+        let next = match ROOT_DIR.mounted_fs(&abs_path) {
+            Some(fs) => fs.root_dir(),
+            None => cur.lookup(&name)?,
+        };
+
+        if (follow_last || !pending.is_empty()) && next.get_attr()?.file_type().is_symlink() {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return ax_err!(FilesystemLoop);
+            }
+            let target = read_link_target(&next)?;
+            if target.is_empty() {
+                return ax_err!(NotFound);
+            } else if target.starts_with('/') {
+                resolved.clear();
+            }
+            push_components(&mut pending, &target);
+            continue;
+        }
+        resolved.push((name, next));
+    }
+
+    let mut abs_path = String::from("/");
+    for (i, (name, _)) in resolved.iter().enumerate() {
+        if i > 0 {
+            abs_path += "/";
+        }
+        abs_path += name;
+    }
+    let node = match resolved.pop() {
+        Some((_, node)) => node,
+        None => ROOT_DIR.main_fs.root_dir(),
+    };
+    Ok((node, abs_path))
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Resolves the parent directory of `path`, following symbolic links, and
+/// returns it with the last component of `path`.
+fn resolve_parent<'a>(dir: Option<&str>, path: &'a str) -> AxResult<(VfsNodeRef, &'a str)> {
+    let path = path.trim_end_matches('/');
+    let (parent_path, name) = match path.rfind('/') {
+        Some(n) => (&path[..n + 1], &path[n + 1..]),
+        None => ("", path),
+    };
+    let parent = resolve(dir, parent_path, true)?;
+    if !parent.get_attr()?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    Ok((parent, name))
+}
+
+fn lookup_at(dir: Option<&str>, path: &str, follow: bool) -> AxResult<(VfsNodeRef, String)> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+    let (node, abs_path) = resolve_with_path(dir, path, follow)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
-        Ok(node)
+        Ok((node, abs_path))
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Looks up the node at `path`, following symbolic links.
+pub(crate) fn lookup(dir: Option<&str>, path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(dir, path, true).map(|(node, _)| node)
+}
+
+/// Same as [`lookup`], but also returns the absolute path of the node without
+/// symbolic links.
+pub(crate) fn lookup_with_path(dir: Option<&str>, path: &str) -> AxResult<(VfsNodeRef, String)> {
+    lookup_at(dir, path, true)
+}
+
+/// Looks up the node at `path`, but does not follow the symbolic link if it
+/// is the last component of `path`.
+pub(crate) fn lookup_nofollow(dir: Option<&str>, path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(dir, path, false).map(|(node, _)| node)
+}
+
+pub(crate) fn create_file(dir: Option<&str>, path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let (parent, name) = resolve_parent(dir, path)?;
+    parent.create(name, VfsNodeType::File)?;
+    parent.lookup(name)
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+pub(crate) fn create_dir(dir: Option<&str>, path: &str) -> AxResult {
+    match lookup_nofollow(dir, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let (parent, name) = resolve_parent(dir, path)?;
+            parent.create(name, VfsNodeType::Dir)
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+pub(crate) fn create_symlink(dir: Option<&str>, target: &str, path: &str) -> AxResult {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let (parent, name) = resolve_parent(dir, path)?;
+    parent.symlink(name, target)
+}
+
+pub(crate) fn create_link(dir: Option<&str>, old_path: &str, new_path: &str) -> AxResult {
+    if new_path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let node = lookup_nofollow(dir, old_path)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied); // no hard links to directories
+    }
+    let (parent, name) = resolve_parent(dir, new_path)?;
+    parent.link(name, &node)
+}
+
+pub(crate) fn read_link(dir: Option<&str>, path: &str) -> AxResult<String> {
+    let node = lookup_nofollow(dir, path)?;
+    if !node.get_attr()?.file_type().is_symlink() {
+        return ax_err!(InvalidInput);
+    }
+    read_link_target(&node)
+}
+
+pub(crate) fn remove_file(dir: Option<&str>, path: &str) -> AxResult {
+    let node = lookup_nofollow(dir, path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let (parent, name) = resolve_parent(dir, path)?;
+        parent.remove(name)
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(dir: Option<&str>, path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -258,18 +393,19 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
+    if ROOT_DIR.contains(&absolute_path_at(dir, path)?) {
         return ax_err!(PermissionDenied);
     }
 
-    let node = lookup(dir, path)?;
+    let node = lookup_nofollow(dir, path.trim_end_matches('/'))?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let (parent, name) = resolve_parent(dir, path)?;
+        parent.remove(name)
     }
 }
 
@@ -283,7 +419,6 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
         abs_path += "/";
     }
     if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
         *CURRENT_DIR_PATH.lock() = "/".into();
         return Ok(());
     }
@@ -295,16 +430,33 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
     } else if !attr.perm().owner_executable() {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
         *CURRENT_DIR_PATH.lock() = abs_path;
         Ok(())
     }
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    if lookup_nofollow(None, new).is_ok() {
         warn!("dst file already exist, now remove it");
         remove_file(None, new)?;
     }
-    parent_node_of(None, old).rename(old, new)
+    let (old, new) = (absolute_path(old)?, absolute_path(new)?);
+    let (src_fs, src_path) = ROOT_DIR.containing_fs(&old);
+    let (dst_fs, dst_path) = ROOT_DIR.containing_fs(&new);
+    if src_path.is_empty() || dst_path.is_empty() {
+        return ax_err!(PermissionDenied); // cannot rename mount points
+    } else if !Arc::ptr_eq(&src_fs, &dst_fs) {
+        return ax_err!(Unsupported); // cannot rename across filesystems
+    }
+    src_fs
+        .root_dir()
+        .rename(src_path.trim_start_matches('/'), dst_path)
+}
+
+pub(crate) fn mount(path: &str, fs: Arc<dyn VfsOps>) -> AxResult {
+    ROOT_DIR.mount(path, fs)
+}
+
+pub(crate) fn umount(path: &str) -> AxResult<Arc<dyn VfsOps>> {
+    ROOT_DIR.umount(path)
 }
@@ -0,0 +1,67 @@
+use axerrno::AxError;
+use driver_block::ramdisk::RamDisk;
+
+use crate::api as fs;
+use crate::dev::{add_spare_disk, Disk};
+
+#[cfg(feature = "ext4")]
+const IMG_PATH: &str = "resources/ext4.img";
+#[cfg(not(feature = "ext4"))]
+const IMG_PATH: &str = "resources/fat16.img";
+
+fn make_disk() -> Disk {
+    let data = std::fs::read(IMG_PATH).expect("failed to load disk image");
+    Disk::new(RamDisk::from(&data))
+}
+
+#[test]
+fn test_mount_disk() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    crate::root::init_rootfs(make_disk());
+    let dev_id = add_spare_disk(make_disk());
+
+    fs::mount_disk(dev_id, "/mnt").unwrap();
+    assert_eq!(
+        fs::mount_disk(dev_id, "/mnt2").err(),
+        Some(AxError::ResourceBusy)
+    );
+    fs::write("/mnt/mounted.txt", "data on the spare disk").unwrap();
+    fs::umount("/mnt").unwrap();
+    assert_eq!(
+        fs::metadata("/mnt/mounted.txt").err(),
+        Some(AxError::NotFound)
+    );
+
+    // mount again at another place
+    fs::mount_disk(dev_id, "/mnt2").unwrap();
+    assert_eq!(
+        fs::read_to_string("/mnt2/mounted.txt").unwrap(),
+        "data on the spare disk"
+    );
+    fs::set_current_dir("/mnt2").unwrap();
+    assert!(fs::read_dir("..")
+        .unwrap()
+        .any(|e| e.unwrap().file_name() == "mnt2"));
+    fs::set_current_dir("/").unwrap();
+    fs::umount("/mnt2").unwrap();
+    fs::mount_disk(dev_id, "/mnt").unwrap();
+    assert!(fs::metadata("/mnt/mounted.txt").unwrap().is_file());
+    fs::umount("/mnt").unwrap();
+
+    // the disk is given back if failed to be mounted
+    fs::write("/not_a_dir.txt", "").unwrap();
+    assert_eq!(
+        fs::mount_disk(dev_id, "/not_a_dir.txt").err(),
+        Some(AxError::NotADirectory)
+    );
+    fs::mount_disk(dev_id, "/mnt").unwrap();
+    fs::umount("/mnt").unwrap();
+    #[cfg(not(feature = "use-ramdisk"))] // a ramdisk is formatted on creation
+    {
+        let empty_id = add_spare_disk(Disk::new(RamDisk::new(0x10_0000)));
+        let err = fs::mount_disk(empty_id, "/empty").unwrap_err();
+        assert_ne!(err, AxError::ResourceBusy);
+        assert_eq!(fs::mount_disk(empty_id, "/empty").err(), Some(err));
+    }
+    assert_eq!(fs::mount_disk(100, "/mnt").err(), Some(AxError::NotFound));
+}
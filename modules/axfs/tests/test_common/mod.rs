@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use axfs::api as fs;
+use axfs_ramfs::RamFileSystem;
 use axio as io;
 
 use fs::{File, FileType, OpenOptions};
@@ -164,6 +167,26 @@ fn test_remove_file_dir() -> Result<()> {
     Ok(())
 }
 
+fn test_parent_of_root() -> Result<()> {
+    println!("test the parent of the root directory:");
+    let cwd = fs::current_dir()?;
+    let root_count = fs::read_dir("/")?.count();
+    assert_eq!(fs::read_dir("/..")?.count(), root_count);
+    assert_eq!(fs::read_dir("/../..//./")?.count(), root_count);
+    assert!(fs::metadata("/../very/long")?.is_dir());
+    assert!(fs::metadata("/tmp/../../very/long/..")?.is_dir());
+
+    fs::set_current_dir("/")?;
+    assert_eq!(fs::read_dir("..")?.count(), root_count);
+    assert!(fs::metadata("../very")?.is_dir());
+    fs::set_current_dir("..")?;
+    assert_eq!(fs::current_dir()?, "/");
+    fs::set_current_dir(&cwd)?;
+
+    println!("test_parent_of_root() OK!");
+    Ok(())
+}
+
 fn test_devfs_ramfs() -> Result<()> {
     const N: usize = 32;
     let mut buf = [1; N];
@@ -242,7 +265,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 1);
     assert_eq!(fs::write(".///tmp///dir//.///test.txt", "test"), Ok(()));
     assert_eq!(fs::read("tmp//././/dir//.///test.txt"), Ok("test".into()));
-    // assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty); // TODO
+    assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty);
     assert_err!(fs::remove_dir("/tmp/dir/../dir"), DirectoryNotEmpty);
     assert_eq!(fs::remove_file("./tmp//dir//test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
@@ -252,11 +275,135 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_links() -> Result<()> {
+    println!("test symbolic links and hard links:");
+    fs::create_dir("/tmp/dir")?;
+    fs::write("/tmp/dir/file.txt", "link me!\n")?;
+
+    // relative and absolute symbolic links
+    fs::symlink("dir/file.txt", "/tmp/rel")?;
+    fs::symlink("/tmp/./dir", "tmp//abs")?;
+    fs::symlink("/very/long", "/tmp/main")?; // to another filesystem
+    assert_eq!(fs::read_link("/tmp/rel")?, "dir/file.txt");
+    assert_eq!(fs::read_to_string("/tmp/rel")?, "link me!\n");
+    assert_eq!(fs::read_to_string("tmp/abs/../abs/file.txt")?, "link me!\n");
+    assert!(fs::metadata("/tmp/main/path/test.txt")?.is_file());
+    assert!(fs::metadata("/tmp/abs")?.is_dir());
+    assert!(fs::symlink_metadata("/tmp/abs")?.is_symlink());
+    assert!(fs::symlink_metadata("/tmp/abs/")?.is_dir());
+    assert!(fs::symlink_metadata("/tmp/abs/file.txt")?.is_file());
+    assert_err!(fs::read_link("/tmp/dir"), InvalidInput);
+    assert_err!(fs::symlink("dir", "/tmp/abs"), AlreadyExists);
+    assert_err!(fs::create_dir("/tmp/abs"), AlreadyExists);
+
+    // create nodes through a symbolic link
+    fs::write("/tmp/abs/new.txt", "new\n")?;
+    assert_eq!(fs::read_to_string("/tmp/dir/new.txt")?, "new\n");
+    let dirents = fs::read_dir("/tmp/abs/")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(dirents.contains(&"new.txt".into()));
+
+    // dangling symbolic links and loops
+    fs::symlink("not-exist", "/tmp/dangling")?;
+    assert_err!(fs::metadata("/tmp/dangling"), NotFound);
+    assert!(fs::symlink_metadata("/tmp/dangling")?.is_symlink());
+    fs::symlink("loop2", "/tmp/loop1")?;
+    fs::symlink("./loop1", "/tmp/loop2")?;
+    assert_err!(fs::metadata("/tmp/loop1"), FilesystemLoop);
+    assert_err!(fs::metadata("/tmp/loop2/file.txt"), FilesystemLoop);
+    assert!(fs::symlink_metadata("/tmp/loop1")?.is_symlink());
+
+    // hard links
+    fs::hard_link("/tmp/dir/file.txt", "/tmp/hard")?;
+    fs::write("/tmp/hard", "changed!\n")?;
+    assert_eq!(fs::read_to_string("/tmp/rel")?, "changed!\n");
+    assert_err!(fs::hard_link("/tmp/dir", "/tmp/hard-dir"), PermissionDenied);
+    assert_err!(fs::hard_link("/tmp/hard", "/tmp/rel"), AlreadyExists);
+
+    // remove the links rather than the targets
+    assert_err!(fs::remove_dir("/tmp/abs"), NotADirectory);
+    fs::remove_file("/tmp/abs")?;
+    assert!(fs::metadata("/tmp/dir")?.is_dir());
+    fs::remove_file("/tmp/dir/file.txt")?;
+    assert_eq!(fs::read_to_string("/tmp/hard")?, "changed!\n");
+    assert_err!(fs::metadata("/tmp/rel"), NotFound);
+    for fname in ["rel", "main", "dangling", "loop1", "loop2", "hard"] {
+        fs::remove_file(&format!("/tmp/{}", fname))?;
+    }
+    fs::remove_file("/tmp/dir/new.txt")?;
+    fs::remove_dir("/tmp/dir")?;
+    assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_mount() -> Result<()> {
+    println!("test nested mounts:");
+    let cwd = fs::current_dir()?;
+    fs::mount("/tmp/a", Arc::new(RamFileSystem::new()))?;
+    fs::write("/tmp/a/file.txt", "in a\n")?;
+    fs::mount("/tmp//a/./b/", Arc::new(RamFileSystem::new()))?;
+    fs::write("/tmp/a/b/file.txt", "in b\n")?;
+    assert_eq!(fs::read_to_string("/tmp/a/b/../file.txt")?, "in a\n");
+    assert_eq!(fs::read_to_string("/tmp/a/b/../../a/b/file.txt")?, "in b\n");
+    assert_eq!(fs::read_dir("/tmp/a")?.count(), 2);
+    assert_err!(
+        fs::mount("/tmp/a/b", Arc::new(RamFileSystem::new())),
+        InvalidInput
+    );
+    assert_err!(
+        fs::mount("/tmp/a/file.txt", Arc::new(RamFileSystem::new())),
+        NotADirectory
+    );
+    assert_err!(fs::mount("/", Arc::new(RamFileSystem::new())), InvalidInput);
+    assert_err!(fs::remove_dir("/tmp/a/b"), PermissionDenied);
+
+    // relative paths from an opened directory also cross the mount points
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.read(true);
+    let dir = axfs::fops::Directory::open_dir("/tmp", &opts)?;
+    let mut buf = [0; 5];
+    let file = dir.open_file_at("a/b/file.txt", &opts)?;
+    assert_eq!(file.read_at(0, &mut buf)?, 5);
+    assert_eq!(&buf, b"in b\n");
+    let dir_a = dir.open_dir_at("a", &opts)?;
+    dir_a.create_file("b/new.txt")?;
+    assert!(fs::metadata("/tmp/a/b/new.txt")?.is_file());
+    assert_eq!(fs::read_dir("/tmp/a/b")?.count(), 2);
+    fs::remove_file("/tmp/a/b/new.txt")?;
+
+    // mount points in use can not be unmounted
+    fs::set_current_dir("/tmp/a/b")?;
+    assert_eq!(fs::read_to_string("../file.txt")?, "in a\n");
+    assert_err!(fs::umount("/tmp/a"), ResourceBusy);
+    assert_err!(fs::umount("/tmp/a/b"), ResourceBusy);
+    fs::set_current_dir(&cwd)?;
+    assert_err!(fs::umount("/tmp/a/file.txt"), InvalidInput);
+    assert_err!(fs::umount("/tmp/a"), ResourceBusy);
+
+    fs::umount("/tmp/a/b")?;
+    assert_err!(fs::metadata("/tmp/a/b/file.txt"), NotFound);
+    assert_eq!(fs::read_to_string("/tmp/a/file.txt")?, "in a\n");
+    fs::umount("/tmp/a")?;
+    assert_err!(fs::umount("/tmp/a"), InvalidInput);
+    assert_err!(fs::metadata("/tmp/a/file.txt"), NotFound);
+    fs::remove_dir("/tmp/a")?;
+    assert_eq!(fs::read_dir("/tmp")?.count(), 0);
+
+    println!("test_mount() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
     test_file_permission().expect("test_file_permission() failed");
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
+    test_parent_of_root().expect("test_parent_of_root() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_links().expect("test_links() failed");
+    test_mount().expect("test_mount() failed");
 }
@@ -1,11 +1,36 @@
 use core::arch::global_asm;
 
 use aarch64_cpu::registers::{ESR_EL1, FAR_EL1};
+use page_table_entry::MappingFlags;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
 
-global_asm!(include_str!("trap.S"));
+// Stack overflows are detected only with `paging`, which maps the guard pages.
+#[cfg(feature = "paging")]
+#[allow(dead_code)] // only used by the trap entry
+#[percpu::def_percpu]
+static EMERGENCY_STACK: crate::trap::EmergencyStack = crate::trap::EmergencyStack::new();
+
+#[cfg(feature = "paging")]
+global_asm!(
+    include_str!("trap.S"),
+    paging = const 1,
+    kstack_region_shift = const crate::trap::KSTACK_REGION_SHIFT,
+    kstack_region_tag = const crate::trap::KSTACK_REGION_TAG,
+    emergency_stack = sym __PERCPU_EMERGENCY_STACK,
+    emergency_stack_size = const crate::trap::EMERGENCY_STACK_SIZE,
+);
+
+#[cfg(not(feature = "paging"))]
+global_asm!(
+    include_str!("trap.S"),
+    paging = const 0,
+    kstack_region_shift = const crate::trap::KSTACK_REGION_SHIFT,
+    kstack_region_tag = const crate::trap::KSTACK_REGION_TAG,
+    emergency_stack = const 0,
+    emergency_stack_size = const crate::trap::EMERGENCY_STACK_SIZE,
+);
 
 #[repr(u8)]
 #[derive(Debug)]
@@ -35,39 +60,62 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
     );
 }
 
+fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+    let access_flags = MappingFlags::EXECUTE;
+    handle_page_fault(tf, iss, access_flags, is_user);
+}
+
+fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+    let wnr = (iss & (1 << 6)) != 0; // WnR: Write not Read
+    let cm = (iss & (1 << 8)) != 0; // CM: Cache maintenance
+    let access_flags = if wnr && !cm {
+        MappingFlags::WRITE
+    } else {
+        MappingFlags::READ
+    };
+    handle_page_fault(tf, iss, access_flags, is_user);
+}
+
+fn handle_page_fault(tf: &TrapFrame, iss: u64, access_flags: MappingFlags, is_user: bool) {
+    let vaddr = FAR_EL1.get() as usize;
+    // Only translation, access flag, and permission faults (xFSC = 0b0001LL,
+    // 0b0010LL or 0b0011LL) are page faults.
+    let fsc = iss & 0b11_1111;
+    let irqs_enabled = tf.spsr & (1 << 7) == 0; // SPSR.I: IRQ masked
+    if matches!(fsc >> 2, 0b0001..=0b0011)
+        && crate::trap::handle_page_fault_extern(vaddr.into(), access_flags, is_user, irqs_enabled)
+    {
+        return;
+    }
+    if is_user {
+        warn!(
+            "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
+            tf.elr, vaddr, iss
+        );
+    } else {
+        panic!(
+            "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
+            tf.elr, vaddr, iss, tf,
+        );
+    }
+}
+
 #[no_mangle]
 fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
+    let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
         Some(ESR_EL1::EC::Value::Brk64) => {
-            let iss = esr.read(ESR_EL1::ISS);
             debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
             tf.elr += 4;
         }
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No supervisor call is supported currently!");
         }
-        Some(ESR_EL1::EC::Value::DataAbortLowerEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            warn!(
-                "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss
-            );
-        }
-        Some(ESR_EL1::EC::Value::DataAbortCurrentEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            panic!(
-                "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss,
-                tf,
-            );
-        }
+        Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_instruction_abort(tf, iss, true),
+        Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_instruction_abort(tf, iss, false),
+        Some(ESR_EL1::EC::Value::DataAbortLowerEL) => handle_data_abort(tf, iss, true),
+        Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => handle_data_abort(tf, iss, false),
         _ => {
             panic!(
                 "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
@@ -1,12 +1,23 @@
+use page_table_entry::MappingFlags;
 use riscv::register::scause::{self, Exception as E, Trap};
+use riscv::register::stval;
 
 use super::TrapFrame;
+use crate::trap::EmergencyStack;
 
 include_asm_marcos!();
 
+#[allow(dead_code)] // only used by the trap entry
+#[percpu::def_percpu]
+static EMERGENCY_STACK: EmergencyStack = EmergencyStack::new();
+
 core::arch::global_asm!(
     include_str!("trap.S"),
     trapframe_size = const core::mem::size_of::<TrapFrame>(),
+    kstack_region_shift = const crate::trap::KSTACK_REGION_SHIFT,
+    kstack_region_tag = const crate::trap::KSTACK_REGION_TAG,
+    emergency_stack = sym __PERCPU_EMERGENCY_STACK,
+    emergency_stack_size = const crate::trap::EMERGENCY_STACK_SIZE,
 );
 
 fn handle_breakpoint(sepc: &mut usize) {
@@ -14,10 +25,34 @@ fn handle_breakpoint(sepc: &mut usize) {
     *sepc += 2
 }
 
+fn handle_page_fault(tf: &TrapFrame, access_flags: MappingFlags, is_user: bool) {
+    let vaddr = stval::read();
+    let irqs_enabled = tf.sstatus & (1 << 5) != 0; // sstatus.SPIE
+    if crate::trap::handle_page_fault_extern(vaddr.into(), access_flags, is_user, irqs_enabled) {
+        return;
+    }
+    if is_user {
+        warn!(
+            "User Page Fault @ {:#x}, fault_vaddr={:#x} ({:?})",
+            tf.sepc, vaddr, access_flags,
+        );
+    } else {
+        panic!(
+            "Kernel Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
+            tf.sepc, vaddr, access_flags, tf,
+        );
+    }
+}
+
 #[no_mangle]
-fn riscv_trap_handler(tf: &mut TrapFrame, _from_user: bool) {
+fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
+        Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, MappingFlags::READ, from_user),
+        Trap::Exception(E::StorePageFault) => handle_page_fault(tf, MappingFlags::WRITE, from_user),
+        Trap::Exception(E::InstructionPageFault) => {
+            handle_page_fault(tf, MappingFlags::EXECUTE, from_user)
+        }
         Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
         Trap::Interrupt(_) => crate::trap::handle_irq_extern(scause.bits()),
         _ => {
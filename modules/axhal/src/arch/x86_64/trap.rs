@@ -1,4 +1,6 @@
+use page_table_entry::MappingFlags;
 use x86::{controlregs::cr2, irq::*};
+use x86_64::structures::idt::PageFaultErrorCode;
 
 use super::context::TrapFrame;
 
@@ -7,27 +9,49 @@ core::arch::global_asm!(include_str!("trap.S"));
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
+fn handle_page_fault(tf: &TrapFrame) {
+    let vaddr = unsafe { cr2() };
+    if let Some(access_flags) = err_code_to_flags(tf.error_code) {
+        let irqs_enabled = tf.rflags & (1 << 9) != 0; // RFLAGS.IF
+        if crate::trap::handle_page_fault_extern(
+            vaddr.into(),
+            access_flags,
+            tf.is_user(),
+            irqs_enabled,
+        ) {
+            return;
+        }
+    }
+    if tf.is_user() {
+        warn!(
+            "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
+            tf.rip, vaddr, tf.error_code,
+        );
+    } else {
+        panic!(
+            "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
+            tf.rip, vaddr, tf.error_code, tf,
+        );
+    }
+}
+
+fn handle_double_fault(tf: &TrapFrame) {
+    // Running on the emergency stack. The most likely cause is a kernel stack
+    // overflow: the CPU fails to push the exception frame of a page fault on
+    // the guard page, and CR2 still holds the faulting address. Other faults
+    // are not passed to the page fault handler, which may take locks.
+    let vaddr = unsafe { cr2() };
+    if (vaddr as isize) >> crate::trap::KSTACK_REGION_SHIFT == crate::trap::KSTACK_REGION_TAG {
+        crate::trap::handle_page_fault_extern(vaddr.into(), MappingFlags::WRITE, false, false);
+    }
+    panic!("#DF @ {:#x}, fault_vaddr={:#x}:\n{:#x?}", tf.rip, vaddr, tf);
+}
+
 #[no_mangle]
 fn x86_trap_handler(tf: &TrapFrame) {
     match tf.vector as u8 {
-        PAGE_FAULT_VECTOR => {
-            if tf.is_user() {
-                warn!(
-                    "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                );
-            } else {
-                panic!(
-                    "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                    tf,
-                );
-            }
-        }
+        PAGE_FAULT_VECTOR => handle_page_fault(tf),
+        DOUBLE_FAULT_VECTOR => handle_double_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
@@ -44,3 +68,17 @@ fn x86_trap_handler(tf: &TrapFrame) {
         }
     }
 }
+
+fn err_code_to_flags(err_code: u64) -> Option<MappingFlags> {
+    let code = PageFaultErrorCode::from_bits_truncate(err_code);
+    if code.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+        // reserved bits are set in the page table entry, not a normal page fault
+        None
+    } else if code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+        Some(MappingFlags::EXECUTE)
+    } else if code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+        Some(MappingFlags::WRITE)
+    } else {
+        Some(MappingFlags::READ)
+    }
+}
@@ -33,13 +33,19 @@ pub fn current_task_ptr<T>() -> *const T {
         // on x86, only one instruction is needed to read the per-CPU task pointer from `gs:[off]`.
         CURRENT_TASK_PTR.read_current_raw() as _
     }
-    #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+    #[cfg(any(
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        all(target_arch = "aarch64", feature = "paging")
+    ))]
     unsafe {
-        // on RISC-V, reading `CURRENT_TASK_PTR` requires multiple instruction, so we disable local IRQs.
+        // on RISC-V, reading `CURRENT_TASK_PTR` requires multiple instruction, so we disable local
+        // IRQs. (On ARM64 with `paging`, `SP_EL0` is the scratch register of the trap entry to
+        // detect stack overflows, so the same is done.)
         let _guard = kernel_guard::IrqSave::new();
         CURRENT_TASK_PTR.read_current_raw() as _
     }
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(all(target_arch = "aarch64", not(feature = "paging")))]
     {
         // on ARM64, we use `SP_EL0` to store the task pointer.
         use tock_registers::interfaces::Readable;
@@ -61,12 +67,16 @@ pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
     {
         CURRENT_TASK_PTR.write_current_raw(ptr as usize)
     }
-    #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+    #[cfg(any(
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        all(target_arch = "aarch64", feature = "paging")
+    ))]
     {
         let _guard = kernel_guard::IrqSave::new();
         CURRENT_TASK_PTR.write_current_raw(ptr as usize)
     }
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(all(target_arch = "aarch64", not(feature = "paging")))]
     {
         use tock_registers::interfaces::Writeable;
         aarch64_cpu::registers::SP_EL0.set(ptr as u64)
@@ -56,12 +56,37 @@ pub mod paging;
 pub mod console {
     pub use super::platform::console::*;
 
+    #[cfg(feature = "irq")]
+    static INPUT_HANDLER: spinlock::SpinNoIrq<Option<fn()>> = spinlock::SpinNoIrq::new(None);
+
     /// Write a slice of bytes to the console.
     pub fn write_bytes(bytes: &[u8]) {
         for c in bytes {
             putchar(*c);
         }
     }
+
+    /// Sets the handler called in the IRQ context when the console receives
+    /// input, which can be read by [`getchar`] then.
+    ///
+    /// Returns `false` if the console of the platform cannot raise interrupts
+    /// on input, so that the input can only be polled.
+    #[cfg(feature = "irq")]
+    pub fn set_input_handler(handler: fn()) -> bool {
+        *INPUT_HANDLER.lock() = Some(handler);
+        super::platform::console::HAS_INPUT_IRQ
+    }
+
+    /// Calls the handler set by [`set_input_handler`], from the console IRQ
+    /// handler of the platform.
+    #[cfg(feature = "irq")]
+    #[allow(dead_code)]
+    pub(crate) fn handle_input() {
+        let handler = *INPUT_HANDLER.lock();
+        if let Some(handler) = handler {
+            handler();
+        }
+    }
 }
 
 /// Miscellaneous operation, e.g. terminate the system.
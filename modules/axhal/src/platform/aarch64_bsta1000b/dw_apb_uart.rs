@@ -26,6 +26,10 @@ pub fn getchar() -> Option<u8> {
     UART.lock().getchar()
 }
 
+/// Whether the console raises interrupts on input.
+#[cfg(feature = "irq")]
+pub(crate) const HAS_INPUT_IRQ: bool = false;
+
 /// UART simply initialize
 pub fn init_early() {
     UART.lock().init();
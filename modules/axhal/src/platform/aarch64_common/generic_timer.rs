@@ -7,6 +7,10 @@ use tock_registers::interfaces::{Readable, Writeable};
 static mut CNTPCT_TO_NANOS_RATIO: Ratio = Ratio::zero();
 static mut NANOS_TO_CNTPCT_RATIO: Ratio = Ratio::zero();
 
+/// The wall time in nanoseconds when the hardware ticks were zero, read from
+/// the RTC during initialization.
+static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+
 /// Returns the current clock time in hardware ticks.
 #[inline]
 pub fn current_ticks() -> u64 {
@@ -25,6 +29,15 @@ pub fn nanos_to_ticks(nanos: u64) -> u64 {
     unsafe { NANOS_TO_CNTPCT_RATIO.mul_trunc(nanos) }
 }
 
+/// Returns the offset in nanoseconds from the monotonic clock to the wall
+/// clock (time since the Unix epoch).
+///
+/// It is always zero on platforms without an RTC.
+#[inline]
+pub fn epochoffset_nanos() -> u64 {
+    unsafe { RTC_EPOCHOFFSET_NANOS }
+}
+
 /// Set a one-shot timer.
 ///
 /// A timer interrupt will be triggered at the given deadline (in nanoseconds).
@@ -48,6 +61,22 @@ pub(crate) fn init_early() {
         CNTPCT_TO_NANOS_RATIO = Ratio::new(crate::time::NANOS_PER_SEC as u32, freq as u32);
         NANOS_TO_CNTPCT_RATIO = CNTPCT_TO_NANOS_RATIO.inverse();
     }
+
+    #[cfg(platform_family = "aarch64-qemu-virt")]
+    {
+        use crate::mem::phys_to_virt;
+        use arm_pl031::Pl031Rtc;
+        use memory_addr::PhysAddr;
+
+        const PL031_BASE: PhysAddr = PhysAddr::from(axconfig::RTC_PADDR);
+
+        let mut rtc = Pl031Rtc::new(phys_to_virt(PL031_BASE).as_mut_ptr());
+        rtc.init();
+        let epoch_nanos = rtc.get_unix_timestamp() as u64 * crate::time::NANOS_PER_SEC;
+        unsafe {
+            RTC_EPOCHOFFSET_NANOS = epoch_nanos.saturating_sub(ticks_to_nanos(current_ticks()));
+        }
+    }
 }
 
 pub(crate) fn init_percpu() {
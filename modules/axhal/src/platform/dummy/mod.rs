@@ -11,6 +11,10 @@ pub mod console {
     pub fn getchar() -> Option<u8> {
         unimplemented!()
     }
+
+    /// Whether the console raises interrupts on input.
+    #[cfg(feature = "irq")]
+    pub(crate) const HAS_INPUT_IRQ: bool = false;
 }
 
 pub mod misc {
@@ -49,6 +53,12 @@ pub mod time {
         nanos
     }
 
+    /// Returns the offset in nanoseconds from the monotonic clock to the
+    /// wall clock (time since the Unix epoch).
+    pub fn epochoffset_nanos() -> u64 {
+        0
+    }
+
     /// Set a one-shot timer.
     ///
     /// A timer interrupt will be triggered at the given deadline (in nanoseconds).
@@ -9,6 +9,10 @@ static mut BOOT_STACK: [u8; TASK_STACK_SIZE] = [0; TASK_STACK_SIZE];
 static mut BOOT_PT_SV39: [u64; 512] = [0; 512];
 
 unsafe fn init_boot_page_table() {
+    // 0x0000_0000..0x4000_0000, VRWX_GAD, 1G block (MMIO devices, e.g., the RTC)
+    BOOT_PT_SV39[0] = (0x00000 << 10) | 0xef;
+    // 0xffff_ffc0_0000_0000..0xffff_ffc0_4000_0000, VRWX_GAD, 1G block
+    BOOT_PT_SV39[0x100] = (0x00000 << 10) | 0xef;
     // 0x8000_0000..0xc000_0000, VRWX_GAD, 1G block
     BOOT_PT_SV39[2] = (0x80000 << 10) | 0xef;
     // 0xffff_ffc0_8000_0000..0xffff_ffc0_c000_0000, VRWX_GAD, 1G block
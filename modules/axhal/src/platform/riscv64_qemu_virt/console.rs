@@ -12,3 +12,7 @@ pub fn getchar() -> Option<u8> {
         c => Some(c as u8),
     }
 }
+
+/// Whether the console raises interrupts on input.
+#[cfg(feature = "irq")]
+pub(crate) const HAS_INPUT_IRQ: bool = false;
@@ -1,8 +1,16 @@
-//! TODO: PLIC
+//! Interrupt handling with the local interrupts in `scause` and the
+//! Platform-Level Interrupt Controller (PLIC).
+//!
+//! IRQ numbers in `1..MAX_IRQ_COUNT` are external interrupt sources of the
+//! PLIC (e.g., the VirtIO MMIO devices use sources 1-8), while the local
+//! interrupts (e.g., the timer) use their `scause` values as IRQ numbers.
 
-use crate::irq::IrqHandler;
+use crate::{irq::IrqHandler, mem::phys_to_virt};
 use lazy_init::LazyInit;
+use memory_addr::PhysAddr;
 use riscv::register::sie;
+use riscv_plic::Plic;
+use spinlock::SpinNoIrq;
 
 /// `Interrupt` bit in `scause`
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
@@ -20,25 +28,47 @@ pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 static TIMER_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
 
 /// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 1024;
+pub const MAX_IRQ_COUNT: usize = riscv_plic::PLIC_MAX_IRQ;
 
 /// The timer IRQ number (supervisor timer interrupt in `scause`).
 pub const TIMER_IRQ_NUM: usize = S_TIMER;
 
-macro_rules! with_cause {
-    ($cause: expr, @TIMER => $timer_op: expr, @EXT => $ext_op: expr $(,)?) => {
-        match $cause {
-            S_TIMER => $timer_op,
-            S_EXT => $ext_op,
-            _ => panic!("invalid trap cause: {:#x}", $cause),
-        }
-    };
+const PLIC_BASE: PhysAddr = PhysAddr::from(axconfig::PLIC_PADDR);
+
+/// The default priority of external interrupts, the threshold of every hart is
+/// 0 so any non-zero priority can be taken.
+const PLIC_DEFAULT_PRIORITY: u32 = 1;
+
+// Claiming and completing are done on the per-hart context registers, no lock.
+static PLIC: Plic = Plic::new(phys_to_virt(PLIC_BASE).as_mut_ptr());
+
+// Serializes the read-modify-write of the enable bits.
+static PLIC_ENABLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+/// Returns the PLIC context ID of the supervisor mode on the current hart.
+///
+/// On QEMU virt, each hart has two contexts: `2 * hart_id` for the machine
+/// mode and `2 * hart_id + 1` for the supervisor mode.
+fn this_context() -> usize {
+    crate::cpu::this_cpu_id() * 2 + 1
 }
 
 /// Enables or disables the given IRQ.
-pub fn set_enable(scause: usize, _enabled: bool) {
-    if scause == S_EXT {
-        // TODO: set enable in PLIC
+///
+/// External interrupts are masked and unmasked at the source level by their
+/// priorities (priority 0 never interrupts), so an IRQ disabled on one hart
+/// can be enabled again on another. Enabling also routes the source to the
+/// PLIC context of the current hart, and the routes are never removed.
+pub fn set_enable(irq_num: usize, enabled: bool) {
+    if irq_num > 0 && irq_num < MAX_IRQ_COUNT {
+        trace!("PLIC set enable: {} {}", irq_num, enabled);
+        let _lock = PLIC_ENABLE_LOCK.lock();
+        if enabled {
+            PLIC.set_enable(this_context(), irq_num, true);
+            PLIC.set_priority(irq_num, PLIC_DEFAULT_PRIORITY);
+        } else {
+            PLIC.set_priority(irq_num, 0);
+        }
     }
 }
 
@@ -46,17 +76,25 @@ pub fn set_enable(scause: usize, _enabled: bool) {
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
 /// the registration failed.
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    with_cause!(
-        scause,
-        @TIMER => if !TIMER_HANDLER.is_init() {
-            TIMER_HANDLER.init_by(handler);
-            true
-        } else {
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    match irq_num {
+        S_TIMER => {
+            if !TIMER_HANDLER.is_init() {
+                TIMER_HANDLER.init_by(handler);
+                true
+            } else {
+                false
+            }
+        }
+        // source 0 is reserved by the PLIC
+        _ if irq_num > 0 && irq_num < MAX_IRQ_COUNT => {
+            crate::irq::register_handler_common(irq_num, handler)
+        }
+        _ => {
+            warn!("register handler for invalid IRQ {:#x}", irq_num);
             false
-        },
-        @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
-    )
+        }
+    }
 }
 
 /// Dispatches the IRQ.
@@ -65,17 +103,25 @@ pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
 /// up in the IRQ handler table and calls the corresponding handler. If
 /// necessary, it also acknowledges the interrupt controller after handling.
 pub fn dispatch_irq(scause: usize) {
-    with_cause!(
-        scause,
-        @TIMER => {
+    match scause {
+        S_TIMER => {
             trace!("IRQ: timer");
             TIMER_HANDLER();
-        },
-        @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
-    );
+        }
+        S_EXT => {
+            if !PLIC.handle_irq(this_context(), crate::irq::dispatch_irq_common) {
+                trace!("IRQ: spurious external interrupt");
+            }
+        }
+        _ => panic!("invalid trap cause: {:#x}", scause),
+    }
 }
 
 pub(super) fn init_percpu() {
+    // allow all external interrupts with non-zero priority on this hart
+    let _lock = PLIC_ENABLE_LOCK.lock();
+    PLIC.init_context(this_context());
+
     // enable soft interrupts, timer interrupts, and external interrupts
     unsafe {
         sie::set_ssoft();
@@ -38,6 +38,7 @@ unsafe extern "C" fn rust_entry_secondary(cpu_id: usize) {
 pub fn platform_init() {
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
+    self::time::init_primary();
     self::time::init_percpu();
 }
 
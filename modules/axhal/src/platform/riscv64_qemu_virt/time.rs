@@ -1,7 +1,17 @@
+use memory_addr::PhysAddr;
 use riscv::register::time;
+use riscv_goldfish::GoldfishRtc;
+
+use crate::mem::phys_to_virt;
 
 const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::TIMER_FREQUENCY as u64;
 
+const RTC_BASE: PhysAddr = PhysAddr::from(axconfig::RTC_PADDR);
+
+/// The wall time in nanoseconds when the hardware ticks were zero, read from
+/// the RTC during initialization.
+static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+
 /// Returns the current clock time in hardware ticks.
 #[inline]
 pub fn current_ticks() -> u64 {
@@ -20,6 +30,13 @@ pub const fn nanos_to_ticks(nanos: u64) -> u64 {
     nanos / NANOS_PER_TICK
 }
 
+/// Returns the offset in nanoseconds from the monotonic clock to the wall
+/// clock (time since the Unix epoch).
+#[inline]
+pub fn epochoffset_nanos() -> u64 {
+    unsafe { RTC_EPOCHOFFSET_NANOS }
+}
+
 /// Set a one-shot timer.
 ///
 /// A timer interrupt will be triggered at the given deadline (in nanoseconds).
@@ -28,6 +45,14 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns));
 }
 
+pub(super) fn init_primary() {
+    let rtc = GoldfishRtc::new(phys_to_virt(RTC_BASE).as_mut_ptr());
+    let epoch_nanos = rtc.get_unix_timestamp_nanos();
+    unsafe {
+        RTC_EPOCHOFFSET_NANOS = epoch_nanos.saturating_sub(ticks_to_nanos(current_ticks()));
+    }
+}
+
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
     sbi_rt::set_timer(0);
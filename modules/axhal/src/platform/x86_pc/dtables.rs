@@ -1,6 +1,7 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
 use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::trap::{EmergencyStack, EMERGENCY_STACK_SIZE};
 use lazy_init::LazyInit;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
@@ -11,12 +12,24 @@ static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
 #[percpu::def_percpu]
 static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
+#[percpu::def_percpu]
+static EMERGENCY_STACK: EmergencyStack = EmergencyStack::new();
+
+fn new_tss() -> TaskStateSegment {
+    let mut tss = TaskStateSegment::new();
+    // the double fault handler runs on the emergency stack of this CPU
+    let stack_top = unsafe { EMERGENCY_STACK.current_ptr() } as u64 + EMERGENCY_STACK_SIZE as u64;
+    tss.interrupt_stack_table[IdtStruct::DOUBLE_FAULT_IST_INDEX as usize] =
+        x86_64::VirtAddr::new(stack_top);
+    tss
+}
+
 fn init_percpu() {
     unsafe {
         IDT.load();
         let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_by(TaskStateSegment::new());
+        tss.init_by(new_tss());
         gdt.init_by(GdtStruct::new(tss));
         gdt.load();
         gdt.load_tss();
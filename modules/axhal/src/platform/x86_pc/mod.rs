@@ -1,6 +1,7 @@
 mod apic;
 mod boot;
 mod dtables;
+mod rtc;
 mod uart16550;
 
 pub mod mem;
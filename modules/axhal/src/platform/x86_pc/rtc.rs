@@ -0,0 +1,110 @@
+//! Real time clock (RTC) of the CMOS, accessed by the I/O ports.
+
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+const CMOS_ADDR_PORT: u16 = 0x70;
+const CMOS_DATA_PORT: u16 = 0x71;
+
+const CMOS_SECOND_REG: u8 = 0x00;
+const CMOS_MINUTE_REG: u8 = 0x02;
+const CMOS_HOUR_REG: u8 = 0x04;
+const CMOS_DAY_REG: u8 = 0x07;
+const CMOS_MONTH_REG: u8 = 0x08;
+const CMOS_YEAR_REG: u8 = 0x09;
+const CMOS_STATUS_A_REG: u8 = 0x0a;
+const CMOS_STATUS_B_REG: u8 = 0x0b;
+
+/// Update in progress (status register A).
+const CMOS_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+/// 24-hour format (status register B).
+const CMOS_24_HOUR_FORMAT: u8 = 1 << 1;
+/// Binary mode instead of BCD (status register B).
+const CMOS_BINARY_FORMAT: u8 = 1 << 2;
+/// PM bit of the hour in the 12-hour format.
+const CMOS_12_HOUR_PM: u8 = 1 << 7;
+
+fn read_cmos(reg: u8) -> u8 {
+    let mut addr = PortWriteOnly::<u8>::new(CMOS_ADDR_PORT);
+    let mut data = Port::<u8>::new(CMOS_DATA_PORT);
+    unsafe {
+        addr.write(reg);
+        data.read()
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct RawDateTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+fn read_raw_datetime() -> RawDateTime {
+    while read_cmos(CMOS_STATUS_A_REG) & CMOS_UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    RawDateTime {
+        second: read_cmos(CMOS_SECOND_REG),
+        minute: read_cmos(CMOS_MINUTE_REG),
+        hour: read_cmos(CMOS_HOUR_REG),
+        day: read_cmos(CMOS_DAY_REG),
+        month: read_cmos(CMOS_MONTH_REG),
+        year: read_cmos(CMOS_YEAR_REG),
+    }
+}
+
+const fn bcd_to_binary(bcd: u8) -> u8 {
+    (bcd & 0x0f) + (bcd >> 4) * 10
+}
+
+/// Returns the number of days since 1970-01-01 of the given date in the
+/// proleptic Gregorian calendar.
+const fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400; // [0, 399]
+    let mp = (month + 9) % 12; // March is 0
+    let doy = (153 * mp + 2) / 5 + day - 1; // [0, 365]
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy; // [0, 146096]
+    era * 146097 + doe - 719468
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+///
+/// The CMOS clock is assumed to be in UTC, and in the 21st century.
+pub fn get_unix_timestamp() -> u64 {
+    // read twice until getting the same values, to avoid reading during an update
+    let mut dt = read_raw_datetime();
+    loop {
+        let next = read_raw_datetime();
+        if next == dt {
+            break;
+        }
+        dt = next;
+    }
+
+    let status_b = read_cmos(CMOS_STATUS_B_REG);
+    let is_pm = dt.hour & CMOS_12_HOUR_PM != 0;
+    let mut hour = dt.hour & !CMOS_12_HOUR_PM;
+    if status_b & CMOS_BINARY_FORMAT == 0 {
+        dt.second = bcd_to_binary(dt.second);
+        dt.minute = bcd_to_binary(dt.minute);
+        hour = bcd_to_binary(hour);
+        dt.day = bcd_to_binary(dt.day);
+        dt.month = bcd_to_binary(dt.month);
+        dt.year = bcd_to_binary(dt.year);
+    }
+    if status_b & CMOS_24_HOUR_FORMAT == 0 {
+        // 12 AM is 0 o'clock, 12 PM is 12 o'clock
+        hour %= 12;
+        if is_pm {
+            hour += 12;
+        }
+    }
+
+    let days = days_from_civil(2000 + dt.year as u64, dt.month as u64, dt.day as u64);
+    days * 86400 + hour as u64 * 3600 + dt.minute as u64 * 60 + dt.second as u64
+}
@@ -9,6 +9,10 @@ static mut NANOS_TO_LAPIC_TICKS_RATIO: ratio::Ratio = ratio::Ratio::zero();
 static mut INIT_TICK: u64 = 0;
 static mut CPU_FREQ_MHZ: u64 = axconfig::TIMER_FREQUENCY as u64 / 1_000_000;
 
+/// The wall time in nanoseconds when the hardware ticks were zero, read from
+/// the RTC during initialization.
+static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
+
 /// Returns the current clock time in hardware ticks.
 pub fn current_ticks() -> u64 {
     unsafe { core::arch::x86_64::_rdtsc() - INIT_TICK }
@@ -24,6 +28,12 @@ pub fn nanos_to_ticks(nanos: u64) -> u64 {
     nanos * unsafe { CPU_FREQ_MHZ } / 1_000
 }
 
+/// Returns the offset in nanoseconds from the monotonic clock to the wall
+/// clock (time since the Unix epoch).
+pub fn epochoffset_nanos() -> u64 {
+    unsafe { RTC_EPOCHOFFSET_NANOS }
+}
+
 /// Set a one-shot timer.
 ///
 /// A timer interrupt will be triggered at the given deadline (in nanoseconds).
@@ -57,6 +67,11 @@ pub(super) fn init_early() {
 }
 
 pub(super) fn init_primary() {
+    let epoch_nanos = super::rtc::get_unix_timestamp() * crate::time::NANOS_PER_SEC;
+    unsafe {
+        RTC_EPOCHOFFSET_NANOS = epoch_nanos.saturating_sub(ticks_to_nanos(current_ticks()));
+    }
+
     #[cfg(feature = "irq")]
     unsafe {
         use x2apic::lapic::{TimerDivide, TimerMode};
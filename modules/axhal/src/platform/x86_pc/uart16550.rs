@@ -100,6 +100,10 @@ pub fn getchar() -> Option<u8> {
     COM1.lock().getchar()
 }
 
+/// Whether the console raises interrupts on input.
+#[cfg(feature = "irq")]
+pub(crate) const HAS_INPUT_IRQ: bool = false;
+
 pub(super) fn init() {
     COM1.lock().init(115200);
 }
@@ -12,7 +12,7 @@ pub type TimeValue = Duration;
 pub use crate::platform::irq::TIMER_IRQ_NUM;
 #[cfg(feature = "irq")]
 pub use crate::platform::time::set_oneshot_timer;
-pub use crate::platform::time::{current_ticks, nanos_to_ticks, ticks_to_nanos};
+pub use crate::platform::time::{current_ticks, epochoffset_nanos, nanos_to_ticks, ticks_to_nanos};
 
 /// Number of milliseconds in a second.
 pub const MILLIS_PER_SEC: u64 = 1_000;
@@ -26,6 +26,9 @@ pub const NANOS_PER_MILLIS: u64 = 1_000_000;
 pub const NANOS_PER_MICROS: u64 = 1_000;
 
 /// Returns the current clock time in nanoseconds.
+///
+/// It is the time of the monotonic clock, which starts from an arbitrary
+/// point (usually the system boot) and is unaffected by the wall clock.
 pub fn current_time_nanos() -> u64 {
     ticks_to_nanos(current_ticks())
 }
@@ -35,6 +38,21 @@ pub fn current_time() -> TimeValue {
     TimeValue::from_nanos(current_time_nanos())
 }
 
+/// Returns the current wall time (time since the Unix epoch) in nanoseconds.
+///
+/// It is read from the real time clock (RTC) at boot, and then advanced by
+/// the monotonic clock. If the platform has no RTC, it is the same as
+/// [`current_time_nanos`].
+pub fn wall_time_nanos() -> u64 {
+    current_time_nanos() + epochoffset_nanos()
+}
+
+/// Returns the current wall time (time since the Unix epoch) in
+/// [`TimeValue`].
+pub fn wall_time() -> TimeValue {
+    TimeValue::from_nanos(wall_time_nanos())
+}
+
 /// Busy waiting for the given duration.
 pub fn busy_wait(dur: Duration) {
     busy_wait_until(current_time() + dur);
@@ -1,6 +1,53 @@
 //! Trap handling.
 
 use crate_interface::{call_interface, def_interface};
+use memory_addr::VirtAddr;
+
+pub use page_table_entry::MappingFlags;
+
+/// A faulting address `vaddr` is in the task stack region if
+/// `(vaddr as isize) >> KSTACK_REGION_SHIFT == KSTACK_REGION_TAG`, which is
+/// checked in the trap entries with only one scratch register.
+///
+/// If the region is not configured, the tag never matches.
+#[allow(dead_code)]
+pub(crate) const KSTACK_REGION_SHIFT: u32 = match axconfig::TASK_STACK_REGION_SIZE {
+    0 => usize::BITS - 1,
+    size => size.trailing_zeros(),
+};
+
+#[allow(dead_code)]
+pub(crate) const KSTACK_REGION_TAG: isize = match axconfig::TASK_STACK_REGION_SIZE {
+    0 => 2,
+    _ => axconfig::TASK_STACK_REGION_BASE as isize >> KSTACK_REGION_SHIFT,
+};
+
+static_assertions::const_assert!(
+    axconfig::TASK_STACK_REGION_SIZE.is_power_of_two() || axconfig::TASK_STACK_REGION_SIZE == 0
+);
+static_assertions::const_assert_eq!(
+    axconfig::TASK_STACK_REGION_BASE & axconfig::TASK_STACK_REGION_SIZE.wrapping_sub(1),
+    0
+);
+
+/// Size of the emergency stack used to handle kernel stack overflows.
+pub(crate) const EMERGENCY_STACK_SIZE: usize = 0x4000; // 16K
+
+/// The stack to switch to when a page fault occurs in the task stack region,
+/// as the faulting task stack may have overflowed and become unusable.
+///
+/// Each CPU has its own one, defined as a per-CPU variable by the trap entry
+/// of the architecture.
+#[allow(dead_code)]
+#[repr(C, align(16))]
+pub(crate) struct EmergencyStack(pub [u8; EMERGENCY_STACK_SIZE]);
+
+#[allow(dead_code)]
+impl EmergencyStack {
+    pub(crate) const fn new() -> Self {
+        Self([0; EMERGENCY_STACK_SIZE])
+    }
+}
 
 /// Trap handler interface.
 ///
@@ -13,7 +60,17 @@ use crate_interface::{call_interface, def_interface};
 pub trait TrapHandler {
     /// Handles interrupt requests for the given IRQ number.
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+
+    /// Handles page faults.
+    ///
+    /// `vaddr` is the faulting virtual address, `access_flags` is the access
+    /// type that caused the fault (one of [`MappingFlags::READ`],
+    /// [`MappingFlags::WRITE`], or [`MappingFlags::EXECUTE`]), and `is_user`
+    /// indicates whether the fault occurred in user mode.
+    ///
+    /// Returns `true` if the page fault is handled successfully (e.g., the
+    /// page is mapped), and the faulting instruction will be re-executed.
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool;
 }
 
 /// Call the external IRQ handler.
@@ -21,3 +78,29 @@ pub trait TrapHandler {
 pub(crate) fn handle_irq_extern(irq_num: usize) {
     call_interface!(TrapHandler::handle_irq, irq_num);
 }
+
+/// Call the external page fault handler.
+///
+/// The handler may sleep (e.g., to load the page of a file mapping), so IRQs
+/// are enabled during the handling if they were enabled before the trap
+/// (`irqs_enabled`). Faults in the task stack region are always handled with
+/// IRQs disabled, as they may be stack overflows handled on the emergency
+/// stack.
+#[allow(dead_code)]
+pub(crate) fn handle_page_fault_extern(
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+    irqs_enabled: bool,
+) -> bool {
+    let enable_irqs =
+        irqs_enabled && (vaddr.as_usize() as isize) >> KSTACK_REGION_SHIFT != KSTACK_REGION_TAG;
+    if enable_irqs {
+        crate::arch::enable_irqs();
+    }
+    let ret = call_interface!(TrapHandler::handle_page_fault, vaddr, access_flags, is_user);
+    if enable_irqs {
+        crate::arch::disable_irqs();
+    }
+    ret
+}
@@ -0,0 +1,334 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+use axalloc::global_allocator;
+use axerrno::{AxError, AxResult};
+use axhal::mem::{phys_to_virt, virt_to_phys, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
+
+use crate::paging_err_to_ax_err;
+
+/// A file-like object that can be mapped into memory by [`Backend::File`].
+pub trait MmapFile: Send + Sync {
+    /// Reads the file content at `offset` into `buf`, returns the number of
+    /// bytes read.
+    ///
+    /// It may read less than `buf.len()` bytes if the end of the file is
+    /// reached, the remaining part of the page will be filled with zeros.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+}
+
+/// Determines how the pages of a [`MemoryArea`] are mapped.
+#[derive(Clone)]
+pub enum Backend {
+    /// Linear mapping, the target physical address is `vaddr - pa_va_offset`.
+    ///
+    /// All pages are mapped when the area is created.
+    Linear {
+        /// `vaddr - paddr`.
+        pa_va_offset: usize,
+    },
+    /// Each page is mapped to a newly allocated (and zeroed) physical frame.
+    ///
+    /// If `populate` is `false`, the frames are allocated on the first access
+    /// (in the page fault handler), otherwise they are allocated when the
+    /// area is created.
+    Alloc {
+        /// Whether to allocate all frames eagerly.
+        populate: bool,
+    },
+    /// Private file mapping. Each page is allocated and filled with the file
+    /// content on the first access.
+    ///
+    /// Modifications are not written back to the file.
+    File {
+        /// The file to be mapped.
+        file: Arc<dyn MmapFile>,
+        /// The file offset corresponding to the start of the area.
+        offset: u64,
+    },
+}
+
+impl Backend {
+    /// Whether the physical frames are allocated (and owned) by the area.
+    const fn owns_frames(&self) -> bool {
+        !matches!(self, Self::Linear { .. })
+    }
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Linear { pa_va_offset } => f
+                .debug_struct("Linear")
+                .field("pa_va_offset", &format_args!("{:#x}", pa_va_offset))
+                .finish(),
+            Self::Alloc { populate } => {
+                f.debug_struct("Alloc").field("populate", populate).finish()
+            }
+            Self::File { offset, .. } => f
+                .debug_struct("File")
+                .field("offset", &format_args!("{:#x}", offset))
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A continuous virtual memory region with the same mapping flags and
+/// backend, also known as VMA.
+#[derive(Debug, Clone)]
+pub struct MemoryArea {
+    start: VirtAddr,
+    size: usize,
+    flags: MappingFlags,
+    backend: Backend,
+}
+
+impl MemoryArea {
+    /// Creates a new memory area. `start` and `size` must be 4K-aligned.
+    pub fn new(start: VirtAddr, size: usize, flags: MappingFlags, backend: Backend) -> Self {
+        Self {
+            start,
+            size,
+            flags,
+            backend,
+        }
+    }
+
+    /// Returns the start virtual address of the area.
+    pub const fn start(&self) -> VirtAddr {
+        self.start
+    }
+
+    /// Returns the end virtual address (exclusive) of the area.
+    pub fn end(&self) -> VirtAddr {
+        self.start + self.size
+    }
+
+    /// Returns the size of the area in bytes.
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the mapping flags of the area.
+    pub const fn flags(&self) -> MappingFlags {
+        self.flags
+    }
+
+    /// Returns the backend of the area.
+    pub const fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    /// Whether the area contains the given virtual address.
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        self.start <= vaddr && vaddr < self.end()
+    }
+
+    /// Maps the area into the page table, only the pages that need to be
+    /// mapped eagerly are mapped.
+    pub(crate) fn map(&self, pt: &mut PageTable) -> AxResult {
+        match self.backend {
+            Backend::Linear { pa_va_offset } => {
+                let paddr = PhysAddr::from(self.start.as_usize().wrapping_sub(pa_va_offset));
+                pt.map_region(self.start, paddr, self.size, self.flags, false)
+                    .map_err(paging_err_to_ax_err)
+            }
+            Backend::Alloc { populate: true } => {
+                let mut vaddr = self.start;
+                while vaddr < self.end() {
+                    let frame = alloc_frame()?;
+                    pt.map(vaddr, frame, PageSize::Size4K, self.flags)
+                        .map_err(|e| {
+                            dealloc_frame(frame);
+                            paging_err_to_ax_err(e)
+                        })?;
+                    vaddr += PAGE_SIZE_4K;
+                }
+                Ok(())
+            }
+            Backend::Alloc { populate: false } | Backend::File { .. } => Ok(()),
+        }
+    }
+
+    /// Unmaps all the mapped pages of the area, and frees the frames owned by
+    /// the area.
+    pub(crate) fn unmap(&self, pt: &mut PageTable) {
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            match pt.unmap(vaddr) {
+                Ok((frame, page_size)) => {
+                    debug_assert_eq!(page_size, PageSize::Size4K);
+                    if self.backend.owns_frames() {
+                        dealloc_frame(frame);
+                    }
+                }
+                Err(PagingError::NotMapped) => {}
+                Err(e) => warn!("failed to unmap page {:#x}: {:?}", vaddr, e),
+            }
+            vaddr += PAGE_SIZE_4K;
+        }
+    }
+
+    /// Changes the mapping flags of the area, and updates all the mapped
+    /// pages.
+    pub(crate) fn protect(&mut self, new_flags: MappingFlags, pt: &mut PageTable) {
+        self.flags = new_flags;
+        let mut vaddr = self.start;
+        while vaddr < self.end() {
+            if pt.query(vaddr).is_ok() {
+                pt.update(vaddr, None, Some(new_flags)).ok();
+            }
+            vaddr += PAGE_SIZE_4K;
+        }
+    }
+
+    /// Returns the file page that backs the page containing `vaddr`, if the
+    /// area is a file mapping.
+    pub(crate) fn file_page(&self, vaddr: VirtAddr) -> Option<FilePage> {
+        match &self.backend {
+            Backend::File { file, offset } => {
+                let vaddr = vaddr.align_down_4k();
+                Some(FilePage {
+                    vaddr,
+                    file: file.clone(),
+                    offset: offset + (vaddr.as_usize() - self.start.as_usize()) as u64,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Maps the page containing `vaddr` on demand.
+    ///
+    /// Returns `false` if the page cannot be mapped by the backend.
+    pub(crate) fn handle_page_fault(&self, vaddr: VirtAddr, pt: &mut PageTable) -> bool {
+        let vaddr = vaddr.align_down_4k();
+        let frame = match &self.backend {
+            Backend::Linear { .. } | Backend::Alloc { populate: true } => return false,
+            Backend::Alloc { populate: false } => alloc_frame(),
+            Backend::File { .. } => self.file_page(vaddr).unwrap().load(),
+        };
+        match frame {
+            Ok(frame) => self.map_page(vaddr, frame, pt),
+            Err(e) => {
+                warn!("failed to populate page {:#x}: {:?}", vaddr, e);
+                false
+            }
+        }
+    }
+
+    /// Maps the page at `vaddr` to the owned `frame`, which is freed if the
+    /// mapping fails.
+    pub(crate) fn map_page(&self, vaddr: VirtAddr, frame: PhysAddr, pt: &mut PageTable) -> bool {
+        match pt.map(vaddr, frame, PageSize::Size4K, self.flags) {
+            Ok(_) => {
+                axhal::arch::flush_tlb(Some(vaddr));
+                true
+            }
+            Err(e) => {
+                warn!("failed to map page {:#x}: {:?}", vaddr, e);
+                dealloc_frame(frame);
+                false
+            }
+        }
+    }
+
+    /// Splits the area at `pos`, the area is shrunk to `[start, pos)`, and the
+    /// remaining part `[pos, end)` is returned.
+    ///
+    /// Returns `None` if `pos` is not in the area (or is the start of it).
+    pub(crate) fn split(&mut self, pos: VirtAddr) -> Option<Self> {
+        if pos <= self.start || pos >= self.end() {
+            return None;
+        }
+        debug_assert!(pos.is_aligned_4k());
+        let left_size = pos.as_usize() - self.start.as_usize();
+        let backend = match &self.backend {
+            Backend::File { file, offset } => Backend::File {
+                file: file.clone(),
+                offset: offset + left_size as u64,
+            },
+            backend => backend.clone(),
+        };
+        let right = Self::new(pos, self.size - left_size, self.flags, backend);
+        self.size = left_size;
+        Some(right)
+    }
+
+    /// Tries to append the `next` area which starts at the end of this area.
+    ///
+    /// Returns `false` if the two areas have different flags or incompatible
+    /// backends.
+    pub(crate) fn try_merge(&mut self, next: &Self) -> bool {
+        if self.end() != next.start || self.flags.bits() != next.flags.bits() {
+            return false;
+        }
+        let mergeable = match (&self.backend, &next.backend) {
+            (Backend::Linear { pa_va_offset: a }, Backend::Linear { pa_va_offset: b }) => a == b,
+            (Backend::Alloc { populate: a }, Backend::Alloc { populate: b }) => a == b,
+            (
+                Backend::File {
+                    file: f1,
+                    offset: o1,
+                },
+                Backend::File {
+                    file: f2,
+                    offset: o2,
+                },
+            ) => Arc::ptr_eq(f1, f2) && o1 + self.size as u64 == *o2,
+            _ => false,
+        };
+        if mergeable {
+            self.size += next.size;
+        }
+        mergeable
+    }
+}
+
+/// A page of a file mapping, which can be loaded without accessing the
+/// address space.
+pub(crate) struct FilePage {
+    /// The page-aligned virtual address of the page.
+    pub vaddr: VirtAddr,
+    file: Arc<dyn MmapFile>,
+    offset: u64,
+}
+
+impl FilePage {
+    /// Allocates a frame and fills it with the file content.
+    ///
+    /// It may sleep on reading the file.
+    pub fn load(&self) -> AxResult<PhysAddr> {
+        let frame = alloc_frame()?;
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K)
+        };
+        match self.file.read_at(self.offset, buf) {
+            Ok(_) => Ok(frame),
+            Err(e) => {
+                dealloc_frame(frame);
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether the two pages are the same page of the same file.
+    pub fn same_as(&self, other: &Self) -> bool {
+        self.vaddr == other.vaddr
+            && Arc::ptr_eq(&self.file, &other.file)
+            && self.offset == other.offset
+    }
+}
+
+pub(crate) fn alloc_frame() -> AxResult<PhysAddr> {
+    let vaddr = global_allocator()
+        .alloc_pages(1, PAGE_SIZE_4K)
+        .map_err(|_| AxError::NoMemory)?;
+    unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, PAGE_SIZE_4K) };
+    Ok(virt_to_phys(vaddr.into()))
+}
+
+pub(crate) fn dealloc_frame(frame: PhysAddr) {
+    global_allocator().dealloc_pages(phys_to_virt(frame).as_usize(), 1);
+}
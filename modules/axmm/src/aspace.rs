@@ -0,0 +1,550 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use axerrno::{ax_err, AxResult};
+use axhal::mem::{phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageTable};
+
+use crate::area::{alloc_frame, dealloc_frame, Backend, FilePage, MemoryArea, MmapFile};
+use crate::paging_err_to_ax_err;
+
+/// A virtual address space, consisting of non-overlapping [`MemoryArea`]s and
+/// the page table that maps them.
+pub struct AddrSpace {
+    base: VirtAddr,
+    end: VirtAddr,
+    areas: BTreeMap<VirtAddr, MemoryArea>,
+    pt: PageTable,
+}
+
+impl AddrSpace {
+    /// Creates a new empty address space that covers `[base, base + size)`.
+    pub fn new_empty(base: VirtAddr, size: usize) -> AxResult<Self> {
+        Ok(Self {
+            base,
+            end: base + size,
+            areas: BTreeMap::new(),
+            pt: PageTable::try_new().map_err(paging_err_to_ax_err)?,
+        })
+    }
+
+    /// Returns the base address of the address space.
+    pub const fn base(&self) -> VirtAddr {
+        self.base
+    }
+
+    /// Returns the end address (exclusive) of the address space.
+    pub const fn end(&self) -> VirtAddr {
+        self.end
+    }
+
+    /// Returns the size of the address space in bytes.
+    pub fn size(&self) -> usize {
+        self.end.as_usize() - self.base.as_usize()
+    }
+
+    /// Returns the reference to the inner page table.
+    pub const fn page_table(&self) -> &PageTable {
+        &self.pt
+    }
+
+    /// Returns the root physical address of the inner page table.
+    pub const fn page_table_root(&self) -> PhysAddr {
+        self.pt.root_paddr()
+    }
+
+    /// Returns an iterator over all the memory areas, in address order.
+    pub fn areas(&self) -> impl Iterator<Item = &MemoryArea> {
+        self.areas.values()
+    }
+
+    /// Returns the memory area that contains `vaddr`, if any.
+    pub fn find_area(&self, vaddr: VirtAddr) -> Option<&MemoryArea> {
+        find_area(&self.areas, vaddr)
+    }
+
+    /// Whether the address range `[start, start + size)` is contained in the
+    /// address space.
+    pub fn contains_range(&self, start: VirtAddr, size: usize) -> bool {
+        self.base <= start
+            && start.as_usize() <= self.end.as_usize()
+            && size <= self.end.as_usize() - start.as_usize()
+    }
+
+    /// Finds a free (unmapped) region of `size` bytes in the address space,
+    /// starting from `hint` (or the base of the address space if `hint` is
+    /// below it).
+    ///
+    /// Returns the start address of the region, which is 4K-aligned.
+    pub fn find_free_area(&self, hint: VirtAddr, size: usize) -> Option<VirtAddr> {
+        let mut start = core::cmp::max(hint, self.base).align_up_4k();
+        let size = memory_addr::align_up_4k(size);
+        for area in self.areas.values() {
+            if area.end() <= start {
+                continue;
+            }
+            if area.start().as_usize() >= start.as_usize().checked_add(size)? {
+                break;
+            }
+            start = area.end();
+        }
+        self.contains_range(start, size).then_some(start)
+    }
+
+    /// Adds a new linear mapping from `[start_vaddr, start_vaddr + size)` to
+    /// `[start_paddr, start_paddr + size)` with the given `flags`.
+    ///
+    /// The addresses and `size` must be 4K-aligned.
+    pub fn map_linear(
+        &mut self,
+        start_vaddr: VirtAddr,
+        start_paddr: PhysAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        if !start_paddr.is_aligned_4k() {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        let pa_va_offset = start_vaddr.as_usize().wrapping_sub(start_paddr.as_usize());
+        let area = MemoryArea::new(start_vaddr, size, flags, Backend::Linear { pa_va_offset });
+        self.map_area(area)
+    }
+
+    /// Adds a new mapping of `[start, start + size)` backed by newly allocated
+    /// physical frames.
+    ///
+    /// If `populate` is `false`, the frames are allocated on page faults.
+    pub fn map_alloc(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        populate: bool,
+    ) -> AxResult {
+        self.map_area(MemoryArea::new(
+            start,
+            size,
+            flags,
+            Backend::Alloc { populate },
+        ))
+    }
+
+    /// Adds a new private mapping of `[start, start + size)` backed by `file`,
+    /// starting from the file offset `offset`.
+    ///
+    /// The file content is loaded on page faults.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn MmapFile>,
+        offset: u64,
+    ) -> AxResult {
+        if !memory_addr::is_aligned_4k(offset as usize) {
+            return ax_err!(InvalidInput, "file offset not aligned");
+        }
+        self.map_area(MemoryArea::new(
+            start,
+            size,
+            flags,
+            Backend::File { file, offset },
+        ))
+    }
+
+    /// Copies the content of the mapped page at `src` to the page at `dst`.
+    ///
+    /// If the page at `dst` is not mapped yet, it is mapped to a newly
+    /// allocated frame, so it is no longer populated by the backend of its
+    /// area.
+    pub fn copy_page(&mut self, src: VirtAddr, dst: VirtAddr) -> AxResult {
+        let (src_frame, _, _) = self.pt.query(src).map_err(paging_err_to_ax_err)?;
+        let dst = dst.align_down_4k();
+        let dst_frame = match self.pt.query(dst) {
+            Ok((frame, _, _)) => frame,
+            Err(_) => {
+                let area = match find_area(&self.areas, dst) {
+                    Some(area) if !matches!(area.backend(), Backend::Linear { .. }) => area,
+                    _ => return ax_err!(InvalidInput, "not a lazily mapped page"),
+                };
+                let frame = alloc_frame()?;
+                if !area.map_page(dst, frame, &mut self.pt) {
+                    return ax_err!(NoMemory);
+                }
+                frame
+            }
+        };
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(src_frame.align_down_4k()).as_ptr(),
+                phys_to_virt(dst_frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        Ok(())
+    }
+
+    /// Removes all the mappings in `[start, start + size)`.
+    ///
+    /// The areas that partially overlap with the range are split, and the
+    /// parts out of the range are kept.
+    pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        self.validate_range(start, size)?;
+        for area in self.take_range(start, start + size) {
+            area.unmap(&mut self.pt);
+        }
+        axhal::arch::flush_tlb(None);
+        Ok(())
+    }
+
+    /// Changes the mapping flags of all the areas in `[start, start + size)`.
+    ///
+    /// The areas that partially overlap with the range are split, and the
+    /// adjacent areas are merged if they become compatible.
+    pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
+        self.validate_range(start, size)?;
+        for mut area in self.take_range(start, start + size) {
+            area.protect(flags, &mut self.pt);
+            self.insert_area(area);
+        }
+        axhal::arch::flush_tlb(None);
+        Ok(())
+    }
+
+    /// Removes all the mappings in the address space.
+    pub fn clear(&mut self) {
+        for (_, area) in core::mem::take(&mut self.areas) {
+            area.unmap(&mut self.pt);
+        }
+        axhal::arch::flush_tlb(None);
+    }
+
+    /// Handles a page fault at `vaddr` with the access type `access_flags`.
+    ///
+    /// Returns `true` if the page fault is resolved, i.e., the faulting page
+    /// belongs to a lazily mapped area that permits the access, and it is
+    /// mapped successfully.
+    ///
+    /// The file of a file mapping is read while `self` is borrowed, see
+    /// [`crate::handle_page_fault`] for loading it without holding the lock.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+        match find_area(&self.areas, vaddr) {
+            Some(area) if area.flags().contains(access_flags) => {
+                area.handle_page_fault(vaddr, &mut self.pt)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the file page to be loaded for the page fault at `vaddr`, if
+    /// it is in a file mapping that permits the access.
+    pub(crate) fn fault_file_page(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> Option<FilePage> {
+        self.find_area(vaddr)
+            .filter(|area| area.flags().contains(access_flags))
+            .and_then(|area| area.file_page(vaddr))
+    }
+
+    /// Maps a file page loaded into `frame` by [`FilePage::load`].
+    ///
+    /// If the page has been mapped, or the area has been changed since the
+    /// page was looked up, the frame is freed and the faulting access is
+    /// expected to be retried. Returns `false` only if the mapping fails.
+    pub(crate) fn map_file_page(&mut self, page: &FilePage, frame: PhysAddr) -> bool {
+        let area = match find_area(&self.areas, page.vaddr) {
+            Some(area) if area.file_page(page.vaddr).is_some_and(|p| p.same_as(page)) => area,
+            _ => {
+                dealloc_frame(frame);
+                return true;
+            }
+        };
+        if self.pt.query(page.vaddr).is_ok() {
+            dealloc_frame(frame);
+            return true;
+        }
+        area.map_page(page.vaddr, frame, &mut self.pt)
+    }
+}
+
+// Private implements.
+impl AddrSpace {
+    fn validate_range(&self, start: VirtAddr, size: usize) -> AxResult {
+        if !start.is_aligned_4k() || !memory_addr::is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        Ok(())
+    }
+
+    fn map_area(&mut self, area: MemoryArea) -> AxResult {
+        self.validate_range(area.start(), area.size())?;
+        if area.size() == 0 {
+            return Ok(());
+        }
+        if self.overlaps(area.start(), area.end()) {
+            return ax_err!(AlreadyExists, "overlaps with an existing area");
+        }
+        debug!(
+            "map area [{:#x}, {:#x}) {:?} {:?}",
+            area.start(),
+            area.end(),
+            area.flags(),
+            area.backend()
+        );
+        if let Err(e) = area.map(&mut self.pt) {
+            area.unmap(&mut self.pt);
+            return Err(e);
+        }
+        self.insert_area(area);
+        Ok(())
+    }
+
+    fn overlaps(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        self.areas
+            .range(..end)
+            .next_back()
+            .is_some_and(|(_, area)| area.end() > start)
+    }
+
+    fn insert_area(&mut self, area: MemoryArea) {
+        insert_area(&mut self.areas, area)
+    }
+
+    fn take_range(&mut self, start: VirtAddr, end: VirtAddr) -> Vec<MemoryArea> {
+        take_range(&mut self.areas, start, end)
+    }
+}
+
+fn find_area(areas: &BTreeMap<VirtAddr, MemoryArea>, vaddr: VirtAddr) -> Option<&MemoryArea> {
+    areas
+        .range(..=vaddr)
+        .next_back()
+        .map(|(_, area)| area)
+        .filter(|area| area.contains(vaddr))
+}
+
+/// Inserts an area that does not overlap with the existing areas, and merges
+/// it with the adjacent areas if possible.
+fn insert_area(areas: &mut BTreeMap<VirtAddr, MemoryArea>, mut area: MemoryArea) {
+    let end = area.end();
+    if let Some(next) = areas.get(&end) {
+        if area.try_merge(next) {
+            areas.remove(&end);
+        }
+    }
+    if let Some((_, prev)) = areas.range_mut(..area.start()).next_back() {
+        if prev.try_merge(&area) {
+            return;
+        }
+    }
+    areas.insert(area.start(), area);
+}
+
+/// Removes the parts of the areas in `[start, end)` and returns them, the
+/// areas crossing the boundaries are split.
+fn take_range(
+    areas: &mut BTreeMap<VirtAddr, MemoryArea>,
+    start: VirtAddr,
+    end: VirtAddr,
+) -> Vec<MemoryArea> {
+    let keys: Vec<_> = areas
+        .range(..end)
+        .rev()
+        .take_while(|(_, area)| area.end() > start)
+        .map(|(&key, _)| key)
+        .collect();
+    let mut taken = Vec::with_capacity(keys.len());
+    for key in keys.into_iter().rev() {
+        let mut area = areas.remove(&key).unwrap();
+        if let Some(right) = area.split(start) {
+            areas.insert(area.start(), area);
+            area = right;
+        }
+        if let Some(right) = area.split(end) {
+            areas.insert(right.start(), right);
+        }
+        taken.push(area);
+    }
+    taken
+}
+
+impl fmt::Debug for AddrSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AddrSpace")
+            .field("va_range", &(self.base.as_usize()..self.end.as_usize()))
+            .field("page_table_root", &self.pt.root_paddr())
+            .field("areas", &self.areas)
+            .finish()
+    }
+}
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyFile;
+
+    impl MmapFile for DummyFile {
+        fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> AxResult<usize> {
+            Ok(0)
+        }
+    }
+
+    const RW: MappingFlags = MappingFlags::READ.union(MappingFlags::WRITE);
+    const R: MappingFlags = MappingFlags::READ;
+
+    fn va(addr: usize) -> VirtAddr {
+        VirtAddr::from(addr)
+    }
+
+    fn alloc_area(start: usize, size: usize, flags: MappingFlags) -> MemoryArea {
+        MemoryArea::new(va(start), size, flags, Backend::Alloc { populate: false })
+    }
+
+    fn ranges<'a>(areas: impl IntoIterator<Item = &'a MemoryArea>) -> Vec<(usize, usize)> {
+        areas
+            .into_iter()
+            .map(|a| (a.start().as_usize(), a.end().as_usize()))
+            .collect()
+    }
+
+    fn file_offset(area: &MemoryArea) -> u64 {
+        match area.backend() {
+            Backend::File { offset, .. } => *offset,
+            _ => panic!("not a file mapping"),
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let mut area = alloc_area(0x1000, 0x4000, RW);
+        assert!(area.split(va(0x1000)).is_none());
+        assert!(area.split(va(0x5000)).is_none());
+        assert!(area.split(va(0x8000)).is_none());
+
+        let right = area.split(va(0x3000)).unwrap();
+        assert_eq!((area.start(), area.size()), (va(0x1000), 0x2000));
+        assert_eq!((right.start(), right.size()), (va(0x3000), 0x2000));
+        assert_eq!(right.flags(), RW);
+
+        // the file offset of the right part is advanced
+        let file: Arc<dyn MmapFile> = Arc::new(DummyFile);
+        let backend = Backend::File {
+            file,
+            offset: 0x10000,
+        };
+        let mut area = MemoryArea::new(va(0x1000), 0x4000, R, backend);
+        let right = area.split(va(0x2000)).unwrap();
+        assert_eq!(file_offset(&area), 0x10000);
+        assert_eq!(file_offset(&right), 0x11000);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut area = alloc_area(0x1000, 0x1000, RW);
+        // not adjacent
+        assert!(!area.try_merge(&alloc_area(0x3000, 0x1000, RW)));
+        // different flags
+        assert!(!area.try_merge(&alloc_area(0x2000, 0x1000, R)));
+        // different backends
+        let linear = MemoryArea::new(va(0x2000), 0x1000, RW, Backend::Linear { pa_va_offset: 0 });
+        assert!(!area.try_merge(&linear));
+        assert!(area.try_merge(&alloc_area(0x2000, 0x1000, RW)));
+        assert_eq!((area.start(), area.size()), (va(0x1000), 0x2000));
+
+        // the linear mappings must have the same offset
+        let mut a = MemoryArea::new(va(0x1000), 0x1000, RW, Backend::Linear { pa_va_offset: 0 });
+        let b = MemoryArea::new(va(0x2000), 0x1000, RW, Backend::Linear { pa_va_offset: 1 });
+        assert!(!a.try_merge(&b));
+
+        // the file mappings must be of the same file with continuous offsets
+        let file: Arc<dyn MmapFile> = Arc::new(DummyFile);
+        let other: Arc<dyn MmapFile> = Arc::new(DummyFile);
+        let file_area = |start, file: &Arc<dyn MmapFile>, offset| {
+            let backend = Backend::File {
+                file: file.clone(),
+                offset,
+            };
+            MemoryArea::new(va(start), 0x1000, R, backend)
+        };
+        let mut a = file_area(0x1000, &file, 0);
+        assert!(!a.try_merge(&file_area(0x2000, &other, 0x1000)));
+        assert!(!a.try_merge(&file_area(0x2000, &file, 0x2000)));
+        assert!(a.try_merge(&file_area(0x2000, &file, 0x1000)));
+        assert_eq!(a.size(), 0x2000);
+    }
+
+    #[test]
+    fn test_insert_merges_neighbors() {
+        let mut areas = BTreeMap::new();
+        insert_area(&mut areas, alloc_area(0x1000, 0x1000, RW));
+        insert_area(&mut areas, alloc_area(0x3000, 0x1000, RW));
+        insert_area(&mut areas, alloc_area(0x5000, 0x1000, R));
+        assert_eq!(
+            ranges(areas.values()),
+            [(0x1000, 0x2000), (0x3000, 0x4000), (0x5000, 0x6000)]
+        );
+        // fills the hole, merged with both sides
+        insert_area(&mut areas, alloc_area(0x2000, 0x1000, RW));
+        assert_eq!(ranges(areas.values()), [(0x1000, 0x4000), (0x5000, 0x6000)]);
+        // different flags, not merged
+        insert_area(&mut areas, alloc_area(0x4000, 0x1000, RW));
+        assert_eq!(ranges(areas.values()), [(0x1000, 0x5000), (0x5000, 0x6000)]);
+    }
+
+    #[test]
+    fn test_take_range() {
+        let mut areas = BTreeMap::new();
+        insert_area(&mut areas, alloc_area(0x1000, 0x3000, RW));
+        insert_area(&mut areas, alloc_area(0x4000, 0x2000, R));
+        insert_area(&mut areas, alloc_area(0x8000, 0x2000, RW));
+
+        // nothing in the range
+        assert!(take_range(&mut areas, va(0x6000), va(0x8000)).is_empty());
+        assert_eq!(areas.len(), 3);
+
+        // crosses the boundary of two areas, both of them are split
+        let taken = take_range(&mut areas, va(0x3000), va(0x5000));
+        assert_eq!(ranges(&taken), [(0x3000, 0x4000), (0x4000, 0x5000)]);
+        assert_eq!(
+            ranges(areas.values()),
+            [(0x1000, 0x3000), (0x5000, 0x6000), (0x8000, 0xa000)]
+        );
+
+        // in the middle of an area
+        let taken = take_range(&mut areas, va(0x1000), va(0x2000));
+        assert_eq!(taken.len(), 1);
+        assert_eq!((taken[0].start(), taken[0].size()), (va(0x1000), 0x1000));
+        assert_eq!(
+            ranges(areas.values()),
+            [(0x2000, 0x3000), (0x5000, 0x6000), (0x8000, 0xa000)]
+        );
+
+        // covers several whole areas
+        let taken = take_range(&mut areas, va(0), va(0x10000));
+        assert_eq!(taken.len(), 3);
+        assert!(areas.is_empty());
+
+        // the taken parts can be inserted back and merged again
+        for area in taken {
+            insert_area(&mut areas, area);
+        }
+        assert_eq!(
+            ranges(areas.values()),
+            [(0x2000, 0x3000), (0x5000, 0x6000), (0x8000, 0xa000)]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! [ArceOS](https://github.com/rcore-os/arceos) virtual memory management
+//! module.
+//!
+//! It manages virtual address spaces ([`AddrSpace`]) on top of the
+//! architecture-specific page tables provided by [`axhal::paging`]. An address
+//! space consists of non-overlapping [`MemoryArea`]s, each of them has its own
+//! mapping flags and [`Backend`]:
+//!
+//! - [`Backend::Linear`]: maps to a contiguous physical memory region.
+//! - [`Backend::Alloc`]: maps to newly allocated frames, eagerly or lazily.
+//! - [`Backend::File`]: private file mapping, loaded lazily.
+//!
+//! The areas are split when part of them is unmapped or protected, and the
+//! adjacent compatible areas are merged.
+//!
+//! The kernel address space is created by [`init_memory_management`], which
+//! linearly maps all the physical memory regions.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod area;
+mod aspace;
+
+pub use self::area::{Backend, MemoryArea, MmapFile};
+pub use self::aspace::AddrSpace;
+
+use axerrno::{AxError, AxResult};
+use axhal::mem::{memory_regions, phys_to_virt, VirtAddr};
+use axhal::paging::{MappingFlags, PagingError};
+use lazy_init::LazyInit;
+use spinlock::SpinNoIrq;
+
+static KERNEL_ASPACE: LazyInit<SpinNoIrq<AddrSpace>> = LazyInit::new();
+
+const fn paging_err_to_ax_err(err: PagingError) -> AxError {
+    match err {
+        PagingError::NoMemory => AxError::NoMemory,
+        PagingError::AlreadyMapped => AxError::AlreadyExists,
+        PagingError::NotMapped => AxError::NotFound,
+        PagingError::NotAligned | PagingError::MappedToHugePage => AxError::InvalidInput,
+    }
+}
+
+/// Creates a new address space for the kernel, where all the physical memory
+/// regions are linearly mapped.
+pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
+    let mut aspace = AddrSpace::new_empty(
+        VirtAddr::from(axconfig::KERNEL_ASPACE_BASE),
+        axconfig::KERNEL_ASPACE_SIZE,
+    )?;
+    for r in memory_regions() {
+        aspace.map_linear(phys_to_virt(r.paddr), r.paddr, r.size, r.flags.into())?;
+    }
+    Ok(aspace)
+}
+
+/// Returns the globally unique kernel address space.
+pub fn kernel_aspace() -> &'static SpinNoIrq<AddrSpace> {
+    &KERNEL_ASPACE
+}
+
+/// Initializes the kernel address space and switches to its page table.
+///
+/// It must be called on the primary CPU after the global allocator is
+/// initialized.
+pub fn init_memory_management() {
+    info!("Initialize virtual memory management...");
+
+    let aspace = new_kernel_aspace().expect("failed to initialize kernel address space");
+    debug!("kernel address space init OK: {:#x?}", aspace);
+    KERNEL_ASPACE.init_by(SpinNoIrq::new(aspace));
+    unsafe { axhal::arch::write_page_table_root(kernel_aspace().lock().page_table_root()) };
+}
+
+/// Switches to the kernel page table on secondary CPUs.
+pub fn init_memory_management_secondary() {
+    unsafe { axhal::arch::write_page_table_root(kernel_aspace().lock().page_table_root()) };
+}
+
+/// Handles a page fault in the kernel address space.
+///
+/// Returns `true` if the page fault is resolved, i.e., the faulting address
+/// is in a lazily mapped area of the kernel address space that permits the
+/// access.
+///
+/// The pages of file mappings are read without holding the lock of the
+/// kernel address space, as reading the file may sleep. The trap handler of
+/// [`axhal`] enables IRQs for it if they were enabled before the fault.
+pub fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    let Some(aspace) = KERNEL_ASPACE.try_get() else {
+        return false;
+    };
+    let mut guard = aspace.lock();
+    let Some(page) = guard.fault_file_page(vaddr, access_flags) else {
+        return guard.handle_page_fault(vaddr, access_flags);
+    };
+    drop(guard);
+    match page.load() {
+        Ok(frame) => aspace.lock().map_file_page(&page, frame),
+        Err(e) => {
+            warn!("failed to load file page {:#x}: {:?}", page.vaddr, e);
+            false
+        }
+    }
+}
@@ -8,12 +8,45 @@
 //!
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
+//! - [`IcmpSocket`]: An ICMP echo ("ping") socket that provides POSIX-like APIs.
+//! - [`RawSocket`]: A raw IP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
 //!
+//! # Interfaces
+//!
+//! A loopback interface `lo` (`127.0.0.1/8` and `::1`) is always created, so
+//! that sockets on the local host work even without a NIC. Each NIC is brought
+//! up as its own interface, `eth0`, `eth1`, ..., with the addresses given by the
+//! `n`-th entries of the comma-separated `AX_IP`, `AX_GW`, `AX_IP6` and
+//! `AX_GW6` lists (or by DHCP and SLAAC).
+//!
+//! The interface to send packets through is picked by a route table, which
+//! holds the subnets of the interface addresses, the default routes of the
+//! interfaces with a gateway, and the loopback ranges of `lo`: the most
+//! specific route wins. A socket bound to a specific local address always
+//! sends through the interface that owns it.
+//!
+//! IPv4 packets larger than the MTU of a NIC (1500 bytes unless set by the
+//! `AX_MTU` list) are fragmented, and the incoming fragments are reassembled,
+//! so that UDP datagrams up to 64 KiB can be sent and received.
+//!
+//! Only the first NIC is used unless the `dyn` feature of `axdriver` is
+//! enabled, as it is the only way to probe multiple devices of a kind.
+//!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `irq`, `multitask`: If both are enabled and all the NICs have IRQs, the
+//!   network stack becomes interrupt-driven: the NIC interrupts wake up the
+//!   tasks blocked on the affected sockets, instead of letting them poll the
+//!   NIC and yield repeatedly.
+//! - `dhcp`: Configure the address, gateway and DNS servers of the NICs by
+//!   DHCPv4 at boot, and renew the leases in the background. The static
+//!   configuration is used if no lease is acquired.
+//! - `pcap`: Capture the frames received and transmitted by the NICs into a
+//!   ring buffer, which can be dumped as a pcap file by [`pcap_dump`]. The
+//!   capture is off at boot, and switched by [`pcap_set_enabled`].
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -36,14 +69,25 @@ pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{IcmpSocket, RawSocket};
+
+#[cfg(feature = "pcap")]
+pub use self::net_impl::{pcap_clear, pcap_dump, pcap_is_enabled, pcap_set_enabled};
 
+use alloc::vec::Vec;
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes the network subsystem by NIC devices.
 pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>) {
     info!("Initialize network subsystem...");
 
-    let dev = net_devs.take_one().expect("No NIC device found!");
-    info!("  use NIC 0: {:?}", dev.device_name());
-    net_impl::init(dev);
+    let mut devs = Vec::new();
+    while let Some(dev) = net_devs.take_one() {
+        info!("  use NIC {}: {:?}", devs.len(), dev.device_name());
+        devs.push(dev);
+    }
+    if devs.is_empty() {
+        warn!("  no NIC device found, only the loopback interface is available");
+    }
+    net_impl::init(devs);
 }
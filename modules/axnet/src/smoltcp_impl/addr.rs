@@ -1,17 +1,32 @@
-use core::net::{IpAddr, SocketAddr};
-use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 pub const fn from_core_ipaddr(ip: IpAddr) -> IpAddress {
     match ip {
         IpAddr::V4(ipv4) => IpAddress::Ipv4(Ipv4Address(ipv4.octets())),
-        _ => panic!("IPv6 not supported"),
+        IpAddr::V6(ipv6) => IpAddress::Ipv6(Ipv6Address(ipv6.octets())),
     }
 }
 
 pub const fn into_core_ipaddr(ip: IpAddress) -> IpAddr {
     match ip {
-        IpAddress::Ipv4(ipv4) => IpAddr::V4(unsafe { core::mem::transmute(ipv4.0) }),
-        // _ => panic!("IPv6 not supported"),
+        IpAddress::Ipv4(ipv4) => {
+            let [a, b, c, d] = ipv4.0;
+            IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+        }
+        IpAddress::Ipv6(ipv6) => {
+            let o = ipv6.0;
+            IpAddr::V6(Ipv6Addr::new(
+                u16::from_be_bytes([o[0], o[1]]),
+                u16::from_be_bytes([o[2], o[3]]),
+                u16::from_be_bytes([o[4], o[5]]),
+                u16::from_be_bytes([o[6], o[7]]),
+                u16::from_be_bytes([o[8], o[9]]),
+                u16::from_be_bytes([o[10], o[11]]),
+                u16::from_be_bytes([o[12], o[13]]),
+                u16::from_be_bytes([o[14], o[15]]),
+            ))
+        }
     }
 }
 
@@ -27,7 +42,7 @@ pub const fn into_core_sockaddr(addr: IpEndpoint) -> SocketAddr {
 }
 
 pub fn is_unspecified(ip: IpAddress) -> bool {
-    ip.as_bytes() == [0, 0, 0, 0]
+    ip.is_unspecified()
 }
 
 pub const UNSPECIFIED_IP: IpAddress = IpAddress::v4(0, 0, 0, 0);
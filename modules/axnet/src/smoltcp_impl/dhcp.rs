@@ -0,0 +1,186 @@
+//! DHCPv4 client, see [RFC 2131].
+//!
+//! Each NIC interface runs its own client: a smoltcp DHCP socket in the socket
+//! set of the interface, which renews the lease as long as the interface is
+//! polled. The configuration it reports is applied to the interface right
+//! after each poll.
+//!
+//! The static configuration of the interface is used until a lease is
+//! acquired, and restored when the lease is lost.
+//!
+//! [RFC 2131]: https://datatracker.ietf.org/doc/html/rfc2131
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use lazy_init::LazyInit;
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::socket::dhcpv4::{self, Event};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address};
+
+use super::{iface_sockets, poll_interfaces, route, set_dns_servers, InterfaceWrapper};
+
+/// How long to wait for a lease at boot, before going on with the static
+/// configuration.
+const BOOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct DhcpClient {
+    /// The index of the interface.
+    iface: usize,
+    /// The DHCP socket in the socket set of the interface.
+    handle: SocketHandle,
+    /// Whether a lease is in use.
+    leased: AtomicBool,
+    /// The static address and gateway, restored when the lease is lost.
+    fallback: (Option<IpCidr>, Option<Ipv4Address>),
+}
+
+static CLIENTS: LazyInit<Vec<DhcpClient>> = LazyInit::new();
+
+/// Replaces the IPv4 address and the default IPv4 route of the interface with
+/// index `index`, in both the interface and the [`route`] table.
+fn set_ipv4_config(
+    iface: &mut Interface,
+    index: usize,
+    cidr: Option<IpCidr>,
+    router: Option<Ipv4Address>,
+) {
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs.retain(|addr| !matches!(addr, IpCidr::Ipv4(_)));
+        if let Some(cidr) = cidr {
+            ip_addrs.push(cidr).unwrap();
+        }
+    });
+    let routes = iface.routes_mut();
+    routes.remove_default_ipv4_route();
+    if let Some(router) = router {
+        routes.add_default_ipv4_route(router).unwrap();
+    }
+
+    route::remove_if(index, |dst| matches!(dst, IpCidr::Ipv4(_)));
+    if let Some(cidr) = cidr {
+        route::add(cidr, index);
+    }
+    if router.is_some() {
+        route::add(IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0), index);
+    }
+}
+
+/// Applies the configuration changes reported by the DHCP socket in the
+/// socket set of the interface with index `index` to the interface.
+pub fn apply_lease(iface: &mut Interface, index: usize, sockets: &mut SocketSet) {
+    let Some(client) = CLIENTS
+        .try_get()
+        .and_then(|clients| clients.iter().find(|client| client.iface == index))
+    else {
+        return;
+    };
+    match sockets.get_mut::<dhcpv4::Socket>(client.handle).poll() {
+        Some(Event::Configured(config)) => {
+            let cidr = IpCidr::Ipv4(config.address);
+            set_ipv4_config(iface, index, Some(cidr), config.router);
+            info!(
+                "DHCP: leased address {} on {}",
+                config.address,
+                super::IFACES[index].name()
+            );
+            if let Some(router) = config.router {
+                info!("DHCP: default gateway {}", router);
+            }
+            let dns_servers: Vec<IpAddress> =
+                config.dns_servers.iter().map(|&ip| ip.into()).collect();
+            if !dns_servers.is_empty() {
+                info!("DHCP: DNS servers {:?}", dns_servers);
+                set_dns_servers(&dns_servers);
+            }
+            client.leased.store(true, Ordering::Release);
+        }
+        Some(Event::Deconfigured) => {
+            let (cidr, gateway) = client.fallback;
+            set_ipv4_config(iface, index, cidr, gateway);
+            set_dns_servers(&[]);
+            client.leased.store(false, Ordering::Release);
+            match cidr {
+                Some(cidr) => warn!("DHCP: lease lost, fall back to {}", cidr),
+                None => warn!("DHCP: lease lost"),
+            }
+        }
+        None => {}
+    }
+}
+
+/// Polls the interfaces whenever the DHCP socket has something to do, as they
+/// may not be polled otherwise for a long time.
+#[cfg(feature = "multitask")]
+fn renew_task() {
+    // Wake up now and then even if no timer is pending, e.g., the lease is
+    // infinite, in case the configuration is changed by the server.
+    const MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+    loop {
+        let delay = CLIENTS
+            .iter()
+            .filter_map(|client| {
+                let iface = &super::IFACES[client.iface];
+                let mut smol_iface = iface.iface.lock();
+                let sockets = iface.sockets.0.lock();
+                smol_iface.poll_delay(InterfaceWrapper::current_time(), &sockets)
+            })
+            .min();
+        let delay = delay.map_or(MAX_INTERVAL, |delay| delay.min(MAX_INTERVAL));
+        axtask::sleep(core::time::Duration::from_micros(delay.total_micros()));
+        poll_interfaces();
+    }
+}
+
+/// Starts the DHCP clients, and waits for the leases for at most
+/// [`BOOT_TIMEOUT`].
+///
+/// `ifaces` are the indices of the interfaces to configure, with their static
+/// address and gateway.
+pub fn start(ifaces: Vec<(usize, Option<IpCidr>, Option<IpAddress>)>) {
+    let clients = ifaces
+        .into_iter()
+        .map(|(iface, fallback_ip, fallback_gateway)| {
+            let fallback_gateway = match fallback_gateway {
+                Some(IpAddress::Ipv4(gateway)) => Some(gateway),
+                _ => None,
+            };
+            DhcpClient {
+                iface,
+                handle: iface_sockets(iface).add(dhcpv4::Socket::new()),
+                leased: AtomicBool::new(false),
+                fallback: (fallback_ip, fallback_gateway),
+            }
+        })
+        .collect();
+    CLIENTS.init_by(clients);
+
+    info!("DHCP: waiting for leases...");
+    let deadline = InterfaceWrapper::current_time() + BOOT_TIMEOUT;
+    while !CLIENTS
+        .iter()
+        .all(|client| client.leased.load(Ordering::Acquire))
+    {
+        if InterfaceWrapper::current_time() >= deadline {
+            for client in CLIENTS.iter() {
+                if !client.leased.load(Ordering::Acquire) {
+                    let name = super::IFACES[client.iface].name();
+                    match client.fallback.0 {
+                        Some(ip) => {
+                            warn!("DHCP: no lease on {}, use the static address {}", name, ip)
+                        }
+                        None => warn!("DHCP: no lease on {}", name),
+                    }
+                }
+            }
+            break;
+        }
+        poll_interfaces();
+        axtask::yield_now();
+    }
+
+    #[cfg(feature = "multitask")]
+    axtask::spawn(renew_task);
+}
@@ -1,44 +1,51 @@
 use alloc::vec::Vec;
 use axerrno::{ax_err_type, AxError, AxResult};
-use core::net::IpAddr;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use smoltcp::iface::SocketHandle;
-use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
+use smoltcp::socket::dns::{self, GetQueryResultError, QueryHandle, StartQueryError};
 use smoltcp::wire::DnsQueryType;
 
 use super::addr::into_core_ipaddr;
-use super::{SocketSetWrapper, ETH0, SOCKET_SET};
+use super::{
+    addr::UNSPECIFIED_IP, dns_servers, egress_iface, iface_sockets, poll_interfaces,
+    SocketSetWrapper, IFACES,
+};
 
 /// A DNS socket.
 struct DnsSocket {
+    /// The index of the interface to the DNS server.
+    iface: usize,
     handle: Option<SocketHandle>,
 }
 
 impl DnsSocket {
-    #[allow(clippy::new_without_default)]
-    /// Creates a new DNS socket.
-    pub fn new() -> Self {
+    /// Creates a new DNS socket on the interface to the DNS server.
+    pub fn new() -> AxResult<Self> {
+        let iface = egress_iface(UNSPECIFIED_IP, dns_servers()[0])
+            .map_err(|_| ax_err_type!(NetworkUnreachable, "socket query() failed: no route"))?;
         let socket = SocketSetWrapper::new_dns_socket();
-        let handle = Some(SOCKET_SET.add(socket));
-        Self { handle }
+        let handle = Some(iface_sockets(iface).add(socket));
+        Ok(Self { iface, handle })
     }
 
     #[allow(dead_code)]
     /// Update the list of DNS servers, will replace all existing servers.
     pub fn update_servers(self, servers: &[smoltcp::wire::IpAddress]) {
-        SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(self.handle.unwrap(), |socket| {
-            socket.update_servers(servers)
-        });
+        iface_sockets(self.iface)
+            .with_socket_mut::<dns::Socket, _, _>(self.handle.unwrap(), |socket| {
+                socket.update_servers(servers)
+            });
     }
 
-    /// Query a address with given DNS query type.
-    pub fn query(&self, name: &str, query_type: DnsQueryType) -> AxResult<Vec<IpAddr>> {
-        // let local_addr = self.local_addr.unwrap_or_else(f);
+    /// Starts a query with given DNS query type, the result is got by
+    /// [`DnsSocket::wait_query`].
+    pub fn start_query(&self, name: &str, query_type: DnsQueryType) -> AxResult<QueryHandle> {
         let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
-        let iface = &ETH0.iface;
-        let query_handle = SOCKET_SET
+        let context = &IFACES[self.iface].iface;
+        iface_sockets(self.iface)
             .with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
-                socket.start_query(iface.lock().context(), name, query_type)
+                socket.start_query(context.lock().context(), name, query_type)
             })
             .map_err(|e| match e {
                 StartQueryError::NoFreeSlot => {
@@ -50,10 +57,17 @@ impl DnsSocket {
                 StartQueryError::NameTooLong => {
                     ax_err_type!(InvalidInput, "socket query() failed: too long name")
                 }
-            })?;
+            })
+    }
+
+    /// Waits for the addresses of a query started by
+    /// [`DnsSocket::start_query`].
+    pub fn wait_query(&self, query_handle: QueryHandle) -> AxResult<Vec<IpAddr>> {
+        let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
+        let sockets = iface_sockets(self.iface);
         loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
+            poll_interfaces();
+            match sockets.with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
                 socket.get_query_result(query_handle).map_err(|e| match e {
                     GetQueryResultError::Pending => AxError::WouldBlock,
                     GetQueryResultError::Failed => {
@@ -78,13 +92,36 @@ impl DnsSocket {
 impl Drop for DnsSocket {
     fn drop(&mut self) {
         if let Some(handle) = self.handle {
-            SOCKET_SET.remove(handle);
+            iface_sockets(self.iface).remove(handle);
         }
     }
 }
 
 /// Public function for DNS query.
+///
+/// It queries both IPv4 (`A`) and IPv6 (`AAAA`) addresses at the same time,
+/// and fails only if both queries fail. `localhost` is resolved to the
+/// loopback addresses without a query.
 pub fn dns_query(name: &str) -> AxResult<alloc::vec::Vec<IpAddr>> {
-    let socket = DnsSocket::new();
-    socket.query(name, DnsQueryType::A)
+    if name.eq_ignore_ascii_case("localhost") {
+        return Ok(alloc::vec![
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ]);
+    }
+    let socket = DnsSocket::new()?;
+    // both queries are sent before waiting for the answers
+    let v4 = socket.start_query(name, DnsQueryType::A);
+    let v6 = socket.start_query(name, DnsQueryType::Aaaa);
+    match (
+        v4.and_then(|q| socket.wait_query(q)),
+        v6.and_then(|q| socket.wait_query(q)),
+    ) {
+        (Ok(mut v4), Ok(v6)) => {
+            v4.extend(v6);
+            Ok(v4)
+        }
+        (Ok(addrs), Err(_)) | (Err(_), Ok(addrs)) => Ok(addrs),
+        (Err(e), Err(_)) => Err(e),
+    }
 }
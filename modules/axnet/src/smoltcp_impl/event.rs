@@ -0,0 +1,221 @@
+//! Blocking support for sockets.
+//!
+//! If both the `irq` and `multitask` features are enabled and all the NICs have
+//! IRQs, the network stack is interrupt-driven: a background task polls the
+//! interfaces when a NIC raises an interrupt or a smoltcp timer expires, and
+//! the tasks blocked on sockets sleep in the [`SocketWaiter`]s, which are woken
+//! by smoltcp through the [`Waker`]s registered to the affected sockets.
+//!
+//! Otherwise, the blocked tasks poll the interface and yield the CPU
+//! repeatedly.
+//!
+//! Other waiters, like `select` and `epoll` in the POSIX layer, can
+//! [`subscribe`](SocketWaiter::subscribe) to the events of a socket, which are
+//! only delivered if the network stack is interrupt-driven.
+
+use alloc::{sync::Arc, task::Wake, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+
+use axhal::time::TimeValue;
+use axsync::Mutex;
+#[cfg(all(feature = "irq", feature = "multitask"))]
+use axtask::WaitQueue;
+use spin::Once;
+
+/// The place where tasks wait for the events of a socket.
+///
+/// It is allocated on first use, so that it can be created in const contexts.
+pub struct SocketWaiter(Once<Arc<WaiterInner>>);
+
+struct WaiterInner {
+    /// Incremented each time an event happens.
+    events: AtomicUsize,
+    /// The wakers to wake on the next event, see [`SocketWaiter::subscribe`].
+    subscribers: Mutex<Vec<Waker>>,
+    #[cfg(all(feature = "irq", feature = "multitask"))]
+    wq: WaitQueue,
+}
+
+impl Wake for WaiterInner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.events.fetch_add(1, Ordering::Release);
+        #[cfg(all(feature = "irq", feature = "multitask"))]
+        self.wq.notify_all(false);
+        let subscribers = core::mem::take(&mut *self.subscribers.lock());
+        for waker in subscribers {
+            waker.wake();
+        }
+    }
+}
+
+impl SocketWaiter {
+    pub const fn new() -> Self {
+        Self(Once::new())
+    }
+
+    fn inner(&self) -> &Arc<WaiterInner> {
+        self.0.call_once(|| {
+            Arc::new(WaiterInner {
+                events: AtomicUsize::new(0),
+                subscribers: Mutex::new(Vec::new()),
+                #[cfg(all(feature = "irq", feature = "multitask"))]
+                wq: WaitQueue::new(),
+            })
+        })
+    }
+
+    /// Returns the number of events happened so far, which should be passed to
+    /// [`wait`](Self::wait) later.
+    ///
+    /// It must be called before checking the socket state.
+    pub fn events(&self) -> usize {
+        self.inner().events.load(Ordering::Acquire)
+    }
+
+    /// Returns a [`Waker`] to be registered to the smoltcp sockets, which
+    /// wakes the tasks waiting in this waiter.
+    pub fn waker(&self) -> Waker {
+        Waker::from(self.inner().clone())
+    }
+
+    /// Registers `waker` to be woken once on the next event.
+    ///
+    /// Returns `false` without registering it if the network stack is not
+    /// interrupt-driven, as the events only happen when some task polls the
+    /// interfaces, so the caller should do so instead of sleeping.
+    pub fn subscribe(&self, waker: &Waker) -> bool {
+        if !is_irq_driven() {
+            return false;
+        }
+        let mut subscribers = self.inner().subscribers.lock();
+        if !subscribers.iter().any(|w| w.will_wake(waker)) {
+            subscribers.push(waker.clone());
+        }
+        true
+    }
+
+    /// Blocks the current task until an event happens after the `events`
+    /// snapshot is taken by [`events`](Self::events).
+    ///
+    /// If the network stack is not interrupt-driven, it just yields the CPU.
+    pub fn wait(&self, events: usize) {
+        #[cfg(all(feature = "irq", feature = "multitask"))]
+        if is_irq_driven() {
+            self.inner().wq.wait_until(|| self.events() != events);
+            return;
+        }
+        let _ = events;
+        axtask::yield_now();
+    }
+
+    /// Like [`wait`](Self::wait), but also wakes up when the `deadline` passes.
+    ///
+    /// Returns `false` without blocking if the `deadline` has already passed.
+    pub fn wait_timeout(&self, events: usize, deadline: Option<TimeValue>) -> bool {
+        let Some(deadline) = deadline else {
+            self.wait(events);
+            return true;
+        };
+        let now = axhal::time::current_time();
+        if now >= deadline {
+            return false;
+        }
+        #[cfg(all(feature = "irq", feature = "multitask"))]
+        if is_irq_driven() {
+            self.inner()
+                .wq
+                .wait_timeout_until(deadline - now, || self.events() != events);
+            return true;
+        }
+        axtask::yield_now();
+        true
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "irq", feature = "multitask"))] {
+        use core::sync::atomic::AtomicBool;
+        use lazy_init::LazyInit;
+
+        use super::IFACES;
+
+        static NET_IRQ_NUMS: LazyInit<Vec<usize>> = LazyInit::new();
+        static IRQ_DRIVEN: AtomicBool = AtomicBool::new(false);
+        static POLL_WQ: WaitQueue = WaitQueue::new();
+        /// Whether the poll task should poll the interface again.
+        static POLL_PENDING: AtomicBool = AtomicBool::new(false);
+        /// Whether the NIC IRQs are disabled by the IRQ handler.
+        static IRQ_MASKED: AtomicBool = AtomicBool::new(false);
+
+        fn is_irq_driven() -> bool {
+            IRQ_DRIVEN.load(Ordering::Acquire)
+        }
+
+        fn net_irq_handler() {
+            // The NIC keeps raising the interrupt until it is acknowledged, so
+            // disable it until the poll task has handled it. It is not known
+            // which NIC raised it, so disable them all.
+            for &irq_num in NET_IRQ_NUMS.iter() {
+                axhal::irq::set_enable(irq_num, false);
+            }
+            IRQ_MASKED.store(true, Ordering::Release);
+            POLL_PENDING.store(true, Ordering::Release);
+            POLL_WQ.notify_one(false);
+        }
+
+        fn poll_task() {
+            loop {
+                POLL_PENDING.store(false, Ordering::Release);
+                let delay = IFACES.iter().filter_map(|iface| iface.poll_on_irq()).min();
+                if IRQ_MASKED.swap(false, Ordering::AcqRel) {
+                    for &irq_num in NET_IRQ_NUMS.iter() {
+                        axhal::irq::set_enable(irq_num, true);
+                    }
+                }
+                let pending = || POLL_PENDING.load(Ordering::Acquire);
+                match delay {
+                    Some(dur) => {
+                        POLL_WQ.wait_timeout_until(dur, pending);
+                    }
+                    None => POLL_WQ.wait_until(pending),
+                }
+            }
+        }
+
+        /// Wakes up the poll task to poll the interface again, as the socket
+        /// states may have been changed (e.g., new data to send).
+        pub fn notify_poll_task() {
+            if is_irq_driven() {
+                POLL_PENDING.store(true, Ordering::Release);
+                POLL_WQ.notify_one(false);
+            }
+        }
+
+        /// Makes the network stack interrupt-driven by the given NIC IRQs.
+        pub fn init(mut irq_nums: Vec<usize>) {
+            // NICs may share an IRQ
+            irq_nums.sort_unstable();
+            irq_nums.dedup();
+            NET_IRQ_NUMS.init_by(irq_nums);
+            for &irq_num in NET_IRQ_NUMS.iter() {
+                if !axhal::irq::register_handler(irq_num, net_irq_handler) {
+                    warn!("failed to register the NIC IRQ {}, fall back to polling", irq_num);
+                    return;
+                }
+            }
+            IRQ_DRIVEN.store(true, Ordering::Release);
+            axtask::spawn(poll_task);
+        }
+    } else {
+        fn is_irq_driven() -> bool {
+            false
+        }
+
+        pub fn notify_poll_task() {}
+    }
+}
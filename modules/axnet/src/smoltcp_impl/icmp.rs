@@ -0,0 +1,332 @@
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use core::task::Waker;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use spin::RwLock;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::icmp::{self, SendError};
+use smoltcp::wire::{Icmpv4Message, Icmpv6Message, IpAddress};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr, is_unspecified, UNSPECIFIED_IP};
+use super::event::SocketWaiter;
+use super::{
+    egress_iface, iface_of_addr, iface_sockets, poll_interfaces, SocketSetWrapper, IFACES,
+};
+
+/// The length of the header of an ICMP echo message.
+const ECHO_HEADER_LEN: usize = 8;
+
+/// An ICMP socket that sends echo requests and receives echo replies, with
+/// POSIX-like APIs.
+///
+/// It works like the `SOCK_DGRAM` ICMP ("ping") socket on Linux: the messages
+/// sent and received are ICMP messages without the IP header, and the
+/// identifier of the echo requests is replaced with the one of the socket, so
+/// that only the replies to this socket are received. The checksum is computed
+/// by the network stack.
+pub struct IcmpSocket {
+    /// The smoltcp sockets, one on each interface that the socket receives
+    /// from, with the index of the interface.
+    handles: RwLock<Vec<(usize, SocketHandle)>>,
+    /// The identifier of the echo requests and replies.
+    ident: u16,
+    local_addr: RwLock<Option<IpAddress>>,
+    peer_addr: RwLock<Option<IpAddress>>,
+    nonblock: AtomicBool,
+    waiter: SocketWaiter,
+}
+
+impl IcmpSocket {
+    /// Creates a new ICMP socket, which receives echo replies from all
+    /// interfaces.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let ident = get_ephemeral_ident();
+        let handles = (0..IFACES.len())
+            .map(|iface| {
+                let mut socket = SocketSetWrapper::new_icmp_socket();
+                socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+                (iface, iface_sockets(iface).add(socket))
+            })
+            .collect();
+        debug!("ICMP socket: created with identifier {}", ident);
+        Self {
+            handles: RwLock::new(handles),
+            ident,
+            local_addr: RwLock::new(None),
+            peer_addr: RwLock::new(None),
+            nonblock: AtomicBool::new(false),
+            waiter: SocketWaiter::new(),
+        }
+    }
+
+    /// Returns the identifier of the echo requests sent by this socket.
+    pub fn ident(&self) -> u16 {
+        self.ident
+    }
+
+    /// Returns the local address, which is unspecified if not bound.
+    pub fn local_addr(&self) -> AxResult<IpAddr> {
+        let addr = self.local_addr.read().unwrap_or(UNSPECIFIED_IP);
+        Ok(into_core_ipaddr(addr))
+    }
+
+    /// Returns the remote address, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn peer_addr(&self) -> AxResult<IpAddr> {
+        match *self.peer_addr.read() {
+            Some(addr) => Ok(into_core_ipaddr(addr)),
+            None => Err(AxError::NotConnected),
+        }
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this ICMP socket into or out of nonblocking mode.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds the socket to the given local address.
+    ///
+    /// If the address is specified, the socket only sends and receives through
+    /// the interface that owns it.
+    pub fn bind(&self, local_addr: IpAddr) -> AxResult {
+        let mut self_local_addr = self.local_addr.write();
+        if self_local_addr.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+
+        let local_addr = from_core_ipaddr(local_addr);
+        if !is_unspecified(local_addr) {
+            let iface = iface_of_addr(local_addr).ok_or_else(|| {
+                ax_err_type!(InvalidInput, "socket bind() failed: address not available")
+            })?;
+            self.handles.write().retain(|&(i, handle)| {
+                if i != iface {
+                    iface_sockets(i).remove(handle);
+                }
+                i == iface
+            });
+        }
+        *self_local_addr = Some(local_addr);
+        debug!("ICMP socket {}: bound on {}", self.ident, local_addr);
+        Ok(())
+    }
+
+    /// Sets the remote address, allowing the `send` and `recv` to be used, and
+    /// only receives the replies from it.
+    pub fn connect(&self, addr: IpAddr) -> AxResult {
+        *self.peer_addr.write() = Some(from_core_ipaddr(addr));
+        debug!("ICMP socket {}: connected to {}", self.ident, addr);
+        Ok(())
+    }
+
+    /// Sends an echo request to the given address. On success, returns the
+    /// number of bytes written.
+    ///
+    /// `buf` is the ICMP message, starting with the echo request header.
+    pub fn send_to(&self, buf: &[u8], remote_addr: IpAddr) -> AxResult<usize> {
+        if remote_addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        self.send_impl(buf, from_core_ipaddr(remote_addr))
+    }
+
+    /// Sends an echo request to the remote address to which it is connected.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        let remote_addr = self.peer_addr.read().ok_or(AxError::NotConnected)?;
+        self.send_impl(buf, remote_addr)
+    }
+
+    /// Receives an echo reply. On success, returns the number of bytes read
+    /// and the origin.
+    ///
+    /// The reply is truncated if `buf` is too small.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        let peer_addr = *self.peer_addr.read();
+        self.recv_impl(buf, peer_addr)
+            .map(|(len, addr)| (len, into_core_ipaddr(addr)))
+    }
+
+    /// Receives an echo reply from the remote address to which it is
+    /// connected. On success, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let remote_addr = self.peer_addr.read().ok_or(AxError::NotConnected)?;
+        self.recv_impl(buf, Some(remote_addr)).map(|(len, _)| len)
+    }
+
+    /// Close the socket.
+    pub fn shutdown(&self) -> AxResult {
+        Ok(())
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        let mut state = PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        };
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket::<icmp::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable &= socket.can_send();
+            });
+        }
+        Ok(state)
+    }
+
+    /// Registers `waker` to be woken once when the socket may become readable
+    /// or writable, i.e., when the result of [`poll`](Self::poll) may change.
+    ///
+    /// Returns `false` if the network stack is not interrupt-driven, then the
+    /// caller should poll the socket repeatedly instead.
+    pub fn register_waker(&self, waker: &Waker) -> bool {
+        if !self.waiter.subscribe(waker) {
+            return false;
+        }
+        let own_waker = self.waiter.waker();
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(&own_waker);
+                socket.register_send_waker(&own_waker);
+            });
+        }
+        true
+    }
+}
+
+/// Private methods
+impl IcmpSocket {
+    fn send_impl(&self, buf: &[u8], remote_addr: IpAddress) -> AxResult<usize> {
+        let echo_request = match remote_addr {
+            IpAddress::Ipv4(_) => u8::from(Icmpv4Message::EchoRequest),
+            IpAddress::Ipv6(_) => u8::from(Icmpv6Message::EchoRequest),
+        };
+        if buf.len() < ECHO_HEADER_LEN || buf[0] != echo_request {
+            return ax_err!(InvalidInput, "socket send() failed: not an echo request");
+        }
+
+        // send through the socket on the interface to the remote, or the one
+        // owning the bound address
+        let local_addr = self.local_addr.read().unwrap_or(UNSPECIFIED_IP);
+        let iface = egress_iface(local_addr, remote_addr)?;
+        let handle = self
+            .handles
+            .read()
+            .iter()
+            .find_map(|&(i, handle)| (i == iface).then_some(handle))
+            .ok_or_else(|| {
+                ax_err_type!(
+                    NetworkUnreachable,
+                    "socket send() failed: bound to another interface"
+                )
+            })?;
+
+        let waker = self.waiter.waker();
+        let len = self.block_on(|| {
+            iface_sockets(iface).with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                let res = match socket.send(buf.len(), remote_addr) {
+                    Ok(packet) => {
+                        packet.copy_from_slice(buf);
+                        packet[4..6].copy_from_slice(&self.ident.to_be_bytes());
+                        Ok(buf.len())
+                    }
+                    Err(SendError::BufferFull) => Err(AxError::WouldBlock),
+                    Err(SendError::Unaddressable) => {
+                        ax_err!(InvalidInput, "socket send() failed: invalid address")
+                    }
+                };
+                if let Err(AxError::WouldBlock) = res {
+                    socket.register_send_waker(&waker);
+                }
+                res
+            })
+        })?;
+        poll_interfaces(); // send the request out
+        Ok(len)
+    }
+
+    fn recv_impl(
+        &self,
+        buf: &mut [u8],
+        remote_addr: Option<IpAddress>,
+    ) -> AxResult<(usize, IpAddress)> {
+        let handles = self.handles.read();
+        let waker = self.waiter.waker();
+        self.block_on(|| {
+            for &(iface, handle) in handles.iter() {
+                let res =
+                    iface_sockets(iface).with_socket_mut::<icmp::Socket, _, _>(handle, |socket| {
+                        while let Ok((packet, addr)) = socket.recv() {
+                            // also skip our own requests looped back by `lo`
+                            let from_remote = remote_addr.is_none() || remote_addr == Some(addr);
+                            if is_echo_reply(packet, addr) && from_remote {
+                                let len = packet.len().min(buf.len());
+                                buf[..len].copy_from_slice(&packet[..len]);
+                                return Ok((len, addr));
+                            }
+                        }
+                        socket.register_recv_waker(&waker);
+                        Err(AxError::WouldBlock)
+                    });
+                match res {
+                    Err(AxError::WouldBlock) => continue, // try other interfaces
+                    res => return res,
+                }
+            }
+            Err(AxError::WouldBlock)
+        })
+    }
+
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                poll_interfaces();
+                let events = self.waiter.events();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => self.waiter.wait(events),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        for &(iface, handle) in self.handles.get_mut().iter() {
+            iface_sockets(iface).remove(handle);
+        }
+    }
+}
+
+/// Whether `packet` from `addr` is an echo reply.
+fn is_echo_reply(packet: &[u8], addr: IpAddress) -> bool {
+    let echo_reply = match addr {
+        IpAddress::Ipv4(_) => u8::from(Icmpv4Message::EchoReply),
+        IpAddress::Ipv6(_) => u8::from(Icmpv6Message::EchoReply),
+    };
+    packet.len() >= ECHO_HEADER_LEN && packet[0] == echo_reply
+}
+
+fn get_ephemeral_ident() -> u16 {
+    static CURR: AtomicU16 = AtomicU16::new(1);
+    CURR.fetch_add(1, Ordering::Relaxed)
+}
@@ -1,26 +1,37 @@
-use alloc::{boxed::Box, collections::VecDeque};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use core::ops::{Deref, DerefMut};
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
-use axsync::Mutex;
+use axsync::{Mutex, MutexGuard};
 use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::tcp::TcpOptions;
+use super::{iface_sockets, IFACES, LISTEN_QUEUE_SIZE};
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
-    syn_queue: VecDeque<SocketHandle>,
+    /// The options of the listening socket, with which the sockets of incoming
+    /// connections are created.
+    opts: TcpOptions,
+    /// The sockets of incoming connections, with the index of the interface
+    /// they come from.
+    syn_queue: VecDeque<(usize, SocketHandle)>,
+    /// Wakes the tasks waiting in `accept()` when a connection is established.
+    waker: Option<Waker>,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(listen_endpoint: IpListenEndpoint, opts: TcpOptions) -> Self {
         Self {
             listen_endpoint,
+            opts,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            waker: None,
         }
     }
 
@@ -35,8 +46,8 @@ impl ListenTableEntry {
 
 impl Drop for ListenTableEntry {
     fn drop(&mut self) {
-        for &handle in &self.syn_queue {
-            SOCKET_SET.remove(handle);
+        for &(iface, handle) in &self.syn_queue {
+            iface_sockets(iface).remove(handle);
         }
     }
 }
@@ -61,12 +72,12 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    pub fn listen(&self, listen_endpoint: IpListenEndpoint, opts: TcpOptions) -> AxResult {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
+            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint, opts)));
             Ok(())
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
@@ -75,25 +86,40 @@ impl ListenTable {
 
     pub fn unlisten(&self, port: u16) {
         debug!("TCP socket unlisten on {}", port);
-        *self.tcp[port as usize].lock() = None;
+        // drop the entry (and the sockets in the SYN queue) after unlocking
+        let _entry = self.tcp[port as usize].lock().take();
     }
 
+    // Note: the socket sets must be locked before the entries, in the same
+    // order as in `incoming_tcp_packet()`. The sets of all interfaces are
+    // locked in the order of their indices, as the polling of an interface
+    // only holds its own set.
+
     pub fn can_accept(&self, port: u16) -> AxResult<bool> {
+        let sockets = lock_all_sockets();
         if let Some(entry) = self.tcp[port as usize].lock().deref() {
-            Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
+            Ok(entry
+                .syn_queue
+                .iter()
+                .any(|&(iface, handle)| is_connected(handle, &sockets[iface])))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
     }
 
-    pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, (IpEndpoint, IpEndpoint))> {
+    /// Takes an established connection, returns the index of its interface,
+    /// its handle, and its local and remote addresses.
+    #[allow(clippy::type_complexity)]
+    pub fn accept(&self, port: u16) -> AxResult<(usize, SocketHandle, (IpEndpoint, IpEndpoint))> {
+        let sockets = lock_all_sockets();
         if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
             let syn_queue = &mut entry.syn_queue;
             let (idx, addr_tuple) = syn_queue
                 .iter()
                 .enumerate()
-                .find_map(|(idx, &handle)| {
-                    is_connected(handle).then(|| (idx, get_addr_tuple(handle)))
+                .find_map(|(idx, &(iface, handle))| {
+                    let sockets = &sockets[iface];
+                    is_connected(handle, sockets).then(|| (idx, get_addr_tuple(handle, sockets)))
                 })
                 .ok_or(AxError::WouldBlock)?; // wait for connection
             if idx > 0 {
@@ -103,17 +129,32 @@ impl ListenTable {
                     syn_queue.len()
                 );
             }
-            let handle = syn_queue.swap_remove_front(idx).unwrap();
-            Ok((handle, addr_tuple))
+            let (iface, handle) = syn_queue.swap_remove_front(idx).unwrap();
+            Ok((iface, handle, addr_tuple))
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
     }
 
+    /// Registers a waker that is woken when any incoming connection on the
+    /// given port is established.
+    pub fn register_waker(&self, port: u16, waker: &Waker) {
+        let mut sockets = lock_all_sockets();
+        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+            for &(iface, handle) in &entry.syn_queue {
+                sockets[iface]
+                    .get_mut::<tcp::Socket>(handle)
+                    .register_recv_waker(waker);
+            }
+            entry.waker = Some(waker.clone());
+        }
+    }
+
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
         dst: IpEndpoint,
+        iface: usize,
         sockets: &mut SocketSet<'_>,
     ) {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
@@ -126,30 +167,35 @@ impl ListenTable {
                 warn!("SYN queue overflow!");
                 return;
             }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
+            let mut socket = entry.opts.new_socket();
             if socket.listen(entry.listen_endpoint).is_ok() {
+                if let Some(waker) = &entry.waker {
+                    socket.register_recv_waker(waker);
+                }
                 let handle = sockets.add(socket);
                 debug!(
                     "TCP socket {}: prepare for connection {} -> {}",
                     handle, src, entry.listen_endpoint
                 );
-                entry.syn_queue.push_back(handle);
+                entry.syn_queue.push_back((iface, handle));
             }
         }
     }
 }
 
-fn is_connected(handle: SocketHandle) -> bool {
-    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-        !matches!(socket.state(), State::Listen | State::SynReceived)
-    })
+fn lock_all_sockets() -> Vec<MutexGuard<'static, SocketSet<'static>>> {
+    IFACES.iter().map(|iface| iface.sockets.0.lock()).collect()
+}
+
+fn is_connected(handle: SocketHandle, sockets: &SocketSet<'_>) -> bool {
+    let socket = sockets.get::<tcp::Socket>(handle);
+    !matches!(socket.state(), State::Listen | State::SynReceived)
 }
 
-fn get_addr_tuple(handle: SocketHandle) -> (IpEndpoint, IpEndpoint) {
-    SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-        (
-            socket.local_endpoint().unwrap(),
-            socket.remote_endpoint().unwrap(),
-        )
-    })
+fn get_addr_tuple(handle: SocketHandle, sockets: &SocketSet<'_>) -> (IpEndpoint, IpEndpoint) {
+    let socket = sockets.get::<tcp::Socket>(handle);
+    (
+        socket.local_endpoint().unwrap(),
+        socket.remote_endpoint().unwrap(),
+    )
 }
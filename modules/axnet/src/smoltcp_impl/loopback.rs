@@ -0,0 +1,89 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use smoltcp::iface::SocketSet;
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::time::Instant;
+
+use super::{snoop_ip_packet, LOOPBACK};
+
+const LOOPBACK_MTU: usize = 65535;
+
+/// Queue at most this many packets, further packets are dropped, as a NIC does
+/// when its ring is full.
+const LOOPBACK_QUEUE_LEN: usize = 256;
+
+/// The device of the loopback interface `lo`, which receives what it
+/// transmits, in FIFO order.
+///
+/// Like [`smoltcp::phy::Loopback`], but it snoops the received packets as
+/// [`DeviceWrapper`](super::DeviceWrapper) does, so that listening sockets can
+/// accept connections from `lo`.
+pub struct LoopbackDevice {
+    queue: VecDeque<Vec<u8>>,
+}
+
+impl LoopbackDevice {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Device for LoopbackDevice {
+    type RxToken<'a> = LoopbackRxToken;
+    type TxToken<'a> = LoopbackTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buf = self.queue.pop_front()?;
+        Some((LoopbackRxToken(buf), LoopbackTxToken(&mut self.queue)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken(&mut self.queue))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = LOOPBACK_MTU;
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ip;
+        caps
+    }
+}
+
+pub struct LoopbackRxToken(Vec<u8>);
+pub struct LoopbackTxToken<'a>(&'a mut VecDeque<Vec<u8>>);
+
+impl RxToken for LoopbackRxToken {
+    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
+        snoop_ip_packet(&self.0, LOOPBACK, sockets).ok();
+    }
+
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        trace!("RECV {} bytes on lo", self.0.len());
+        f(&mut self.0)
+    }
+}
+
+impl<'a> TxToken for LoopbackTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0; len];
+        let ret = f(&mut buf);
+        trace!("SEND {} bytes on lo", len);
+        if self.0.len() < LOOPBACK_QUEUE_LEN {
+            self.0.push_back(buf);
+        } else {
+            warn!("lo: queue is full, packet dropped");
+        }
+        ret
+    }
+}
@@ -1,15 +1,26 @@
 mod addr;
 mod bench;
+#[cfg(feature = "dhcp")]
+mod dhcp;
 mod dns;
+mod event;
+mod icmp;
 mod listen_table;
+mod loopback;
+#[cfg(feature = "pcap")]
+mod pcap;
+mod raw;
+mod route;
+mod slaac;
 mod tcp;
 mod udp;
 
-use alloc::vec;
+use alloc::{format, string::String, vec, vec::Vec};
 use core::cell::RefCell;
 use core::ops::DerefMut;
 
 use axdriver::prelude::*;
+use axerrno::{AxError, AxResult};
 use axhal::time::{current_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
 use driver_net::{DevError, NetBufPtr};
@@ -18,11 +29,19 @@ use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
 use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{
+    EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpProtocol, IpVersion, Ipv4Address,
+    Ipv6Address,
+};
 
 use self::listen_table::ListenTable;
+use self::loopback::{LoopbackDevice, LoopbackRxToken, LoopbackTxToken};
 
 pub use self::dns::dns_query;
+pub use self::icmp::IcmpSocket;
+#[cfg(feature = "pcap")]
+pub use self::pcap::{pcap_clear, pcap_dump, pcap_is_enabled, pcap_set_enabled};
+pub use self::raw::RawSocket;
 pub use self::tcp::TcpSocket;
 pub use self::udp::UdpSocket;
 
@@ -37,34 +56,76 @@ macro_rules! env_or_default {
 
 const IP: &str = env_or_default!("AX_IP");
 const GATEWAY: &str = env_or_default!("AX_GW");
+const IP6: &str = env_or_default!("AX_IP6");
+const GATEWAY6: &str = env_or_default!("AX_GW6");
+const MTU: &str = env_or_default!("AX_MTU");
 const DNS_SEVER: &str = "8.8.8.8";
 const IP_PREFIX: u8 = 24;
+const IP6_PREFIX: u8 = 64;
 
 const STANDARD_MTU: usize = 1500;
+/// The minimum MTU of IPv6 links, see [RFC 8200].
+///
+/// [RFC 8200]: https://datatracker.ietf.org/doc/html/rfc8200#section-5
+const MIN_MTU: usize = 1280;
+const ETHERNET_HEADER_LEN: usize = 14;
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
 
+// The default buffer sizes of TCP and UDP sockets, which can be changed by
+// `SO_RCVBUF` and `SO_SNDBUF`.
 const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
+const ICMP_RX_BUF_LEN: usize = 16 * 1024;
+const ICMP_TX_BUF_LEN: usize = 16 * 1024;
+const RAW_RX_BUF_LEN: usize = 64 * 1024;
+const RAW_TX_BUF_LEN: usize = 64 * 1024;
 const LISTEN_QUEUE_SIZE: usize = 512;
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
-static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
+/// The network interfaces: `lo`, then `eth0`, `eth1`, ... on the NICs.
+static IFACES: LazyInit<Vec<InterfaceWrapper>> = LazyInit::new();
+
+/// The index of the loopback interface `lo` in [`IFACES`].
+const LOOPBACK: usize = 0;
+/// The index of the first NIC interface `eth0` in [`IFACES`], which is absent
+/// if no NIC is found.
+const ETH0: usize = 1;
+
+/// The DNS servers used by new DNS sockets, [`DNS_SEVER`] if empty.
+static DNS_SERVERS: Mutex<Vec<IpAddress>> = Mutex::new(Vec::new());
 
 struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
 
 struct DeviceWrapper {
     inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
+    /// The index of the interface on this NIC.
+    iface: usize,
+    /// The IP MTU of the link, larger IPv4 packets are fragmented.
+    mtu: usize,
+}
+
+/// The device of an interface.
+enum NetDevice {
+    Loopback(LoopbackDevice),
+    Ethernet(DeviceWrapper),
 }
 
+/// A network interface and the sockets on it.
+///
+/// Each interface has its own socket set, and only sends the packets of its
+/// own sockets, so that a socket never sends through another interface, e.g.,
+/// `lo` does not take the packets to a remote host.
 struct InterfaceWrapper {
-    name: &'static str,
-    ether_addr: EthernetAddress,
-    dev: Mutex<DeviceWrapper>,
+    name: String,
+    /// The index of the interface in [`IFACES`].
+    index: usize,
+    ether_addr: Option<EthernetAddress>,
+    dev: Mutex<NetDevice>,
     iface: Mutex<Interface>,
+    sockets: SocketSetWrapper<'static>,
 }
 
 impl<'a> SocketSetWrapper<'a> {
@@ -72,27 +133,50 @@ impl<'a> SocketSetWrapper<'a> {
         Self(Mutex::new(SocketSet::new(vec![])))
     }
 
-    pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
-        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]);
-        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]);
+    pub fn new_tcp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::tcp::Socket<'a> {
+        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; rx_buf_len]);
+        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; tx_buf_len]);
         socket::tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer)
     }
 
-    pub fn new_udp_socket() -> socket::udp::Socket<'a> {
+    pub fn new_udp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::udp::Socket<'a> {
         let udp_rx_buffer = socket::udp::PacketBuffer::new(
             vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_RX_BUF_LEN],
+            vec![0; rx_buf_len],
         );
         let udp_tx_buffer = socket::udp::PacketBuffer::new(
             vec![socket::udp::PacketMetadata::EMPTY; 8],
-            vec![0; UDP_TX_BUF_LEN],
+            vec![0; tx_buf_len],
         );
         socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
     }
 
+    pub fn new_icmp_socket() -> socket::icmp::Socket<'a> {
+        let icmp_rx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_RX_BUF_LEN],
+        );
+        let icmp_tx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_TX_BUF_LEN],
+        );
+        socket::icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer)
+    }
+
+    pub fn new_raw_socket(ip_version: IpVersion, protocol: IpProtocol) -> socket::raw::Socket<'a> {
+        let raw_rx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; RAW_RX_BUF_LEN],
+        );
+        let raw_tx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; RAW_TX_BUF_LEN],
+        );
+        socket::raw::Socket::new(ip_version, protocol, raw_rx_buffer, raw_tx_buffer)
+    }
+
     pub fn new_dns_socket() -> socket::dns::Socket<'a> {
-        let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
-        socket::dns::Socket::new(&[server_addr], vec![])
+        socket::dns::Socket::new(&dns_servers(), vec![])
     }
 
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
@@ -119,10 +203,6 @@ impl<'a> SocketSetWrapper<'a> {
         f(socket)
     }
 
-    pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
-    }
-
     pub fn remove(&self, handle: SocketHandle) {
         self.0.lock().remove(handle);
         debug!("socket {}: destroyed", handle);
@@ -130,17 +210,22 @@ impl<'a> SocketSetWrapper<'a> {
 }
 
 impl InterfaceWrapper {
-    fn new(name: &'static str, dev: AxNetDevice, ether_addr: EthernetAddress) -> Self {
-        let mut config = Config::new(HardwareAddress::Ethernet(ether_addr));
+    fn new(name: String, index: usize, mut dev: NetDevice, hardware_addr: HardwareAddress) -> Self {
+        let mut config = Config::new(hardware_addr);
         config.random_seed = RANDOM_SEED;
 
-        let mut dev = DeviceWrapper::new(dev);
         let iface = Mutex::new(Interface::new(config, &mut dev, Self::current_time()));
+        let ether_addr = match hardware_addr {
+            HardwareAddress::Ethernet(ether_addr) => Some(ether_addr),
+            _ => None,
+        };
         Self {
             name,
+            index,
             ether_addr,
             dev: Mutex::new(dev),
             iface,
+            sockets: SocketSetWrapper::new(),
         }
     }
 
@@ -149,40 +234,116 @@ impl InterfaceWrapper {
     }
 
     pub fn name(&self) -> &str {
-        self.name
-    }
-
-    pub fn ethernet_address(&self) -> EthernetAddress {
-        self.ether_addr
+        &self.name
     }
 
+    /// Adds an address to the interface, and a route to its subnet.
     pub fn setup_ip_addr(&self, ip: IpAddress, prefix_len: u8) {
         let mut iface = self.iface.lock();
         iface.update_ip_addrs(|ip_addrs| {
             ip_addrs.push(IpCidr::new(ip, prefix_len)).unwrap();
         });
+        route::add(IpCidr::new(ip, prefix_len), self.index);
     }
 
+    /// Adds a default route through the gateway to the interface, and a
+    /// default route to the interface.
     pub fn setup_gateway(&self, gateway: IpAddress) {
         let mut iface = self.iface.lock();
-        match gateway {
-            IpAddress::Ipv4(v4) => iface.routes_mut().add_default_ipv4_route(v4).unwrap(),
+        let default = match gateway {
+            IpAddress::Ipv4(v4) => {
+                iface.routes_mut().add_default_ipv4_route(v4).unwrap();
+                IpAddress::Ipv4(Ipv4Address::UNSPECIFIED)
+            }
+            IpAddress::Ipv6(v6) => {
+                iface.routes_mut().add_default_ipv6_route(v6).unwrap();
+                IpAddress::Ipv6(Ipv6Address::UNSPECIFIED)
+            }
         };
+        route::add(IpCidr::new(default, 0), self.index);
+    }
+
+    /// Returns the address of this interface to send packets to `dst` from.
+    pub fn source_addr(&self, dst: IpAddress) -> Option<IpAddress> {
+        self.iface.lock().get_source_address(&dst)
     }
 
-    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
+    /// Whether `ip` is one of the addresses of this interface.
+    pub fn has_ip_addr(&self, ip: IpAddress) -> bool {
+        self.iface.lock().has_ip_addr(ip)
+    }
+
+    pub fn poll(&self) {
         let mut dev = self.dev.lock();
         let mut iface = self.iface.lock();
-        let mut sockets = sockets.lock();
+        let mut sockets = self.sockets.0.lock();
         let timestamp = Self::current_time();
         iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        self.apply_config(&mut iface, &mut sockets);
+    }
+
+    /// Applies the configuration learned in the last poll, see [`slaac`] and
+    /// `dhcp`.
+    #[cfg_attr(not(feature = "dhcp"), allow(unused_variables))]
+    fn apply_config(&self, iface: &mut Interface, sockets: &mut SocketSet) {
+        if let Some(ether_addr) = self.ether_addr {
+            slaac::apply_router_advert(iface, self.index, ether_addr);
+            #[cfg(feature = "dhcp")]
+            dhcp::apply_lease(iface, self.index, sockets);
+        }
+    }
+
+    /// Starts IPv6 address autoconfiguration, see [`slaac`].
+    pub fn start_slaac(&self) {
+        let ether_addr = self
+            .ether_addr
+            .expect("SLAAC requires an Ethernet interface");
+        let mut dev = self.dev.lock();
+        slaac::start(
+            dev.deref_mut(),
+            self.index,
+            ether_addr,
+            Self::current_time(),
+        );
+    }
+
+    /// Runs `f` on the NIC of the interface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is the loopback interface.
+    fn with_nic<R>(&self, f: impl FnOnce(&mut DeviceWrapper) -> R) -> R {
+        match self.dev.lock().deref_mut() {
+            NetDevice::Ethernet(dev) => f(dev),
+            NetDevice::Loopback(_) => panic!("{} is not a NIC", self.name),
+        }
+    }
+
+    /// Acknowledges the NIC interrupt (if any) and polls the interface,
+    /// returns the time until the next poll is required, or [`None`] if no
+    /// timer is pending.
+    #[cfg(all(feature = "irq", feature = "multitask"))]
+    pub fn poll_on_irq(&self) -> Option<core::time::Duration> {
+        let mut dev = self.dev.lock();
+        let mut iface = self.iface.lock();
+        let mut sockets = self.sockets.0.lock();
+        if let NetDevice::Ethernet(dev) = dev.deref_mut() {
+            dev.inner.borrow_mut().ack_interrupt();
+        }
+        iface.poll(Self::current_time(), dev.deref_mut(), &mut sockets);
+        self.apply_config(&mut iface, &mut sockets);
+        iface
+            .poll_delay(Self::current_time(), &sockets)
+            .map(|delay| core::time::Duration::from_micros(delay.total_micros()))
     }
 }
 
 impl DeviceWrapper {
-    fn new(inner: AxNetDevice) -> Self {
+    fn new(inner: AxNetDevice, iface: usize, mtu: usize) -> Self {
         Self {
             inner: RefCell::new(inner),
+            iface,
+            mtu,
         }
     }
 }
@@ -210,7 +371,10 @@ impl Device for DeviceWrapper {
                 return None;
             }
         };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+        Some((
+            AxNetRxToken(&self.inner, rx_buf, self.iface),
+            AxNetTxToken(&self.inner),
+        ))
     }
 
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
@@ -228,19 +392,19 @@ impl Device for DeviceWrapper {
 
     fn capabilities(&self) -> DeviceCapabilities {
         let mut caps = DeviceCapabilities::default();
-        caps.max_transmission_unit = 1514;
+        caps.max_transmission_unit = self.mtu + ETHERNET_HEADER_LEN;
         caps.max_burst_size = None;
         caps.medium = Medium::Ethernet;
         caps
     }
 }
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
+struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr, usize);
 struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
 
 impl<'a> RxToken for AxNetRxToken<'a> {
     fn preprocess(&self, sockets: &mut SocketSet<'_>) {
-        snoop_tcp_packet(self.1.packet(), sockets).ok();
+        snoop_ether_frame(self.1.packet(), self.2, sockets).ok();
     }
 
     fn consume<R, F>(self, f: F) -> R
@@ -253,6 +417,8 @@ impl<'a> RxToken for AxNetRxToken<'a> {
             rx_buf.packet_len(),
             rx_buf.packet()
         );
+        #[cfg(feature = "pcap")]
+        pcap::capture(rx_buf.packet());
         let result = f(rx_buf.packet_mut());
         self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
         result
@@ -268,63 +434,385 @@ impl<'a> TxToken for AxNetTxToken<'a> {
         let mut tx_buf = dev.alloc_tx_buffer(len).unwrap();
         let ret = f(tx_buf.packet_mut());
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
+        #[cfg(feature = "pcap")]
+        pcap::capture(tx_buf.packet());
         dev.transmit(tx_buf).unwrap();
         ret
     }
 }
 
-fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smoltcp::wire::Error> {
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
+impl Device for NetDevice {
+    type RxToken<'a> = NetRxToken<'a> where Self: 'a;
+    type TxToken<'a> = NetTxToken<'a> where Self: 'a;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        match self {
+            Self::Loopback(dev) => dev
+                .receive(timestamp)
+                .map(|(rx, tx)| (NetRxToken::Loopback(rx), NetTxToken::Loopback(tx))),
+            Self::Ethernet(dev) => dev
+                .receive(timestamp)
+                .map(|(rx, tx)| (NetRxToken::Ethernet(rx), NetTxToken::Ethernet(tx))),
+        }
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        match self {
+            Self::Loopback(dev) => dev.transmit(timestamp).map(NetTxToken::Loopback),
+            Self::Ethernet(dev) => dev.transmit(timestamp).map(NetTxToken::Ethernet),
+        }
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        match self {
+            Self::Loopback(dev) => dev.capabilities(),
+            Self::Ethernet(dev) => dev.capabilities(),
+        }
+    }
+}
+
+enum NetRxToken<'a> {
+    Loopback(LoopbackRxToken),
+    Ethernet(AxNetRxToken<'a>),
+}
+
+enum NetTxToken<'a> {
+    Loopback(LoopbackTxToken<'a>),
+    Ethernet(AxNetTxToken<'a>),
+}
+
+impl<'a> RxToken for NetRxToken<'a> {
+    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
+        match self {
+            Self::Loopback(token) => token.preprocess(sockets),
+            Self::Ethernet(token) => token.preprocess(sockets),
+        }
+    }
+
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        match self {
+            Self::Loopback(token) => token.consume(f),
+            Self::Ethernet(token) => token.consume(f),
+        }
+    }
+}
+
+impl<'a> TxToken for NetTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        match self {
+            Self::Loopback(token) => token.consume(len, f),
+            Self::Ethernet(token) => token.consume(len, f),
+        }
+    }
+}
+
+fn snoop_ether_frame(
+    buf: &[u8],
+    iface: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    use smoltcp::wire::{EthernetFrame, EthernetProtocol, Ipv6Packet};
 
     let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
-
-    if ipv4_packet.next_header() == IpProtocol::Tcp {
-        let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
-        let src_addr = (ipv4_packet.src_addr(), tcp_packet.src_port()).into();
-        let dst_addr = (ipv4_packet.dst_addr(), tcp_packet.dst_port()).into();
-        let is_first = tcp_packet.syn() && !tcp_packet.ack();
-        if is_first {
-            // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
+    match ether_frame.ethertype() {
+        EthernetProtocol::Ipv4 => snoop_ip_packet(ether_frame.payload(), iface, sockets)?,
+        EthernetProtocol::Ipv6 => {
+            slaac::snoop_router_advert(&Ipv6Packet::new_checked(ether_frame.payload())?, iface)?;
+            snoop_ip_packet(ether_frame.payload(), iface, sockets)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn snoop_ip_packet(
+    buf: &[u8],
+    iface: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    use smoltcp::wire::{IpProtocol, IpVersion, Ipv4Packet, Ipv6Packet};
+
+    if buf.is_empty() {
+        return Err(smoltcp::wire::Error);
+    }
+    match IpVersion::of_packet(buf)? {
+        IpVersion::Ipv4 => {
+            let ipv4_packet = Ipv4Packet::new_checked(buf)?;
+            if ipv4_packet.next_header() == IpProtocol::Tcp {
+                let src_addr = ipv4_packet.src_addr().into();
+                let dst_addr = ipv4_packet.dst_addr().into();
+                snoop_tcp_packet(src_addr, dst_addr, ipv4_packet.payload(), iface, sockets)?;
+            }
+        }
+        IpVersion::Ipv6 => {
+            let ipv6_packet = Ipv6Packet::new_checked(buf)?;
+            if ipv6_packet.next_header() == IpProtocol::Tcp {
+                let src_addr = ipv6_packet.src_addr().into();
+                let dst_addr = ipv6_packet.dst_addr().into();
+                snoop_tcp_packet(src_addr, dst_addr, ipv6_packet.payload(), iface, sockets)?;
+            }
         }
     }
     Ok(())
 }
 
+fn snoop_tcp_packet(
+    src_addr: IpAddress,
+    dst_addr: IpAddress,
+    payload: &[u8],
+    iface: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    let tcp_packet = smoltcp::wire::TcpPacket::new_checked(payload)?;
+    let src_addr = (src_addr, tcp_packet.src_port()).into();
+    let dst_addr = (dst_addr, tcp_packet.dst_port()).into();
+    let is_first = tcp_packet.syn() && !tcp_packet.ack();
+    if is_first {
+        // create a socket for the first incoming TCP packet, as the later accept() returns.
+        LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, iface, sockets);
+    }
+    Ok(())
+}
+
+/// Returns the DNS servers, which are given by DHCP if any.
+fn dns_servers() -> Vec<IpAddress> {
+    let servers = DNS_SERVERS.lock();
+    if servers.is_empty() {
+        vec![DNS_SEVER.parse().expect("invalid DNS server address")]
+    } else {
+        servers.clone()
+    }
+}
+
+/// Replaces the DNS servers used by new DNS sockets, or restores the default
+/// one if `servers` is empty.
+#[cfg_attr(not(feature = "dhcp"), allow(dead_code))]
+fn set_dns_servers(servers: &[IpAddress]) {
+    *DNS_SERVERS.lock() = servers.to_vec();
+}
+
+/// Returns the sockets on the interface with the given index.
+fn iface_sockets(iface: usize) -> &'static SocketSetWrapper<'static> {
+    &IFACES[iface].sockets
+}
+
+/// Returns the index of the interface to send packets from `local_addr` to
+/// `remote_addr`.
+///
+/// A socket bound to a specific local address is pinned to the interface that
+/// owns it, otherwise (`local_addr` is unspecified) the interface is picked by
+/// the [`route`] table. It fails with
+/// [`NetworkUnreachable`](AxError::NetworkUnreachable) if there is no route to
+/// `remote_addr`, or no interface owns `local_addr` any more.
+fn egress_iface(local_addr: IpAddress, remote_addr: IpAddress) -> AxResult<usize> {
+    if addr::is_unspecified(local_addr) {
+        route::lookup(remote_addr).ok_or(AxError::NetworkUnreachable)
+    } else {
+        iface_of_addr(local_addr).ok_or(AxError::NetworkUnreachable)
+    }
+}
+
+/// Returns the index of the interface that owns the address `ip`.
+fn iface_of_addr(ip: IpAddress) -> Option<usize> {
+    IFACES.iter().position(|iface| iface.has_ip_addr(ip))
+}
+
 /// Poll the network stack.
 ///
 /// It may receive packets from the NIC and process them, and transmit queued
 /// packets to the NIC.
 pub fn poll_interfaces() {
-    SOCKET_SET.poll_interfaces();
+    for iface in IFACES.iter() {
+        iface.poll();
+    }
+    event::notify_poll_task();
 }
 
 /// Benchmark raw socket transmit bandwidth.
 pub fn bench_transmit() {
-    ETH0.dev.lock().bench_transmit_bandwidth();
+    let eth0 = IFACES.get(ETH0).expect("No NIC device found!");
+    eth0.with_nic(|dev| dev.bench_transmit_bandwidth());
 }
 
 /// Benchmark raw socket receive bandwidth.
 pub fn bench_receive() {
-    ETH0.dev.lock().bench_receive_bandwidth();
+    let eth0 = IFACES.get(ETH0).expect("No NIC device found!");
+    eth0.with_nic(|dev| dev.bench_receive_bandwidth());
 }
 
-pub(crate) fn init(net_dev: AxNetDevice) {
+/// The static configuration of a NIC interface.
+///
+/// `AX_IP`, `AX_GW`, `AX_IP6`, `AX_GW6` and `AX_MTU` are comma-separated lists,
+/// whose `n`-th entries configure `eth<n>`. An address may have a prefix length,
+/// like `10.0.2.15/24`, and an empty or absent entry leaves it unconfigured (or
+/// [`STANDARD_MTU`] for the MTU).
+struct StaticConfig {
+    ip: Option<IpCidr>,
+    gateway: Option<IpAddress>,
+    ip6: Option<IpCidr>,
+    gateway6: Option<IpAddress>,
+    mtu: usize,
+}
+
+impl StaticConfig {
+    /// Returns the configuration of the `n`-th NIC.
+    fn of_nic(n: usize) -> Self {
+        let nth = |list: &'static str| {
+            list.split(',')
+                .map(str::trim)
+                .nth(n)
+                .filter(|s| !s.is_empty())
+        };
+        let cidr = |list, prefix_len, what| {
+            nth(list).map(|s| {
+                parse_cidr(s, prefix_len)
+                    .unwrap_or_else(|| panic!("invalid {} of NIC {}: {:?}", what, n, s))
+            })
+        };
+        let addr = |list, what| {
+            nth(list).map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("invalid {} of NIC {}: {:?}", what, n, s))
+            })
+        };
+        Self {
+            ip: cidr(IP, IP_PREFIX, "IP address"),
+            gateway: addr(GATEWAY, "gateway IP address"),
+            ip6: cidr(IP6, IP6_PREFIX, "IPv6 address"),
+            gateway6: addr(GATEWAY6, "IPv6 gateway address"),
+            mtu: nth(MTU).map_or(STANDARD_MTU, |s| {
+                s.parse()
+                    .ok()
+                    .filter(|mtu| (MIN_MTU..=STANDARD_MTU).contains(mtu))
+                    .unwrap_or_else(|| panic!("invalid MTU of NIC {}: {:?}", n, s))
+            }),
+        }
+    }
+}
+
+/// Parses an address with an optional prefix length, `default_prefix_len` if
+/// absent.
+fn parse_cidr(s: &str, default_prefix_len: u8) -> Option<IpCidr> {
+    let (ip, prefix_len) = match s.split_once('/') {
+        Some((ip, prefix_len)) => (ip.parse().ok()?, prefix_len.parse().ok()?),
+        None => (s.parse().ok()?, default_prefix_len),
+    };
+    let max_prefix_len = match ip {
+        IpAddress::Ipv4(_) => 32,
+        IpAddress::Ipv6(_) => 128,
+    };
+    (prefix_len <= max_prefix_len).then(|| IpCidr::new(ip, prefix_len))
+}
+
+fn new_loopback() -> InterfaceWrapper {
+    let lo = InterfaceWrapper::new(
+        "lo".into(),
+        LOOPBACK,
+        NetDevice::Loopback(LoopbackDevice::new()),
+        HardwareAddress::Ip,
+    );
+    let ip = IpAddress::v4(127, 0, 0, 1);
+    let ip6 = IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1);
+    lo.setup_ip_addr(ip, 8);
+    lo.setup_ip_addr(ip6, 128);
+
+    info!("created net interface {:?}:", lo.name());
+    info!("  ip:       {}/{}", ip, 8);
+    info!("  ip6:      {}/{}", ip6, 128);
+    lo
+}
+
+/// Creates the interface `eth<n>` on the `n`-th NIC.
+fn new_eth(n: usize, net_dev: AxNetDevice) -> InterfaceWrapper {
+    let index = ETH0 + n;
     let ether_addr = EthernetAddress(net_dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
+    let irq_num = net_dev.irq_num();
+    let config = StaticConfig::of_nic(n);
+    let eth = InterfaceWrapper::new(
+        format!("eth{}", n),
+        index,
+        NetDevice::Ethernet(DeviceWrapper::new(net_dev, index, config.mtu)),
+        HardwareAddress::Ethernet(ether_addr),
+    );
+
+    if let Some(ip) = config.ip {
+        eth.setup_ip_addr(ip.address(), ip.prefix_len());
+    }
+    if let Some(gateway) = config.gateway {
+        eth.setup_gateway(gateway);
+    }
 
-    let ip = IP.parse().expect("invalid IP address");
-    let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
-    eth0.setup_gateway(gateway);
+    let link_local_ip6 = IpAddress::Ipv6(slaac::link_local_addr(ether_addr));
+    eth.setup_ip_addr(link_local_ip6, IP6_PREFIX);
+    if let Some(ip6) = config.ip6 {
+        eth.setup_ip_addr(ip6.address(), ip6.prefix_len());
+    }
+    if let Some(gateway6) = config.gateway6 {
+        eth.setup_gateway(gateway6);
+    }
 
-    ETH0.init_by(eth0);
-    SOCKET_SET.init_by(SocketSetWrapper::new());
+    info!("created net interface {:?}:", eth.name());
+    info!("  ether:    {}", ether_addr);
+    info!("  mtu:      {}", config.mtu);
+    match config.ip {
+        Some(ip) => info!("  ip:       {}", ip),
+        None => info!("  ip:       (none)"),
+    }
+    if let Some(gateway) = config.gateway {
+        info!("  gateway:  {}", gateway);
+    }
+    info!("  ip6:      {}/{}", link_local_ip6, IP6_PREFIX);
+    match config.ip6 {
+        Some(ip6) => info!("  ip6:      {}", ip6),
+        None => {
+            info!("  ip6:      (SLAAC)");
+            eth.start_slaac();
+        }
+    }
+    if let Some(gateway6) = config.gateway6 {
+        info!("  gateway6: {}", gateway6);
+    }
+    if let Some(irq_num) = irq_num {
+        info!("  irq:      {}", irq_num);
+    }
+    eth
+}
+
+/// Creates the loopback interface `lo`, and `eth0`, `eth1`, ... on the NICs.
+pub(crate) fn init(net_devs: Vec<AxNetDevice>) {
+    #[cfg_attr(
+        not(all(feature = "irq", feature = "multitask")),
+        allow(unused_variables)
+    )]
+    let irq_nums: Option<Vec<usize>> = net_devs.iter().map(|dev| dev.irq_num()).collect();
+
+    let mut ifaces = vec![new_loopback()];
+    for (n, net_dev) in net_devs.into_iter().enumerate() {
+        ifaces.push(new_eth(n, net_dev));
+    }
+    IFACES.init_by(ifaces);
     LISTEN_TABLE.init_by(ListenTable::new());
 
-    info!("created net interface {:?}:", ETH0.name());
-    info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
-    info!("  gateway:  {}", gateway);
+    #[cfg(feature = "dhcp")]
+    if IFACES.len() > ETH0 {
+        let nics = (0..IFACES.len() - ETH0).map(|n| {
+            let config = StaticConfig::of_nic(n);
+            (ETH0 + n, config.ip, config.gateway)
+        });
+        dhcp::start(nics.collect());
+    }
+
+    // a NIC without IRQ is only polled by the blocked tasks
+    #[cfg(all(feature = "irq", feature = "multitask"))]
+    match irq_nums {
+        Some(irq_nums) if !irq_nums.is_empty() => event::init(irq_nums),
+        Some(_) => {}
+        None => warn!("not all NICs have an IRQ, fall back to polling"),
+    }
 }
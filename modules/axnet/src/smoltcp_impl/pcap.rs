@@ -0,0 +1,126 @@
+//! Packet capture in the [pcap] format, to inspect the traffic of the NICs
+//! with Wireshark or tcpdump.
+//!
+//! Once enabled, every frame received or transmitted by [`DeviceWrapper`] is
+//! copied into a ring buffer, which drops the oldest frames when it holds more
+//! than [`PCAP_BUF_LEN`] bytes. The loopback interface is not captured.
+//!
+//! [pcap]: https://wiki.wireshark.org/Development/LibpcapFileFormat
+//! [`DeviceWrapper`]: super::DeviceWrapper
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axhal::time::wall_time;
+use axsync::Mutex;
+
+/// The maximum total length of the captured frames in the ring buffer.
+const PCAP_BUF_LEN: usize = 1024 * 1024;
+
+/// The maximum length of a captured frame, longer frames are truncated.
+const SNAP_LEN: usize = 65535;
+
+/// The link-layer header type of IEEE 802.3 Ethernet.
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// A captured frame.
+struct Record {
+    /// The wall-clock time when the frame was captured, since the Unix epoch.
+    timestamp: Duration,
+    /// The original length of the frame.
+    orig_len: usize,
+    data: Vec<u8>,
+}
+
+impl Record {
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.timestamp.as_secs() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.timestamp.subsec_micros().to_le_bytes());
+        buf.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(self.orig_len as u32).to_le_bytes());
+        buf.extend_from_slice(&self.data);
+    }
+}
+
+struct RingBuffer {
+    records: VecDeque<Record>,
+    /// The total length of the frames in `records`.
+    len: usize,
+}
+
+impl RingBuffer {
+    const fn new() -> Self {
+        Self {
+            records: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        self.len += record.data.len();
+        self.records.push_back(record);
+        while self.len > PCAP_BUF_LEN {
+            let oldest = self.records.pop_front().unwrap();
+            self.len -= oldest.data.len();
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CAPTURE: Mutex<RingBuffer> = Mutex::new(RingBuffer::new());
+
+/// Captures the frame if the capture is enabled.
+pub(super) fn capture(frame: &[u8]) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    let record = Record {
+        timestamp: wall_time(),
+        orig_len: frame.len(),
+        data: frame[..frame.len().min(SNAP_LEN)].to_vec(),
+    };
+    CAPTURE.lock().push(record);
+}
+
+/// Starts or stops capturing the frames of the NICs.
+///
+/// The frames captured so far are kept, see [`pcap_clear`].
+pub fn pcap_set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Release);
+    info!(
+        "packet capture {}",
+        if enabled { "started" } else { "stopped" }
+    );
+}
+
+/// Whether the frames of the NICs are being captured.
+pub fn pcap_is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Discards the captured frames.
+pub fn pcap_clear() {
+    let mut capture = CAPTURE.lock();
+    capture.records.clear();
+    capture.len = 0;
+}
+
+/// Returns the captured frames as the content of a pcap file, oldest first.
+pub fn pcap_dump() -> Vec<u8> {
+    let capture = CAPTURE.lock();
+    let mut buf = Vec::with_capacity(24 + capture.len + 16 * capture.records.len());
+    // global header: magic, version 2.4, UTC offset, accuracy, snaplen, linktype
+    buf.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    buf.extend_from_slice(&2u16.to_le_bytes());
+    buf.extend_from_slice(&4u16.to_le_bytes());
+    buf.extend_from_slice(&0i32.to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes());
+    buf.extend_from_slice(&(SNAP_LEN as u32).to_le_bytes());
+    buf.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+    for record in capture.records.iter() {
+        record.write_to(&mut buf);
+    }
+    buf
+}
@@ -0,0 +1,390 @@
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use spin::RwLock;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::raw::{self, SendError};
+use smoltcp::wire::{
+    Icmpv6Packet, IpAddress, IpProtocol, IpVersion, Ipv4Packet, Ipv4Repr, Ipv6Packet, Ipv6Repr,
+};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr, is_unspecified, UNSPECIFIED_IP};
+use super::event::SocketWaiter;
+use super::{
+    egress_iface, iface_of_addr, iface_sockets, poll_interfaces, SocketSetWrapper, IFACES,
+};
+
+/// The hop limit of the packets sent by raw sockets.
+const HOP_LIMIT: u8 = 64;
+
+/// A raw IP socket that provides POSIX-like APIs.
+///
+/// It sends and receives the packets of one IP protocol, like the `SOCK_RAW`
+/// socket on Linux: the IP header is built by the network stack on sending,
+/// and it is included in the packets received on IPv4 sockets but not on IPv6
+/// ones. The checksum of ICMPv6 messages is computed by the network stack, but
+/// that of the other protocols is up to the user.
+pub struct RawSocket {
+    /// The smoltcp sockets, one on each interface that the socket receives
+    /// from, with the index of the interface.
+    handles: RwLock<Vec<(usize, SocketHandle)>>,
+    ip_version: IpVersion,
+    protocol: IpProtocol,
+    local_addr: RwLock<Option<IpAddress>>,
+    peer_addr: RwLock<Option<IpAddress>>,
+    nonblock: AtomicBool,
+    waiter: SocketWaiter,
+}
+
+impl RawSocket {
+    /// Creates a new raw socket for the IP protocol number `protocol`, which
+    /// receives from all interfaces.
+    ///
+    /// The socket is for IPv6 if `ipv6` is true, and for IPv4 otherwise.
+    pub fn new(ipv6: bool, protocol: u8) -> Self {
+        let ip_version = if ipv6 {
+            IpVersion::Ipv6
+        } else {
+            IpVersion::Ipv4
+        };
+        let protocol = IpProtocol::from(protocol);
+        let handles = (0..IFACES.len())
+            .map(|iface| {
+                let socket = SocketSetWrapper::new_raw_socket(ip_version, protocol);
+                (iface, iface_sockets(iface).add(socket))
+            })
+            .collect();
+        debug!("raw socket: created for {} {}", ip_version, protocol);
+        Self {
+            handles: RwLock::new(handles),
+            ip_version,
+            protocol,
+            local_addr: RwLock::new(None),
+            peer_addr: RwLock::new(None),
+            nonblock: AtomicBool::new(false),
+            waiter: SocketWaiter::new(),
+        }
+    }
+
+    /// Returns the local address, which is unspecified if not bound.
+    pub fn local_addr(&self) -> AxResult<IpAddr> {
+        let addr = self.local_addr.read().unwrap_or(UNSPECIFIED_IP);
+        Ok(into_core_ipaddr(addr))
+    }
+
+    /// Returns the remote address, or
+    /// [`Err(NotConnected)`](AxError::NotConnected) if not connected.
+    pub fn peer_addr(&self) -> AxResult<IpAddr> {
+        match *self.peer_addr.read() {
+            Some(addr) => Ok(into_core_ipaddr(addr)),
+            None => Err(AxError::NotConnected),
+        }
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this raw socket into or out of nonblocking mode.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds the socket to the given local address, which is used as the
+    /// source address of the packets sent.
+    ///
+    /// If the address is specified, the socket only sends and receives through
+    /// the interface that owns it.
+    pub fn bind(&self, local_addr: IpAddr) -> AxResult {
+        let mut self_local_addr = self.local_addr.write();
+        if self_local_addr.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+
+        let local_addr = self.check_family(from_core_ipaddr(local_addr))?;
+        if !is_unspecified(local_addr) {
+            let iface = iface_of_addr(local_addr).ok_or_else(|| {
+                ax_err_type!(InvalidInput, "socket bind() failed: address not available")
+            })?;
+            self.handles.write().retain(|&(i, handle)| {
+                if i != iface {
+                    iface_sockets(i).remove(handle);
+                }
+                i == iface
+            });
+        }
+        *self_local_addr = Some(local_addr);
+        debug!("raw socket: bound on {}", local_addr);
+        Ok(())
+    }
+
+    /// Sets the remote address, allowing the `send` and `recv` to be used, and
+    /// only receives the packets from it.
+    pub fn connect(&self, addr: IpAddr) -> AxResult {
+        let addr = self.check_family(from_core_ipaddr(addr))?;
+        *self.peer_addr.write() = Some(addr);
+        debug!("raw socket: connected to {}", addr);
+        Ok(())
+    }
+
+    /// Sends a packet with the payload `buf` to the given address. On success,
+    /// returns the number of bytes written.
+    pub fn send_to(&self, buf: &[u8], remote_addr: IpAddr) -> AxResult<usize> {
+        if remote_addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        let remote_addr = self.check_family(from_core_ipaddr(remote_addr))?;
+        self.send_impl(buf, remote_addr)
+    }
+
+    /// Sends a packet with the payload `buf` to the remote address to which it
+    /// is connected.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        let remote_addr = self.peer_addr.read().ok_or(AxError::NotConnected)?;
+        self.send_impl(buf, remote_addr)
+    }
+
+    /// Receives a packet. On success, returns the number of bytes read and the
+    /// origin.
+    ///
+    /// The packet is truncated if `buf` is too small.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        let peer_addr = *self.peer_addr.read();
+        self.recv_impl(buf, peer_addr)
+            .map(|(len, addr)| (len, into_core_ipaddr(addr)))
+    }
+
+    /// Receives a packet from the remote address to which it is connected. On
+    /// success, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let remote_addr = self.peer_addr.read().ok_or(AxError::NotConnected)?;
+        self.recv_impl(buf, Some(remote_addr)).map(|(len, _)| len)
+    }
+
+    /// Close the socket.
+    pub fn shutdown(&self) -> AxResult {
+        Ok(())
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        let mut state = PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        };
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket::<raw::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable &= socket.can_send();
+            });
+        }
+        Ok(state)
+    }
+
+    /// Registers `waker` to be woken once when the socket may become readable
+    /// or writable, i.e., when the result of [`poll`](Self::poll) may change.
+    ///
+    /// Returns `false` if the network stack is not interrupt-driven, then the
+    /// caller should poll the socket repeatedly instead.
+    pub fn register_waker(&self, waker: &Waker) -> bool {
+        if !self.waiter.subscribe(waker) {
+            return false;
+        }
+        let own_waker = self.waiter.waker();
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(&own_waker);
+                socket.register_send_waker(&own_waker);
+            });
+        }
+        true
+    }
+}
+
+/// Private methods
+impl RawSocket {
+    fn check_family(&self, addr: IpAddress) -> AxResult<IpAddress> {
+        if addr.version() == self.ip_version {
+            Ok(addr)
+        } else {
+            ax_err!(InvalidInput, "raw socket: address family mismatch")
+        }
+    }
+
+    fn send_impl(&self, buf: &[u8], remote_addr: IpAddress) -> AxResult<usize> {
+        // send through the socket on the interface to the remote, or the one
+        // owning the bound address
+        let local_addr = self.local_addr.read().unwrap_or(UNSPECIFIED_IP);
+        let iface = egress_iface(local_addr, remote_addr)?;
+        let handle = self
+            .handles
+            .read()
+            .iter()
+            .find_map(|&(i, handle)| (i == iface).then_some(handle))
+            .ok_or_else(|| {
+                ax_err_type!(
+                    NetworkUnreachable,
+                    "socket send() failed: bound to another interface"
+                )
+            })?;
+        let src_addr = if is_unspecified(local_addr) {
+            IFACES[iface].source_addr(remote_addr).ok_or_else(|| {
+                ax_err_type!(
+                    NetworkUnreachable,
+                    "socket send() failed: no source address"
+                )
+            })?
+        } else {
+            local_addr
+        };
+
+        let waker = self.waiter.waker();
+        let len = self.block_on(|| {
+            iface_sockets(iface).with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                let res = self.emit_packet(socket, src_addr, remote_addr, buf);
+                if let Err(AxError::WouldBlock) = res {
+                    socket.register_send_waker(&waker);
+                }
+                res
+            })
+        })?;
+        poll_interfaces(); // send the packet out
+        Ok(len)
+    }
+
+    /// Enqueues a packet with the IP header and the payload `buf` to `socket`.
+    fn emit_packet(
+        &self,
+        socket: &mut raw::Socket,
+        src_addr: IpAddress,
+        dst_addr: IpAddress,
+        buf: &[u8],
+    ) -> AxResult<usize> {
+        let map_err = |e| match e {
+            SendError::BufferFull => AxError::WouldBlock,
+        };
+        match (src_addr, dst_addr) {
+            (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+                let repr = Ipv4Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: buf.len(),
+                    hop_limit: HOP_LIMIT,
+                };
+                let packet = socket
+                    .send(repr.buffer_len() + buf.len())
+                    .map_err(map_err)?;
+                let mut packet = Ipv4Packet::new_unchecked(packet);
+                repr.emit(&mut packet, &ChecksumCapabilities::default());
+                packet.payload_mut().copy_from_slice(buf);
+            }
+            (IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) => {
+                let repr = Ipv6Repr {
+                    src_addr,
+                    dst_addr,
+                    next_header: self.protocol,
+                    payload_len: buf.len(),
+                    hop_limit: HOP_LIMIT,
+                };
+                let packet = socket
+                    .send(repr.buffer_len() + buf.len())
+                    .map_err(map_err)?;
+                let mut packet = Ipv6Packet::new_unchecked(packet);
+                repr.emit(&mut packet);
+                let payload = packet.payload_mut();
+                payload.copy_from_slice(buf);
+                if self.protocol == IpProtocol::Icmpv6 {
+                    Icmpv6Packet::new_checked(payload)
+                        .map_err(|_| {
+                            ax_err_type!(InvalidInput, "socket send() failed: bad ICMPv6")
+                        })?
+                        .fill_checksum(&src_addr.into(), &dst_addr.into());
+                }
+            }
+            _ => return ax_err!(InvalidInput, "raw socket: address family mismatch"),
+        }
+        Ok(buf.len())
+    }
+
+    fn recv_impl(
+        &self,
+        buf: &mut [u8],
+        remote_addr: Option<IpAddress>,
+    ) -> AxResult<(usize, IpAddress)> {
+        let handles = self.handles.read();
+        let waker = self.waiter.waker();
+        self.block_on(|| {
+            for &(iface, handle) in handles.iter() {
+                let res =
+                    iface_sockets(iface).with_socket_mut::<raw::Socket, _, _>(handle, |socket| {
+                        while let Ok(packet) = socket.recv() {
+                            let (src_addr, data) = match self.ip_version {
+                                IpVersion::Ipv4 => {
+                                    let Ok(ipv4) = Ipv4Packet::new_checked(packet) else {
+                                        continue;
+                                    };
+                                    (IpAddress::Ipv4(ipv4.src_addr()), packet)
+                                }
+                                IpVersion::Ipv6 => {
+                                    let Ok(ipv6) = Ipv6Packet::new_checked(packet) else {
+                                        continue;
+                                    };
+                                    (IpAddress::Ipv6(ipv6.src_addr()), ipv6.payload())
+                                }
+                            };
+                            if remote_addr.is_none() || remote_addr == Some(src_addr) {
+                                let len = data.len().min(buf.len());
+                                buf[..len].copy_from_slice(&data[..len]);
+                                return Ok((len, src_addr));
+                            }
+                        }
+                        socket.register_recv_waker(&waker);
+                        Err(AxError::WouldBlock)
+                    });
+                match res {
+                    Err(AxError::WouldBlock) => continue, // try other interfaces
+                    res => return res,
+                }
+            }
+            Err(AxError::WouldBlock)
+        })
+    }
+
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                poll_interfaces();
+                let events = self.waiter.events();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => self.waiter.wait(events),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RawSocket {
+    fn drop(&mut self) {
+        for &(iface, handle) in self.handles.get_mut().iter() {
+            iface_sockets(iface).remove(handle);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! The route table, which picks the interface to send packets to a destination.
+//!
+//! It only decides the egress interface: the next hop (the gateway or the
+//! destination itself) is then chosen by the routes of that interface in
+//! smoltcp.
+//!
+//! The table holds the subnets of the interface addresses, the default routes
+//! of the interfaces with a gateway, and the loopback ranges of `lo`. The most
+//! specific route wins, and the earliest added one on a tie.
+
+use alloc::vec::Vec;
+
+use axsync::Mutex;
+use smoltcp::wire::{IpAddress, IpCidr};
+
+/// An entry of the route table.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// The destinations.
+    pub dst: IpCidr,
+    /// The index of the interface to send packets to `dst`.
+    pub iface: usize,
+}
+
+static ROUTES: Mutex<Vec<Route>> = Mutex::new(Vec::new());
+
+/// Adds a route to the table, if not present.
+pub fn add(dst: IpCidr, iface: usize) {
+    let route = Route { dst, iface };
+    let mut routes = ROUTES.lock();
+    if !routes.contains(&route) {
+        routes.push(route);
+    }
+}
+
+/// Removes the routes of the interface `iface` that satisfy `f`.
+#[cfg_attr(not(feature = "dhcp"), allow(dead_code))]
+pub fn remove_if<F: FnMut(&IpCidr) -> bool>(iface: usize, mut f: F) {
+    ROUTES
+        .lock()
+        .retain(|route| route.iface != iface || !f(&route.dst));
+}
+
+/// Returns the index of the interface to send packets to `dst`, or [`None`]
+/// if there is no route.
+pub fn lookup(dst: IpAddress) -> Option<usize> {
+    // `max_by_key` returns the last maximum, so search backwards
+    ROUTES
+        .lock()
+        .iter()
+        .rev()
+        .filter(|route| route.dst.contains_addr(&dst))
+        .max_by_key(|route| route.dst.prefix_len())
+        .map(|route| route.iface)
+}
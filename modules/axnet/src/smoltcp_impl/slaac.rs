@@ -0,0 +1,165 @@
+//! IPv6 stateless address autoconfiguration (SLAAC), see [RFC 4862].
+//!
+//! smoltcp does not process Router Advertisements, so they are snooped on
+//! the receive path, and the advertised prefix is applied to the interface
+//! after the poll in which it was received.
+//!
+//! [RFC 4862]: https://datatracker.ietf.org/doc/html/rfc4862
+
+use alloc::collections::BTreeMap;
+
+use axsync::Mutex;
+use smoltcp::iface::Interface;
+use smoltcp::phy::{ChecksumCapabilities, Device, TxToken};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, Icmpv6Packet, Icmpv6Repr,
+    IpAddress, IpCidr, IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags,
+    NdiscRepr, RawHardwareAddress,
+};
+
+use super::route;
+
+/// The prefix length of addresses configured by SLAAC.
+const SLAAC_PREFIX_LEN: u8 = 64;
+
+/// The indices of the interfaces on which SLAAC is in use, i.e., no static
+/// IPv6 address is configured, with the prefix and the router of the last
+/// Router Advertisement received on each, which have not been applied to the
+/// interface.
+static PENDING_ADVERTS: Mutex<BTreeMap<usize, Option<(Ipv6Address, Ipv6Address)>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Forms an address from the 64-bit `prefix` and the modified EUI-64 interface
+/// identifier of the Ethernet address.
+fn eui64_addr(prefix: Ipv6Address, ether_addr: EthernetAddress) -> Ipv6Address {
+    let mac = ether_addr.as_bytes();
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&prefix.as_bytes()[..8]);
+    bytes[8..11].copy_from_slice(&mac[..3]);
+    bytes[8] ^= 0x02; // universal/local bit
+    bytes[11] = 0xff;
+    bytes[12] = 0xfe;
+    bytes[13..].copy_from_slice(&mac[3..]);
+    Ipv6Address(bytes)
+}
+
+/// Returns the link-local address of the interface with the Ethernet address.
+pub fn link_local_addr(ether_addr: EthernetAddress) -> Ipv6Address {
+    eui64_addr(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), ether_addr)
+}
+
+/// Starts SLAAC on the interface with index `iface` by sending a Router
+/// Solicitation to all routers, so that they send the Router Advertisement
+/// immediately instead of waiting for the next period.
+pub fn start<D: Device>(
+    dev: &mut D,
+    iface: usize,
+    ether_addr: EthernetAddress,
+    timestamp: Instant,
+) {
+    PENDING_ADVERTS.lock().insert(iface, None);
+    let ndisc_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+        lladdr: Some(RawHardwareAddress::from(ether_addr)),
+    });
+    let ip_repr = Ipv6Repr {
+        src_addr: link_local_addr(ether_addr),
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_ROUTERS,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: ndisc_repr.buffer_len(),
+        hop_limit: 255,
+    };
+    let ether_repr = EthernetRepr {
+        src_addr: ether_addr,
+        dst_addr: EthernetAddress::from_bytes(&[0x33, 0x33, 0, 0, 0, 2]),
+        ethertype: EthernetProtocol::Ipv6,
+    };
+
+    let Some(tx_token) = dev.transmit(timestamp) else {
+        warn!("failed to send the router solicitation: NIC is busy");
+        return;
+    };
+    let len = ether_repr.buffer_len() + ip_repr.buffer_len() + ndisc_repr.buffer_len();
+    tx_token.consume(len, |buf| {
+        let mut frame = EthernetFrame::new_unchecked(buf);
+        ether_repr.emit(&mut frame);
+        let mut ip_packet = Ipv6Packet::new_unchecked(frame.payload_mut());
+        ip_repr.emit(&mut ip_packet);
+        let mut icmp_packet = Icmpv6Packet::new_unchecked(ip_packet.payload_mut());
+        ndisc_repr.emit(
+            &ip_repr.src_addr.into(),
+            &ip_repr.dst_addr.into(),
+            &mut icmp_packet,
+            &ChecksumCapabilities::default(),
+        );
+    });
+}
+
+/// Records the autoconfiguration prefix if `ip_packet` is a Router
+/// Advertisement received on the interface with index `iface`.
+pub fn snoop_router_advert(
+    ip_packet: &Ipv6Packet<&[u8]>,
+    iface: usize,
+) -> Result<(), smoltcp::wire::Error> {
+    if !PENDING_ADVERTS.lock().contains_key(&iface) {
+        return Ok(());
+    } else if ip_packet.next_header() != IpProtocol::Icmpv6 || ip_packet.hop_limit() != 255 {
+        return Ok(()); // not a valid neighbor discovery message
+    }
+    let ip_repr = Ipv6Repr::parse(ip_packet)?;
+    let icmp_packet = Icmpv6Packet::new_checked(ip_packet.payload())?;
+    let icmp_repr = Icmpv6Repr::parse(
+        &ip_repr.src_addr.into(),
+        &ip_repr.dst_addr.into(),
+        &icmp_packet,
+        &ChecksumCapabilities::default(),
+    )?;
+    if let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+        prefix_info: Some(info),
+        ..
+    }) = icmp_repr
+    {
+        if info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            && info.prefix_len == SLAAC_PREFIX_LEN
+            && !info.prefix.is_link_local()
+            && info.valid_lifetime.total_millis() > 0
+        {
+            PENDING_ADVERTS
+                .lock()
+                .insert(iface, Some((info.prefix, ip_repr.src_addr)));
+        }
+    }
+    Ok(())
+}
+
+/// Configures the address and the default route of the interface with index
+/// `index` from the last Router Advertisement received on it, if any.
+pub fn apply_router_advert(iface: &mut Interface, index: usize, ether_addr: EthernetAddress) {
+    let Some((prefix, router)) = PENDING_ADVERTS
+        .lock()
+        .get_mut(&index)
+        .and_then(|pending| pending.take())
+    else {
+        return;
+    };
+    let addr = IpAddress::Ipv6(eui64_addr(prefix, ether_addr));
+    if !iface.has_ip_addr(addr) {
+        let mut added = false;
+        iface.update_ip_addrs(|ip_addrs| {
+            added = ip_addrs.push(IpCidr::new(addr, SLAAC_PREFIX_LEN)).is_ok();
+        });
+        if !added {
+            warn!("SLAAC: no room for address {}/{}", addr, SLAAC_PREFIX_LEN);
+            return;
+        }
+        info!("SLAAC: configured address {}/{}", addr, SLAAC_PREFIX_LEN);
+    }
+    route::add(IpCidr::new(addr, SLAAC_PREFIX_LEN), index);
+    route::add(IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0), index);
+    let routes = iface.routes_mut();
+    let has_default = routes.remove_default_ipv6_route().is_some();
+    routes.add_default_ipv6_route(router).ok();
+    if !has_default {
+        info!("SLAAC: default gateway {}", router);
+    }
+}
@@ -1,17 +1,25 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::Waker;
+use core::time::Duration;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
 use axsync::Mutex;
+use spin::RwLock;
 
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, State};
+use smoltcp::socket::Socket;
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
-use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
+use super::addr::{
+    from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT, UNSPECIFIED_IP,
+};
+use super::event::SocketWaiter;
+use super::{egress_iface, iface_sockets, poll_interfaces, SocketSetWrapper, IFACES, LISTEN_TABLE};
+use super::{TCP_RX_BUF_LEN, TCP_TX_BUF_LEN};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -25,6 +33,60 @@ const STATE_CONNECTING: u8 = 2;
 const STATE_CONNECTED: u8 = 3;
 const STATE_LISTENING: u8 = 4;
 
+/// The number of unanswered keep-alive packets after which the connection is
+/// aborted, the same as the default of `TCP_KEEPCNT` on Linux.
+const KEEP_ALIVE_PROBES: u32 = 9;
+
+/// The options of a TCP socket.
+///
+/// The buffer sizes take effect when the smoltcp socket is created, i.e., on
+/// `connect()`, or when a connection comes in to a listening socket. The
+/// sockets accepted from a listening socket inherit its options.
+#[derive(Clone, Copy)]
+pub struct TcpOptions {
+    rx_buf_len: usize,
+    tx_buf_len: usize,
+    nodelay: bool,
+    keep_alive: Option<Duration>,
+    linger: Option<Duration>,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    reuse_addr: bool,
+}
+
+impl TcpOptions {
+    const fn new() -> Self {
+        Self {
+            rx_buf_len: TCP_RX_BUF_LEN,
+            tx_buf_len: TCP_TX_BUF_LEN,
+            nodelay: false,
+            keep_alive: None,
+            linger: None,
+            recv_timeout: None,
+            send_timeout: None,
+            reuse_addr: false,
+        }
+    }
+
+    /// Creates a smoltcp socket with these options.
+    pub fn new_socket(&self) -> tcp::Socket<'static> {
+        let mut socket = SocketSetWrapper::new_tcp_socket(self.rx_buf_len, self.tx_buf_len);
+        self.apply(&mut socket);
+        socket
+    }
+
+    /// Applies the options that can be changed on an existing smoltcp socket.
+    fn apply(&self, socket: &mut tcp::Socket) {
+        socket.set_nagle_enabled(!self.nodelay);
+        socket.set_keep_alive(self.keep_alive.map(into_smoltcp_duration));
+        // abort the connection if the peer stops responding
+        socket.set_timeout(
+            self.keep_alive
+                .map(|interval| into_smoltcp_duration(interval * KEEP_ALIVE_PROBES)),
+        );
+    }
+}
+
 /// A TCP socket that provides POSIX-like APIs.
 ///
 /// - [`connect`] is for TCP clients.
@@ -37,16 +99,23 @@ const STATE_LISTENING: u8 = 4;
 /// [`accept`]: TcpSocket::accept
 pub struct TcpSocket {
     state: AtomicU8,
-    handle: UnsafeCell<Option<SocketHandle>>,
+    /// The index of the interface and the handle of the smoltcp socket on it.
+    handle: UnsafeCell<Option<(usize, SocketHandle)>>,
     local_addr: UnsafeCell<IpEndpoint>,
     peer_addr: UnsafeCell<IpEndpoint>,
     nonblock: AtomicBool,
+    opts: RwLock<TcpOptions>,
+    /// The pending error of a failed nonblocking `connect()`, reported by
+    /// `SO_ERROR`.
+    error: Mutex<Option<AxError>>,
+    waiter: SocketWaiter,
 }
 
 unsafe impl Sync for TcpSocket {}
 
 impl TcpSocket {
     /// Creates a new TCP socket.
+    #[allow(clippy::new_without_default)]
     pub const fn new() -> Self {
         Self {
             state: AtomicU8::new(STATE_CLOSED),
@@ -54,21 +123,32 @@ impl TcpSocket {
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             nonblock: AtomicBool::new(false),
+            opts: RwLock::new(TcpOptions::new()),
+            error: Mutex::new(None),
+            waiter: SocketWaiter::new(),
         }
     }
 
-    /// Creates a new TCP socket that is already connected.
-    const fn new_connected(
+    /// Creates a new TCP socket that is already connected, with the options
+    /// inherited from the listening socket.
+    fn new_connected(
+        iface: usize,
         handle: SocketHandle,
         local_addr: IpEndpoint,
         peer_addr: IpEndpoint,
+        opts: TcpOptions,
     ) -> Self {
+        iface_sockets(iface)
+            .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| opts.apply(socket));
         Self {
             state: AtomicU8::new(STATE_CONNECTED),
-            handle: UnsafeCell::new(Some(handle)),
+            handle: UnsafeCell::new(Some((iface, handle))),
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
             nonblock: AtomicBool::new(false),
+            opts: RwLock::new(opts),
+            error: Mutex::new(None),
+            waiter: SocketWaiter::new(),
         }
     }
 
@@ -115,23 +195,193 @@ impl TcpSocket {
         self.nonblock.store(nonblocking, Ordering::Release);
     }
 
+    /// Returns whether the Nagle algorithm is disabled (`TCP_NODELAY`).
+    pub fn nodelay(&self) -> bool {
+        self.opts.read().nodelay
+    }
+
+    /// Disables or enables the Nagle algorithm (`TCP_NODELAY`).
+    ///
+    /// If set, small segments are sent out immediately instead of being
+    /// coalesced until the outstanding data is acknowledged.
+    pub fn set_nodelay(&self, nodelay: bool) {
+        self.update_opts(|opts| opts.nodelay = nodelay);
+    }
+
+    /// Returns the interval of the keep-alive packets (`SO_KEEPALIVE`), or
+    /// `None` if they are disabled.
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.opts.read().keep_alive
+    }
+
+    /// Sends keep-alive packets at the given interval when the connection is
+    /// idle (`SO_KEEPALIVE`), or disables them with `None`.
+    ///
+    /// The connection is aborted if the peer does not respond to 9 keep-alive
+    /// packets in a row.
+    pub fn set_keep_alive(&self, interval: Option<Duration>) -> AxResult {
+        if interval == Some(Duration::ZERO) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_keep_alive() failed: zero interval"
+            );
+        }
+        self.update_opts(|opts| opts.keep_alive = interval);
+        Ok(())
+    }
+
+    /// Returns the `SO_LINGER` option.
+    pub fn linger(&self) -> Option<Duration> {
+        self.opts.read().linger
+    }
+
+    /// Sets the `SO_LINGER` option, which controls how [`shutdown`] handles
+    /// the data not yet sent:
+    ///
+    /// - `None`: it returns immediately, and the data are sent in the
+    ///   background.
+    /// - `Some(Duration::ZERO)`: the data are discarded, and the connection is
+    ///   reset.
+    /// - `Some(timeout)`: it blocks until the data are sent and acknowledged,
+    ///   or the timeout expires.
+    ///
+    /// [`shutdown`]: Self::shutdown
+    pub fn set_linger(&self, linger: Option<Duration>) {
+        self.opts.write().linger = linger;
+    }
+
+    /// Returns the timeout of [`recv`](Self::recv) and
+    /// [`accept`](Self::accept) (`SO_RCVTIMEO`).
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        self.opts.read().recv_timeout
+    }
+
+    /// Sets the timeout of [`recv`](Self::recv) and [`accept`](Self::accept)
+    /// (`SO_RCVTIMEO`), after which they fail with
+    /// [`Err(WouldBlock)`](AxError::WouldBlock). `None` means blocking
+    /// indefinitely.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout == Some(Duration::ZERO) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_recv_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.write().recv_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the timeout of [`send`](Self::send) (`SO_SNDTIMEO`).
+    pub fn send_timeout(&self) -> Option<Duration> {
+        self.opts.read().send_timeout
+    }
+
+    /// Sets the timeout of [`send`](Self::send) (`SO_SNDTIMEO`), after which
+    /// it fails with [`Err(WouldBlock)`](AxError::WouldBlock). `None` means
+    /// blocking indefinitely.
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout == Some(Duration::ZERO) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_send_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.write().send_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the size of the receive buffer (`SO_RCVBUF`).
+    pub fn recv_buffer_size(&self) -> usize {
+        match self.connected_handle() {
+            Some((iface, handle)) => iface_sockets(iface)
+                .with_socket::<tcp::Socket, _, _>(handle, |socket| socket.recv_capacity()),
+            None => self.opts.read().rx_buf_len,
+        }
+    }
+
+    /// Sets the size of the receive buffer (`SO_RCVBUF`).
+    ///
+    /// It does not resize the buffer of an established connection.
+    pub fn set_recv_buffer_size(&self, size: usize) -> AxResult {
+        if size == 0 {
+            return ax_err!(InvalidInput, "socket set_recv_buffer_size() failed");
+        }
+        self.opts.write().rx_buf_len = size;
+        Ok(())
+    }
+
+    /// Returns the size of the send buffer (`SO_SNDBUF`).
+    pub fn send_buffer_size(&self) -> usize {
+        match self.connected_handle() {
+            Some((iface, handle)) => iface_sockets(iface)
+                .with_socket::<tcp::Socket, _, _>(handle, |socket| socket.send_capacity()),
+            None => self.opts.read().tx_buf_len,
+        }
+    }
+
+    /// Sets the size of the send buffer (`SO_SNDBUF`).
+    ///
+    /// It does not resize the buffer of an established connection.
+    pub fn set_send_buffer_size(&self, size: usize) -> AxResult {
+        if size == 0 {
+            return ax_err!(InvalidInput, "socket set_send_buffer_size() failed");
+        }
+        self.opts.write().tx_buf_len = size;
+        Ok(())
+    }
+
+    /// Returns whether [`bind`](Self::bind) may reuse a local port that is
+    /// still used by other connections (`SO_REUSEADDR`).
+    pub fn reuse_address(&self) -> bool {
+        self.opts.read().reuse_addr
+    }
+
+    /// Allows [`bind`](Self::bind) to reuse a local port that is still used
+    /// by other connections, e.g., in the `TIME-WAIT` state (`SO_REUSEADDR`).
+    ///
+    /// A port with a listening socket can never be reused.
+    pub fn set_reuse_address(&self, reuse: bool) {
+        self.opts.write().reuse_addr = reuse;
+    }
+
+    /// Takes the pending error of the socket (`SO_ERROR`), which is set when a
+    /// nonblocking [`connect`](Self::connect) fails.
+    pub fn take_error(&self) -> Option<AxError> {
+        if self.is_connecting() {
+            self.poll_connect().ok(); // check whether the connection failed
+        }
+        self.error.lock().take()
+    }
+
     /// Connects to the given address and port.
     ///
     /// The local port is generated automatically.
     pub fn connect(&self, remote_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CONNECTING, || {
-            // SAFETY: no other threads can read or write these fields.
-            let handle = unsafe { self.handle.get().read() }
-                .unwrap_or_else(|| SOCKET_SET.add(SocketSetWrapper::new_tcp_socket()));
-
-            // TODO: check remote addr unreachable
             let remote_endpoint = from_core_sockaddr(remote_addr);
             let bound_endpoint = self.bound_endpoint()?;
-            let iface = &ETH0.iface;
-            let (local_endpoint, remote_endpoint) = SOCKET_SET
+            let local_addr = bound_endpoint.addr.unwrap_or(UNSPECIFIED_IP);
+            let iface = egress_iface(local_addr, remote_endpoint.addr)?;
+
+            // SAFETY: no other threads can read or write these fields.
+            let handle = match unsafe { self.handle.get().read() } {
+                Some((old_iface, handle)) if old_iface == iface => handle,
+                old => {
+                    // the socket of a failed connection on another interface
+                    if let Some((old_iface, handle)) = old {
+                        iface_sockets(old_iface).remove(handle);
+                    }
+                    let handle = iface_sockets(iface).add(self.opts.read().new_socket());
+                    unsafe { self.handle.get().write(Some((iface, handle))) };
+                    handle
+                }
+            };
+
+            let context = &IFACES[iface].iface;
+            let (local_endpoint, remote_endpoint) = iface_sockets(iface)
                 .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                     socket
-                        .connect(iface.lock().context(), remote_endpoint, bound_endpoint)
+                        .connect(context.lock().context(), remote_endpoint, bound_endpoint)
                         .or_else(|e| match e {
                             ConnectError::InvalidState => {
                                 ax_err!(BadState, "socket connect() failed")
@@ -150,7 +400,6 @@ impl TcpSocket {
                 // have changed the state to `BUSY`.
                 self.local_addr.get().write(local_endpoint);
                 self.peer_addr.get().write(remote_endpoint);
-                self.handle.get().write(Some(handle));
             }
             Ok(())
         })
@@ -158,15 +407,24 @@ impl TcpSocket {
 
         // Here our state must be `CONNECTING`, and only one thread can run here.
         if self.is_nonblocking() {
+            poll_interfaces(); // send the SYN packet
             Err(AxError::WouldBlock)
         } else {
-            self.block_on(|| {
+            // SAFETY: `self.handle` should be initialized above.
+            let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+            let waker = self.waiter.waker();
+            self.block_on(None, || {
+                // woken up on state changes
+                iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_send_waker(&waker)
+                });
                 let PollState { writable, .. } = self.poll_connect()?;
                 if !writable {
                     Err(AxError::WouldBlock)
                 } else if self.get_state() == STATE_CONNECTED {
                     Ok(())
                 } else {
+                    self.error.lock().take(); // reported here instead
                     ax_err!(ConnectionRefused, "socket connect() failed")
                 }
             })
@@ -181,9 +439,12 @@ impl TcpSocket {
     /// [`accept`](Self::accept).
     pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
         self.update_state(STATE_CLOSED, STATE_CLOSED, || {
-            // TODO: check addr is available
             if local_addr.port() == 0 {
                 local_addr.set_port(get_ephemeral_port()?);
+            } else if !LISTEN_TABLE.can_listen(local_addr.port())
+                || (!self.reuse_address() && is_port_in_use(local_addr.port()))
+            {
+                return ax_err!(AddrInUse, "socket bind() failed");
             }
             // SAFETY: no other threads can read or write `self.local_addr` as we
             // have changed the state to `BUSY`.
@@ -209,7 +470,7 @@ impl TcpSocket {
             unsafe {
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            LISTEN_TABLE.listen(bound_endpoint, *self.opts.read())?;
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
@@ -229,29 +490,53 @@ impl TcpSocket {
 
         // SAFETY: `self.local_addr` should be initialized after `bind()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
-            let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
+        let waker = self.waiter.waker();
+        self.block_on(self.recv_timeout(), || {
+            // woken up when any incoming connection is established
+            LISTEN_TABLE.register_waker(local_port, &waker);
+            let (iface, handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
             debug!("TCP socket accepted a new connection {}", peer_addr);
-            Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
+            Ok(TcpSocket::new_connected(
+                iface,
+                handle,
+                local_addr,
+                peer_addr,
+                *self.opts.read(),
+            ))
         })
     }
 
     /// Close the connection.
+    ///
+    /// How the data not yet sent are handled depends on the
+    /// [`linger`](Self::set_linger) option.
     pub fn shutdown(&self) -> AxResult {
         // stream
-        self.update_state(STATE_CONNECTED, STATE_CLOSED, || {
-            // SAFETY: `self.handle` should be initialized in a connected socket, and
-            // no other threads can read or write it.
-            let handle = unsafe { self.handle.get().read().unwrap() };
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                debug!("TCP socket {}: shutting down", handle);
-                socket.close();
-            });
-            unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
-            SOCKET_SET.poll_interfaces();
-            Ok(())
-        })
-        .unwrap_or(Ok(()))?;
+        let linger = self.linger();
+        let closed = self
+            .update_state(STATE_CONNECTED, STATE_CLOSED, || {
+                // SAFETY: `self.handle` should be initialized in a connected socket, and
+                // no other threads can read or write it.
+                let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+                iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    if linger == Some(Duration::ZERO) {
+                        debug!("TCP socket {}: aborting", handle);
+                        socket.abort();
+                    } else {
+                        debug!("TCP socket {}: shutting down", handle);
+                        socket.close();
+                    }
+                });
+                unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
+                poll_interfaces();
+                Ok(Some((iface, handle)))
+            })
+            .unwrap_or(Ok(None))?;
+        if let (Some((iface, handle)), Some(timeout)) = (closed, linger) {
+            if !timeout.is_zero() {
+                self.wait_for_sent(iface, handle, timeout);
+            }
+        }
 
         // listener
         self.update_state(STATE_LISTENING, STATE_CLOSED, || {
@@ -260,7 +545,7 @@ impl TcpSocket {
             let local_port = unsafe { self.local_addr.get().read().port };
             unsafe { self.local_addr.get().write(UNSPECIFIED_ENDPOINT) }; // clear bound address
             LISTEN_TABLE.unlisten(local_port);
-            SOCKET_SET.poll_interfaces();
+            poll_interfaces();
             Ok(())
         })
         .unwrap_or(Ok(()))?;
@@ -278,9 +563,10 @@ impl TcpSocket {
         }
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
-        let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+        let waker = self.waiter.waker();
+        self.block_on(self.recv_timeout(), || {
+            iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() {
                     // not open
                     ax_err!(ConnectionRefused, "socket recv() failed")
@@ -296,6 +582,7 @@ impl TcpSocket {
                     Ok(len)
                 } else {
                     // no more data
+                    socket.register_recv_waker(&waker);
                     Err(AxError::WouldBlock)
                 }
             })
@@ -311,9 +598,10 @@ impl TcpSocket {
         }
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
-        let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+        let waker = self.waiter.waker();
+        let len = self.block_on(self.send_timeout(), || {
+            iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                 if !socket.is_active() || !socket.may_send() {
                     // closed by remote
                     ax_err!(ConnectionReset, "socket send() failed")
@@ -326,10 +614,13 @@ impl TcpSocket {
                     Ok(len)
                 } else {
                     // tx buffer is full
+                    socket.register_send_waker(&waker);
                     Err(AxError::WouldBlock)
                 }
             })
-        })
+        })?;
+        poll_interfaces(); // send the data out
+        Ok(len)
     }
 
     /// Whether the socket is readable or writable.
@@ -341,9 +632,39 @@ impl TcpSocket {
             _ => Ok(PollState {
                 readable: false,
                 writable: false,
+                hangup: false,
             }),
         }
     }
+
+    /// Registers `waker` to be woken once when the socket may become readable
+    /// or writable, i.e., when the result of [`poll`](Self::poll) may change.
+    ///
+    /// Returns `false` if the network stack is not interrupt-driven, then the
+    /// caller should poll the socket repeatedly instead.
+    pub fn register_waker(&self, waker: &Waker) -> bool {
+        if !self.waiter.subscribe(waker) {
+            return false;
+        }
+        let own_waker = self.waiter.waker();
+        match self.get_state() {
+            STATE_CONNECTING | STATE_CONNECTED => {
+                if let Some((iface, handle)) = self.connected_handle() {
+                    iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                        socket.register_recv_waker(&own_waker);
+                        socket.register_send_waker(&own_waker);
+                    });
+                }
+            }
+            STATE_LISTENING => {
+                // SAFETY: `self.local_addr` should be initialized in a listening socket.
+                let local_port = unsafe { self.local_addr.get().read().port };
+                LISTEN_TABLE.register_waker(local_port, &own_waker);
+            }
+            _ => {}
+        }
+        true
+    }
 }
 
 /// Private methods
@@ -402,6 +723,44 @@ impl TcpSocket {
         self.get_state() == STATE_LISTENING
     }
 
+    /// Returns the smoltcp socket if connecting or connected.
+    fn connected_handle(&self) -> Option<(usize, SocketHandle)> {
+        match self.get_state() {
+            // SAFETY: `self.handle` should be initialized in these states.
+            STATE_CONNECTING | STATE_CONNECTED => unsafe { self.handle.get().read() },
+            _ => None,
+        }
+    }
+
+    /// Updates the options, and applies them to the smoltcp socket if any.
+    fn update_opts(&self, f: impl FnOnce(&mut TcpOptions)) {
+        let mut opts = self.opts.write();
+        f(&mut opts);
+        if let Some((iface, handle)) = self.connected_handle() {
+            iface_sockets(iface)
+                .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| opts.apply(socket));
+        }
+    }
+
+    /// Blocks until the data in the send buffer are sent and acknowledged, or
+    /// the timeout expires, for the `SO_LINGER` option.
+    fn wait_for_sent(&self, iface: usize, handle: SocketHandle, timeout: Duration) {
+        let waker = self.waiter.waker();
+        let res = self.block_on(Some(timeout), || {
+            iface_sockets(iface).with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                if !socket.is_active() || socket.send_queue() == 0 {
+                    Ok(())
+                } else {
+                    socket.register_send_waker(&waker);
+                    Err(AxError::WouldBlock)
+                }
+            })
+        });
+        if res.is_err() {
+            debug!("TCP socket {}: linger timed out", handle);
+        }
+    }
+
     fn bound_endpoint(&self) -> AxResult<IpListenEndpoint> {
         // SAFETY: no other threads can read or write `self.local_addr`.
         let local_addr = unsafe { self.local_addr.get().read() };
@@ -421,9 +780,9 @@ impl TcpSocket {
 
     fn poll_connect(&self) -> AxResult<PollState> {
         // SAFETY: `self.handle` should be initialized above.
-        let handle = unsafe { self.handle.get().read().unwrap() };
-        let writable =
-            SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| match socket.state() {
+        let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+        let writable = iface_sockets(iface).with_socket::<tcp::Socket, _, _>(handle, |socket| {
+            match socket.state() {
                 State::SynSent => false, // wait for connection
                 State::Established => {
                     self.set_state(STATE_CONNECTED); // connected
@@ -439,23 +798,27 @@ impl TcpSocket {
                         self.local_addr.get().write(UNSPECIFIED_ENDPOINT);
                         self.peer_addr.get().write(UNSPECIFIED_ENDPOINT);
                     }
+                    *self.error.lock() = Some(AxError::ConnectionRefused);
                     self.set_state(STATE_CLOSED); // connection failed
                     true
                 }
-            });
+            }
+        });
         Ok(PollState {
             readable: false,
             writable,
+            hangup: false,
         })
     }
 
     fn poll_stream(&self) -> AxResult<PollState> {
         // SAFETY: `self.handle` should be initialized in a connected socket.
-        let handle = unsafe { self.handle.get().read().unwrap() };
-        SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+        let (iface, handle) = unsafe { self.handle.get().read().unwrap() };
+        iface_sockets(iface).with_socket::<tcp::Socket, _, _>(handle, |socket| {
             Ok(PollState {
                 readable: !socket.may_recv() || socket.can_recv(),
                 writable: !socket.may_send() || socket.can_send(),
+                hangup: !socket.may_recv() && !socket.may_send(),
             })
         })
     }
@@ -466,6 +829,7 @@ impl TcpSocket {
         Ok(PollState {
             readable: LISTEN_TABLE.can_accept(local_addr.port)?,
             writable: false,
+            hangup: false,
         })
     }
 
@@ -474,18 +838,31 @@ impl TcpSocket {
     /// If the socket is non-blocking, it calls the function once and returns
     /// immediately. Otherwise, it may call the function multiple times if it
     /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    ///
+    /// Before returning [`Err(WouldBlock)`](AxError::WouldBlock), the function
+    /// should register the waker of `self.waiter` to the sockets it waits for,
+    /// so that the current thread sleeps until they are ready.
+    ///
+    /// If `timeout` is given, it returns [`Err(WouldBlock)`](AxError::WouldBlock)
+    /// when the function still fails after the timeout expires.
+    fn block_on<F, T>(&self, timeout: Option<Duration>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
             f()
         } else {
+            let deadline = timeout.map(|t| axhal::time::current_time() + t);
             loop {
-                SOCKET_SET.poll_interfaces();
+                poll_interfaces();
+                let events = self.waiter.events();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if !self.waiter.wait_timeout(events, deadline) {
+                            return Err(AxError::WouldBlock);
+                        }
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -497,12 +874,32 @@ impl Drop for TcpSocket {
     fn drop(&mut self) {
         self.shutdown().ok();
         // Safe because we have mut reference to `self`.
-        if let Some(handle) = unsafe { self.handle.get().read() } {
-            SOCKET_SET.remove(handle);
+        if let Some((iface, handle)) = unsafe { self.handle.get().read() } {
+            iface_sockets(iface).remove(handle);
         }
     }
 }
 
+/// Whether any TCP connection, including the ones in the `TIME-WAIT` state,
+/// uses the given local port.
+fn is_port_in_use(port: u16) -> bool {
+    IFACES.iter().any(|iface| {
+        iface
+            .sockets
+            .0
+            .lock()
+            .iter()
+            .any(|(_, socket)| match socket {
+                Socket::Tcp(socket) => socket.local_endpoint().is_some_and(|e| e.port == port),
+                _ => false,
+            })
+    })
+}
+
+fn into_smoltcp_duration(d: Duration) -> smoltcp::time::Duration {
+    smoltcp::time::Duration::from_micros(d.as_micros() as u64)
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
@@ -1,5 +1,8 @@
+use alloc::{vec, vec::Vec};
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+use core::time::Duration;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
@@ -8,30 +11,54 @@ use spin::RwLock;
 
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::udp::{self, BindError, SendError};
+use smoltcp::socket::Socket;
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
-use super::{SocketSetWrapper, SOCKET_SET};
+use super::event::SocketWaiter;
+use super::{
+    egress_iface, iface_of_addr, iface_sockets, poll_interfaces, SocketSetWrapper, IFACES,
+};
+use super::{UDP_RX_BUF_LEN, UDP_TX_BUF_LEN};
+
+/// The options of a UDP socket. The buffer sizes take effect on `bind()`.
+struct UdpOptions {
+    rx_buf_len: usize,
+    tx_buf_len: usize,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    reuse_addr: bool,
+}
 
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
-    handle: SocketHandle,
+    /// The smoltcp sockets, one on each interface that the socket is bound to,
+    /// with the index of the interface. They are created in `bind()`.
+    handles: RwLock<Vec<(usize, SocketHandle)>>,
     local_addr: RwLock<Option<IpEndpoint>>,
     peer_addr: RwLock<Option<IpEndpoint>>,
     nonblock: AtomicBool,
+    opts: RwLock<UdpOptions>,
+    waiter: SocketWaiter,
 }
 
 impl UdpSocket {
     /// Creates a new UDP socket.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_udp_socket();
-        let handle = SOCKET_SET.add(socket);
         Self {
-            handle,
+            handles: RwLock::new(Vec::new()),
             local_addr: RwLock::new(None),
             peer_addr: RwLock::new(None),
             nonblock: AtomicBool::new(false),
+            opts: RwLock::new(UdpOptions {
+                rx_buf_len: UDP_RX_BUF_LEN,
+                tx_buf_len: UDP_TX_BUF_LEN,
+                recv_timeout: None,
+                send_timeout: None,
+                reuse_addr: false,
+            }),
+            waiter: SocketWaiter::new(),
         }
     }
 
@@ -69,34 +96,150 @@ impl UdpSocket {
         self.nonblock.store(nonblocking, Ordering::Release);
     }
 
+    /// Returns the timeout of [`recv`](Self::recv) and
+    /// [`recv_from`](Self::recv_from) (`SO_RCVTIMEO`).
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        self.opts.read().recv_timeout
+    }
+
+    /// Sets the timeout of [`recv`](Self::recv) and
+    /// [`recv_from`](Self::recv_from) (`SO_RCVTIMEO`), after which they fail
+    /// with [`Err(WouldBlock)`](AxError::WouldBlock). `None` means blocking
+    /// indefinitely.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout == Some(Duration::ZERO) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_recv_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.write().recv_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the timeout of [`send`](Self::send) and
+    /// [`send_to`](Self::send_to) (`SO_SNDTIMEO`).
+    pub fn send_timeout(&self) -> Option<Duration> {
+        self.opts.read().send_timeout
+    }
+
+    /// Sets the timeout of [`send`](Self::send) and [`send_to`](Self::send_to)
+    /// (`SO_SNDTIMEO`), after which they fail with
+    /// [`Err(WouldBlock)`](AxError::WouldBlock). `None` means blocking
+    /// indefinitely.
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) -> AxResult {
+        if timeout == Some(Duration::ZERO) {
+            return ax_err!(
+                InvalidInput,
+                "socket set_send_timeout() failed: zero timeout"
+            );
+        }
+        self.opts.write().send_timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the size of the receive buffer (`SO_RCVBUF`).
+    pub fn recv_buffer_size(&self) -> usize {
+        match self.handles.read().first() {
+            Some(&(iface, handle)) => iface_sockets(iface)
+                .with_socket::<udp::Socket, _, _>(handle, |socket| socket.payload_recv_capacity()),
+            None => self.opts.read().rx_buf_len,
+        }
+    }
+
+    /// Sets the size of the receive buffer (`SO_RCVBUF`).
+    ///
+    /// It does not resize the buffer of a bound socket.
+    pub fn set_recv_buffer_size(&self, size: usize) -> AxResult {
+        if size == 0 {
+            return ax_err!(InvalidInput, "socket set_recv_buffer_size() failed");
+        }
+        self.opts.write().rx_buf_len = size;
+        Ok(())
+    }
+
+    /// Returns the size of the send buffer (`SO_SNDBUF`).
+    pub fn send_buffer_size(&self) -> usize {
+        match self.handles.read().first() {
+            Some(&(iface, handle)) => iface_sockets(iface)
+                .with_socket::<udp::Socket, _, _>(handle, |socket| socket.payload_send_capacity()),
+            None => self.opts.read().tx_buf_len,
+        }
+    }
+
+    /// Sets the size of the send buffer (`SO_SNDBUF`).
+    ///
+    /// It does not resize the buffer of a bound socket.
+    pub fn set_send_buffer_size(&self, size: usize) -> AxResult {
+        if size == 0 {
+            return ax_err!(InvalidInput, "socket set_send_buffer_size() failed");
+        }
+        self.opts.write().tx_buf_len = size;
+        Ok(())
+    }
+
+    /// Returns whether [`bind`](Self::bind) may reuse a local port that is
+    /// bound by other UDP sockets (`SO_REUSEADDR`).
+    pub fn reuse_address(&self) -> bool {
+        self.opts.read().reuse_addr
+    }
+
+    /// Allows [`bind`](Self::bind) to reuse a local port that is bound by
+    /// other UDP sockets (`SO_REUSEADDR`).
+    pub fn set_reuse_address(&self, reuse: bool) {
+        self.opts.write().reuse_addr = reuse;
+    }
+
     /// Binds an unbound socket to the given address and port.
     ///
+    /// If the address is unspecified, it receives datagrams from all
+    /// interfaces, otherwise only from the interface that owns the address.
+    ///
     /// It's must be called before [`send_to`](Self::send_to) and
     /// [`recv_from`](Self::recv_from).
     pub fn bind(&self, mut local_addr: SocketAddr) -> AxResult {
         let mut self_local_addr = self.local_addr.write();
 
-        if local_addr.port() == 0 {
-            local_addr.set_port(get_ephemeral_port()?);
-        }
         if self_local_addr.is_some() {
             return ax_err!(InvalidInput, "socket bind() failed: already bound");
         }
+        let opts = self.opts.read();
+        if local_addr.port() == 0 {
+            local_addr.set_port(get_ephemeral_port()?);
+        } else if !opts.reuse_addr && is_port_in_use(local_addr.port()) {
+            return ax_err!(AddrInUse, "socket bind() failed");
+        }
 
         let local_endpoint = from_core_sockaddr(local_addr);
         let endpoint = IpListenEndpoint {
             addr: (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr),
             port: local_endpoint.port,
         };
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+        let ifaces = match endpoint.addr {
+            Some(addr) => vec![iface_of_addr(addr).ok_or_else(|| {
+                ax_err_type!(InvalidInput, "socket bind() failed: address not available")
+            })?],
+            None => (0..IFACES.len()).collect(),
+        };
+
+        let mut handles = self.handles.write();
+        for iface in ifaces {
+            let mut socket = SocketSetWrapper::new_udp_socket(opts.rx_buf_len, opts.tx_buf_len);
             socket.bind(endpoint).or_else(|e| match e {
                 BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
                 BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
-            })
-        })?;
+            })?;
+            let handle = iface_sockets(iface).add(socket);
+            debug!(
+                "UDP socket {}: bound on {} of {}",
+                handle,
+                endpoint,
+                IFACES[iface].name()
+            );
+            handles.push((iface, handle));
+        }
 
         *self_local_addr = Some(local_endpoint);
-        debug!("UDP socket {}: bound on {}", self.handle, endpoint);
         Ok(())
     }
 
@@ -142,7 +285,7 @@ impl UdpSocket {
         }
 
         *self_peer_addr = Some(from_core_sockaddr(addr));
-        debug!("UDP socket {}: connected to {}", self.handle, addr);
+        debug!("UDP socket: connected to {}", addr);
         Ok(())
     }
 
@@ -172,11 +315,13 @@ impl UdpSocket {
 
     /// Close the socket.
     pub fn shutdown(&self) -> AxResult {
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-            debug!("UDP socket {}: shutting down", self.handle);
-            socket.close();
-        });
-        SOCKET_SET.poll_interfaces();
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                debug!("UDP socket {}: shutting down", handle);
+                socket.close();
+            });
+        }
+        poll_interfaces();
         Ok(())
     }
 
@@ -186,14 +331,40 @@ impl UdpSocket {
             return Ok(PollState {
                 readable: false,
                 writable: false,
+                hangup: false,
             });
         }
-        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-            Ok(PollState {
-                readable: socket.can_recv(),
-                writable: socket.can_send(),
-            })
-        })
+        let mut state = PollState {
+            readable: false,
+            writable: true,
+            hangup: false,
+        };
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket::<udp::Socket, _, _>(handle, |socket| {
+                state.readable |= socket.can_recv();
+                state.writable &= socket.can_send();
+            });
+        }
+        Ok(state)
+    }
+
+    /// Registers `waker` to be woken once when the socket may become readable
+    /// or writable, i.e., when the result of [`poll`](Self::poll) may change.
+    ///
+    /// Returns `false` if the network stack is not interrupt-driven, then the
+    /// caller should poll the socket repeatedly instead.
+    pub fn register_waker(&self, waker: &Waker) -> bool {
+        if !self.waiter.subscribe(waker) {
+            return false;
+        }
+        let own_waker = self.waiter.waker();
+        for &(iface, handle) in self.handles.read().iter() {
+            iface_sockets(iface).with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                socket.register_recv_waker(&own_waker);
+                socket.register_send_waker(&own_waker);
+            });
+        }
+        true
     }
 }
 
@@ -207,13 +378,29 @@ impl UdpSocket {
     }
 
     fn send_impl(&self, buf: &[u8], remote_endpoint: IpEndpoint) -> AxResult<usize> {
-        if self.local_addr.read().is_none() {
+        let Some(local_endpoint) = *self.local_addr.read() else {
             return ax_err!(NotConnected, "socket send() failed");
-        }
+        };
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-                if socket.can_send() {
+        // send through the socket on the interface to the remote, or the one
+        // owning the bound address
+        let iface = egress_iface(local_endpoint.addr, remote_endpoint.addr)?;
+        let handle = self
+            .handles
+            .read()
+            .iter()
+            .find_map(|&(i, handle)| (i == iface).then_some(handle))
+            .ok_or_else(|| {
+                ax_err_type!(
+                    NetworkUnreachable,
+                    "socket send() failed: bound to another interface"
+                )
+            })?;
+
+        let waker = self.waiter.waker();
+        let len = self.block_on(self.send_timeout(), || {
+            iface_sockets(iface).with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                let res = if socket.can_send() {
                     socket
                         .send_slice(buf, remote_endpoint)
                         .map_err(|e| match e {
@@ -221,14 +408,20 @@ impl UdpSocket {
                             SendError::Unaddressable => {
                                 ax_err_type!(ConnectionRefused, "socket send() failed")
                             }
-                        })?;
-                    Ok(buf.len())
+                        })
+                        .map(|_| buf.len())
                 } else {
                     // tx buffer is full
                     Err(AxError::WouldBlock)
+                };
+                if let Err(AxError::WouldBlock) = res {
+                    socket.register_send_waker(&waker);
                 }
+                res
             })
-        })
+        })?;
+        poll_interfaces(); // send the datagram out
+        Ok(len)
     }
 
     fn recv_impl<F, T>(&self, mut op: F) -> AxResult<T>
@@ -239,31 +432,51 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-                if socket.can_recv() {
-                    // data available
-                    op(socket)
-                } else {
-                    // no more data
-                    Err(AxError::WouldBlock)
+        let handles = self.handles.read();
+        let waker = self.waiter.waker();
+        self.block_on(self.recv_timeout(), || {
+            for &(iface, handle) in handles.iter() {
+                let res =
+                    iface_sockets(iface).with_socket_mut::<udp::Socket, _, _>(handle, |socket| {
+                        let res = if socket.can_recv() {
+                            // data available
+                            op(socket)
+                        } else {
+                            // no more data
+                            Err(AxError::WouldBlock)
+                        };
+                        if let Err(AxError::WouldBlock) = res {
+                            socket.register_recv_waker(&waker);
+                        }
+                        res
+                    });
+                match res {
+                    Err(AxError::WouldBlock) => continue, // try other interfaces
+                    res => return res,
                 }
-            })
+            }
+            Err(AxError::WouldBlock)
         })
     }
 
-    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    fn block_on<F, T>(&self, timeout: Option<Duration>, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
     {
         if self.is_nonblocking() {
             f()
         } else {
+            let deadline = timeout.map(|t| axhal::time::current_time() + t);
             loop {
-                SOCKET_SET.poll_interfaces();
+                poll_interfaces();
+                let events = self.waiter.events();
                 match f() {
                     Ok(t) => return Ok(t),
-                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(AxError::WouldBlock) => {
+                        if !self.waiter.wait_timeout(events, deadline) {
+                            return Err(AxError::WouldBlock);
+                        }
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -274,10 +487,27 @@ impl UdpSocket {
 impl Drop for UdpSocket {
     fn drop(&mut self) {
         self.shutdown().ok();
-        SOCKET_SET.remove(self.handle);
+        for &(iface, handle) in self.handles.get_mut().iter() {
+            iface_sockets(iface).remove(handle);
+        }
     }
 }
 
+/// Whether any UDP socket is bound to the given local port.
+fn is_port_in_use(port: u16) -> bool {
+    IFACES.iter().any(|iface| {
+        iface
+            .sockets
+            .0
+            .lock()
+            .iter()
+            .any(|(_, socket)| match socket {
+                Socket::Udp(socket) => socket.is_open() && socket.endpoint().port == port,
+                _ => false,
+            })
+    })
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
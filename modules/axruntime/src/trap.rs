@@ -1,3 +1,5 @@
+use axhal::{mem::VirtAddr, trap::MappingFlags};
+
 struct TrapHandlerImpl;
 
 #[crate_interface::impl_interface]
@@ -10,4 +12,35 @@ impl axhal::trap::TrapHandler for TrapHandlerImpl {
             drop(guard); // rescheduling may occur when preemption is re-enabled.
         }
     }
+
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+        #[cfg(feature = "paging")]
+        if !is_user {
+            // Task stacks are populated, so faults in their region are never
+            // resolved. They are checked without locking the kernel address
+            // space, which may be held by the overflowing task.
+            if in_task_stack_region(vaddr) {
+                #[cfg(feature = "multitask")]
+                if let Some(curr) = axtask::current_may_uninit() {
+                    if curr.stack_guard_contains(vaddr) {
+                        panic!(
+                            "stack overflow in task {}: fault_vaddr={:#x}",
+                            curr.id_name(),
+                            vaddr
+                        );
+                    }
+                }
+                return false;
+            }
+            return axmm::handle_page_fault(vaddr, access_flags);
+        }
+        let _ = (vaddr, access_flags, is_user);
+        false
+    }
+}
+
+#[cfg(feature = "paging")]
+fn in_task_stack_region(vaddr: VirtAddr) -> bool {
+    let base = axconfig::TASK_STACK_REGION_BASE;
+    (base..base + axconfig::TASK_STACK_REGION_SIZE).contains(&vaddr.as_usize())
 }
@@ -2,13 +2,19 @@
 
 use alloc::{string::String, sync::Arc};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+pub(crate) use crate::run_queue::current_run_queue;
 
+#[doc(cfg(feature = "multitask"))]
+pub use crate::cpumask::CpuMask;
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task::{CurrentTask, TaskId, TaskInner};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub use crate::timers::AlarmToken;
+
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
 
@@ -62,6 +68,9 @@ pub fn current() -> CurrentTask {
 }
 
 /// Initializes the task scheduler (for the primary CPU).
+///
+/// Each CPU has its own run queue. Idle CPUs steal ready tasks from the run
+/// queues of other CPUs.
 pub fn init_scheduler() {
     info!("Initialize scheduling...");
 
@@ -84,7 +93,18 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
+}
+
+/// Registers `waker` to be woken when the `deadline` passes.
+///
+/// It is the building block of timers in async runtimes. The waker is woken
+/// in the timer interrupt handler, unless the alarm is canceled by the
+/// returned [`AlarmToken`] before that.
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn set_alarm_waker(deadline: axhal::time::TimeValue, waker: core::task::Waker) -> AlarmToken {
+    crate::timers::set_alarm_waker(deadline, waker)
 }
 
 /// Spawns a new task with the given parameters.
@@ -95,7 +115,7 @@ where
     F: FnOnce() + Send + 'static,
 {
     let task = TaskInner::new(f, name, stack_size);
-    RUN_QUEUE.lock().add_task(task.clone());
+    current_run_queue().add_task(task.clone());
     task
 }
 
@@ -122,13 +142,32 @@ where
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Sets the CPU affinity of the current task, i.e., the set of CPUs on which
+/// it is allowed to run.
+///
+/// If the current CPU is not in the set, the current task is migrated to one
+/// of the CPUs in the set immediately.
+///
+/// Returns `false` if the set does not contain any CPU.
+pub fn set_current_affinity(cpumask: CpuMask) -> bool {
+    if cpumask.is_empty() {
+        return false;
+    }
+    let rq = current_run_queue();
+    current().set_cpumask(cpumask);
+    if !cpumask.get(axhal::cpu::this_cpu_id()) {
+        rq.yield_current();
+    }
+    true
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
@@ -143,14 +182,14 @@ pub fn sleep(dur: core::time::Duration) {
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    current_run_queue().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
 /// Exits the current task.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
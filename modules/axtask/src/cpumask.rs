@@ -0,0 +1,89 @@
+use core::fmt;
+
+const _: () = assert!(axconfig::SMP <= CpuMask::MAX_CPUS, "too many CPUs");
+
+/// A set of CPUs, used to restrict on which CPUs a task is allowed to run.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CpuMask(u64);
+
+impl CpuMask {
+    /// The maximum number of CPUs that can be represented.
+    pub const MAX_CPUS: usize = u64::BITS as usize;
+
+    /// Creates an empty CPU set.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Creates a CPU set that contains all CPUs of the platform.
+    pub const fn full() -> Self {
+        if axconfig::SMP == Self::MAX_CPUS {
+            Self(u64::MAX)
+        } else {
+            Self((1 << axconfig::SMP) - 1)
+        }
+    }
+
+    /// Creates a CPU set that contains only the given CPU.
+    pub const fn one_shot(cpu_id: usize) -> Self {
+        assert!(cpu_id < axconfig::SMP);
+        Self(1 << cpu_id)
+    }
+
+    /// Creates a CPU set from the raw bits, the i-th bit indicates whether the
+    /// i-th CPU is in the set.
+    ///
+    /// Bits of non-existent CPUs are ignored.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits & Self::full().0)
+    }
+
+    /// Returns the raw bits of the CPU set.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Whether the given CPU is in the set.
+    pub const fn get(&self, cpu_id: usize) -> bool {
+        cpu_id < axconfig::SMP && self.0 & (1 << cpu_id) != 0
+    }
+
+    /// Adds (if `value` is `true`) or removes (if `value` is `false`) the
+    /// given CPU to/from the set.
+    pub fn set(&mut self, cpu_id: usize, value: bool) {
+        assert!(cpu_id < axconfig::SMP);
+        if value {
+            self.0 |= 1 << cpu_id;
+        } else {
+            self.0 &= !(1 << cpu_id);
+        }
+    }
+
+    /// Whether the set contains no CPU.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of CPUs in the set.
+    pub const fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns an iterator over the IDs of CPUs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let bits = self.0;
+        (0..axconfig::SMP).filter(move |&i| bits & (1 << i) != 0)
+    }
+}
+
+impl Default for CpuMask {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl fmt::Debug for CpuMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -4,6 +4,10 @@
 //! creation, scheduling, sleeping, termination, etc. The scheduler algorithm
 //! is configurable by cargo features.
 //!
+//! Each CPU has its own run queue, and idle CPUs steal ready tasks from the
+//! run queues of busy ones. A task can be pinned to a set of CPUs by
+//! [`set_current_affinity`].
+//!
 //! # Cargo Features
 //!
 //! - `multitask`: Enable multi-task support. If it's enabled, complex task
@@ -13,6 +17,9 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `paging`: Allocate task kernel stacks in a dedicated virtual region of the
+//!   kernel address space, with an unmapped guard page below each stack to
+//!   detect stack overflows.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -35,6 +42,7 @@ cfg_if::cfg_if! {
         extern crate log;
         extern crate alloc;
 
+        mod cpumask;
         mod run_queue;
         mod task;
         mod api;
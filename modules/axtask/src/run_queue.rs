@@ -1,14 +1,18 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kernel_guard::NoPreemptIrqSave;
 use lazy_init::LazyInit;
 use scheduler::BaseScheduler;
-use spinlock::SpinNoIrq;
+use spinlock::{SpinNoIrq, SpinRaw};
 
 use crate::task::{CurrentTask, TaskState};
-use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::{AxTask, AxTaskRef, Scheduler, TaskInner, WaitQueue};
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+#[percpu::def_percpu]
+static RUN_QUEUE: LazyInit<AxRunQueue> = LazyInit::new();
 
 // TODO: per-CPU
 static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
@@ -18,55 +22,132 @@ static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
+/// The task that was switched out by the last context switch on this CPU. Its
+/// `on_cpu` flag is cleared by the next task once the switch is finished.
+#[percpu::def_percpu]
+static PREV_TASK: usize = 0;
+
+/// The run queue of a CPU.
+///
+/// Each CPU has its own run queue, the tasks in it can only be picked by that
+/// CPU, unless they are stolen by other idle CPUs.
 pub(crate) struct AxRunQueue {
-    scheduler: Scheduler,
+    cpu_id: usize,
+    /// Number of ready tasks in the scheduler, used for load balancing.
+    nr_ready: AtomicUsize,
+    /// IRQs must be disabled before locking it, which is guaranteed by
+    /// [`CurrentRunQueueRef`].
+    scheduler: SpinRaw<Scheduler>,
+}
+
+/// A reference to the run queue of the current CPU.
+///
+/// Both IRQs and preemption are disabled until it is dropped, so the current
+/// task will not be migrated to another CPU in the meantime.
+pub(crate) struct CurrentRunQueueRef {
+    inner: &'static AxRunQueue,
+    _guard: NoPreemptIrqSave,
+}
+
+impl Deref for CurrentRunQueueRef {
+    type Target = AxRunQueue;
+    fn deref(&self) -> &Self::Target {
+        self.inner
+    }
+}
+
+/// Gets the run queue of the current CPU.
+pub(crate) fn current_run_queue() -> CurrentRunQueueRef {
+    let guard = NoPreemptIrqSave::new();
+    // Safety: IRQs and preemption are disabled.
+    let inner = unsafe { RUN_QUEUE.current_ref_raw().get_unchecked() };
+    CurrentRunQueueRef {
+        inner,
+        _guard: guard,
+    }
+}
+
+/// Gets the run queue of the given CPU, or returns [`None`] if that CPU has
+/// not initialized its run queue yet.
+fn run_queue_of(cpu_id: usize) -> Option<&'static AxRunQueue> {
+    unsafe { RUN_QUEUE.remote_ref_raw(cpu_id) }.try_get()
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
-        let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
-        let mut scheduler = Scheduler::new();
-        scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+    fn new(cpu_id: usize) -> Self {
+        Self {
+            cpu_id,
+            nr_ready: AtomicUsize::new(0),
+            scheduler: SpinRaw::new(Scheduler::new()),
+        }
+    }
+
+    /// Returns the number of ready tasks in the run queue.
+    fn nr_ready(&self) -> usize {
+        self.nr_ready.load(Ordering::Relaxed)
+    }
+
+    /// Puts a ready task into the run queue.
+    fn enqueue(&self, task: AxTaskRef) {
+        let mut scheduler = self.scheduler.lock();
+        scheduler.add_task(task);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Selects a run queue to put the given task in.
+    ///
+    /// This run queue is preferred if the task is allowed to run on this CPU,
+    /// otherwise the least loaded run queue among the allowed CPUs is chosen.
+    fn select_run_queue(&self, task: &AxTaskRef) -> &AxRunQueue {
+        let cpumask = task.cpumask();
+        if cpumask.get(self.cpu_id) {
+            return self;
+        }
+        cpumask
+            .iter()
+            .filter_map(run_queue_of)
+            .min_by_key(|rq| rq.nr_ready())
+            .unwrap_or(self)
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
-        debug!("task spawn: {}", task.id_name());
+    pub fn add_task(&self, task: AxTaskRef) {
+        let rq = self.select_run_queue(&task);
+        debug!("task spawn: {} on CPU {}", task.id_name(), rq.cpu_id);
         assert!(task.is_ready());
-        self.scheduler.add_task(task);
+        rq.enqueue(task);
     }
 
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        if !curr.is_idle() && self.scheduler.lock().task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
     }
 
-    pub fn yield_current(&mut self) {
+    pub fn yield_current(&self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
+    pub fn set_current_priority(&self, prio: isize) -> bool {
         self.scheduler
+            .lock()
             .set_priority(crate::current().as_task_ref(), prio)
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(&self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we get the reference of the current run queue, we must
+        // have both IRQs and preemption disabled. So we need to set
+        // `current_disable_count` to 1 in `can_preempt()` to obtain the
+        // preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,7 +162,7 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(&self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
@@ -91,15 +172,15 @@ impl AxRunQueue {
             axhal::misc::terminate();
         } else {
             curr.set_state(TaskState::Exited);
-            curr.notify_exit(exit_code, self);
+            curr.notify_exit(exit_code);
             EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            WAIT_FOR_EXIT.notify_one(false);
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
+    pub fn block_current<F>(&self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
     {
@@ -117,12 +198,23 @@ impl AxRunQueue {
         self.resched(false);
     }
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
-        debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
-            if resched {
+    /// Wakes up a blocked task, and puts it into a run queue.
+    ///
+    /// The task may be put into the run queue of another CPU if it's not
+    /// allowed to run on the current CPU.
+    pub fn unblock_task(&self, task: AxTaskRef, resched: bool) {
+        // Only one of the wakers (timer or `notify()`) can succeed.
+        if task.transition_state(TaskState::Blocked, TaskState::Ready) {
+            debug!("task unblock: {}", task.id_name());
+            // The task may have just been blocked on another CPU, and that CPU
+            // has not switched it out yet. Wait until it is really off the CPU
+            // before it can be picked by anyone.
+            while task.on_cpu() {
+                core::hint::spin_loop();
+            }
+            let rq = self.select_run_queue(&task);
+            rq.enqueue(task); // TODO: priority
+            if resched && core::ptr::eq(rq, self) {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
             }
@@ -130,7 +222,7 @@ impl AxRunQueue {
     }
 
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(&self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -138,8 +230,10 @@ impl AxRunQueue {
 
         let now = axhal::time::current_time();
         if now < deadline {
-            crate::timers::set_alarm_wakeup(deadline, curr.clone());
+            // Set the state before the alarm, since the timer may expire on
+            // another CPU immediately.
             curr.set_state(TaskState::Blocked);
+            crate::timers::set_alarm_wakeup(deadline, curr.clone());
             self.resched(false);
         }
     }
@@ -148,22 +242,89 @@ impl AxRunQueue {
 impl AxRunQueue {
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
-    fn resched(&mut self, preempt: bool) {
+    fn resched(&self, preempt: bool) {
         let prev = crate::current();
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                self.put_prev_task(prev.clone(), preempt);
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
+        let next = self.pick_next_task(&prev).unwrap_or_else(|| unsafe {
             // Safety: IRQs must be disabled at this time.
             IDLE_TASK.current_ref_raw().get_unchecked().clone()
         });
         self.switch_to(prev, next);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn put_prev_task(&self, prev: AxTaskRef, preempt: bool) {
+        if prev.cpumask().get(self.cpu_id) {
+            self.scheduler.lock().put_prev_task(prev, preempt);
+            self.nr_ready.fetch_add(1, Ordering::Relaxed);
+        } else {
+            // The task is no longer allowed to run on this CPU, migrate it.
+            let rq = self.select_run_queue(&prev);
+            debug!(
+                "task migrate: {}, CPU {} -> {}",
+                prev.id_name(),
+                self.cpu_id,
+                rq.cpu_id
+            );
+            rq.enqueue(prev);
+        }
+    }
+
+    /// Picks the next task to run from this run queue, or steals one from
+    /// other CPUs if this run queue is empty.
+    fn pick_next_task(&self, prev: &CurrentTask) -> Option<AxTaskRef> {
+        if let Some(task) = Self::pick_runnable(&mut self.scheduler.lock(), prev, self.cpu_id) {
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            return Some(task);
+        }
+        self.steal_task(prev)
+    }
+
+    /// Tries to steal a ready task from the run queues of other CPUs, starting
+    /// from the next CPU of this one.
+    fn steal_task(&self, prev: &CurrentTask) -> Option<AxTaskRef> {
+        for cpu_id in (self.cpu_id + 1..axconfig::SMP).chain(0..self.cpu_id) {
+            let victim = match run_queue_of(cpu_id) {
+                Some(rq) if rq.nr_ready() > 0 => rq,
+                _ => continue,
+            };
+            // Do not wait for a busy run queue, just try the next one.
+            let Some(mut scheduler) = victim.scheduler.try_lock() else {
+                continue;
+            };
+            if let Some(task) = Self::pick_runnable(&mut scheduler, prev, self.cpu_id) {
+                victim.nr_ready.fetch_sub(1, Ordering::Relaxed);
+                debug!(
+                    "task steal: {}, CPU {} -> {}",
+                    task.id_name(),
+                    cpu_id,
+                    self.cpu_id
+                );
+                return Some(task);
+            }
+        }
+        None
+    }
+
+    /// Picks the first task in `scheduler` that can run on the CPU `cpu_id`.
+    ///
+    /// Tasks that are not allowed to run on the CPU, or are still being switched
+    /// out by another CPU are skipped and left in place.
+    fn pick_runnable(
+        scheduler: &mut Scheduler,
+        prev: &CurrentTask,
+        cpu_id: usize,
+    ) -> Option<AxTaskRef> {
+        scheduler.pick_next_task_filter(|task| {
+            task.cpumask().get(cpu_id) && (!task.on_cpu() || prev.ptr_eq(task))
+        })
+    }
+
+    fn switch_to(&self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
             prev_task.id_name(),
@@ -175,6 +336,7 @@ impl AxRunQueue {
         if prev_task.ptr_eq(&next_task) {
             return;
         }
+        next_task.set_on_cpu(true);
 
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
@@ -185,12 +347,32 @@ impl AxRunQueue {
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
+            // `prev_task` is still running on this CPU until the switch is
+            // finished, the next task will clear its `on_cpu` flag.
+            PREV_TASK.write_current_raw(Arc::as_ptr(prev_task.as_task_ref()) as usize);
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            // We are now running as `prev_task` again, maybe on another CPU.
+            clear_prev_task_on_cpu();
         }
     }
 }
 
+/// Marks the previous task on this CPU as no longer running, so that it can be
+/// picked by other CPUs.
+///
+/// # Safety
+///
+/// It must be called exactly once by the next task right after a context switch,
+/// with IRQs disabled.
+pub(crate) unsafe fn clear_prev_task_on_cpu() {
+    let prev = PREV_TASK.read_current_raw() as *const AxTask;
+    if !prev.is_null() {
+        (*prev).set_on_cpu(false);
+    }
+}
+
 fn gc_entry() {
     loop {
         // Drop all exited tasks and recycle resources.
@@ -199,7 +381,7 @@ fn gc_entry() {
             // Do not do the slow drops in the critical section.
             let task = EXITED_TASKS.lock().pop_front();
             if let Some(task) = task {
-                if Arc::strong_count(&task) == 1 {
+                if Arc::strong_count(&task) == 1 && !task.on_cpu() {
                     // If I'm the last holder of the task, drop it immediately.
                     drop(task);
                 } else {
@@ -214,6 +396,8 @@ fn gc_entry() {
 }
 
 pub(crate) fn init() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
@@ -221,13 +405,20 @@ pub(crate) fn init() {
     let main_task = TaskInner::new_init("main".into());
     main_task.set_state(TaskState::Running);
 
-    RUN_QUEUE.init_by(AxRunQueue::new());
+    let rq = AxRunQueue::new(cpu_id);
+    let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
+    rq.enqueue(gc_task);
+    RUN_QUEUE.with_current(|r| r.init_by(rq));
     unsafe { CurrentTask::init_current(main_task) }
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     let idle_task = TaskInner::new_init("idle".into());
     idle_task.set_state(TaskState::Running);
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
+
+    RUN_QUEUE.with_current(|r| r.init_by(AxRunQueue::new(cpu_id)));
     unsafe { CurrentTask::init_current(idle_task) }
 }
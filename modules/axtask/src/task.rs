@@ -1,7 +1,10 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
 use core::ops::Deref;
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
-use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
+use core::{cell::UnsafeCell, fmt};
+
+#[cfg(not(feature = "paging"))]
+use core::{alloc::Layout, ptr::NonNull};
 
 #[cfg(feature = "preempt")]
 use core::sync::atomic::AtomicUsize;
@@ -12,7 +15,10 @@ use axhal::tls::TlsArea;
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
 
-use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
+#[cfg(feature = "paging")]
+use memory_addr::PAGE_SIZE_4K;
+
+use crate::{AxTask, AxTaskRef, CpuMask, WaitQueue};
 
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -38,6 +44,12 @@ pub struct TaskInner {
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
 
+    /// CPUs on which the task is allowed to run.
+    cpumask: AtomicU64,
+    /// Whether the task is running on a CPU, or is being switched out but the
+    /// context switch has not finished yet.
+    on_cpu: AtomicBool,
+
     in_wait_queue: AtomicBool,
     #[cfg(feature = "irq")]
     in_timer_list: AtomicBool,
@@ -101,6 +113,11 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Gets the set of CPUs on which the task is allowed to run.
+    pub fn cpumask(&self) -> CpuMask {
+        CpuMask::from_bits(self.cpumask.load(Ordering::Acquire))
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -109,6 +126,15 @@ impl TaskInner {
             .wait_until(|| self.state() == TaskState::Exited);
         Some(self.exit_code.load(Ordering::Acquire))
     }
+
+    /// Whether `vaddr` is in the guard page below the kernel stack of the
+    /// task, i.e., an access to it indicates a stack overflow.
+    #[cfg(feature = "paging")]
+    pub fn stack_guard_contains(&self, vaddr: VirtAddr) -> bool {
+        self.kstack
+            .as_ref()
+            .is_some_and(|kstack| kstack.guard_contains(vaddr))
+    }
 }
 
 // private methods
@@ -121,6 +147,8 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            cpumask: AtomicU64::new(CpuMask::full().bits()),
+            on_cpu: AtomicBool::new(false),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
             in_timer_list: AtomicBool::new(false),
@@ -171,6 +199,7 @@ impl TaskInner {
     pub(crate) fn new_init(name: String) -> AxTaskRef {
         let mut t = Self::new_common(TaskId::new(), name);
         t.is_init = true;
+        t.on_cpu = AtomicBool::new(true);
         if t.name == "idle" {
             t.is_idle = true;
         }
@@ -187,6 +216,20 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Atomically changes the state from `current` to `new`, returns whether
+    /// the transition succeeds.
+    #[inline]
+    pub(crate) fn transition_state(&self, current: TaskState, new: TaskState) -> bool {
+        self.state
+            .compare_exchange(
+                current as u8,
+                new as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -197,11 +240,6 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
-    #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
-    }
-
     #[inline]
     pub(crate) const fn is_init(&self) -> bool {
         self.is_init
@@ -212,6 +250,21 @@ impl TaskInner {
         self.is_idle
     }
 
+    #[inline]
+    pub(crate) fn set_cpumask(&self, cpumask: CpuMask) {
+        self.cpumask.store(cpumask.bits(), Ordering::Release)
+    }
+
+    #[inline]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release)
+    }
+
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
         self.in_wait_queue.load(Ordering::Acquire)
@@ -265,16 +318,16 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let rq = crate::current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    pub(crate) fn notify_exit(&self, exit_code: i32) {
         self.exit_code.store(exit_code, Ordering::Release);
-        self.wait_for_exit.notify_all_locked(false, rq);
+        self.wait_for_exit.notify_all(false);
     }
 
     #[inline]
@@ -299,11 +352,13 @@ impl Drop for TaskInner {
     }
 }
 
+#[cfg(not(feature = "paging"))]
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
 }
 
+#[cfg(not(feature = "paging"))]
 impl TaskStack {
     pub fn alloc(size: usize) -> Self {
         let layout = Layout::from_size_align(size, 16).unwrap();
@@ -318,12 +373,69 @@ impl TaskStack {
     }
 }
 
+#[cfg(not(feature = "paging"))]
 impl Drop for TaskStack {
     fn drop(&mut self) {
         unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
     }
 }
 
+/// A task stack in the task stack region of the kernel address space, with an
+/// unmapped guard page below it.
+///
+/// The guard page is reserved as an area without any access permission, so
+/// no other stack can be placed on it.
+#[cfg(feature = "paging")]
+struct TaskStack {
+    guard: VirtAddr,
+    size: usize,
+}
+
+#[cfg(feature = "paging")]
+impl TaskStack {
+    pub fn alloc(size: usize) -> Self {
+        use axhal::paging::MappingFlags;
+
+        let region_base = VirtAddr::from(axconfig::TASK_STACK_REGION_BASE);
+        let region_end = region_base + axconfig::TASK_STACK_REGION_SIZE;
+        let mut aspace = axmm::kernel_aspace().lock();
+        let guard = aspace
+            .find_free_area(region_base, size + PAGE_SIZE_4K)
+            .filter(|&guard| guard + PAGE_SIZE_4K + size <= region_end)
+            .expect("no free space in the task stack region");
+        aspace
+            .map_alloc(guard, PAGE_SIZE_4K, MappingFlags::empty(), false)
+            .expect("failed to reserve the stack guard page");
+        aspace
+            .map_alloc(
+                guard + PAGE_SIZE_4K,
+                size,
+                MappingFlags::READ | MappingFlags::WRITE,
+                true,
+            )
+            .expect("failed to allocate task stack");
+        Self { guard, size }
+    }
+
+    pub fn top(&self) -> VirtAddr {
+        self.guard + PAGE_SIZE_4K + self.size
+    }
+
+    pub fn guard_contains(&self, vaddr: VirtAddr) -> bool {
+        self.guard <= vaddr && vaddr < self.guard + PAGE_SIZE_4K
+    }
+}
+
+#[cfg(feature = "paging")]
+impl Drop for TaskStack {
+    fn drop(&mut self) {
+        let mut aspace = axmm::kernel_aspace().lock();
+        if let Err(e) = aspace.unmap(self.guard, PAGE_SIZE_4K + self.size) {
+            warn!("failed to free task stack at {:#x}: {:?}", self.guard, e);
+        }
+    }
+}
+
 use core::mem::ManuallyDrop;
 
 /// A wrapper of [`AxTaskRef`] as the current task.
@@ -379,8 +491,8 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // finish the context switch that was started by the previous task
+    unsafe { crate::run_queue::clear_prev_task_on_cpu() };
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
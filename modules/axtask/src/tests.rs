@@ -1,7 +1,7 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, Once};
 
-use crate::{self as axtask, current, WaitQueue};
+use crate::{self as axtask, current, CpuMask, WaitQueue};
 
 static INIT: Once = Once::new();
 static SERIAL: Mutex<()> = Mutex::new(());
@@ -128,3 +128,28 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_cpu_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let mut cpumask = CpuMask::new();
+    assert!(cpumask.is_empty());
+    assert!(!axtask::set_current_affinity(cpumask));
+
+    cpumask.set(0, true);
+    assert_eq!(cpumask, CpuMask::one_shot(0));
+    assert!(cpumask.get(0));
+    assert_eq!(cpumask.iter().collect::<Vec<_>>(), vec![0]);
+
+    let task = axtask::spawn(move || {
+        assert!(axtask::set_current_affinity(cpumask));
+        assert_eq!(current().cpumask(), cpumask);
+        axtask::yield_now();
+        assert_eq!(axhal::cpu::this_cpu_id(), 0);
+    });
+    task.join();
+    assert!(axtask::set_current_affinity(CpuMask::full()));
+    assert_eq!(current().cpumask(), CpuMask::full());
+}
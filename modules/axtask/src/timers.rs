@@ -1,34 +1,72 @@
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+
 use axhal::time::current_time;
 use lazy_init::LazyInit;
 use spinlock::SpinNoIrq;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
-use crate::{AxTaskRef, RUN_QUEUE};
+use crate::{current_run_queue, AxTaskRef};
 
 // TODO: per-CPU
-static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
+static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<AlarmEvent>>> = LazyInit::new();
+
+enum AlarmEvent {
+    /// Unblocks a sleeping task.
+    TaskWakeup(AxTaskRef),
+    /// Wakes a [`Waker`], e.g., of an async task, with the ID of its
+    /// [`AlarmToken`].
+    Waker(u64, Waker),
+}
 
-struct TaskWakeupEvent(AxTaskRef);
+/// The token of an alarm set by [`set_alarm_waker`](crate::set_alarm_waker),
+/// which can be used to cancel it.
+#[derive(Debug)]
+pub struct AlarmToken(u64);
 
-impl TimerEvent for TaskWakeupEvent {
+impl AlarmToken {
+    /// Cancels the alarm, so that the waker will not be woken. It does nothing
+    /// if the alarm has already expired.
+    pub fn cancel(self) {
+        TIMER_LIST
+            .lock()
+            .cancel(|t| matches!(t, AlarmEvent::Waker(id, _) if *id == self.0));
+    }
+}
+
+impl TimerEvent for AlarmEvent {
     fn callback(self, _now: TimeValue) {
-        let mut rq = RUN_QUEUE.lock();
-        self.0.set_in_timer_list(false);
-        rq.unblock_task(self.0, true);
+        match self {
+            AlarmEvent::TaskWakeup(task) => {
+                let rq = current_run_queue();
+                task.set_in_timer_list(false);
+                rq.unblock_task(task, true);
+            }
+            AlarmEvent::Waker(_, waker) => waker.wake(),
+        }
     }
 }
 
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
     let mut timers = TIMER_LIST.lock();
     task.set_in_timer_list(true);
-    timers.set(deadline, TaskWakeupEvent(task));
+    timers.set(deadline, AlarmEvent::TaskWakeup(task));
+}
+
+pub fn set_alarm_waker(deadline: TimeValue, waker: Waker) -> AlarmToken {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    TIMER_LIST
+        .lock()
+        .set(deadline, AlarmEvent::Waker(id, waker));
+    AlarmToken(id)
 }
 
 pub fn cancel_alarm(task: &AxTaskRef) {
     let mut timers = TIMER_LIST.lock();
     task.set_in_timer_list(false);
-    timers.cancel(|t| Arc::ptr_eq(&t.0, task));
+    timers.cancel(|t| matches!(t, AlarmEvent::TaskWakeup(t) if Arc::ptr_eq(t, task)));
 }
 
 pub fn check_events() {
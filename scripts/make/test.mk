@@ -1,8 +1,11 @@
 # Test scripts
 
 define unit_test
+  $(call run_cmd,bash,crates/axfs_ext4/resources/create_test_img.sh)
+  $(call run_cmd,bash,modules/axfs/resources/create_ext4_img.sh)
   $(call run_cmd,cargo test,-p percpu $(1) -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "ext4" -- --nocapture)
   $(call run_cmd,cargo test,--workspace --exclude "arceos-*" $(1) -- --nocapture)
 endef
 
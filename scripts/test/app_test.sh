@@ -114,15 +114,27 @@ if [ -z "$1" ]; then
         "apps/task/sleep"
         "apps/task/priority"
         "apps/task/tls"
+        "apps/task/sync"
         "apps/net/httpclient"
+        "apps/net/udpfrag"
+        "apps/net/asyncecho"
+        "apps/net/loopback"
+        "apps/net/ping"
         "apps/c/helloworld"
         "apps/c/memtest"
+        "apps/c/mmap"
         "apps/c/sqlite3"
         "apps/c/httpclient"
+        "apps/c/ipv6"
+        "apps/c/sockopt"
+        "apps/c/unix"
+        "apps/c/epoll"
+        "apps/c/poll"
         "apps/c/pthread/basic"
         "apps/c/pthread/sleep"
         "apps/c/pthread/pipe"
         "apps/c/pthread/parallel"
+        "apps/c/pthread/sync"
     )
 else
     test_list="$@"
@@ -1,7 +1,26 @@
+#include <stdarg.h>
 #include <stddef.h>
 #include <stdio.h>
 #include <sys/mman.h>
 
+#ifdef AX_CONFIG_MMAP
+
+void *ax_mremap(void *old_address, size_t old_size, size_t new_size, int flags);
+
+void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
+             ... /* void *new_address */)
+{
+    return ax_mremap(old_address, old_size, new_size, flags);
+}
+
+int madvise(void *addr, size_t len, int advice)
+{
+    // the advice is only a hint, ignore it
+    return 0;
+}
+
+#else // AX_CONFIG_MMAP
+
 // TODO:
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
 {
@@ -37,3 +56,5 @@ int madvise(void *addr, size_t len, int advice)
     unimplemented();
     return 0;
 }
+
+#endif // AX_CONFIG_MMAP
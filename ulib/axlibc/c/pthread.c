@@ -32,10 +32,8 @@ int pthread_cancel(pthread_t t)
     return 0;
 }
 
-// TODO
-int pthread_mutex_trylock(pthread_mutex_t *m)
+int pthread_mutex_destroy(pthread_mutex_t *m)
 {
-    unimplemented();
     return 0;
 }
 
@@ -46,35 +44,44 @@ int pthread_setname_np(pthread_t thread, const char *name)
     return 0;
 }
 
-int pthread_cond_init(pthread_cond_t *restrict c, const pthread_condattr_t *restrict a)
+int pthread_cond_destroy(pthread_cond_t *c)
 {
-    *c = (pthread_cond_t){0};
-    if (a) {
-        c->_c_clock = a->__attr & 0x7fffffff;
-        if (a->__attr >> 31)
-            c->_c_shared = (void *)-1;
-    }
     return 0;
 }
 
-// TODO
-int pthread_cond_signal(pthread_cond_t *__cond)
+int pthread_condattr_init(pthread_condattr_t *a)
 {
-    unimplemented();
+    *a = (pthread_condattr_t){0};
     return 0;
 }
 
-// TODO
-int pthread_cond_wait(pthread_cond_t *__restrict__ __cond, pthread_mutex_t *__restrict__ __mutex)
+int pthread_condattr_destroy(pthread_condattr_t *a)
 {
-    unimplemented();
     return 0;
 }
 
-// TODO
-int pthread_cond_broadcast(pthread_cond_t *c)
+int pthread_condattr_setclock(pthread_condattr_t *a, clockid_t clk)
+{
+    if (clk < 0 || clk - 2U < 2)
+        return EINVAL;
+    a->__attr &= 0x80000000;
+    a->__attr |= clk;
+    return 0;
+}
+
+int pthread_condattr_getclock(const pthread_condattr_t *restrict a, clockid_t *restrict clk)
+{
+    *clk = a->__attr & 0x7fffffff;
+    return 0;
+}
+
+int pthread_rwlock_destroy(pthread_rwlock_t *rw)
+{
+    return 0;
+}
+
+int pthread_barrier_destroy(pthread_barrier_t *b)
 {
-    unimplemented();
     return 0;
 }
 
@@ -21,6 +21,8 @@
 #define IOV_MAX    1024
 
 #define PTHREAD_STACK_MIN 2048
+#define PTHREAD_KEYS_MAX 128
+#define PTHREAD_DESTRUCTOR_ITERATIONS 4
 
 #define LOGIN_NAME_MAX 256
 #ifndef NAME_MAX
@@ -31,6 +31,8 @@
 #define MAP_ANONYMOUS 0x20 /* Don't use a file.  */
 #endif
 #define MAP_ANON MAP_ANONYMOUS
+#define MAP_NORESERVE 0x04000 /* Don't check for reservations.  */
+#define MAP_POPULATE  0x08000 /* Populate (prefault) pagetables.  */
 /* When MAP_HUGETLB is set bits [26:31] encode the log2 of the huge page size.  */
 #define MAP_HUGE_SHIFT 26
 #define MAP_HUGE_MASK  0x3f
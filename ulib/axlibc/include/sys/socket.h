@@ -53,7 +53,13 @@ struct sockaddr_storage {
     unsigned long __ss_align;
 };
 
+struct linger {
+    int l_onoff;
+    int l_linger;
+};
+
 int socket(int, int, int);
+int socketpair(int, int, int, int[2]);
 int shutdown(int, int);
 
 int bind(int, const struct sockaddr *, socklen_t);
@@ -18,12 +18,16 @@
 //! - Lib C functions
 //!     - `fd`: Enable file descriptor table.
 //!     - `pipe`: Enable pipe support.
+//!     - `mmap`: Enable memory mapping ([mmap]) support.
 //!     - `select`: Enable synchronous I/O multiplexing ([select]) support.
 //!     - `epoll`: Enable event polling ([epoll]) support.
+//!     - `poll`: Enable waiting for events on file descriptors ([poll]) support.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 //! [select]: https://man7.org/linux/man-pages/man2/select.2.html
 //! [epoll]: https://man7.org/linux/man-pages/man7/epoll.7.html
+//! [poll]: https://man7.org/linux/man-pages/man2/poll.2.html
+//! [mmap]: https://man7.org/linux/man-pages/man2/mmap.2.html
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
 #![feature(doc_cfg)]
@@ -53,10 +57,12 @@ mod utils;
 mod fd_ops;
 #[cfg(feature = "fs")]
 mod fs;
-#[cfg(any(feature = "select", feature = "epoll"))]
+#[cfg(any(feature = "select", feature = "epoll", feature = "poll"))]
 mod io_mpx;
 #[cfg(feature = "alloc")]
 mod malloc;
+#[cfg(feature = "mmap")]
+mod mmap;
 #[cfg(feature = "net")]
 mod net;
 #[cfg(feature = "pipe")]
@@ -100,18 +106,38 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{ax_open, fstat, getcwd, link, lseek, lstat, readlink, rename, stat, symlink};
+
+#[cfg(feature = "mmap")]
+pub use self::mmap::{ax_mremap, mmap, mprotect, munmap};
 
 #[cfg(feature = "net")]
 pub use self::net::{
-    accept, bind, connect, freeaddrinfo, getaddrinfo, getpeername, getsockname, listen, recv,
-    recvfrom, send, sendto, shutdown, socket,
+    accept, bind, connect, freeaddrinfo, getaddrinfo, getpeername, getsockname, getsockopt, listen,
+    recv, recvfrom, send, sendto, setsockopt, shutdown, socket, socketpair,
 };
 
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
 #[cfg(feature = "multitask")]
-pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+pub use self::pthread::{
+    pthread_barrier_init, pthread_barrier_wait, pthread_getspecific, pthread_key_create,
+    pthread_key_delete, pthread_once, pthread_setspecific,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_cond_broadcast, pthread_cond_init, pthread_cond_signal, pthread_cond_timedwait,
+    pthread_cond_wait,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_mutex_init, pthread_mutex_lock, pthread_mutex_trylock, pthread_mutex_unlock,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_rwlock_init, pthread_rwlock_rdlock, pthread_rwlock_tryrdlock, pthread_rwlock_trywrlock,
+    pthread_rwlock_unlock, pthread_rwlock_wrlock,
+};
 
 #[cfg(feature = "pipe")]
 pub use self::pipe::pipe;
@@ -120,6 +146,8 @@ pub use self::pipe::pipe;
 pub use self::io_mpx::select;
 #[cfg(feature = "epoll")]
 pub use self::io_mpx::{epoll_create, epoll_ctl, epoll_wait};
+#[cfg(feature = "poll")]
+pub use self::io_mpx::{poll, ppoll};
 
 #[cfg(feature = "fp_simd")]
 pub use self::strtod::{strtod, strtof};
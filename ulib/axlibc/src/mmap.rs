@@ -0,0 +1,59 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_mmap, sys_mprotect, sys_mremap, sys_munmap};
+
+use crate::{ctypes, utils::e};
+
+/// `MAP_FAILED` in C.
+const MAP_FAILED: *mut c_void = usize::MAX as *mut c_void;
+
+/// Converts the returned address of `sys_mmap` and `sys_mremap`, sets `errno`
+/// and returns `MAP_FAILED` on error.
+fn map_result(ret: *mut c_void) -> *mut c_void {
+    let ret = ret as isize;
+    if (-4095..0).contains(&ret) {
+        crate::errno::set_errno(-ret as c_int);
+        MAP_FAILED
+    } else {
+        ret as _
+    }
+}
+
+/// Creates a new mapping in the virtual address space.
+#[no_mangle]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    map_result(sys_mmap(addr, len, prot, flags, fd, off))
+}
+
+/// Removes the mappings in the specified address range.
+#[no_mangle]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: usize) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Changes the access protections of the mappings in the specified address
+/// range.
+#[no_mangle]
+pub unsafe extern "C" fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int {
+    e(sys_mprotect(addr, len, prot))
+}
+
+/// Expands or shrinks an existing mapping.
+///
+/// TODO: `new_address` is ignored as `MREMAP_FIXED` is not supported
+#[no_mangle]
+pub unsafe extern "C" fn ax_mremap(
+    old_addr: *mut c_void,
+    old_size: usize,
+    new_size: usize,
+    flags: c_int,
+) -> *mut c_void {
+    map_result(sys_mremap(old_addr, old_size, new_size, flags))
+}
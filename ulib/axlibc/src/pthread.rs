@@ -1,6 +1,6 @@
 use crate::{ctypes, utils::e};
 use arceos_posix_api as api;
-use core::ffi::{c_int, c_void};
+use core::ffi::{c_int, c_uint, c_void};
 
 /// Returns the `pthread` struct of current thread.
 #[no_mangle]
@@ -57,3 +57,157 @@ pub unsafe extern "C" fn pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t)
 pub unsafe extern "C" fn pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     e(api::sys_pthread_mutex_unlock(mutex))
 }
+
+/// Converts the negative error codes returned by the `sys_pthread_*` functions
+/// to the error numbers returned by the `pthread_*` functions.
+fn pthread_e(ret: c_int) -> c_int {
+    if ret < 0 {
+        -ret
+    } else {
+        ret
+    }
+}
+
+/// Try to lock the given mutex, returns `EBUSY` if it is already locked.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_mutex_trylock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
+    pthread_e(api::sys_pthread_mutex_trylock(mutex))
+}
+
+/// Initialize a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    pthread_e(api::sys_pthread_cond_init(cond, attr))
+}
+
+/// Wait on the condition variable, with the given mutex locked.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    pthread_e(api::sys_pthread_cond_wait(cond, mutex))
+}
+
+/// Wait on the condition variable, with the given mutex locked, until the
+/// absolute time `abstime`.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    pthread_e(api::sys_pthread_cond_timedwait(cond, mutex, abstime))
+}
+
+/// Wake up at least one thread waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pthread_e(api::sys_pthread_cond_signal(cond))
+}
+
+/// Wake up all threads waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pthread_e(api::sys_pthread_cond_broadcast(cond))
+}
+
+/// Initialize a readers-writer lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_init(rwlock, attr))
+}
+
+/// Lock the readers-writer lock for reading.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_rdlock(rwlock))
+}
+
+/// Try to lock the readers-writer lock for reading.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_tryrdlock(rwlock))
+}
+
+/// Lock the readers-writer lock for writing.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_wrlock(rwlock))
+}
+
+/// Try to lock the readers-writer lock for writing.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_trywrlock(rwlock))
+}
+
+/// Unlock the readers-writer lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pthread_e(api::sys_pthread_rwlock_unlock(rwlock))
+}
+
+/// Initialize a barrier that waits for `count` threads.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    attr: *const ctypes::pthread_barrierattr_t,
+    count: c_uint,
+) -> c_int {
+    pthread_e(api::sys_pthread_barrier_init(barrier, attr, count))
+}
+
+/// Wait on the barrier until all threads arrive.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    match api::sys_pthread_barrier_wait(barrier) {
+        ctypes::PTHREAD_BARRIER_SERIAL_THREAD => ctypes::PTHREAD_BARRIER_SERIAL_THREAD,
+        ret => pthread_e(ret),
+    }
+}
+
+/// Call `init_routine` only once with the same `once_control`.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_once(
+    once_control: *mut ctypes::pthread_once_t,
+    init_routine: extern "C" fn(),
+) -> c_int {
+    pthread_e(api::sys_pthread_once(once_control, init_routine))
+}
+
+/// Create a thread-specific data key.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_key_create(
+    key: *mut ctypes::pthread_key_t,
+    destructor: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> c_int {
+    pthread_e(api::sys_pthread_key_create(key, destructor))
+}
+
+/// Delete a thread-specific data key.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_key_delete(key: ctypes::pthread_key_t) -> c_int {
+    pthread_e(api::sys_pthread_key_delete(key))
+}
+
+/// Get the value of the key of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_getspecific(key: ctypes::pthread_key_t) -> *mut c_void {
+    api::sys_pthread_getspecific(key)
+}
+
+/// Set the value of the key of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setspecific(
+    key: ctypes::pthread_key_t,
+    value: *const c_void,
+) -> c_int {
+    pthread_e(api::sys_pthread_setspecific(key, value))
+}
@@ -3,7 +3,7 @@ use core::ffi::c_int;
 
 use crate::{ctypes, utils::e};
 
-/// Get clock time since booting
+/// Get the time of the specified clock
 #[no_mangle]
 pub unsafe extern "C" fn clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
     e(sys_clock_gettime(clk, ts))
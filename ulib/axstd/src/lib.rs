@@ -30,8 +30,11 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext4`: Use ext4 as the main filesystem instead of FAT.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
+//!     - `dhcp`: Configure the network by DHCPv4 instead of the static address.
+//!     - `pcap`: Capture the frames of the NICs for inspection in Wireshark.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
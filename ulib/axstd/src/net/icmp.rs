@@ -0,0 +1,80 @@
+use super::IpAddr;
+use crate::io;
+
+use arceos_api::net::{self as api, AxIcmpSocketHandle};
+
+/// An ICMP socket that sends echo requests and receives echo replies.
+///
+/// The messages sent and received are ICMP messages without the IP header,
+/// like the ICMP "ping" sockets on Linux. The identifier of the echo requests
+/// is replaced with [`ident`](Self::ident), and the checksum is computed by
+/// the network stack.
+pub struct IcmpSocket(AxIcmpSocketHandle);
+
+impl IcmpSocket {
+    /// Creates a new ICMP socket, which receives echo replies from all
+    /// interfaces.
+    pub fn new() -> io::Result<IcmpSocket> {
+        Ok(IcmpSocket(api::ax_icmp_socket()))
+    }
+
+    /// Creates an ICMP socket bound to the given local address, which only
+    /// sends and receives through the interface that owns the address.
+    pub fn bind(addr: IpAddr) -> io::Result<IcmpSocket> {
+        let socket = api::ax_icmp_socket();
+        api::ax_icmp_bind(&socket, addr)?;
+        Ok(IcmpSocket(socket))
+    }
+
+    /// Returns the identifier of the echo requests sent by this socket.
+    pub fn ident(&self) -> u16 {
+        api::ax_icmp_ident(&self.0)
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<IpAddr> {
+        api::ax_icmp_socket_addr(&self.0)
+    }
+
+    /// Returns the address of the remote peer this socket was connected to.
+    pub fn peer_addr(&self) -> io::Result<IpAddr> {
+        api::ax_icmp_peer_addr(&self.0)
+    }
+
+    /// Moves this ICMP socket into or out of nonblocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        api::ax_icmp_set_nonblocking(&self.0, nonblocking)
+    }
+
+    /// Receives an echo reply on the socket. On success, returns the number of
+    /// bytes read and the origin.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, IpAddr)> {
+        api::ax_icmp_recv_from(&self.0, buf)
+    }
+
+    /// Sends an echo request on the socket to the given address. On success,
+    /// returns the number of bytes written.
+    ///
+    /// `buf` must start with an ICMP (or ICMPv6) echo request header.
+    pub fn send_to(&self, buf: &[u8], addr: IpAddr) -> io::Result<usize> {
+        api::ax_icmp_send_to(&self.0, buf, addr)
+    }
+
+    /// Connects this ICMP socket to a remote address, allowing the `send` and
+    /// `recv` to be used, and only receives the replies from it.
+    pub fn connect(&self, addr: IpAddr) -> io::Result<()> {
+        api::ax_icmp_connect(&self.0, addr)
+    }
+
+    /// Sends an echo request on the socket to the remote address to which it
+    /// is connected.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        api::ax_icmp_send(&self.0, buf)
+    }
+
+    /// Receives an echo reply on the socket from the remote address to which
+    /// it is connected. On success, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        api::ax_icmp_recv(&self.0, buf)
+    }
+}
@@ -1,12 +1,14 @@
-//! Networking primitives for TCP/UDP communication.
+//! Networking primitives for TCP/UDP/ICMP communication.
 //!
-//! This module provides networking functionality for the Transmission Control and User
-//! Datagram Protocols, as well as types for IP and socket addresses.
+//! This module provides networking functionality for the Transmission Control, User
+//! Datagram and Internet Control Message Protocols, as well as types for IP and socket
+//! addresses.
 //!
 //! # Organization
 //!
 //! * [`TcpListener`] and [`TcpStream`] provide functionality for communication over TCP
 //! * [`UdpSocket`] provides functionality for communication over UDP
+//! * [`IcmpSocket`] sends ICMP echo requests and receives the replies, e.g., to ping a host
 //! * [`IpAddr`] represents IP addresses of either IPv4 or IPv6; [`Ipv4Addr`] and
 //!   [`Ipv6Addr`] are respectively IPv4 and IPv6 addresses
 //! * [`SocketAddr`] represents socket addresses of either IPv4 or IPv6; [`SocketAddrV4`]
@@ -14,10 +16,12 @@
 //! * [`ToSocketAddrs`] is a trait that is used for generic address resolution when interacting
 //!   with networking objects like [`TcpListener`], [`TcpStream`] or [`UdpSocket`]
 
+mod icmp;
 mod socket_addr;
 mod tcp;
 mod udp;
 
+pub use self::icmp::IcmpSocket;
 pub use self::socket_addr::{IpAddr, Ipv4Addr, Ipv6Addr};
 pub use self::socket_addr::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 pub use self::tcp::{TcpListener, TcpStream};
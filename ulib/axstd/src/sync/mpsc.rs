@@ -0,0 +1,504 @@
+//! Multi-producer, single-consumer FIFO queue communication primitives,
+//! similar to [`std::sync::mpsc`](https://doc.rust-lang.org/std/sync/mpsc/index.html).
+//!
+//! The channels are created by [`channel`] (unbounded) or [`sync_channel`]
+//! (bounded), and are implemented by a queue protected by a [`Mutex`], with
+//! [`Condvar`]s to wait for messages or free space.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::fmt;
+use core::mem;
+use core::time::Duration;
+
+use super::{Condvar, Mutex, MutexGuard};
+use crate::time::Instant;
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    /// The receiver is blocked waiting for messages.
+    receiver_waiting: bool,
+    /// The number of received messages, for the rendezvous channels.
+    received: usize,
+}
+
+struct Channel<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    /// The capacity of a bounded channel, 0 for a rendezvous channel.
+    bound: Option<usize>,
+}
+
+impl<T> Channel<T> {
+    fn new(bound: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receiver_alive: true,
+                receiver_waiting: false,
+                received: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            bound,
+        })
+    }
+
+    fn is_full(&self, state: &State<T>) -> bool {
+        // a rendezvous channel holds at most one message being handed over
+        self.bound
+            .is_some_and(|bound| state.queue.len() >= bound.max(1))
+    }
+
+    /// Pushes `t` into the queue, which must not be full, and waits for it to
+    /// be received if it is a rendezvous channel.
+    ///
+    /// Returns `t` back if the receiver is dropped before receiving it.
+    fn push(&self, mut state: MutexGuard<State<T>>, t: T) -> Result<(), T> {
+        state.queue.push_back(t);
+        self.not_empty.notify_one();
+        if self.bound == Some(0) {
+            let ticket = state.received + state.queue.len();
+            let mut state = self.not_full.wait_while(state, |state| {
+                state.receiver_alive && state.received < ticket
+            });
+            if state.received < ticket {
+                // the only message left in the queue is ours
+                return Err(state.queue.pop_front().unwrap());
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, t: T) -> Result<(), SendError<T>> {
+        let mut state = self.state.lock();
+        while state.receiver_alive && self.is_full(&state) {
+            state = self.not_full.wait(state);
+        }
+        if !state.receiver_alive {
+            return Err(SendError(t));
+        }
+        self.push(state, t).map_err(SendError)
+    }
+
+    fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        let state = self.state.lock();
+        if !state.receiver_alive {
+            Err(TrySendError::Disconnected(t))
+        } else if self.is_full(&state) || (self.bound == Some(0) && !state.receiver_waiting) {
+            // a rendezvous channel only accepts a message if the receiver is
+            // waiting for it
+            Err(TrySendError::Full(t))
+        } else {
+            self.push(state, t).map_err(TrySendError::Disconnected)
+        }
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let t = state.queue.pop_front()?;
+        state.received += 1;
+        self.not_full.notify_all();
+        Some(t)
+    }
+
+    fn recv(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(t) = self.pop(&mut state) {
+                return Ok(t);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline - Instant::now();
+                    if timeout.is_zero() {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    Some(timeout)
+                }
+                None => None,
+            };
+            state.receiver_waiting = true;
+            state = match timeout {
+                Some(timeout) => self.not_empty.wait_timeout(state, timeout).0,
+                None => self.not_empty.wait(state),
+            };
+            state.receiver_waiting = false;
+        }
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state.lock();
+        match self.pop(&mut state) {
+            Some(t) => Ok(t),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn add_sender(&self) {
+        self.state.lock().senders += 1;
+    }
+
+    fn drop_sender(&self) {
+        let mut state = self.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.not_empty.notify_all();
+        }
+    }
+
+    fn drop_receiver(&self) {
+        let mut state = self.state.lock();
+        state.receiver_alive = false;
+        // the message of a rendezvous channel is returned to its sender
+        let queue = if self.bound == Some(0) {
+            VecDeque::new()
+        } else {
+            mem::take(&mut state.queue)
+        };
+        self.not_full.notify_all();
+        drop(state);
+        // drop the messages never to be received, after unlocking, as their
+        // destructors may use the channel
+        drop(queue);
+    }
+}
+
+/// Creates a new asynchronous channel, returning the sender/receiver halves.
+///
+/// All data sent on the [`Sender`] will become available on the [`Receiver`]
+/// in the same order as it was sent, and no [`send`](Sender::send) will block
+/// the calling task.
+#[must_use]
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Channel::new(None);
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Creates a new synchronous, bounded channel.
+///
+/// The [`SyncSender::send`] blocks when the buffer of `bound` messages is
+/// full. If `bound` is 0, the channel becomes a "rendezvous" channel, where
+/// each send will not return until a receive is paired with it.
+#[must_use]
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let chan = Channel::new(Some(bound));
+    (SyncSender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// The sending-half of the channel type created by [`channel`].
+pub struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The sending-half of the channel type created by [`sync_channel`].
+pub struct SyncSender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The receiving half of the channel types.
+pub struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Attempts to send a value on this channel, returning it back if it
+    /// could not be sent, because the receiver has been dropped.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        self.chan.send(t)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> SyncSender<T> {
+    /// Sends a value on this synchronous channel, blocking until there is
+    /// space in the buffer, or it is received for a rendezvous channel.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        self.chan.send(t)
+    }
+
+    /// Attempts to send a value on this channel without blocking.
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(t)
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.add_sender();
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for SyncSender<T> {
+    fn drop(&mut self) {
+        self.chan.drop_sender();
+    }
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncSender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Attempts to return a pending value on this receiver without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Attempts to wait for a value on this receiver, returning an error if
+    /// all the senders have been dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.chan.recv(None).map_err(|_| RecvError)
+    }
+
+    /// Attempts to wait for a value on this receiver, returning an error if
+    /// all the senders have been dropped, or if it waits more than `timeout`.
+    ///
+    /// The timeout only takes effect with the feature `irq` enabled.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.chan.recv(Some(deadline)),
+            None => self.recv().map_err(RecvTimeoutError::from),
+        }
+    }
+
+    /// Returns an iterator that will block waiting for messages, until all
+    /// the senders have been dropped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+
+    /// Returns an iterator that will attempt to yield all pending values,
+    /// without blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.drop_receiver();
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over messages on a [`Receiver`], created by
+/// [`iter`](Receiver::iter).
+#[derive(Debug)]
+pub struct Iter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An iterator that attempts to yield all pending values for a [`Receiver`],
+/// created by [`try_iter`](Receiver::try_iter).
+#[derive(Debug)]
+pub struct TryIter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An owning iterator over messages on a [`Receiver`], created by
+/// [`into_iter`](Receiver::into_iter).
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> Iterator for TryIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+/// An error returned from the [`Sender::send`] or [`SyncSender::send`]
+/// function on channels, containing the value failed to send.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// An error returned from the [`recv`](Receiver::recv) function on a
+/// [`Receiver`], when all the senders have been dropped.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+/// The possible reasons that [`try_recv`](Receiver::try_recv) could not
+/// return data.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// This channel is currently empty, but the senders have not yet
+    /// disconnected, so data may yet become available.
+    Empty,
+    /// All the senders have been dropped, so there will never be any more
+    /// data received on it.
+    Disconnected,
+}
+
+/// The possible reasons that [`recv_timeout`](Receiver::recv_timeout) could
+/// not return data.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// This channel is currently empty, but the senders have not yet
+    /// disconnected, so data may yet become available.
+    Timeout,
+    /// All the senders have been dropped, so there will never be any more
+    /// data received on it.
+    Disconnected,
+}
+
+/// The possible reasons that [`SyncSender::try_send`] could not send data,
+/// containing the value failed to send.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The data could not be sent as it would require that the callee block
+    /// to send the data.
+    Full(T),
+    /// The receiver has been dropped.
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "sending on a closed channel".fmt(f)
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(..) => "Full(..)".fmt(f),
+            TrySendError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(..) => "sending on a full channel".fmt(f),
+            TrySendError::Disconnected(..) => "sending on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(err: SendError<T>) -> TrySendError<T> {
+        TrySendError::Disconnected(err.0)
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "receiving on a closed channel".fmt(f)
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Disconnected => "receiving on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl From<RecvError> for TryRecvError {
+    fn from(err: RecvError) -> TryRecvError {
+        match err {
+            RecvError => TryRecvError::Disconnected,
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel".fmt(f),
+            RecvTimeoutError::Disconnected => "channel is empty and sending half is closed".fmt(f),
+        }
+    }
+}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(err: RecvError) -> RecvTimeoutError {
+        match err {
+            RecvError => RecvTimeoutError::Disconnected,
+        }
+    }
+}